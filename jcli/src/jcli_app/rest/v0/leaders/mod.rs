@@ -6,7 +6,8 @@ use structopt::StructOpt;
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub enum Leaders {
-    /// Get list of leader IDs
+    /// Get list of leaders, with the KES key status of the Genesis-Praos
+    /// leaders
     Get {
         #[structopt(flatten)]
         addr: HostAddr,
@@ -15,7 +16,8 @@ pub enum Leaders {
         #[structopt(flatten)]
         output_format: OutputFormat,
     },
-    /// Register new leader and get its ID
+    /// Register new leader and get its ID. A secret with the stake pool of
+    /// an existing leader replaces its keys, e.g. with a renewed KES key.
     Post {
         #[structopt(flatten)]
         addr: HostAddr,
@@ -39,6 +41,26 @@ pub enum Leaders {
 
     /// Leadership log operations
     Logs(GetLogs),
+    /// Get the slots assigned to the leaders for the current epoch, and for
+    /// the next epoch once its leadership is known for sure
+    Schedule {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+    },
+    /// Get the KES period, remaining evolutions and expiry date of the KES
+    /// keys of the Genesis-Praos leaders
+    Kes {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+    },
 }
 
 #[derive(StructOpt)]
@@ -70,6 +92,16 @@ impl Leaders {
                 debug,
                 output_format,
             }) => get_logs(addr, debug, output_format),
+            Leaders::Schedule {
+                addr,
+                debug,
+                output_format,
+            } => get_schedule(addr, debug, output_format),
+            Leaders::Kes {
+                addr,
+                debug,
+                output_format,
+            } => get_kes(addr, debug, output_format),
         }
     }
 }
@@ -118,3 +150,31 @@ fn get_logs(addr: HostAddr, debug: DebugFlag, output_format: OutputFormat) -> Re
     println!("{}", formatted);
     Ok(())
 }
+
+fn get_schedule(
+    addr: HostAddr,
+    debug: DebugFlag,
+    output_format: OutputFormat,
+) -> Result<(), Error> {
+    let url = addr
+        .with_segments(&["v0", "leaders", "schedule"])?
+        .into_url();
+    let builder = reqwest::blocking::Client::new().get(url);
+    let response = RestApiSender::new(builder, &debug).send()?;
+    response.ok_response()?;
+    let schedule = response.body().json_value()?;
+    let formatted = output_format.format_json(schedule)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
+fn get_kes(addr: HostAddr, debug: DebugFlag, output_format: OutputFormat) -> Result<(), Error> {
+    let url = addr.with_segments(&["v0", "leaders", "kes"])?.into_url();
+    let builder = reqwest::blocking::Client::new().get(url);
+    let response = RestApiSender::new(builder, &debug).send()?;
+    response.ok_response()?;
+    let kes = response.body().json_value()?;
+    let formatted = output_format.format_json(kes)?;
+    println!("{}", formatted);
+    Ok(())
+}
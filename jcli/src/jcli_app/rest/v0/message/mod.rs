@@ -35,6 +35,34 @@ pub enum Message {
         #[structopt(flatten)]
         output_format: OutputFormat,
     },
+
+    /// get the fragments waiting in the message pool of the node, with their
+    /// size, fee, origin and age
+    Pending {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+        /// include the hex-encoded fragments
+        #[structopt(long)]
+        full: bool,
+    },
+
+    /// get the status of the given fragments in the node's logs on the
+    /// message pool. The fragments unknown to the node are left out.
+    Status {
+        #[structopt(flatten)]
+        addr: HostAddr,
+        #[structopt(flatten)]
+        debug: DebugFlag,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+        /// hex-encoded IDs of the fragments
+        #[structopt(required = true)]
+        fragment_ids: Vec<String>,
+    },
 }
 
 impl Message {
@@ -46,6 +74,18 @@ impl Message {
                 debug,
                 output_format,
             } => get_logs(addr, debug, output_format),
+            Message::Pending {
+                addr,
+                debug,
+                output_format,
+                full,
+            } => get_pending(addr, debug, output_format, full),
+            Message::Status {
+                addr,
+                debug,
+                output_format,
+                fragment_ids,
+            } => get_status(addr, debug, output_format, fragment_ids),
         }
     }
 }
@@ -61,6 +101,46 @@ fn get_logs(addr: HostAddr, debug: DebugFlag, output_format: OutputFormat) -> Re
     Ok(())
 }
 
+fn get_pending(
+    addr: HostAddr,
+    debug: DebugFlag,
+    output_format: OutputFormat,
+    full: bool,
+) -> Result<(), Error> {
+    let url = addr
+        .with_segments(&["v0", "fragment", "pending"])?
+        .into_url();
+    let builder = reqwest::blocking::Client::new()
+        .get(url)
+        .query(&[("full", full)]);
+    let response = RestApiSender::new(builder, &debug).send()?;
+    response.ok_response()?;
+    let pending = response.body().json_value()?;
+    let formatted = output_format.format_json(pending)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
+fn get_status(
+    addr: HostAddr,
+    debug: DebugFlag,
+    output_format: OutputFormat,
+    fragment_ids: Vec<String>,
+) -> Result<(), Error> {
+    let url = addr
+        .with_segments(&["v0", "fragment", "status"])?
+        .into_url();
+    let builder = reqwest::blocking::Client::new()
+        .get(url)
+        .query(&[("fragment_ids", fragment_ids.join(","))]);
+    let response = RestApiSender::new(builder, &debug).send()?;
+    response.ok_response()?;
+    let statuses = response.body().json_value()?;
+    let formatted = output_format.format_json(statuses)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
 fn post_message(file: Option<PathBuf>, addr: HostAddr, debug: DebugFlag) -> Result<(), Error> {
     let msg_hex = io::read_line(&file)?;
     let msg_bin = hex::decode(&msg_hex)?;
@@ -1,3 +1,4 @@
+use crate::{interfaces::FragmentOrigin, time::Duration};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
@@ -6,6 +7,41 @@ pub struct PoolMaxEntries(usize);
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct LogMaxEntries(usize);
 
+/// algorithm used to select the fragments from the mempool when
+/// creating a new block
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FragmentSelection {
+    /// select the fragments in the order they were received
+    OldestFirst,
+    /// select the fragments paying the highest fee per byte first
+    FeeDensity,
+}
+
+/// how long a fragment is kept in the mempool before it expires
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FragmentTtl {
+    /// number of slots, from the slot the fragment was received in
+    Slots(u64),
+    /// wall clock time since the fragment was received
+    Duration(Duration),
+}
+
+/// maximum number of entries in the mempool for the fragments received from
+/// each client of an origin, the clients being told apart by IP address. No
+/// quota is applied to an origin which is not set.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OriginMaxEntries {
+    /// fragments received from the REST interface
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rest: Option<PoolMaxEntries>,
+    /// fragments received from the peers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<PoolMaxEntries>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Mempool {
@@ -15,6 +51,20 @@ pub struct Mempool {
     /// maximum number of entries in the fragment logs
     #[serde(default)]
     pub log_max_entries: LogMaxEntries,
+    /// keep a copy of the fragment logs in the node storage directory, so
+    /// they are reloaded when the node restarts
+    #[serde(default)]
+    pub persistent_logs: bool,
+    /// algorithm used to select the fragments to put in a new block
+    #[serde(default)]
+    pub fragment_selection: FragmentSelection,
+    /// time to live of the fragments in the mempool, the fragments are kept
+    /// until they are added in a block or evicted if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment_ttl: Option<FragmentTtl>,
+    /// maximum number of entries in the mempool for each fragment origin
+    #[serde(default)]
+    pub origin_max_entries: OriginMaxEntries,
 }
 
 impl Default for PoolMaxEntries {
@@ -29,11 +79,21 @@ impl Default for LogMaxEntries {
     }
 }
 
+impl Default for FragmentSelection {
+    fn default() -> Self {
+        FragmentSelection::OldestFirst
+    }
+}
+
 impl Default for Mempool {
     fn default() -> Self {
         Mempool {
             pool_max_entries: PoolMaxEntries::default(),
             log_max_entries: LogMaxEntries::default(),
+            persistent_logs: false,
+            fragment_selection: FragmentSelection::default(),
+            fragment_ttl: None,
+            origin_max_entries: OriginMaxEntries::default(),
         }
     }
 }
@@ -50,6 +110,16 @@ impl From<PoolMaxEntries> for usize {
     }
 }
 
+impl OriginMaxEntries {
+    /// the quota of the given origin, if any
+    pub fn get(&self, origin: FragmentOrigin) -> Option<PoolMaxEntries> {
+        match origin {
+            FragmentOrigin::Rest => self.rest,
+            FragmentOrigin::Network => self.network,
+        }
+    }
+}
+
 impl From<usize> for LogMaxEntries {
     fn from(s: usize) -> Self {
         LogMaxEntries(s)
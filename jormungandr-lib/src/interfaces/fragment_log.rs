@@ -1,4 +1,8 @@
-use crate::{crypto::hash::Hash, interfaces::BlockDate, time::SystemTime};
+use crate::{
+    crypto::hash::Hash,
+    interfaces::{BlockDate, Value},
+    time::{Duration, SystemTime},
+};
 use chain_impl_mockchain::key;
 use serde::{Deserialize, Serialize};
 
@@ -27,6 +31,12 @@ pub enum FragmentStatus {
     Rejected { reason: String },
     /// The fragment has been added in a block
     InABlock { date: BlockDate, block: Hash },
+    /// the fragment stayed in the pool longer than its time to live, it
+    /// won't be added in a block
+    Expired,
+    /// the fragment has been removed from the pool to make room for other
+    /// fragments, it won't be added in a block
+    Evicted { reason: String },
 }
 
 /// the log associated to a given fragment
@@ -39,6 +49,47 @@ pub struct FragmentLog {
     status: FragmentStatus,
 }
 
+/// the verdict of the node on a fragment submitted to it
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FragmentSubmissionStatus {
+    /// the fragment has been added to the pool and propagated to the peers
+    Accepted,
+    /// the fragment has been refused by the pool
+    Rejected { reason: String },
+}
+
+/// the result of the submission of a fragment to the node
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FragmentSubmission {
+    fragment_id: Hash,
+    status: FragmentSubmissionStatus,
+}
+
+/// a fragment of the pool spending from an account, with the spending counter
+/// it is signed for
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AccountPendingFragment {
+    fragment_id: Hash,
+    spending_counter: u32,
+}
+
+/// a fragment waiting in the pool to be added in a block
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PendingFragment {
+    fragment_id: Hash,
+    received_from: FragmentOrigin,
+    received_at: SystemTime,
+    /// time spent in the pool so far
+    age: Duration,
+    /// size of the fragment in a block, in bytes
+    size: u32,
+    /// fee paid by the fragment, according to the fee settings of the tip
+    fee: Value,
+    /// the hex-encoded fragment, only given on request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fragment: Option<String>,
+}
+
 impl FragmentStatus {
     #[inline]
     pub fn is_pending(&self) -> bool {
@@ -62,6 +113,20 @@ impl FragmentStatus {
             false
         }
     }
+
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self == &FragmentStatus::Expired
+    }
+
+    #[inline]
+    pub fn is_evicted(&self) -> bool {
+        if let FragmentStatus::Evicted { .. } = &self {
+            true
+        } else {
+            false
+        }
+    }
 }
 
 impl FragmentLog {
@@ -92,6 +157,16 @@ impl FragmentLog {
         self.status().is_in_a_block()
     }
 
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        self.status().is_expired()
+    }
+
+    #[inline]
+    pub fn is_evicted(&self) -> bool {
+        self.status().is_evicted()
+    }
+
     /// set the new status
     #[inline]
     pub fn modify(&mut self, new_status: FragmentStatus) {
@@ -124,3 +199,122 @@ impl FragmentLog {
         &self.status
     }
 }
+
+impl FragmentSubmission {
+    #[inline]
+    pub fn accepted(fragment_id: key::Hash) -> Self {
+        FragmentSubmission {
+            fragment_id: fragment_id.into(),
+            status: FragmentSubmissionStatus::Accepted,
+        }
+    }
+
+    #[inline]
+    pub fn rejected(fragment_id: key::Hash, reason: String) -> Self {
+        FragmentSubmission {
+            fragment_id: fragment_id.into(),
+            status: FragmentSubmissionStatus::Rejected { reason },
+        }
+    }
+
+    #[inline]
+    pub fn is_accepted(&self) -> bool {
+        self.status == FragmentSubmissionStatus::Accepted
+    }
+
+    #[inline]
+    pub fn fragment_id(&self) -> &Hash {
+        &self.fragment_id
+    }
+
+    #[inline]
+    pub fn status(&self) -> &FragmentSubmissionStatus {
+        &self.status
+    }
+}
+
+impl AccountPendingFragment {
+    #[inline]
+    pub fn new(fragment_id: key::Hash, spending_counter: u32) -> Self {
+        AccountPendingFragment {
+            fragment_id: fragment_id.into(),
+            spending_counter,
+        }
+    }
+
+    #[inline]
+    pub fn fragment_id(&self) -> &Hash {
+        &self.fragment_id
+    }
+
+    #[inline]
+    pub fn spending_counter(&self) -> u32 {
+        self.spending_counter
+    }
+}
+
+impl PendingFragment {
+    #[inline]
+    pub fn new(
+        fragment_id: key::Hash,
+        received_from: FragmentOrigin,
+        received_at: SystemTime,
+        age: Duration,
+        size: u32,
+        fee: Value,
+    ) -> Self {
+        PendingFragment {
+            fragment_id: fragment_id.into(),
+            received_from,
+            received_at,
+            age,
+            size,
+            fee,
+            fragment: None,
+        }
+    }
+
+    /// set the hex-encoded fragment
+    #[inline]
+    pub fn with_fragment(self, fragment_hex: String) -> Self {
+        PendingFragment {
+            fragment: Some(fragment_hex),
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn fragment_id(&self) -> &Hash {
+        &self.fragment_id
+    }
+
+    #[inline]
+    pub fn received_from(&self) -> &FragmentOrigin {
+        &self.received_from
+    }
+
+    #[inline]
+    pub fn received_at(&self) -> &SystemTime {
+        &self.received_at
+    }
+
+    #[inline]
+    pub fn age(&self) -> &Duration {
+        &self.age
+    }
+
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    #[inline]
+    pub fn fee(&self) -> &Value {
+        &self.fee
+    }
+
+    #[inline]
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+}
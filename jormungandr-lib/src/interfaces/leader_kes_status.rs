@@ -0,0 +1,132 @@
+use crate::{interfaces::EnclaveLeaderId, time::SystemTime};
+use serde::{Deserialize, Serialize};
+
+/// the state of the KES key of a Genesis-Praos leader of the enclave
+///
+/// The key lifetime starts with the validity of the stake pool registration
+/// and lasts one period of the block0 `kes_update_speed` per evolution of the
+/// key. The lifetime is only known if the stake pool is registered in the
+/// ledger.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeaderKesStatus {
+    enclave_leader_id: EnclaveLeaderId,
+    pool_id: String,
+    /// the KES key of the leader is the one registered for its stake pool
+    key_registered: bool,
+    period: Option<u32>,
+    remaining_evolutions: Option<u32>,
+    expiry_time: Option<SystemTime>,
+}
+
+impl LeaderKesStatus {
+    /// status of a leader whose stake pool is not registered in the ledger
+    pub fn unregistered(enclave_leader_id: EnclaveLeaderId, pool_id: String) -> Self {
+        LeaderKesStatus {
+            enclave_leader_id,
+            pool_id,
+            key_registered: false,
+            period: None,
+            remaining_evolutions: None,
+            expiry_time: None,
+        }
+    }
+
+    pub fn new(
+        enclave_leader_id: EnclaveLeaderId,
+        pool_id: String,
+        key_registered: bool,
+        period: u32,
+        remaining_evolutions: u32,
+        expiry_time: SystemTime,
+    ) -> Self {
+        LeaderKesStatus {
+            enclave_leader_id,
+            pool_id,
+            key_registered,
+            period: Some(period),
+            remaining_evolutions: Some(remaining_evolutions),
+            expiry_time: Some(expiry_time),
+        }
+    }
+
+    pub fn enclave_leader_id(&self) -> &EnclaveLeaderId {
+        &self.enclave_leader_id
+    }
+    pub fn pool_id(&self) -> &str {
+        &self.pool_id
+    }
+    pub fn key_registered(&self) -> bool {
+        self.key_registered
+    }
+    pub fn period(&self) -> Option<u32> {
+        self.period
+    }
+    pub fn remaining_evolutions(&self) -> Option<u32> {
+        self.remaining_evolutions
+    }
+    pub fn expiry_time(&self) -> Option<&SystemTime> {
+        self.expiry_time.as_ref()
+    }
+}
+
+/// a leader of the enclave, with the state of its KES key if it is a
+/// Genesis-Praos leader
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LeaderStatus {
+    GenesisPraos(LeaderKesStatus),
+    Bft { enclave_leader_id: EnclaveLeaderId },
+}
+
+impl LeaderStatus {
+    pub fn enclave_leader_id(&self) -> &EnclaveLeaderId {
+        match self {
+            LeaderStatus::GenesisPraos(kes_status) => kes_status.enclave_leader_id(),
+            LeaderStatus::Bft { enclave_leader_id } => enclave_leader_id,
+        }
+    }
+
+    pub fn kes_status(&self) -> Option<&LeaderKesStatus> {
+        match self {
+            LeaderStatus::GenesisPraos(kes_status) => Some(kes_status),
+            LeaderStatus::Bft { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn leader_status_is_decoded_by_its_fields() {
+        let leader_id = EnclaveLeaderId::new().next();
+        let expiry_time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let statuses = vec![
+            LeaderStatus::GenesisPraos(LeaderKesStatus::new(
+                leader_id,
+                "ea830e5d9647af89a5e9a4d4089e6e855891a533316adf4a42b7bf1372389b74".to_owned(),
+                true,
+                10,
+                4085,
+                expiry_time.into(),
+            )),
+            LeaderStatus::GenesisPraos(LeaderKesStatus::unregistered(
+                leader_id.next(),
+                "ea830e5d9647af89a5e9a4d4089e6e855891a533316adf4a42b7bf1372389b74".to_owned(),
+            )),
+            LeaderStatus::Bft {
+                enclave_leader_id: leader_id.next().next(),
+            },
+        ];
+
+        let json = serde_json::to_string(&statuses).unwrap();
+        let decoded: Vec<LeaderStatus> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, statuses);
+        assert_eq!(
+            serde_json::to_value(&statuses[2]).unwrap(),
+            serde_json::json!({ "enclave_leader_id": 3 })
+        );
+    }
+}
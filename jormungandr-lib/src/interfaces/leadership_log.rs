@@ -23,6 +23,11 @@ pub enum LeadershipLogStatus {
         parent: Hash,
         chain_length: u32,
     },
+    /// the enclave refused to sign the block, the leader already signed a
+    /// block for the slot `last_signed` which is not before this one
+    SigningRefused {
+        last_signed: BlockDate,
+    },
 }
 
 /// provides information regarding events in the leadership schedule
@@ -38,6 +43,15 @@ pub struct LeadershipLog {
     enclave_leader_id: EnclaveLeaderId,
 }
 
+/// a slot assigned to one of the leaders of the enclave in the leadership
+/// schedule of an epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeadershipScheduleEntry {
+    enclave_leader_id: EnclaveLeaderId,
+    scheduled_at_date: BlockDate,
+    scheduled_at_time: SystemTime,
+}
+
 impl EnclaveLeaderId {
     pub fn new() -> Self {
         EnclaveLeaderId(0)
@@ -127,6 +141,30 @@ impl LeadershipLog {
     }
 }
 
+impl LeadershipScheduleEntry {
+    pub fn new(
+        enclave_leader_id: EnclaveLeaderId,
+        scheduled_at_date: BlockDate,
+        scheduled_at_time: SystemTime,
+    ) -> Self {
+        LeadershipScheduleEntry {
+            enclave_leader_id,
+            scheduled_at_date,
+            scheduled_at_time,
+        }
+    }
+
+    pub fn enclave_leader_id(&self) -> &EnclaveLeaderId {
+        &self.enclave_leader_id
+    }
+    pub fn scheduled_at_date(&self) -> &BlockDate {
+        &self.scheduled_at_date
+    }
+    pub fn scheduled_at_time(&self) -> &SystemTime {
+        &self.scheduled_at_time
+    }
+}
+
 impl fmt::Display for EnclaveLeaderId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.0.fmt(f)
@@ -7,6 +7,7 @@ mod certificate;
 mod committee;
 mod config;
 mod fragment_log;
+mod leader_kes_status;
 mod leadership_log;
 mod linear_fee;
 mod old_address;
@@ -38,14 +39,19 @@ pub use self::certificate::{
 };
 pub use self::committee::CommitteeIdDef;
 pub use self::config::*;
-pub use self::fragment_log::{FragmentLog, FragmentOrigin, FragmentStatus};
+pub use self::fragment_log::{
+    AccountPendingFragment, FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission,
+    FragmentSubmissionStatus, PendingFragment,
+};
+pub use self::leader_kes_status::{LeaderKesStatus, LeaderStatus};
 pub use self::leadership_log::{
-    EnclaveLeaderId, LeadershipLog, LeadershipLogId, LeadershipLogStatus,
+    EnclaveLeaderId, LeadershipLog, LeadershipLogId, LeadershipLogStatus, LeadershipScheduleEntry,
 };
 pub use self::linear_fee::LinearFeeDef;
 pub use self::old_address::OldAddress;
 pub use self::peer_stats::{
-    Info, Logs, PeerRecord, PeerStats, Profile, Record, Strike, Subscription, When,
+    Info, Logs, PeerAllowList, PeerRecord, PeerReputation, PeerStats, Profile, Record, Strike,
+    Subscription, When,
 };
 pub use self::ratio::{ParseRatioError, Ratio};
 pub use self::reward_parameters::RewardParams;
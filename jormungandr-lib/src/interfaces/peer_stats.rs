@@ -1,4 +1,4 @@
-use crate::time::{SecondsSinceUnixEpoch, SystemTime};
+use crate::time::{Duration, SecondsSinceUnixEpoch, SystemTime};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -13,6 +13,36 @@ pub struct PeerStats {
     pub last_gossip_received: Option<SystemTime>,
 }
 
+/// the allow-list of a node in permissioned mode, and the number of attempts
+/// of peers outside of it it rejected since the node started
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PeerAllowList {
+    pub addresses: Vec<String>,
+    pub identities: Vec<String>,
+    /// TLS connections refused for the identity of the peer
+    pub rejected_connections: u64,
+    /// subscriptions refused for the address of the peer
+    pub rejected_subscriptions: u64,
+    /// nodes dropped from the received gossip
+    pub rejected_gossip_nodes: u64,
+}
+
+/// the reputation of a peer, scored from the blocks it delivered and the
+/// protocol violations it committed
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct PeerReputation {
+    /// the IP address of the peer, the reputation is kept by IP address
+    pub address: String,
+    pub score: i32,
+    /// smoothed round trip time of the protocol handshakes with the peer
+    pub latency: Option<Duration>,
+    pub violations: u32,
+    /// the peer is banned until this time
+    pub banned_until: Option<SystemTime>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct PeerRecord {
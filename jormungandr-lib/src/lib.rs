@@ -5,4 +5,5 @@ extern crate quickcheck;
 pub mod crypto;
 pub mod interfaces;
 pub mod multiaddr;
+pub mod signer;
 pub mod time;
@@ -0,0 +1,152 @@
+//! Protocol spoken between the node and an external signer, the process
+//! holding the secret keys of the node's leaders.
+//!
+//! The node connects to the signer over a local socket and sends one
+//! [`Request`] at a time, waiting for its [`Response`] before sending the
+//! next one. Every message is framed as its length, a big endian `u32`,
+//! followed by its bincode encoding.
+//!
+//! The signer evaluates the leadership of the epochs itself: the node sends
+//! the ledger state the epoch leadership is created from, so the VRF keys do
+//! not have to leave the signer.
+//!
+//! [`Request`]: ./enum.Request.html
+//! [`Response`]: ./enum.Response.html
+
+use crate::interfaces::{BlockDate, EnclaveLeaderId};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use thiserror::Error;
+
+/// maximum size of a message, the serialized ledger states sent with
+/// [`Request::SetLeadership`] can be large.
+///
+/// [`Request::SetLeadership`]: ./enum.Request.html#variant.SetLeadership
+pub const MAX_MESSAGE_SIZE: u32 = 1 << 30;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error on the connection with the signer")]
+    Io(#[from] io::Error),
+    #[error("cannot encode or decode the message")]
+    Encoding(#[from] bincode::Error),
+    #[error("the message is too large ({0} bytes)")]
+    TooLarge(u64),
+}
+
+/// a request sent by the node to the signer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    /// list the leaders held by the signer
+    Leaders,
+    /// set the leadership the following `Evaluate` requests of the
+    /// connection are computed with: the epoch and the ledger state the
+    /// epoch leadership is created from, serialized.
+    SetLeadership { epoch: u32, ledger: Vec<u8> },
+    /// evaluate the leadership of all the leaders for `nb_slots` slots of
+    /// the epoch, starting at the slot `slot_start`
+    Evaluate { slot_start: u32, nb_slots: u32 },
+    /// sign the authenticated data of a BFT block header for the given
+    /// date. The signer refuses to sign a header for another date, or for a
+    /// date at or before the last one it signed for the leader.
+    SignBft {
+        leader_id: EnclaveLeaderId,
+        date: BlockDate,
+        data: Vec<u8>,
+    },
+    /// sign the authenticated data of a Genesis-Praos block header for the
+    /// given date, with the same checks as `SignBft`
+    SignGenesisPraos {
+        leader_id: EnclaveLeaderId,
+        date: BlockDate,
+        data: Vec<u8>,
+    },
+}
+
+/// the response of the signer to a [`Request`]
+///
+/// [`Request`]: ./enum.Request.html
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Response {
+    /// the leaders held by the signer
+    Leaders(Vec<Leader>),
+    /// the leadership was set
+    LeadershipSet,
+    /// the slots the leaders are elected for, ordered by date
+    Events(Vec<LeaderEvent>),
+    /// the signature of the header data
+    Signature(Vec<u8>),
+    /// the leader is not held by the signer, or does not have the key
+    /// required for the request
+    UnknownLeader,
+    /// the request failed
+    Error(String),
+}
+
+/// a leader held by the signer, with the public keys identifying it on the
+/// blockchain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Leader {
+    pub leader_id: EnclaveLeaderId,
+    /// the public key of the BFT leader, if the signer holds its key
+    pub bft_leader: Option<Vec<u8>>,
+    /// the stake pool identifier, if the signer holds the Genesis-Praos keys
+    /// of the leader
+    pub pool_id: Option<Vec<u8>>,
+    /// the public KES key of the Genesis-Praos leader, so the node can tell
+    /// whether it is the one registered for the stake pool
+    pub kes_public_key: Option<Vec<u8>>,
+}
+
+/// a slot a leader of the signer is elected for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderEvent {
+    pub leader_id: EnclaveLeaderId,
+    pub date: BlockDate,
+    pub output: LeaderOutput,
+}
+
+/// the proof of the election of a leader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LeaderOutput {
+    /// the public key of the BFT leader
+    Bft { leader: Vec<u8> },
+    /// the stake pool identifier and the VRF proof of the election
+    GenesisPraos {
+        pool_id: Vec<u8>,
+        vrf_proof: Vec<u8>,
+    },
+}
+
+/// write a framed message
+pub fn write_message<W, T>(writer: &mut W, message: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: Serialize,
+{
+    let bytes = bincode::serialize(message)?;
+    if bytes.len() as u64 > MAX_MESSAGE_SIZE as u64 {
+        return Err(Error::TooLarge(bytes.len() as u64));
+    }
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// read a framed message
+pub fn read_message<R, T>(reader: &mut R) -> Result<T, Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MESSAGE_SIZE {
+        return Err(Error::TooLarge(len as u64));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bincode::deserialize(&bytes)?)
+}
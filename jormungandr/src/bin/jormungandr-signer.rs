@@ -0,0 +1,353 @@
+//! reference external signer for jormungandr.
+//!
+//! Holds the secret keys of the leaders and serves the node configured with
+//! `leadership.external_signer` over a Unix socket, see
+//! `jormungandr_lib::signer` for the protocol.
+
+use chain_core::property::Deserialize as _;
+use chain_crypto::{Blake2b256, Ed25519, SumEd25519_12, VerificationAlgorithm};
+use chain_impl_mockchain::{
+    block::{BlockDate, Header, Proof},
+    leadership::{BftLeader, GenesisLeader, Leader, LeaderOutput, Leadership},
+    ledger::Ledger,
+};
+use jormungandr_lib::{
+    interfaces::{self, EnclaveLeaderId as LeaderId, NodeSecret},
+    signer::{self, Request, Response},
+};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(name = "jormungandr-signer", rename_all = "kebab-case")]
+struct CommandLine {
+    /// path of the Unix socket to listen on
+    #[structopt(long = "socket", parse(from_os_str))]
+    socket: PathBuf,
+
+    /// the secret files of the leaders held by the signer
+    #[structopt(long = "secret", parse(from_os_str), required = true)]
+    secrets: Vec<PathBuf>,
+
+    /// file keeping the date of the last block signed by each leader, the
+    /// signer refuses to sign again for this date or an earlier one
+    #[structopt(long = "signing-history", parse(from_os_str))]
+    signing_history: PathBuf,
+}
+
+type Leaders = Arc<BTreeMap<LeaderId, Leader>>;
+
+fn load_leaders(secrets: &[PathBuf]) -> Result<Leaders, Box<dyn Error>> {
+    let mut leaders = BTreeMap::new();
+    let mut leader_id = LeaderId::new();
+    for path in secrets {
+        let secret: NodeSecret = serde_yaml::from_reader(File::open(path)?)?;
+        let leader = Leader {
+            bft_leader: secret.bft.map(|bft| BftLeader {
+                sig_key: bft.signing_key.into_secret_key(),
+            }),
+            genesis_leader: secret.genesis.map(|genesis| GenesisLeader {
+                node_id: Blake2b256::from(genesis.node_id).into(),
+                sig_key: genesis.sig_key.into_secret_key(),
+                vrf_key: genesis.vrf_key.into_secret_key(),
+            }),
+        };
+        leader_id = leader_id.next();
+        leaders.insert(leader_id, leader);
+    }
+    Ok(Arc::new(leaders))
+}
+
+/// the date of the last block signed by each leader, by public identity of
+/// the leader: the BFT public key or the stake pool identifier
+struct SigningHistory {
+    path: PathBuf,
+    last_signed: BTreeMap<String, interfaces::BlockDate>,
+}
+
+impl SigningHistory {
+    fn load(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let last_signed = match File::open(&path) {
+            Ok(file) => serde_json::from_reader(file)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(SigningHistory { path, last_signed })
+    }
+
+    /// record that the leader is about to sign a block for the given date,
+    /// the history is written to the file before the block is signed
+    fn record(&mut self, leader: String, date: interfaces::BlockDate) -> Result<(), String> {
+        if let Some(last_signed) = self.last_signed.get(&leader) {
+            if date <= *last_signed {
+                return Err(format!(
+                    "the leader already signed a block for {}, refusing to sign for {}",
+                    last_signed, date
+                ));
+            }
+        }
+        let previous = self.last_signed.insert(leader.clone(), date);
+        if let Err(err) = self.write() {
+            match previous {
+                Some(previous) => self.last_signed.insert(leader, previous),
+                None => self.last_signed.remove(&leader),
+            };
+            return Err(format!("cannot write the signing history: {}", err));
+        }
+        Ok(())
+    }
+
+    fn write(&self) -> Result<(), Box<dyn Error>> {
+        // the file is replaced at once, so a crash cannot leave it truncated
+        let tmp_path = self.path.with_extension("tmp");
+        let file = File::create(&tmp_path)?;
+        serde_json::to_writer(&file, &self.last_signed)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// decode the authenticated data of a header and check it is for the given
+/// date. The signature is not part of the authenticated data, a blank one is
+/// appended so the data can be decoded as a header.
+fn decode_header<A: VerificationAlgorithm>(
+    data: &[u8],
+    date: interfaces::BlockDate,
+) -> Result<Header, String> {
+    let mut bytes = data.to_vec();
+    bytes.resize(data.len() + A::SIGNATURE_SIZE, 0);
+    let header = Header::deserialize(bytes.as_slice())
+        .map_err(|err| format!("invalid header data: {}", err))?;
+    if header.block_date() != date.into() {
+        return Err(format!(
+            "the header is for {}, not for {}",
+            interfaces::BlockDate::from(header.block_date()),
+            date
+        ));
+    }
+    Ok(header)
+}
+
+/// state of a connection with a node
+struct Session {
+    leaders: Leaders,
+    leadership: Option<Leadership>,
+    signing_history: Arc<Mutex<SigningHistory>>,
+}
+
+impl Session {
+    fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Leaders => Response::Leaders(
+                self.leaders
+                    .iter()
+                    .map(|(leader_id, leader)| signer::Leader {
+                        leader_id: *leader_id,
+                        bft_leader: leader
+                            .bft_leader
+                            .as_ref()
+                            .map(|l| l.sig_key.to_public().as_ref().to_vec()),
+                        pool_id: leader
+                            .genesis_leader
+                            .as_ref()
+                            .map(|l| l.node_id.as_ref().to_vec()),
+                        kes_public_key: leader
+                            .genesis_leader
+                            .as_ref()
+                            .map(|l| l.sig_key.to_public().as_ref().to_vec()),
+                    })
+                    .collect(),
+            ),
+            Request::SetLeadership { epoch, ledger } => {
+                match Ledger::deserialize(ledger.as_slice()) {
+                    Ok(ledger) => {
+                        self.leadership = Some(Leadership::new(epoch, &ledger));
+                        Response::LeadershipSet
+                    }
+                    Err(err) => Response::Error(format!("invalid ledger state: {}", err)),
+                }
+            }
+            Request::Evaluate {
+                slot_start,
+                nb_slots,
+            } => match &self.leadership {
+                Some(leadership) => {
+                    Response::Events(self.evaluate(leadership, slot_start, nb_slots))
+                }
+                None => Response::Error("the leadership is not set".to_owned()),
+            },
+            Request::SignBft {
+                leader_id,
+                date,
+                data,
+            } => match self
+                .leaders
+                .get(&leader_id)
+                .and_then(|l| l.bft_leader.as_ref())
+            {
+                Some(leader) => {
+                    let result = decode_header::<Ed25519>(&data, date).and_then(|header| {
+                        match header.proof() {
+                            Proof::Bft(_) => (),
+                            _ => return Err("not a BFT header".to_owned()),
+                        }
+                        self.signing_history
+                            .lock()
+                            .unwrap()
+                            .record(leader.sig_key.to_public().to_string(), date)
+                    });
+                    match result {
+                        Ok(()) => {
+                            Response::Signature(leader.sig_key.sign_slice(&data).as_ref().to_vec())
+                        }
+                        Err(reason) => Response::Error(reason),
+                    }
+                }
+                None => Response::UnknownLeader,
+            },
+            Request::SignGenesisPraos {
+                leader_id,
+                date,
+                data,
+            } => match self
+                .leaders
+                .get(&leader_id)
+                .and_then(|l| l.genesis_leader.as_ref())
+            {
+                Some(leader) => {
+                    let result = decode_header::<SumEd25519_12>(&data, date).and_then(|header| {
+                        if header.get_stakepool_id() != Some(leader.node_id.clone()) {
+                            return Err("not a header of the stake pool of the leader".to_owned());
+                        }
+                        self.signing_history
+                            .lock()
+                            .unwrap()
+                            .record(leader.node_id.to_string(), date)
+                    });
+                    match result {
+                        Ok(()) => {
+                            Response::Signature(leader.sig_key.sign_slice(&data).as_ref().to_vec())
+                        }
+                        Err(reason) => Response::Error(reason),
+                    }
+                }
+                None => Response::UnknownLeader,
+            },
+        }
+    }
+
+    fn evaluate(
+        &self,
+        leadership: &Leadership,
+        slot_start: u32,
+        nb_slots: u32,
+    ) -> Vec<signer::LeaderEvent> {
+        let mut events = Vec::new();
+        for slot in slot_start..slot_start + nb_slots {
+            let date: BlockDate = leadership.date_at_slot(slot);
+            for (leader_id, leader) in self.leaders.iter() {
+                let output = match leadership.is_leader_for_date(leader, date) {
+                    Ok(LeaderOutput::Bft(leader)) => signer::LeaderOutput::Bft {
+                        leader: leader.as_public_key().as_ref().to_vec(),
+                    },
+                    Ok(LeaderOutput::GenesisPraos(pool_id, vrf_proof)) => {
+                        signer::LeaderOutput::GenesisPraos {
+                            pool_id: pool_id.as_ref().to_vec(),
+                            vrf_proof: vrf_proof.bytes().to_vec(),
+                        }
+                    }
+                    Ok(LeaderOutput::None) | Err(_) => continue,
+                };
+                events.push(signer::LeaderEvent {
+                    leader_id: *leader_id,
+                    date: date.into(),
+                    output,
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(unix)]
+fn serve(
+    socket: &Path,
+    leaders: Leaders,
+    signing_history: SigningHistory,
+) -> Result<(), Box<dyn Error>> {
+    use nix::sys::stat::{umask, Mode};
+    use std::os::unix::net::UnixListener;
+
+    // a socket left behind by a previous run would prevent binding
+    if socket.exists() {
+        std::fs::remove_file(socket)?;
+    }
+    // the socket gives access to the keys of the leaders, it is created with
+    // the mode 0600 so only the user running the signer can connect to it
+    let previous_umask = umask(Mode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(socket);
+    umask(previous_umask);
+    let listener = listener?;
+    eprintln!(
+        "serving {} leader(s) on {}",
+        leaders.len(),
+        socket.display()
+    );
+
+    // the history is shared between the connections, so two nodes connected
+    // to the signer cannot sign for the same slot
+    let signing_history = Arc::new(Mutex::new(signing_history));
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let mut session = Session {
+            leaders: Arc::clone(&leaders),
+            leadership: None,
+            signing_history: Arc::clone(&signing_history),
+        };
+        std::thread::spawn(move || loop {
+            let request = match signer::read_message(&mut stream) {
+                Ok(request) => request,
+                Err(signer::Error::Io(_)) => break,
+                Err(err) => {
+                    eprintln!("invalid request: {}", err);
+                    break;
+                }
+            };
+            let response = session.handle(request);
+            if let Err(err) = signer::write_message(&mut stream, &response) {
+                eprintln!("cannot send the response: {}", err);
+                break;
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(
+    _socket: &Path,
+    _leaders: Leaders,
+    _signing_history: SigningHistory,
+) -> Result<(), Box<dyn Error>> {
+    Err("the signer is only supported on Unix platforms".into())
+}
+
+fn main() {
+    let command_line = CommandLine::from_args();
+    let result = load_leaders(&command_line.secrets).and_then(|leaders| {
+        let signing_history = SigningHistory::load(command_line.signing_history)?;
+        serve(&command_line.socket, leaders, signing_history)
+    });
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
@@ -49,7 +49,11 @@ See Internal documentation for more details: doc/internal_design.md
 [`Branch`]: ./struct.Branch.html
 */
 
-use super::{branch::Branches, reference_cache::RefCache};
+use super::{
+    branch::Branches,
+    reference_cache::RefCache,
+    snapshot::{LedgerSnapshot, LedgerSnapshots},
+};
 use crate::{
     blockcfg::{
         Block, Block0Error, BlockDate, ChainLength, Epoch, EpochRewardsInfo, Header, HeaderHash,
@@ -60,8 +64,8 @@ use crate::{
 use chain_impl_mockchain::{leadership::Verification, ledger};
 use chain_time::TimeFrame;
 use slog::Logger;
-use std::sync::Arc;
-use tokio::stream::StreamExt;
+use std::{path::PathBuf, sync::Arc};
+use tokio::{stream::StreamExt, task::spawn_blocking};
 
 // derive
 use thiserror::Error;
@@ -114,6 +118,11 @@ error_chain! {
         CannotApplyBlock {
             description("Block cannot be applied on top of the previous block's ledger state"),
         }
+
+        LedgerSnapshotNotOnMainBranch (hash: HeaderHash) {
+            description("The ledger snapshot is not an ancestor of the storage's HEAD"),
+            display("The ledger snapshot's block '{}' is not an ancestor of the storage's HEAD", hash),
+        }
     }
 }
 
@@ -130,6 +139,9 @@ pub enum HeaderChainVerifyError {
 
 pub const MAIN_BRANCH_TAG: &str = "HEAD";
 
+/// minimum number of blocks between two consecutive ledger snapshots
+const LEDGER_SNAPSHOT_MIN_DISTANCE: u32 = 1_000;
+
 /// Performs lightweight sanity checks on information fields of a block header
 /// against those in the header of the block's parent.
 /// The `parent` header must have been retrieved based on, or otherwise
@@ -184,6 +196,8 @@ pub struct Blockchain {
     block0: HeaderHash,
 
     rewards_report_all: bool,
+
+    ledger_snapshots: Option<LedgerSnapshots>,
 }
 
 pub enum PreCheckedHeader {
@@ -225,6 +239,7 @@ pub enum PreCheckedHeader {
 pub struct PostCheckedHeader {
     header: Header,
     epoch_leadership_schedule: Arc<Leadership>,
+    epoch_leadership_ledger: Arc<Ledger>,
     epoch_ledger_parameters: Arc<LedgerParameters>,
     parent_ledger_state: Arc<Ledger>,
     time_frame: Arc<TimeFrame>,
@@ -271,6 +286,7 @@ impl Blockchain {
         storage: Storage,
         cache_capacity: usize,
         rewards_report_all: bool,
+        ledger_snapshots: Option<LedgerSnapshots>,
     ) -> Self {
         Blockchain {
             branches: Branches::new(),
@@ -279,6 +295,7 @@ impl Blockchain {
             storage,
             block0,
             rewards_report_all,
+            ledger_snapshots,
         }
     }
 
@@ -290,6 +307,11 @@ impl Blockchain {
         &self.storage
     }
 
+    /// tells if the rewards distributed to every account are recorded
+    pub fn rewards_report_all(&self) -> bool {
+        self.rewards_report_all
+    }
+
     pub fn branches(&self) -> &Branches {
         &self.branches
     }
@@ -306,6 +328,40 @@ impl Blockchain {
         ledger: Ledger,
         time_frame: Arc<TimeFrame>,
         leadership: Arc<Leadership>,
+        leadership_ledger: Arc<Ledger>,
+        epoch_rewards_info: Option<Arc<EpochRewardsInfo>>,
+        ledger_parameters: Arc<LedgerParameters>,
+        previous_epoch_state: Option<Arc<Ref>>,
+    ) -> Arc<Ref> {
+        let reference = self
+            .create_reference(
+                header_hash,
+                header,
+                ledger,
+                time_frame,
+                leadership,
+                leadership_ledger,
+                epoch_rewards_info,
+                ledger_parameters,
+                previous_epoch_state,
+            )
+            .await;
+        self.ref_cache
+            .insert(header_hash, Arc::clone(&reference))
+            .await;
+        reference
+    }
+
+    /// same as `create_and_store_reference` but the `Ref` is not added to
+    /// the cache, it cannot be found with `get_ref`.
+    async fn create_reference(
+        &self,
+        header_hash: HeaderHash,
+        header: Header,
+        ledger: Ledger,
+        time_frame: Arc<TimeFrame>,
+        leadership: Arc<Leadership>,
+        leadership_ledger: Arc<Ledger>,
         epoch_rewards_info: Option<Arc<EpochRewardsInfo>>,
         ledger_parameters: Arc<LedgerParameters>,
         previous_epoch_state: Option<Arc<Ref>>,
@@ -313,21 +369,19 @@ impl Blockchain {
         let chain_length = header.chain_length();
 
         let multiverse = self.ledgers.clone();
-        let ref_cache = self.ref_cache.clone();
 
         let ledger_ref = multiverse.insert(chain_length, header_hash, ledger).await;
         let reference = Ref::new(
             ledger_ref,
             time_frame,
             leadership,
+            leadership_ledger,
             ledger_parameters,
             epoch_rewards_info,
             header,
             previous_epoch_state,
         );
-        let reference = Arc::new(reference);
-        ref_cache.insert(header_hash, Arc::clone(&reference)).await;
-        reference
+        Arc::new(reference)
     }
 
     /// get `Ref` of the given header hash
@@ -431,6 +485,7 @@ impl Blockchain {
         let (
             parent_ledger_state,
             epoch_leadership_schedule,
+            epoch_leadership_ledger,
             epoch_ledger_parameters,
             epoch_rewards_info,
             time_frame,
@@ -449,6 +504,7 @@ impl Blockchain {
         Ok(PostCheckedHeader {
             header,
             epoch_leadership_schedule,
+            epoch_leadership_ledger,
             epoch_ledger_parameters,
             epoch_rewards_info,
             parent_ledger_state,
@@ -484,6 +540,7 @@ impl Blockchain {
         let header = post_checked_header.header;
         let block_id = header.hash();
         let epoch_leadership_schedule = post_checked_header.epoch_leadership_schedule;
+        let epoch_leadership_ledger = post_checked_header.epoch_leadership_ledger;
         let epoch_rewards_info = post_checked_header.epoch_rewards_info;
         let epoch_ledger_parameters = post_checked_header.epoch_ledger_parameters;
         let time_frame = post_checked_header.time_frame;
@@ -495,6 +552,7 @@ impl Blockchain {
             new_ledger,
             time_frame,
             epoch_leadership_schedule,
+            epoch_leadership_ledger,
             epoch_rewards_info,
             epoch_ledger_parameters,
             previous_epoch_state,
@@ -549,21 +607,7 @@ impl Blockchain {
 
         let mut branches = self.branches.clone();
 
-        let time_frame = {
-            use crate::blockcfg::Block0DataSource as _;
-
-            let start_time = block0
-                .start_time()
-                .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
-            let slot_duration = block0
-                .slot_duration()
-                .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
-
-            TimeFrame::new(
-                chain_time::Timeline::new(start_time),
-                chain_time::SlotDuration::from_secs(slot_duration.as_secs() as u32),
-            )
-        };
+        let time_frame = block0_time_frame(block0)?;
 
         // we lift the creation of the ledger in the future type
         // this allow chaining of the operation and lifting the error handling
@@ -572,6 +616,7 @@ impl Blockchain {
             .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
         let block0_leadership = Leadership::new(block0_date.epoch, &block0_ledger);
         let ledger_parameters = block0_leadership.ledger_parameters().clone();
+        let block0_leadership_ledger = Arc::new(block0_ledger.clone());
 
         let b = self
             .create_and_store_reference(
@@ -580,6 +625,7 @@ impl Blockchain {
                 block0_ledger,
                 Arc::new(time_frame),
                 Arc::new(block0_leadership),
+                block0_leadership_ledger,
                 None, // block0 has no reward distribution
                 Arc::new(ledger_parameters),
                 None,
@@ -637,6 +683,11 @@ impl Blockchain {
     /// from the block0 to the `Head` of the storage (the last known block which
     /// made consensus).
     ///
+    /// If ledger snapshots are enabled, the most recent valid snapshot is used
+    /// as the starting point and only the blocks after it are applied. A
+    /// snapshot that cannot be read or does not match the content of the
+    /// storage is skipped and the node falls back to replaying from the block0.
+    ///
     /// The Future will returns a branch pointing to the `Head`.
     ///
     /// # Errors
@@ -671,16 +722,183 @@ impl Blockchain {
             return Err(ErrorKind::NoTag(MAIN_BRANCH_TAG.to_owned()).into());
         };
 
+        if let Some(branch) = self
+            .load_from_ledger_snapshots(&block0, head_hash, logger)
+            .await
+        {
+            return Ok(branch);
+        }
+
         let block0_branch = self.apply_block0(&block0).await?;
 
+        self.apply_blocks_from_storage(block0_branch, block0_id, head_hash, logger)
+            .await
+    }
+
+    /// try the available ledger snapshots, most recent first, and return the
+    /// branch pointing to the `Head` of the storage built from the first one
+    /// that could be loaded. Returns `None` if snapshots are disabled or if
+    /// none of them could be used.
+    async fn load_from_ledger_snapshots(
+        &self,
+        block0: &Block,
+        head_hash: HeaderHash,
+        logger: &Logger,
+    ) -> Option<Branch> {
+        let snapshots = self.ledger_snapshots.as_ref()?;
+
+        let available = match snapshots.list() {
+            Ok(available) => available,
+            Err(err) => {
+                warn!(
+                    logger,
+                    "cannot list the ledger snapshots, replaying the blockchain from block0";
+                    "directory" => ?snapshots.dir(),
+                    "reason" => %err,
+                );
+                return None;
+            }
+        };
+
+        for (_, path) in available {
+            info!(logger, "loading ledger snapshot"; "path" => ?path);
+            let result = async {
+                let snapshot = self.read_ledger_snapshot(path.clone()).await?;
+                let from = snapshot.header.hash();
+                let branch = self
+                    .load_from_ledger_snapshot(block0, snapshot, head_hash)
+                    .await?;
+                self.apply_blocks_from_storage(branch, from, head_hash, logger)
+                    .await
+            }
+            .await;
+
+            match result {
+                Ok(branch) => {
+                    self.branches.clone().add(branch.clone()).await;
+                    return Some(branch);
+                }
+                Err(err) => {
+                    warn!(
+                        logger,
+                        "cannot resume from the ledger snapshot, skipping it";
+                        "path" => ?path,
+                        "reason" => %err,
+                    );
+                }
+            }
+        }
+        None
+    }
+
+    async fn read_ledger_snapshot(&self, path: PathBuf) -> Result<LedgerSnapshot> {
+        let snapshots = self
+            .ledger_snapshots
+            .clone()
+            .expect("ledger snapshots to be enabled");
+        let block0 = self.block0;
+        let rewards_report_all = self.rewards_report_all;
+
+        spawn_blocking(move || snapshots.read(&path, block0, rewards_report_all))
+            .await
+            .unwrap()
+            .map_err(|err| Error::with_chain(err, "Cannot read the ledger snapshot"))
+    }
+
+    /// recreate the `Ref` of the block of the given snapshot and return a
+    /// branch pointing to it. The branch is not added to the blockchain's
+    /// branches.
+    async fn load_from_ledger_snapshot(
+        &self,
+        block0: &Block,
+        snapshot: LedgerSnapshot,
+        head_hash: HeaderHash,
+    ) -> Result<Branch> {
+        let LedgerSnapshot {
+            header,
+            ledger,
+            previous_epoch_header,
+            previous_epoch_ledger,
+            leadership_ledger,
+            epoch_rewards_info,
+        } = snapshot;
+        let hash = header.hash();
+
+        if hash != head_hash {
+            let ancestor = self
+                .storage
+                .find_closest_ancestor(vec![hash], head_hash)
+                .await
+                .map_err(|e| Error::with_chain(e, "Cannot check the ledger snapshot's block"))?;
+            if ancestor.is_none() {
+                return Err(ErrorKind::LedgerSnapshotNotOnMainBranch(hash).into());
+            }
+        }
+
+        let time_frame = Arc::new(block0_time_frame(block0)?);
+        let leadership = Arc::new(Leadership::new(
+            header.block_date().epoch,
+            &leadership_ledger,
+        ));
+        let ledger_parameters = Arc::new(leadership.ledger_parameters().clone());
+        let leadership_ledger = Arc::new(leadership_ledger);
+
+        // only the ledger state of the previous epoch's `Ref` is used when
+        // processing the following blocks, so it can share the leadership
+        // of the snapshot's epoch. It is kept out of the cache: its leadership
+        // is not the one of its own epoch, the blocks forking from it must
+        // not be checked against it
+        let previous_epoch_state = self
+            .create_reference(
+                previous_epoch_header.hash(),
+                previous_epoch_header,
+                previous_epoch_ledger,
+                Arc::clone(&time_frame),
+                Arc::clone(&leadership),
+                Arc::clone(&leadership_ledger),
+                None,
+                Arc::clone(&ledger_parameters),
+                None,
+            )
+            .await;
+
+        let reference = self
+            .create_and_store_reference(
+                hash,
+                header,
+                ledger,
+                time_frame,
+                leadership,
+                leadership_ledger,
+                epoch_rewards_info.map(Arc::new),
+                ledger_parameters,
+                Some(previous_epoch_state),
+            )
+            .await;
+
+        Ok(Branch::new(reference))
+    }
+
+    /// apply the blocks of the storage between `from` (excluded) and `to`
+    /// on top of the given branch. The branch is expected to point to `from`.
+    async fn apply_blocks_from_storage(
+        &self,
+        mut branch: Branch,
+        from: HeaderHash,
+        to: HeaderHash,
+        logger: &Logger,
+    ) -> Result<Branch> {
+        if from == to {
+            return Ok(branch);
+        }
+
         let mut block_stream = self
             .storage
-            .stream_from_to(block0_id, head_hash)
+            .stream_from_to(from, to)
             .await
             .map(Box::pin)
-            .map_err(|e| Error::with_chain(e, "Cannot iterate blocks from block0 to HEAD"))?;
+            .map_err(|e| Error::with_chain(e, "Cannot iterate blocks from storage up to HEAD"))?;
 
-        let mut branch = block0_branch;
         let mut count = 0u64;
 
         let mut block_processing = std::time::Duration::from_secs(0);
@@ -690,7 +908,7 @@ impl Blockchain {
                 Err(e) => {
                     return Err(Error::with_chain(
                         e,
-                        "Error while iterating between block0 and HEAD",
+                        "Error while iterating blocks from storage up to HEAD",
                     ))
                 }
                 Ok(block) => {
@@ -756,11 +974,79 @@ impl Blockchain {
         Ok(branch)
     }
 
+    /// write a snapshot of the ledger state of the block that is
+    /// `epoch_stability_depth` blocks behind the given tip.
+    ///
+    /// Nothing is written if the snapshots are disabled, if the stable
+    /// block is too close to the last snapshot, or if its `Ref` or the
+    /// state of its previous epoch is not available in memory.
+    pub async fn write_ledger_snapshot(&self, tip: Arc<Ref>, logger: &Logger) -> Result<()> {
+        let snapshots = match &self.ledger_snapshots {
+            Some(snapshots) => snapshots.clone(),
+            None => return Ok(()),
+        };
+
+        let epoch_stability_depth = tip.epoch_ledger_parameters().epoch_stability_depth;
+        let mut stable = tip;
+        for _ in 0..epoch_stability_depth {
+            match self.ref_cache.get(stable.block_parent_hash()).await {
+                Some(parent) => stable = parent,
+                None => return Ok(()),
+            }
+        }
+
+        // there is no previous epoch state to save in the epoch of the block0
+        if stable.last_ref_previous_epoch().is_none() {
+            return Ok(());
+        }
+        let leadership_ledger = Arc::clone(stable.epoch_leadership_ledger());
+
+        let chain_length: u32 = stable.chain_length().into();
+        let latest = snapshots
+            .latest_chain_length()
+            .map_err(|e| Error::with_chain(e, "Cannot list the ledger snapshots"))?;
+        if let Some(latest) = latest {
+            if chain_length < latest.saturating_add(LEDGER_SNAPSHOT_MIN_DISTANCE) {
+                return Ok(());
+            }
+        }
+
+        let block0 = self.block0;
+        let description = stable.header().description();
+        let path = spawn_blocking(move || snapshots.write(block0, &stable, &leadership_ledger))
+            .await
+            .unwrap()
+            .map_err(|e| Error::with_chain(e, "Cannot write the ledger snapshot"))?;
+
+        info!(
+            logger,
+            "ledger snapshot written at {}", description;
+            "path" => ?path,
+        );
+        Ok(())
+    }
+
     pub async fn get_checkpoints(&self, branch: &Branch) -> Checkpoints {
         Checkpoints::new_from(branch.get_ref().await)
     }
 }
 
+fn block0_time_frame(block0: &Block) -> Result<TimeFrame> {
+    use crate::blockcfg::Block0DataSource as _;
+
+    let start_time = block0
+        .start_time()
+        .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
+    let slot_duration = block0
+        .slot_duration()
+        .map_err(|err| Error::with_chain(err, ErrorKind::Block0InitialLedgerError))?;
+
+    Ok(TimeFrame::new(
+        chain_time::Timeline::new(start_time),
+        chain_time::SlotDuration::from_secs(slot_duration.as_secs() as u32),
+    ))
+}
+
 fn write_reward_info(
     epoch: Epoch,
     parent_hash: HeaderHash,
@@ -815,6 +1101,7 @@ pub fn new_epoch_leadership_from(
 ) -> (
     Arc<Ledger>,
     Arc<Leadership>,
+    Arc<Ledger>,
     Arc<LedgerParameters>,
     Option<Arc<EpochRewardsInfo>>,
     Arc<TimeFrame>,
@@ -822,6 +1109,7 @@ pub fn new_epoch_leadership_from(
 ) {
     let parent_ledger_state = parent.ledger().clone();
     let parent_epoch_leadership_schedule = parent.epoch_leadership_schedule().clone();
+    let parent_epoch_leadership_ledger = parent.epoch_leadership_ledger().clone();
     let parent_epoch_ledger_parameters = parent.epoch_ledger_parameters().clone();
     let parent_epoch_rewards_info = parent.epoch_rewards_info().cloned();
     let parent_time_frame = parent.time_frame().clone();
@@ -878,6 +1166,7 @@ pub fn new_epoch_leadership_from(
         (
             transition_state,
             leadership,
+            epoch_state,
             ledger_parameters,
             epoch_rewards_info,
             parent_time_frame,
@@ -887,6 +1176,7 @@ pub fn new_epoch_leadership_from(
         (
             parent_ledger_state,
             parent_epoch_leadership_schedule,
+            parent_epoch_leadership_ledger,
             parent_epoch_ledger_parameters,
             parent_epoch_rewards_info,
             parent_time_frame,
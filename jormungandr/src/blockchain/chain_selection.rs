@@ -1,9 +1,13 @@
-use crate::blockchain::{Ref, Storage};
+use crate::blockcfg::HeaderHash;
+use crate::blockchain::{Ref, Storage, StorageError};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
 pub enum ComparisonResult {
     PreferCurrent,
     PreferCandidate,
+    /// the candidate would have been preferred but switching to it would
+    /// roll back the current branch deeper than the `epoch_stability_depth`
+    RejectDeepFork,
 }
 
 /// chose which of the two Ref is the most interesting to keep as a branch
@@ -13,18 +17,35 @@ pub enum ComparisonResult {
 /// on the same block date is to a requirement to call this function as it will still
 /// work to make a choice as to which of these two Ref is the right choice.
 ///
-pub fn compare_against(storage: &Storage, current: &Ref, candidate: &Ref) -> ComparisonResult {
+/// A candidate forking from the current branch deeper than the
+/// `epoch_stability_depth` is never preferred.
+///
+pub async fn compare_against(
+    storage: &Storage,
+    current: &Ref,
+    candidate: &Ref,
+) -> Result<ComparisonResult, StorageError> {
     let epoch_stability_depth = current.epoch_ledger_parameters().epoch_stability_depth;
 
-    let rollback_possible =
-        check_rollback_up_to(epoch_stability_depth, storage, current, candidate);
-
     let not_in_future = !is_in_future(candidate);
 
-    if rollback_possible && not_in_future && current.chain_length() < candidate.chain_length() {
-        ComparisonResult::PreferCandidate
+    if !(not_in_future && current.chain_length() < candidate.chain_length()) {
+        return Ok(ComparisonResult::PreferCurrent);
+    }
+
+    let rollback_possible = check_rollback_up_to(
+        epoch_stability_depth,
+        storage,
+        current.hash(),
+        candidate.hash(),
+        candidate.block_parent_hash(),
+    )
+    .await?;
+
+    if rollback_possible {
+        Ok(ComparisonResult::PreferCandidate)
     } else {
-        ComparisonResult::PreferCurrent
+        Ok(ComparisonResult::RejectDeepFork)
     }
 }
 
@@ -34,11 +55,149 @@ fn is_in_future(node: &Ref) -> bool {
     node.elapsed().is_err()
 }
 
-fn check_rollback_up_to(
-    _epoch_stability_depth: u32,
-    _: &Storage,
-    _ref1: &Ref,
-    _ref2: &Ref,
-) -> bool {
-    true
+/// returns `true` if switching from `current` to `candidate` does not roll
+/// back more than `epoch_stability_depth` blocks of the current branch,
+/// i.e. if the ancestor of `current` at `epoch_stability_depth` is also an
+/// ancestor of `candidate`.
+async fn check_rollback_up_to(
+    epoch_stability_depth: u32,
+    storage: &Storage,
+    current: HeaderHash,
+    candidate: HeaderHash,
+    candidate_parent: HeaderHash,
+) -> Result<bool, StorageError> {
+    // the most common case: the candidate is extending the current branch
+    if candidate_parent == current {
+        return Ok(true);
+    }
+
+    let stable_ancestor = storage
+        .get_nth_ancestor(current, epoch_stability_depth as u64)
+        .await?;
+
+    match stable_ancestor {
+        // the current branch is not yet longer than the stability depth,
+        // any fork is still acceptable
+        None => Ok(true),
+        Some(stable_ancestor) => storage
+            .find_closest_ancestor(vec![stable_ancestor], candidate)
+            .await
+            .map(|ancestor| ancestor.is_some()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{
+        Block, BlockDate, BlockVersion, Contents, ContentsBuilder, HeaderBuilderNew,
+    };
+    use chain_storage::BlockStoreBuilder;
+    use slog::Logger;
+
+    const EPOCH_STABILITY_DEPTH: u32 = 3;
+
+    fn block0() -> Block {
+        let contents: Contents = ContentsBuilder::new().into();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_genesis()
+            .set_date(BlockDate::first())
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    /// the child of `parent`, `slot` tells apart the siblings of a fork
+    fn child(parent: &Block, slot: u32) -> Block {
+        let contents: Contents = ContentsBuilder::new().into();
+        let date = parent.header.block_date();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_parent(&parent.header.id(), parent.header.chain_length().increase())
+            .set_date(BlockDate {
+                epoch: date.epoch,
+                slot_id: date.slot_id + 1 + slot,
+            })
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    /// store a branch of `length` blocks on top of `parent`
+    async fn branch(storage: &Storage, parent: &Block, length: usize, slot: u32) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for i in 0..length {
+            let block = child(
+                blocks.last().unwrap_or(parent),
+                if i == 0 { slot } else { 0 },
+            );
+            storage.put_block(block.clone()).await.unwrap();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    async fn storage_with_block0() -> (Storage, Block) {
+        let logger = Logger::root(slog::Discard, o!());
+        let storage = Storage::new(BlockStoreBuilder::memory().build(), logger);
+        let block0 = block0();
+        storage.put_block(block0.clone()).await.unwrap();
+        (storage, block0)
+    }
+
+    async fn check(storage: &Storage, current: &Block, candidate: &Block) -> bool {
+        check_rollback_up_to(
+            EPOCH_STABILITY_DEPTH,
+            storage,
+            current.header.hash(),
+            candidate.header.hash(),
+            candidate.header.block_parent_hash(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fork_at_stability_depth_is_accepted() {
+        let (storage, block0) = storage_with_block0().await;
+        let main = branch(&storage, &block0, 5, 0).await;
+        let current = main.last().unwrap();
+        // forks from the ancestor of the tip at the stability depth
+        let fork_point = &main[main.len() - 1 - EPOCH_STABILITY_DEPTH as usize];
+        let fork = branch(&storage, fork_point, 4, 1).await;
+
+        assert!(check(&storage, current, fork.last().unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn fork_past_stability_depth_is_rejected() {
+        let (storage, block0) = storage_with_block0().await;
+        let main = branch(&storage, &block0, 5, 0).await;
+        let current = main.last().unwrap();
+        let fork_point = &main[main.len() - 2 - EPOCH_STABILITY_DEPTH as usize];
+        let fork = branch(&storage, fork_point, 5, 1).await;
+
+        assert!(!check(&storage, current, fork.last().unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn fork_without_stable_ancestor_is_accepted() {
+        let (storage, block0) = storage_with_block0().await;
+        // the current branch is not longer than the stability depth
+        let main = branch(&storage, &block0, EPOCH_STABILITY_DEPTH as usize - 1, 0).await;
+        let fork = branch(&storage, &block0, EPOCH_STABILITY_DEPTH as usize, 1).await;
+
+        assert!(check(&storage, main.last().unwrap(), fork.last().unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn fork_with_missing_ancestor_is_rejected() {
+        let (storage, block0) = storage_with_block0().await;
+        let main = branch(&storage, &block0, 5, 0).await;
+        // the candidate is not linked to the blocks in the storage
+        let orphan = child(&child(&block0, 1), 0);
+
+        assert!(!check(&storage, main.last().unwrap(), &orphan).await);
+    }
 }
@@ -28,6 +28,13 @@ impl<T> Multiverse<T> {
         let guard = self.inner.read().await;
         guard.get_ref(&hash)
     }
+
+    /// remove all the states from the multiverse, the states still referenced
+    /// elsewhere are kept alive by their `Ref`
+    pub async fn clear(&self) {
+        let mut guard = self.inner.write().await;
+        *guard = MultiverseData::new();
+    }
 }
 
 impl<T: Clone> Multiverse<T> {
@@ -9,7 +9,7 @@ use crate::{
     blockchain::Checkpoints,
     intercom::{self, BlockMsg, ExplorerMsg, NetworkMsg, PropagateMsg, TransactionMsg},
     log,
-    network::p2p::Address,
+    network::p2p::{Address, PeerEvent},
     stats_counter::StatsCounter,
     utils::{
         async_msg::{self, MessageBox, MessageQueue},
@@ -31,6 +31,7 @@ type PullHeadersScheduler = FireForgetScheduler<HeaderHash, Address, Checkpoints
 type GetNextBlockScheduler = FireForgetScheduler<HeaderHash, Address, ()>;
 
 const BRANCH_REPROCESSING_INTERVAL: Duration = Duration::from_secs(60);
+const LEDGER_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(600);
 
 const DEFAULT_TIMEOUT_PROCESS_LEADERSHIP: u64 = 5;
 const DEFAULT_TIMEOUT_PROCESS_ANNOUNCEMENT: u64 = 5;
@@ -68,6 +69,7 @@ impl Process {
         mut input: MessageQueue<BlockMsg>,
     ) {
         self.start_branch_reprocessing(&service_info);
+        self.start_ledger_snapshots(&service_info);
         let pull_headers_scheduler = self.spawn_pull_headers_scheduler(&service_info);
         let get_next_block_scheduler = self.spawn_get_next_block_scheduler(&service_info);
         while let Some(msg) = input.next().await {
@@ -137,11 +139,12 @@ impl Process {
                         node_id,
                         pull_headers_scheduler.clone(),
                         get_next_block_scheduler.clone(),
+                        network_msg_box,
                         logger.clone(),
                     ),
                 )
             }
-            BlockMsg::NetworkBlocks(handle) => {
+            BlockMsg::NetworkBlocks(handle, node_id) => {
                 info!(info.logger(), "receiving block stream from network");
 
                 let logger = info.logger().clone();
@@ -158,12 +161,13 @@ impl Process {
                         explorer_msg_box,
                         get_next_block_scheduler,
                         handle,
+                        node_id,
                         stats_counter,
                         logger,
                     ),
                 );
             }
-            BlockMsg::ChainHeaders(handle) => {
+            BlockMsg::ChainHeaders(handle, node_id) => {
                 info!(info.logger(), "receiving header stream from network");
 
                 let logger = info.logger().new(o!(log::KEY_SUB_TASK => "chain_pull"));
@@ -176,6 +180,7 @@ impl Process {
                         logger,
                         blockchain,
                         handle,
+                        node_id,
                         pull_headers_scheduler,
                         network_msg_box,
                     ),
@@ -196,6 +201,16 @@ impl Process {
         )
     }
 
+    fn start_ledger_snapshots(&self, info: &TokioServiceInfo) {
+        let tip = self.blockchain_tip.clone();
+        let blockchain = self.blockchain.clone();
+        let logger = info.logger().clone();
+
+        info.run_periodic_fallible("ledger snapshot", LEDGER_SNAPSHOT_INTERVAL, move || {
+            write_ledger_snapshot(logger.clone(), blockchain.clone(), tip.clone())
+        })
+    }
+
     fn spawn_pull_headers_scheduler(&self, info: &TokioServiceInfo) -> PullHeadersScheduler {
         let network_msgbox = self.network_msgbox.clone();
         let scheduler_logger = info.logger().clone();
@@ -263,6 +278,9 @@ fn try_request_fragment_removal(
 /// this function will re-process the tip against the different branches
 /// this is because a branch may have become more interesting with time
 /// moving forward and branches may have been dismissed
+///
+/// The deep forks rejected here were already counted when they were first
+/// processed.
 async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) -> Result<(), Error> {
     let branches: Vec<Arc<Ref>> = blockchain.branches().branches().await;
 
@@ -274,13 +292,64 @@ async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) ->
         .collect::<Vec<_>>();
 
     for other in others {
-        process_new_ref(&logger, &mut blockchain, tip.clone(), Arc::clone(other)).await?
+        apply_chain_selection(&logger, &mut blockchain, tip.clone(), Arc::clone(other)).await?;
+    }
+
+    Ok(())
+}
+
+async fn write_ledger_snapshot(
+    logger: Logger,
+    blockchain: Blockchain,
+    tip: Tip,
+) -> Result<(), Error> {
+    let tip_ref = tip.get_ref().await;
+    blockchain.write_ledger_snapshot(tip_ref, &logger).await
+}
+
+/// process a new candidate block on top of the blockchain, see
+/// `apply_chain_selection`.
+///
+/// A candidate forking from the current tip deeper than the epoch stability
+/// depth is counted in the node's statistics, only once per branch: when the
+/// branch becomes longer than the current tip, i.e. its parent was not
+/// already a rejected deep fork itself.
+pub async fn process_new_ref(
+    logger: &Logger,
+    blockchain: &mut Blockchain,
+    tip: Tip,
+    candidate: Arc<Ref>,
+    stats_counter: &StatsCounter,
+) -> Result<(), Error> {
+    let comparison =
+        apply_chain_selection(logger, blockchain, tip.clone(), Arc::clone(&candidate)).await?;
+
+    if comparison == ComparisonResult::RejectDeepFork {
+        let parent = blockchain.get_ref(candidate.block_parent_hash()).await?;
+        let parent_rejected = match parent {
+            None => false,
+            Some(parent) => {
+                let tip_ref = tip.get_ref().await;
+                chain_selection::compare_against(blockchain.storage(), &tip_ref, &parent)
+                    .await
+                    .map_err(|e| {
+                        Error::with_chain(
+                            e,
+                            "Cannot compare the candidate's parent against the tip",
+                        )
+                    })?
+                    == ComparisonResult::RejectDeepFork
+            }
+        };
+        if !parent_rejected {
+            stats_counter.add_deep_fork_rejected_cnt(1);
+        }
     }
 
     Ok(())
 }
 
-/// process a new candidate block on top of the blockchain, this function may:
+/// apply the chain selection to a candidate block, this function may:
 ///
 /// * update the current tip if the candidate's parent is the current tip;
 /// * update a branch if the candidate parent is that branch's tip;
@@ -289,16 +358,23 @@ async fn reprocess_tip(logger: Logger, mut blockchain: Blockchain, tip: Tip) ->
 /// If the current tip is not the one being updated we will then trigger
 /// chain selection after updating that other branch as it may be possible that
 /// this branch just became more interesting for the current consensus algorithm.
-pub async fn process_new_ref(
+///
+/// A candidate forking from the current tip deeper than the epoch stability
+/// depth is never switched to, it is kept as a branch.
+async fn apply_chain_selection(
     logger: &Logger,
     blockchain: &mut Blockchain,
     mut tip: Tip,
     candidate: Arc<Ref>,
-) -> Result<(), Error> {
+) -> Result<ComparisonResult, Error> {
     let candidate_hash = candidate.hash();
     let tip_ref = tip.get_ref().await;
 
-    match chain_selection::compare_against(blockchain.storage(), &tip_ref, &candidate) {
+    let comparison = chain_selection::compare_against(blockchain.storage(), &tip_ref, &candidate)
+        .await
+        .map_err(|e| Error::with_chain(e, "Cannot compare the candidate against the tip"))?;
+
+    match comparison {
         ComparisonResult::PreferCurrent => {
             info!(
                 logger,
@@ -307,6 +383,14 @@ pub async fn process_new_ref(
                 tip_ref.header().description(),
             );
         }
+        ComparisonResult::RejectDeepFork => {
+            warn!(
+                logger,
+                "rejecting branch with tip {} forking deeper than the epoch stability depth | current-tip {}",
+                candidate.header().description(),
+                tip_ref.header().description(),
+            );
+        }
         ComparisonResult::PreferCandidate => {
             if tip_ref.hash() == candidate.block_parent_hash() {
                 info!(
@@ -343,7 +427,7 @@ pub async fn process_new_ref(
         }
     }
 
-    Ok(())
+    Ok(comparison)
 }
 
 async fn process_and_propagate_new_ref(
@@ -352,12 +436,13 @@ async fn process_and_propagate_new_ref(
     tip: Tip,
     new_block_ref: Arc<Ref>,
     mut network_msg_box: MessageBox<NetworkMsg>,
+    stats_counter: &StatsCounter,
 ) -> Result<(), Error> {
     let header = new_block_ref.header().clone();
     let hash = header.hash();
     debug!(logger, "processing the new block and propagating"; "hash" => %hash);
 
-    process_new_ref(logger, blockchain, tip, new_block_ref).await?;
+    process_new_ref(logger, blockchain, tip, new_block_ref, stats_counter).await?;
 
     debug!(logger, "propagating block to the network"; "hash" => %hash);
     network_msg_box
@@ -392,6 +477,7 @@ async fn process_leadership_block(
         blockchain_tip,
         Arc::clone(&new_block_ref),
         network_msg_box,
+        &stats_counter,
     )
     .await?;
 
@@ -450,12 +536,23 @@ async fn process_block_announcement(
     node_id: Address,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut get_next_block_scheduler: GetNextBlockScheduler,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     logger: Logger,
 ) -> Result<(), Error> {
-    let pre_checked = blockchain
-        .pre_check_header(header, false)
-        .await
-        .map_err(|err| Error::with_chain(err, "cannot process block announcement"))?;
+    let pre_checked = match blockchain.pre_check_header(header, false).await {
+        Ok(pre_checked) => pre_checked,
+        Err(err) => {
+            if let ErrorKind::BlockHeaderVerificationFailed(_) = err.0 {
+                report_peer(
+                    &mut network_msg_box,
+                    Some(&node_id),
+                    PeerEvent::InvalidHeader,
+                    &logger,
+                );
+            }
+            return Err(Error::with_chain(err, "cannot process block announcement"));
+        }
+    };
     match pre_checked {
         PreCheckedHeader::AlreadyPresent { .. } => {
             debug!(logger, "block is already present");
@@ -500,10 +597,11 @@ async fn process_network_blocks(
     mut blockchain: Blockchain,
     blockchain_tip: Tip,
     mut tx_msg_box: MessageBox<TransactionMsg>,
-    network_msg_box: MessageBox<NetworkMsg>,
+    mut network_msg_box: MessageBox<NetworkMsg>,
     mut explorer_msg_box: Option<MessageBox<ExplorerMsg>>,
     mut get_next_block_scheduler: GetNextBlockScheduler,
     handle: intercom::RequestStreamHandle<Block, ()>,
+    node_id: Option<Address>,
     stats_counter: StatsCounter,
     logger: Logger,
 ) -> Result<(), Error> {
@@ -528,6 +626,12 @@ async fn process_network_blocks(
                 match res {
                     Ok(Some(r)) => {
                         stats_counter.add_block_recv_cnt(1);
+                        report_peer(
+                            &mut network_msg_box,
+                            node_id.as_ref(),
+                            PeerEvent::BlockDelivered,
+                            &logger,
+                        );
                         stream = stream_tail;
                         candidate = Some(r);
                     }
@@ -541,6 +645,19 @@ async fn process_network_blocks(
                             "validation of an incoming block failed";
                             "reason" => ?e,
                         );
+                        // the ledger errors are wrapped in `CannotApplyBlock`
+                        // when the block contents are applied
+                        if let ErrorKind::BlockHeaderVerificationFailed(_)
+                        | ErrorKind::CannotApplyBlock
+                        | ErrorKind::Ledger(_) = e.0
+                        {
+                            report_peer(
+                                &mut network_msg_box,
+                                node_id.as_ref(),
+                                PeerEvent::InvalidBlock,
+                                &logger,
+                            );
+                        }
                         reply.reply_error(network_block_error_into_reply(e));
                         break candidate;
                     }
@@ -561,6 +678,7 @@ async fn process_network_blocks(
                 blockchain_tip,
                 Arc::clone(&new_block_ref),
                 network_msg_box,
+                &stats_counter,
             )
             .await?;
 
@@ -691,6 +809,7 @@ async fn process_chain_headers(
     logger: Logger,
     blockchain: Blockchain,
     handle: intercom::RequestStreamHandle<Header, ()>,
+    node_id: Option<Address>,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut network_msg_box: MessageBox<NetworkMsg>,
 ) {
@@ -702,6 +821,16 @@ async fn process_chain_headers(
                 "error processing an incoming header stream";
                 "reason" => %e,
             );
+            match e {
+                candidate::Error::BrokenHeaderChain(_)
+                | candidate::Error::HeaderChainVerificationFailed(_) => report_peer(
+                    &mut network_msg_box,
+                    node_id.as_ref(),
+                    PeerEvent::InvalidHeader,
+                    &logger,
+                ),
+                _ => {}
+            }
             reply.reply_error(chain_header_error_into_reply(e));
         }
         Ok((header_ids, _maybe_remainder)) => {
@@ -730,6 +859,23 @@ async fn process_chain_headers(
     }
 }
 
+/// updates the reputation of the node the blocks or headers came from,
+/// if it is known
+fn report_peer(
+    network_msg_box: &mut MessageBox<NetworkMsg>,
+    node_id: Option<&Address>,
+    event: PeerEvent,
+    logger: &Logger,
+) {
+    if let Some(node_id) = node_id {
+        network_msg_box
+            .try_send(NetworkMsg::ReportPeer(node_id.clone(), event))
+            .unwrap_or_else(
+                |e| error!(logger, "cannot report the peer to the network"; "reason" => ?e),
+            );
+    }
+}
+
 fn network_block_error_into_reply(err: chain::Error) -> intercom::Error {
     use super::chain::ErrorKind::*;
 
@@ -26,6 +26,11 @@ pub struct Ref {
     /// this object will be shared between different Ref of the same epoch
     epoch_leadership_schedule: Arc<Leadership>,
 
+    /// the ledger state the epoch leadership was created from
+    ///
+    /// The object will be shared between different Ref of the same epoch
+    epoch_leadership_ledger: Arc<Ledger>,
+
     /// pointer to the current ledger parameters
     ///
     /// The object will be shared between different Ref of the same epoch
@@ -52,6 +57,7 @@ impl Ref {
         ledger: multiverse::Ref<Ledger>,
         time_frame: Arc<TimeFrame>,
         epoch_leadership_schedule: Arc<Leadership>,
+        epoch_leadership_ledger: Arc<Ledger>,
         epoch_ledger_parameters: Arc<LedgerParameters>,
         epoch_rewards_info: Option<Arc<EpochRewardsInfo>>,
         header: Header,
@@ -67,6 +73,7 @@ impl Ref {
             ledger,
             time_frame,
             epoch_leadership_schedule,
+            epoch_leadership_ledger,
             epoch_ledger_parameters,
             epoch_rewards_info,
             header,
@@ -114,6 +121,11 @@ impl Ref {
         &self.epoch_leadership_schedule
     }
 
+    /// get the ledger state the epoch leadership was created from
+    pub fn epoch_leadership_ledger(&self) -> &Arc<Ledger> {
+        &self.epoch_leadership_ledger
+    }
+
     pub fn epoch_ledger_parameters(&self) -> &Arc<LedgerParameters> {
         &self.epoch_ledger_parameters
     }
@@ -0,0 +1,548 @@
+use crate::{
+    blockcfg::{ChainLength, EpochRewardsInfo, Header, HeaderHash, Ledger, RewardsInfoParameters},
+    blockchain::Ref,
+};
+use chain_core::property::{Deserialize as _, Serialize as _};
+use jormungandr_lib::{
+    crypto::{account::Identifier, hash::Hash},
+    interfaces::Value,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write as _},
+    path::{Path, PathBuf},
+};
+
+/// name of the directory, in the node storage directory, where the
+/// ledger snapshots are written
+pub const LEDGER_SNAPSHOTS_DIRECTORY: &str = "ledger_snapshots";
+
+/// version of the snapshot file format. Snapshots written with another
+/// version are ignored and the node falls back to replaying the blocks.
+const FORMAT_VERSION: u32 = 1;
+
+const FILE_PREFIX: &str = "ledger-";
+const FILE_EXTENSION: &str = "snapshot";
+const TMP_FILE_PREFIX: &str = "tmp.";
+
+/// number of snapshots to keep on disk, the older ones are removed every
+/// time a new snapshot is written
+const SNAPSHOTS_TO_KEEP: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error while accessing the ledger snapshot")]
+    Io(#[from] io::Error),
+    #[error("cannot encode or decode the ledger snapshot")]
+    Encoding(#[from] bincode::Error),
+    #[error("unsupported ledger snapshot format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("the ledger snapshot was created for a different block0 ({0})")]
+    Block0Mismatch(HeaderHash),
+    #[error("the ledger snapshot contains an invalid block header")]
+    InvalidHeader(#[source] io::Error),
+    #[error("the ledger snapshot contains an invalid ledger state")]
+    InvalidLedger(#[source] io::Error),
+}
+
+/// the content of a ledger snapshot, enough to recreate the [`Ref`] of
+/// a stable block without replaying the blocks from the block0.
+///
+/// [`Ref`]: ./struct.Ref.html
+pub struct LedgerSnapshot {
+    /// the header of the block the snapshot was taken at
+    pub header: Header,
+    /// the ledger state after applying the block
+    pub ledger: Ledger,
+    /// the header of the last block of the previous epoch
+    pub previous_epoch_header: Header,
+    /// the ledger state of the last block of the previous epoch
+    pub previous_epoch_ledger: Ledger,
+    /// the ledger state the epoch's leadership was built from
+    pub leadership_ledger: Ledger,
+    /// the rewards info distributed at the beginning of the epoch (if any)
+    pub epoch_rewards_info: Option<EpochRewardsInfo>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    version: u32,
+    block0: Hash,
+    header: Vec<u8>,
+    ledger: Vec<u8>,
+    previous_epoch_header: Vec<u8>,
+    previous_epoch_ledger: Vec<u8>,
+    leadership_ledger: Vec<u8>,
+    epoch_rewards_info: Option<RewardsInfoFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RewardsInfoFile {
+    drawn: Value,
+    fees: Value,
+    treasury: Value,
+    stake_pools: Vec<(Hash, Value, Value)>,
+    accounts: Vec<(Identifier, Value)>,
+}
+
+/// access to the ledger snapshots written in a directory of the node's storage
+#[derive(Clone)]
+pub struct LedgerSnapshots {
+    dir: PathBuf,
+}
+
+impl LedgerSnapshots {
+    pub fn new(dir: PathBuf) -> Self {
+        LedgerSnapshots { dir }
+    }
+
+    /// create the snapshots handler for the given node storage directory
+    pub fn in_storage_dir(storage_dir: &Path) -> Self {
+        Self::new(storage_dir.join(LEDGER_SNAPSHOTS_DIRECTORY))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// list the snapshot files present in the directory, the most recent
+    /// (in chain length) first.
+    pub fn list(&self) -> io::Result<Vec<(u32, PathBuf)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if let Some(chain_length) = parse_file_name(&path) {
+                snapshots.push((chain_length, path));
+            }
+        }
+        snapshots.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Ok(snapshots)
+    }
+
+    /// chain length of the most recent snapshot, if any
+    pub fn latest_chain_length(&self) -> io::Result<Option<u32>> {
+        self.list()
+            .map(|snapshots| snapshots.first().map(|(chain_length, _)| *chain_length))
+    }
+
+    /// write the snapshot of the given `Ref` and remove the older snapshots.
+    ///
+    /// The `reference` must have a `Ref` to the last block of the previous
+    /// epoch and `leadership_ledger` must be the ledger state the epoch
+    /// leadership of `reference` was created from.
+    ///
+    /// This function is blocking, the serialization of the ledger states
+    /// may take a while.
+    pub fn write(
+        &self,
+        block0: HeaderHash,
+        reference: &Ref,
+        leadership_ledger: &Ledger,
+    ) -> Result<PathBuf, Error> {
+        let previous_epoch = reference
+            .last_ref_previous_epoch()
+            .expect("snapshot requires the previous epoch's state");
+
+        let file = SnapshotFile {
+            version: FORMAT_VERSION,
+            block0: block0.into(),
+            header: reference.header().serialize_as_vec()?,
+            ledger: reference.ledger().serialize_as_vec()?,
+            previous_epoch_header: previous_epoch.header().serialize_as_vec()?,
+            previous_epoch_ledger: previous_epoch.ledger().serialize_as_vec()?,
+            leadership_ledger: leadership_ledger.serialize_as_vec()?,
+            epoch_rewards_info: reference
+                .epoch_rewards_info()
+                .map(|info| RewardsInfoFile::from(info.as_ref())),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+
+        let file_name = file_name(reference.chain_length(), reference.hash());
+        let file_path = self.dir.join(&file_name);
+        let file_path_tmp = self.dir.join(format!("{}{}", TMP_FILE_PREFIX, file_name));
+
+        {
+            let mut buf = BufWriter::new(File::create(&file_path_tmp)?);
+            bincode::serialize_into(&mut buf, &file)?;
+            buf.flush()?;
+            buf.get_ref().sync_all()?;
+        }
+        fs::rename(&file_path_tmp, &file_path)?;
+
+        self.prune()?;
+
+        Ok(file_path)
+    }
+
+    /// read and decode the snapshot file at the given path. The rewards info
+    /// reports the rewards of each account only with `rewards_report_all`, the
+    /// node's `--rewards-report-all` setting.
+    ///
+    /// This function is blocking.
+    pub fn read(
+        &self,
+        path: &Path,
+        block0: HeaderHash,
+        rewards_report_all: bool,
+    ) -> Result<LedgerSnapshot, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let file: SnapshotFile = bincode::deserialize_from(reader)?;
+
+        if file.version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(file.version));
+        }
+        let snapshot_block0 = file.block0.into_hash();
+        if snapshot_block0 != block0 {
+            return Err(Error::Block0Mismatch(snapshot_block0));
+        }
+
+        Ok(LedgerSnapshot {
+            header: Header::deserialize(file.header.as_slice()).map_err(Error::InvalidHeader)?,
+            ledger: Ledger::deserialize(file.ledger.as_slice()).map_err(Error::InvalidLedger)?,
+            previous_epoch_header: Header::deserialize(file.previous_epoch_header.as_slice())
+                .map_err(Error::InvalidHeader)?,
+            previous_epoch_ledger: Ledger::deserialize(file.previous_epoch_ledger.as_slice())
+                .map_err(Error::InvalidLedger)?,
+            leadership_ledger: Ledger::deserialize(file.leadership_ledger.as_slice())
+                .map_err(Error::InvalidLedger)?,
+            epoch_rewards_info: file
+                .epoch_rewards_info
+                .map(|info| info.into_rewards_info(rewards_report_all)),
+        })
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        for (_, path) in self.list()?.into_iter().skip(SNAPSHOTS_TO_KEEP) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn file_name(chain_length: ChainLength, hash: HeaderHash) -> String {
+    format!(
+        "{}{}-{}.{}",
+        FILE_PREFIX, chain_length, hash, FILE_EXTENSION
+    )
+}
+
+fn parse_file_name(path: &Path) -> Option<u32> {
+    let name = path.file_name()?.to_str()?;
+    if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_EXTENSION) {
+        return None;
+    }
+    name[FILE_PREFIX.len()..].split('-').next()?.parse().ok()
+}
+
+impl<'a> From<&'a EpochRewardsInfo> for RewardsInfoFile {
+    fn from(info: &'a EpochRewardsInfo) -> Self {
+        RewardsInfoFile {
+            drawn: info.drawn.into(),
+            fees: info.fees.into(),
+            treasury: info.treasury.into(),
+            stake_pools: info
+                .stake_pools
+                .iter()
+                .map(|(id, (taxed, distributed))| {
+                    (id.clone().into(), (*taxed).into(), (*distributed).into())
+                })
+                .collect(),
+            accounts: info
+                .accounts
+                .iter()
+                .map(|(id, received)| (id.clone().into(), (*received).into()))
+                .collect(),
+        }
+    }
+}
+
+impl RewardsInfoFile {
+    fn into_rewards_info(self, rewards_report_all: bool) -> EpochRewardsInfo {
+        let parameters = if rewards_report_all {
+            RewardsInfoParameters::report_all()
+        } else {
+            RewardsInfoParameters::default()
+        };
+        let mut info = EpochRewardsInfo::new(parameters);
+        info.drawn = self.drawn.into();
+        info.fees = self.fees.into();
+        info.treasury = self.treasury.into();
+        info.stake_pools = self
+            .stake_pools
+            .into_iter()
+            .map(|(id, taxed, distributed)| {
+                (id.into_digest_of(), (taxed.into(), distributed.into()))
+            })
+            .collect();
+        info.accounts = self
+            .accounts
+            .into_iter()
+            .map(|(id, received)| (id.to_inner(), received.into()))
+            .collect();
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{
+        Block, BlockDate, BlockVersion, Contents, ContentsBuilder, HeaderBuilderNew,
+    };
+    use crate::blockchain::{Blockchain, Multiverse, Storage, MAIN_BRANCH_TAG};
+    use chain_core::property::Serialize as _;
+    use chain_storage::BlockStoreBuilder;
+    use jormungandr_lib::interfaces::{
+        block0_configuration_documented_example, Block0Configuration,
+    };
+    use slog::Logger;
+    use std::sync::Arc;
+
+    /// a temporary directory, removed when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "jormungandr-ledger-snapshots-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn block0() -> Block {
+        let config: Block0Configuration =
+            serde_yaml::from_str(&block0_configuration_documented_example()).unwrap();
+        config.to_block()
+    }
+
+    fn child_block(parent: &Ref) -> Block {
+        let contents: Contents = ContentsBuilder::new().into();
+        let date = parent.block_date();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_parent(&parent.hash(), parent.chain_length().increase())
+            .set_date(BlockDate {
+                epoch: date.epoch,
+                slot_id: date.slot_id + 1,
+            })
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    /// the `Ref` of a block on top of `parent` with the same ledger state,
+    /// `parent` standing for the last block of the previous epoch
+    async fn child_ref(parent: &Arc<Ref>, block: &Block) -> Arc<Ref> {
+        let ledger = Multiverse::new()
+            .insert(
+                block.header.chain_length(),
+                block.header.hash(),
+                parent.ledger().as_ref().clone(),
+            )
+            .await;
+        Arc::new(Ref::new(
+            ledger,
+            Arc::clone(parent.time_frame()),
+            Arc::clone(parent.epoch_leadership_schedule()),
+            Arc::clone(parent.epoch_leadership_ledger()),
+            Arc::clone(parent.epoch_ledger_parameters()),
+            None,
+            block.header.clone(),
+            Some(Arc::clone(parent)),
+        ))
+    }
+
+    /// a blockchain with only the block0 in its storage, and the `Ref` of
+    /// the block0
+    async fn blockchain(snapshots: &Path) -> (Blockchain, Arc<Ref>) {
+        let logger = Logger::root(slog::Discard, o!());
+        let storage = Storage::new(BlockStoreBuilder::memory().build(), logger.clone());
+        let block0 = block0();
+        let block0_hash = block0.header.hash();
+        storage.put_block(block0.clone()).await.unwrap();
+        storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), block0_hash)
+            .await
+            .unwrap();
+
+        let blockchain = reload(&storage, block0_hash, snapshots);
+        let branch = blockchain.load_from_storage(block0, &logger).await.unwrap();
+        let block0_ref = branch.get_ref().await;
+        (blockchain, block0_ref)
+    }
+
+    /// a new blockchain on the given storage, as after a restart of the node
+    fn reload(storage: &Storage, block0: HeaderHash, snapshots: &Path) -> Blockchain {
+        Blockchain::new(
+            block0,
+            storage.clone(),
+            100,
+            false,
+            Some(LedgerSnapshots::new(snapshots.to_owned())),
+        )
+    }
+
+    async fn load_tip(blockchain: &Blockchain) -> Arc<Ref> {
+        let logger = Logger::root(slog::Discard, o!());
+        blockchain
+            .load_from_storage(block0(), &logger)
+            .await
+            .unwrap()
+            .get_ref()
+            .await
+    }
+
+    #[tokio::test]
+    async fn snapshot_round_trip() {
+        let dir = TestDir::new("round-trip");
+        let (blockchain, block0_ref) = blockchain(&dir.0).await;
+        let snapshots = LedgerSnapshots::new(dir.0.clone());
+        let block0 = *blockchain.block0();
+        let reference = child_ref(&block0_ref, &child_block(&block0_ref)).await;
+
+        let path = snapshots
+            .write(block0, &reference, reference.epoch_leadership_ledger())
+            .unwrap();
+        let snapshot = snapshots.read(&path, block0, false).unwrap();
+
+        assert_eq!(snapshot.header.hash(), reference.hash());
+        assert_eq!(snapshot.previous_epoch_header.hash(), block0_ref.hash());
+        assert_eq!(
+            snapshot.ledger.serialize_as_vec().unwrap(),
+            reference.ledger().serialize_as_vec().unwrap()
+        );
+        assert_eq!(
+            snapshot.leadership_ledger.serialize_as_vec().unwrap(),
+            reference
+                .epoch_leadership_ledger()
+                .serialize_as_vec()
+                .unwrap()
+        );
+        assert!(snapshot.epoch_rewards_info.is_none());
+
+        match snapshots.read(&path, HeaderHash::zero_hash(), false) {
+            Err(Error::Block0Mismatch(hash)) => assert_eq!(hash, block0),
+            _ => panic!("the snapshot of another blockchain was read"),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_latest_snapshots_are_kept() {
+        let dir = TestDir::new("rotation");
+        let (blockchain, block0_ref) = blockchain(&dir.0).await;
+        let snapshots = LedgerSnapshots::new(dir.0.clone());
+        let block0 = *blockchain.block0();
+
+        let mut parent = Arc::clone(&block0_ref);
+        let mut chain_lengths = Vec::new();
+        for _ in 0..3 {
+            let reference = child_ref(&parent, &child_block(&parent)).await;
+            snapshots
+                .write(block0, &reference, reference.epoch_leadership_ledger())
+                .unwrap();
+            chain_lengths.push(u32::from(reference.chain_length()));
+            parent = reference;
+        }
+
+        let kept: Vec<u32> = snapshots
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|(chain_length, _)| chain_length)
+            .collect();
+        assert_eq!(kept, vec![chain_lengths[2], chain_lengths[1]]);
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), SNAPSHOTS_TO_KEEP);
+    }
+
+    #[tokio::test]
+    async fn snapshot_is_resumed_from() {
+        let dir = TestDir::new("resume");
+        let (blockchain, block0_ref) = blockchain(&dir.0).await;
+        let block = child_block(&block0_ref);
+        let reference = child_ref(&block0_ref, &block).await;
+        let storage = blockchain.storage();
+        storage.put_block(block).await.unwrap();
+        storage
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), reference.hash())
+            .await
+            .unwrap();
+        LedgerSnapshots::new(dir.0.clone())
+            .write(
+                *blockchain.block0(),
+                &reference,
+                reference.epoch_leadership_ledger(),
+            )
+            .unwrap();
+
+        let reloaded = reload(storage, *blockchain.block0(), &dir.0);
+        let tip = load_tip(&reloaded).await;
+
+        assert_eq!(tip.hash(), reference.hash());
+        let previous_epoch = tip.last_ref_previous_epoch().unwrap();
+        assert_eq!(previous_epoch.hash(), block0_ref.hash());
+        // the previous epoch's state does not have the leadership of its epoch
+        assert!(reloaded.get_ref(block0_ref.hash()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn corrupt_snapshot_falls_back_to_block0() {
+        let dir = TestDir::new("corrupt");
+        let (blockchain, block0_ref) = blockchain(&dir.0).await;
+        let reference = child_ref(&block0_ref, &child_block(&block0_ref)).await;
+        let path = LedgerSnapshots::new(dir.0.clone())
+            .write(
+                *blockchain.block0(),
+                &reference,
+                reference.epoch_leadership_ledger(),
+            )
+            .unwrap();
+        fs::write(&path, b"not a ledger snapshot").unwrap();
+
+        let reloaded = reload(blockchain.storage(), *blockchain.block0(), &dir.0);
+        let tip = load_tip(&reloaded).await;
+
+        assert_eq!(tip.hash(), block0_ref.hash());
+        assert!(tip.last_ref_previous_epoch().is_none());
+    }
+
+    #[tokio::test]
+    async fn snapshot_off_the_main_branch_falls_back_to_block0() {
+        let dir = TestDir::new("off-branch");
+        let (blockchain, block0_ref) = blockchain(&dir.0).await;
+        // the block of the snapshot is not in the storage
+        let reference = child_ref(&block0_ref, &child_block(&block0_ref)).await;
+        LedgerSnapshots::new(dir.0.clone())
+            .write(
+                *blockchain.block0(),
+                &reference,
+                reference.epoch_leadership_ledger(),
+            )
+            .unwrap();
+
+        let reloaded = reload(blockchain.storage(), *blockchain.block0(), &dir.0);
+        let tip = load_tip(&reloaded).await;
+
+        assert_eq!(tip.hash(), block0_ref.hash());
+        assert!(tip.last_ref_previous_epoch().is_none());
+    }
+}
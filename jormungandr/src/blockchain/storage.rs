@@ -340,6 +340,25 @@ impl Storage {
         Ok(())
     }
 
+    /// Return the hash of the ancestor of the given block at the given
+    /// `distance`, or `None` if the block is closer than `distance` to the
+    /// block0.
+    pub async fn get_nth_ancestor(
+        &self,
+        header_hash: HeaderHash,
+        distance: u64,
+    ) -> Result<Option<HeaderHash>, Error> {
+        self.run(move |connection| {
+            let block_info = connection.get_block_info(&header_hash)?;
+            if block_info.chain_length <= distance {
+                return Ok(None);
+            }
+            for_path_to_nth_ancestor(connection, &header_hash, distance, |_| {})
+                .map(|ancestor_info| Some(ancestor_info.block_hash))
+        })
+        .await
+    }
+
     pub async fn find_closest_ancestor(
         &self,
         checkpoints: Vec<HeaderHash>,
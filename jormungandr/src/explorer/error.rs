@@ -1,3 +1,4 @@
+use super::stable_index;
 use crate::{blockchain::StorageError, intercom};
 
 error_chain! {
@@ -5,6 +6,7 @@ error_chain! {
         StorageError(StorageError);
         // FIXME: fold into StorageError with more generic work in intercom streaming
         StreamingError(intercom::Error);
+        StableIndexError(stable_index::Error);
     }
     errors {
         BlockNotFound(hash: String) {
@@ -27,6 +29,14 @@ error_chain! {
             description("tried to index already indexed chainlength in the given branch")
             display("chain length: {} is already indexed", chain_length)
         }
+        VotePlanAlreadyExists(id: String) {
+            description("tried to index an already indexed vote plan")
+            display("vote plan '{}' is already indexed", id)
+        }
+        VotePlanNotFound(id: String) {
+            description("vote cast for a vote plan that is not indexed")
+            display("vote plan '{}' of the vote cast cannot be found in the explorer", id)
+        }
         BootstrapError(msg: String) {
             description("failed to initialize explorer's database from storage")
             display("the explorer's database couldn't be initialized: {}", msg)
@@ -1,6 +1,10 @@
 use super::error::ErrorKind;
-use super::scalars::{BlockCount, IndexCursor, PoolCount, TransactionCount};
-use super::{Block, Context, Pool, Transaction};
+use super::scalars::{
+    BlockCount, IndexCursor, PoolCount, TransactionCount, VoteCount, VotePlanCount,
+};
+use super::{
+    BalanceChange, Block, Context, Pool, Transaction, TransactionUnspentOutputs, Vote, VotePlan,
+};
 use crate::blockcfg::HeaderHash;
 use crate::explorer::indexing::ExplorerTransaction;
 use juniper::FieldResult;
@@ -69,6 +73,62 @@ impl PoolEdge {
     }
 }
 
+#[juniper::object(
+    Context = Context
+)]
+impl VotePlanEdge {
+    pub fn node(&self) -> &VotePlan {
+        &self.node
+    }
+
+    /// A cursor for use in pagination
+    pub fn cursor(&self) -> &IndexCursor {
+        &self.cursor
+    }
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl VoteEdge {
+    pub fn node(&self) -> &Vote {
+        &self.node
+    }
+
+    /// A cursor for use in pagination
+    pub fn cursor(&self) -> &IndexCursor {
+        &self.cursor
+    }
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl BalanceChangeEdge {
+    pub fn node(&self) -> &BalanceChange {
+        &self.node
+    }
+
+    /// A cursor for use in pagination
+    pub fn cursor(&self) -> &IndexCursor {
+        &self.cursor
+    }
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl UnspentOutputsEdge {
+    pub fn node(&self) -> &TransactionUnspentOutputs {
+        &self.node
+    }
+
+    /// A cursor for use in pagination
+    pub fn cursor(&self) -> &IndexCursor {
+        &self.cursor
+    }
+}
+
 #[juniper::object(
     Context = Context,
     name = "BlockConnection"
@@ -126,6 +186,84 @@ impl PoolConnection {
     }
 }
 
+#[juniper::object(
+    Context = Context,
+    name = "VotePlanConnection"
+)]
+impl VotePlanConnection {
+    pub fn page_info(&self) -> &PageInfo {
+        &self.page_info
+    }
+
+    pub fn edges(&self) -> &Vec<VotePlanEdge> {
+        &self.edges
+    }
+
+    /// A count of the total number of objects in this connection, ignoring pagination.
+    pub fn total_count(&self) -> &VotePlanCount {
+        &self.total_count
+    }
+}
+
+#[juniper::object(
+    Context = Context,
+    name = "VoteConnection"
+)]
+impl VoteConnection {
+    pub fn page_info(&self) -> &PageInfo {
+        &self.page_info
+    }
+
+    pub fn edges(&self) -> &Vec<VoteEdge> {
+        &self.edges
+    }
+
+    /// A count of the total number of objects in this connection, ignoring pagination.
+    pub fn total_count(&self) -> &VoteCount {
+        &self.total_count
+    }
+}
+
+#[juniper::object(
+    Context = Context,
+    name = "BalanceChangeConnection"
+)]
+impl BalanceChangeConnection {
+    pub fn page_info(&self) -> &PageInfo {
+        &self.page_info
+    }
+
+    pub fn edges(&self) -> &Vec<BalanceChangeEdge> {
+        &self.edges
+    }
+
+    /// A count of the total number of objects in this connection, ignoring pagination.
+    pub fn total_count(&self) -> &TransactionCount {
+        &self.total_count
+    }
+}
+
+#[juniper::object(
+    Context = Context,
+    name = "UnspentOutputsConnection"
+)]
+impl UnspentOutputsConnection {
+    pub fn page_info(&self) -> &PageInfo {
+        &self.page_info
+    }
+
+    /// The transactions without unspent outputs sent to the address have no edge
+    pub fn edges(&self) -> &Vec<UnspentOutputsEdge> {
+        &self.edges
+    }
+
+    /// A count of the total number of transactions of the address, ignoring
+    /// pagination.
+    pub fn total_count(&self) -> &TransactionCount {
+        &self.total_count
+    }
+}
+
 pub struct PageInfo {
     pub has_next_page: bool,
     pub has_previous_page: bool,
@@ -154,6 +292,26 @@ pub struct PoolEdge {
     pub cursor: IndexCursor,
 }
 
+pub struct VotePlanEdge {
+    node: VotePlan,
+    pub cursor: IndexCursor,
+}
+
+pub struct VoteEdge {
+    node: Vote,
+    pub cursor: IndexCursor,
+}
+
+pub struct BalanceChangeEdge {
+    node: BalanceChange,
+    cursor: IndexCursor,
+}
+
+pub struct UnspentOutputsEdge {
+    node: TransactionUnspentOutputs,
+    cursor: IndexCursor,
+}
+
 pub trait Edge {
     type Node;
     fn new(node: Self::Node, cursor: IndexCursor) -> Self;
@@ -186,6 +344,23 @@ where
         pagination_arguments: ValidatedPaginationArguments<I>,
         get_node_range: impl Fn(PaginationInterval<I>) -> Vec<(E::Node, I)>,
     ) -> FieldResult<Connection<E, C>>
+    where
+        I: TryFrom<u64>,
+        u64: From<I>,
+        I: Clone,
+        IndexCursor: From<I>,
+    {
+        Self::try_new(bounds, pagination_arguments, |range| {
+            Ok(get_node_range(range))
+        })
+    }
+
+    /// same as `new`, for the nodes which may fail to be fetched
+    pub fn try_new<I>(
+        bounds: PaginationInterval<I>,
+        pagination_arguments: ValidatedPaginationArguments<I>,
+        get_node_range: impl Fn(PaginationInterval<I>) -> FieldResult<Vec<(E::Node, I)>>,
+    ) -> FieldResult<Connection<E, C>>
     where
         I: TryFrom<u64>,
         u64: From<I>,
@@ -228,7 +403,7 @@ where
             .map_err(|_| "computed page interval is outside pagination boundaries")
             .unwrap();
 
-        let edges: Vec<_> = get_node_range(page_interval)
+        let edges: Vec<_> = get_node_range(page_interval)?
             .iter()
             .map(|(hash, node_pagination_identifier)| {
                 E::new((*hash).clone(), node_pagination_identifier.clone().into())
@@ -257,6 +432,10 @@ where
 pub type BlockConnection = Connection<BlockEdge, BlockCount>;
 pub type TransactionConnection = Connection<TransactionEdge, TransactionCount>;
 pub type PoolConnection = Connection<PoolEdge, PoolCount>;
+pub type VotePlanConnection = Connection<VotePlanEdge, VotePlanCount>;
+pub type VoteConnection = Connection<VoteEdge, VoteCount>;
+pub type BalanceChangeConnection = Connection<BalanceChangeEdge, TransactionCount>;
+pub type UnspentOutputsConnection = Connection<UnspentOutputsEdge, TransactionCount>;
 
 #[derive(Clone)]
 pub enum TransactionNodeFetchInfo {
@@ -305,6 +484,50 @@ impl Edge for PoolEdge {
     }
 }
 
+impl Edge for VotePlanEdge {
+    type Node = VotePlan;
+    fn new(node: Self::Node, cursor: IndexCursor) -> Self {
+        VotePlanEdge { node, cursor }
+    }
+
+    fn cursor<'a>(&'a self) -> &'a IndexCursor {
+        &self.cursor
+    }
+}
+
+impl Edge for VoteEdge {
+    type Node = Vote;
+    fn new(node: Self::Node, cursor: IndexCursor) -> Self {
+        VoteEdge { node, cursor }
+    }
+
+    fn cursor<'a>(&'a self) -> &'a IndexCursor {
+        &self.cursor
+    }
+}
+
+impl Edge for BalanceChangeEdge {
+    type Node = BalanceChange;
+    fn new(node: Self::Node, cursor: IndexCursor) -> Self {
+        BalanceChangeEdge { node, cursor }
+    }
+
+    fn cursor<'a>(&'a self) -> &'a IndexCursor {
+        &self.cursor
+    }
+}
+
+impl Edge for UnspentOutputsEdge {
+    type Node = TransactionUnspentOutputs;
+    fn new(node: Self::Node, cursor: IndexCursor) -> Self {
+        UnspentOutputsEdge { node, cursor }
+    }
+
+    fn cursor<'a>(&'a self) -> &'a IndexCursor {
+        &self.cursor
+    }
+}
+
 fn compute_range_boundaries(
     total_elements: InclusivePaginationInterval<u64>,
     pagination_arguments: ValidatedPaginationArguments<u64>,
@@ -24,5 +24,9 @@ error_chain! {
             description("failed to parse address"),
             display("invalid address: {}", address)
         }
+        RewardsNotReported {
+            description("the rewards of the accounts are not recorded"),
+            display("the rewards of the accounts are not recorded, the node needs to run with --rewards-report-all")
+        }
     }
 }
@@ -1,33 +1,45 @@
 mod connections;
 mod error;
 mod scalars;
+mod subscription;
 use self::connections::{
-    BlockConnection, InclusivePaginationInterval, PaginationArguments, PaginationInterval,
-    PoolConnection, TransactionConnection, TransactionNodeFetchInfo,
+    BalanceChangeConnection, BlockConnection, InclusivePaginationInterval, PaginationArguments,
+    PaginationInterval, PoolConnection, TransactionConnection, TransactionNodeFetchInfo,
+    UnspentOutputsConnection, VoteConnection, VotePlanConnection,
 };
 use self::error::ErrorKind;
 use super::indexing::{
-    BlockProducer, EpochData, ExplorerAddress, ExplorerBlock, ExplorerTransaction, StakePoolData,
+    BalanceTotals, BlockProducer, EpochData, ExplorerAddress, ExplorerBlock, ExplorerProposal,
+    ExplorerTransaction, ExplorerVote, ExplorerVotePlan, StakePoolData,
 };
 use super::persistent_sequence::PersistentSequence;
 use crate::blockcfg::{self, FragmentId, HeaderHash};
+use crate::blockchain;
 use cardano_legacy_address::Addr as OldAddress;
+use chain_impl_mockchain::account;
 use chain_impl_mockchain::certificate;
 use chain_impl_mockchain::key::BftLeaderId;
+use chain_impl_mockchain::value;
 use futures::executor::block_on;
 pub use juniper::http::GraphQLRequest;
 use juniper::{graphql_union, EmptyMutation, FieldResult, RootNode};
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use self::scalars::{
-    BlockCount, ChainLength, EpochNumber, IndexCursor, NonZero, PoolId, PublicKey, Slot,
-    TimeOffsetSeconds, Value,
+    BlockCount, ChainLength, EpochNumber, ExternalProposalId, IndexCursor, NonZero, PoolId,
+    PublicKey, Slot, SpendingCounter, TimeOffsetSeconds, Value, VoteCount, VotePlanId,
 };
 
 use crate::explorer::{ExplorerDB, Settings};
 
+pub use self::subscription::{
+    create_subscription_schema, SubscriptionContext, SubscriptionRequest, SubscriptionResponse,
+    SubscriptionSchema,
+};
+
 #[derive(Clone)]
 pub struct Block {
     hash: HeaderHash,
@@ -409,6 +421,50 @@ impl Address {
 
         Ok(Address { id: addr })
     }
+
+    /// the identifier of the account associated with this address, if any
+    fn account_id(&self) -> Option<account::Identifier> {
+        match &self.id {
+            ExplorerAddress::New(chain_addr::Address(_, chain_addr::Kind::Account(key)))
+            | ExplorerAddress::New(chain_addr::Address(_, chain_addr::Kind::Group(_, key))) => {
+                Some(key.clone().into())
+            }
+            _ => None,
+        }
+    }
+
+    fn account_state(&self, context: &Context) -> FieldResult<Option<account::AccountState<()>>> {
+        let account_id = match self.account_id() {
+            Some(account_id) => account_id,
+            None => return Ok(None),
+        };
+
+        Ok(latest_ref(context)?
+            .ledger()
+            .accounts()
+            .get_state(&account_id)
+            .ok()
+            .cloned())
+    }
+
+    fn get_transactions(&self, context: &Context) -> PersistentSequence<FragmentId> {
+        block_on(context.db.get_transactions_by_address(&self.id))
+            .unwrap_or(PersistentSequence::<FragmentId>::new())
+    }
+
+    /// the pagination boundaries over the transactions of this address
+    fn transactions_boundaries(
+        transactions: &PersistentSequence<FragmentId>,
+    ) -> PaginationInterval<u64> {
+        if transactions.len() > 0 {
+            PaginationInterval::Inclusive(InclusivePaginationInterval {
+                lower_bound: 0u64,
+                upper_bound: transactions.len() - 1,
+            })
+        } else {
+            PaginationInterval::Empty
+        }
+    }
 }
 
 impl From<&ExplorerAddress> for Address {
@@ -433,8 +489,215 @@ impl Address {
         }
     }
 
-    fn delegation() -> FieldResult<Pool> {
-        Err(ErrorKind::Unimplemented.into())
+    /// The stake pools the account of this address delegates to, at the tip of the
+    /// explorer. `null` if the address doesn't have an account in the ledger.
+    fn delegation(&self, context: &Context) -> FieldResult<Option<Delegation>> {
+        Ok(self.account_state(context)?.map(|state| Delegation {
+            delegation: state.delegation().clone(),
+        }))
+    }
+
+    /// The balance of the account of this address, at the tip of the explorer. `null`
+    /// if the address doesn't have an account in the ledger.
+    fn balance(&self, context: &Context) -> FieldResult<Option<Value>> {
+        Ok(self
+            .account_state(context)?
+            .map(|state| Value::from(state.value())))
+    }
+
+    /// The spending counter of the account of this address, to use in the next
+    /// transaction spending from it. `null` if the address doesn't have an account
+    /// in the ledger.
+    fn spending_counter(&self, context: &Context) -> FieldResult<Option<SpendingCounter>> {
+        Ok(self
+            .account_state(context)?
+            .map(|state| SpendingCounter::from(state.get_counter())))
+    }
+
+    /// The rewards received by the account of this address, from the latest epoch
+    /// back to the oldest one still known by the node. The epochs without rewards
+    /// for the account are skipped.
+    ///
+    /// The rewards of each account are only recorded when the node runs with
+    /// `--rewards-report-all`, this is an error otherwise.
+    fn rewards(&self, context: &Context) -> FieldResult<Vec<EpochReward>> {
+        if !context.db.blockchain.rewards_report_all() {
+            return Err(ErrorKind::RewardsNotReported.into());
+        }
+
+        let account_id = match self.account_id() {
+            Some(account_id) => account_id,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut rewards = Vec::new();
+        let mut current = latest_ref(context)?;
+        loop {
+            if let Some(rewards_info) = current.epoch_rewards_info() {
+                if let Some(amount) = rewards_info.accounts.get(&account_id) {
+                    rewards.push(EpochReward {
+                        epoch: Epoch {
+                            id: current.block_date().epoch,
+                        },
+                        amount: *amount,
+                    });
+                }
+            }
+            current = match current.last_ref_previous_epoch() {
+                Some(previous) => Arc::clone(previous),
+                None => break,
+            };
+        }
+
+        Ok(rewards)
+    }
+
+    /// The balance of this address after each of the transactions involving it, in
+    /// the chain order. Only the inputs and outputs of the transactions are taken
+    /// into account, the rewards of an account are not part of this history: the
+    /// balance is the total received by the address minus the total sent from it,
+    /// and is zero when more was sent than received.
+    fn balance_history(
+        &self,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<IndexCursor>,
+        after: Option<IndexCursor>,
+        context: &Context,
+    ) -> FieldResult<BalanceChangeConnection> {
+        let transactions = self.get_transactions(context);
+
+        let pagination_arguments = PaginationArguments {
+            first,
+            last,
+            before: before.map(u64::from),
+            after: after.map(u64::from),
+        }
+        .validate()?;
+
+        BalanceChangeConnection::try_new(
+            Self::transactions_boundaries(&transactions),
+            pagination_arguments,
+            |range: PaginationInterval<u64>| {
+                let range = match range {
+                    PaginationInterval::Empty => return Ok(vec![]),
+                    PaginationInterval::Inclusive(range) => range,
+                };
+
+                // the totals before the page, indexed with the previous transaction
+                let mut totals = match range.lower_bound.checked_sub(1) {
+                    Some(previous) => {
+                        let totals = block_on(context.db.get_address_totals(&self.id, previous));
+                        totals.ok_or(ErrorKind::InternalError(
+                            "address totals were not found for its transaction".to_owned(),
+                        ))?
+                    }
+                    None => BalanceTotals::default(),
+                };
+
+                let mut changes = Vec::new();
+                for i in range.lower_bound..=range.upper_bound {
+                    let id = match transactions.get(i) {
+                        Some(id) => *id.as_ref(),
+                        None => continue,
+                    };
+                    let block = Transaction::from_valid_id(id).get_block(context)?;
+                    let contents = block.transactions.get(&id).ok_or(ErrorKind::InternalError(
+                        "transaction was not found in respective block".to_owned(),
+                    ))?;
+                    let (received, sent) = contents.received_and_sent(&self.id);
+                    totals = totals.add(received, sent);
+
+                    let change = BalanceChange {
+                        transaction: Transaction {
+                            id,
+                            block_hash: Some(block.id()),
+                            contents: Some(contents.clone()),
+                        },
+                        date: block.date(),
+                        received: value::Value(received),
+                        sent: value::Value(sent),
+                        balance: value::Value(totals.balance()),
+                    };
+                    changes.push((change, i));
+                }
+                Ok(changes)
+            },
+        )
+    }
+
+    /// The outputs sent to this address that are not spent at the tip of the explorer,
+    /// grouped by transaction. Always empty for the account addresses and the legacy
+    /// addresses.
+    ///
+    /// The pagination is done over the transactions of the address, the transactions
+    /// without unspent outputs sent to the address are skipped.
+    fn utxos(
+        &self,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<IndexCursor>,
+        after: Option<IndexCursor>,
+        context: &Context,
+    ) -> FieldResult<UnspentOutputsConnection> {
+        let transactions = match &self.id {
+            ExplorerAddress::Old(_) => PersistentSequence::<FragmentId>::new(),
+            ExplorerAddress::New(_) => self.get_transactions(context),
+        };
+        let ledger = latest_ref(context)?.ledger();
+
+        let pagination_arguments = PaginationArguments {
+            first,
+            last,
+            before: before.map(u64::from),
+            after: after.map(u64::from),
+        }
+        .validate()?;
+
+        UnspentOutputsConnection::try_new(
+            Self::transactions_boundaries(&transactions),
+            pagination_arguments,
+            |range: PaginationInterval<u64>| {
+                let range = match range {
+                    PaginationInterval::Empty => return Ok(vec![]),
+                    PaginationInterval::Inclusive(range) => range,
+                };
+
+                let mut nodes = Vec::new();
+                for i in range.lower_bound..=range.upper_bound {
+                    let id = match transactions.get(i) {
+                        Some(id) => *id.as_ref(),
+                        None => continue,
+                    };
+                    let contents = Transaction::from_valid_id(id).get_contents(context)?;
+                    let mut outputs = Vec::new();
+                    for (index, output) in contents.outputs().iter().enumerate() {
+                        if output.address != self.id {
+                            continue;
+                        }
+                        let index =
+                            u8::try_from(index).expect("at most 256 outputs in a transaction");
+                        if let Some(unspent) = ledger.utxo_out(id, index) {
+                            outputs.push(UnspentOutput {
+                                transaction: id,
+                                index,
+                                amount: unspent.value,
+                            });
+                        }
+                    }
+                    if !outputs.is_empty() {
+                        nodes.push((
+                            TransactionUnspentOutputs {
+                                transaction: id,
+                                outputs,
+                            },
+                            i,
+                        ));
+                    }
+                }
+                Ok(nodes)
+            },
+        )
     }
 
     fn transactions(
@@ -445,8 +708,7 @@ impl Address {
         after: Option<IndexCursor>,
         context: &Context,
     ) -> FieldResult<TransactionConnection> {
-        let transactions = block_on(context.db.get_transactions_by_address(&self.id))
-            .unwrap_or(PersistentSequence::<FragmentId>::new());
+        let transactions = self.get_transactions(context);
 
         let boundaries = if transactions.len() > 0 {
             PaginationInterval::Inclusive(InclusivePaginationInterval {
@@ -482,6 +744,167 @@ impl Address {
     }
 }
 
+struct EpochReward {
+    epoch: Epoch,
+    amount: value::Value,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl EpochReward {
+    pub fn epoch(&self) -> &Epoch {
+        &self.epoch
+    }
+
+    pub fn amount(&self) -> Value {
+        self.amount.into()
+    }
+}
+
+#[derive(Clone)]
+pub struct BalanceChange {
+    transaction: Transaction,
+    date: blockcfg::BlockDate,
+    received: value::Value,
+    sent: value::Value,
+    balance: value::Value,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl BalanceChange {
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn date(&self) -> BlockDate {
+        self.date.into()
+    }
+
+    /// The total of the outputs of the transaction sent to the address
+    pub fn received(&self) -> Value {
+        self.received.into()
+    }
+
+    /// The total of the inputs of the transaction spent from the address
+    pub fn sent(&self) -> Value {
+        self.sent.into()
+    }
+
+    /// The balance of the address after the transaction
+    pub fn balance(&self) -> Value {
+        self.balance.into()
+    }
+}
+
+#[derive(Clone)]
+struct UnspentOutput {
+    transaction: FragmentId,
+    index: u8,
+    amount: value::Value,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl UnspentOutput {
+    pub fn transaction(&self) -> Transaction {
+        Transaction::from_valid_id(self.transaction)
+    }
+
+    /// The index of the output in the transaction
+    pub fn index(&self) -> i32 {
+        self.index.into()
+    }
+
+    pub fn amount(&self) -> Value {
+        self.amount.into()
+    }
+}
+
+#[derive(Clone)]
+pub struct TransactionUnspentOutputs {
+    transaction: FragmentId,
+    outputs: Vec<UnspentOutput>,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl TransactionUnspentOutputs {
+    pub fn transaction(&self) -> Transaction {
+        Transaction::from_valid_id(self.transaction)
+    }
+
+    /// The unspent outputs of the transaction sent to the address
+    pub fn outputs(&self) -> &Vec<UnspentOutput> {
+        &self.outputs
+    }
+}
+
+struct Delegation {
+    delegation: chain_impl_mockchain::account::DelegationType,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl Delegation {
+    /// The stake pools delegated to, along with the parts of the stake they receive.
+    /// Empty if the account doesn't delegate.
+    pub fn pools(&self) -> Vec<PoolDelegation> {
+        use chain_impl_mockchain::account::DelegationType;
+
+        match &self.delegation {
+            DelegationType::NonDelegated => vec![],
+            DelegationType::Full(id) => vec![PoolDelegation {
+                pool: id.clone(),
+                parts: 1,
+            }],
+            DelegationType::Ratio(delegation_ratio) => delegation_ratio
+                .pools()
+                .iter()
+                .map(|(pool, parts)| PoolDelegation {
+                    pool: pool.clone(),
+                    parts: *parts,
+                })
+                .collect(),
+        }
+    }
+
+    /// The number of parts the stake is split in
+    pub fn total_parts(&self) -> i32 {
+        use chain_impl_mockchain::account::DelegationType;
+
+        match &self.delegation {
+            DelegationType::NonDelegated => 0,
+            DelegationType::Full(_) => 1,
+            DelegationType::Ratio(delegation_ratio) => delegation_ratio.parts().into(),
+        }
+    }
+}
+
+struct PoolDelegation {
+    pool: certificate::PoolId,
+    parts: u8,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl PoolDelegation {
+    pub fn pool(&self) -> Pool {
+        Pool::from_valid_id(self.pool.clone())
+    }
+
+    /// The parts of the stake delegated to the pool
+    pub fn parts(&self) -> i32 {
+        self.parts.into()
+    }
+}
+
 /*--------------------------------------------*/
 /*------------------Certificates-------------*/
 /*------------------------------------------*/
@@ -739,6 +1162,35 @@ impl PoolUpdate {
     // TODO: Updated keys?
 }
 
+struct VoteCast {
+    vote_cast: certificate::VoteCast,
+}
+
+impl From<certificate::VoteCast> for VoteCast {
+    fn from(vote_cast: certificate::VoteCast) -> VoteCast {
+        VoteCast { vote_cast }
+    }
+}
+
+#[juniper::object(
+    Context = Context,
+)]
+impl VoteCast {
+    pub fn vote_plan(&self) -> VotePlan {
+        VotePlan::from_valid_id(self.vote_cast.vote_plan().clone())
+    }
+
+    pub fn proposal_index(&self) -> i32 {
+        self.vote_cast.proposal_index().into()
+    }
+
+    pub fn choice(&self) -> i32 {
+        match self.vote_cast.payload() {
+            chain_impl_mockchain::vote::Payload::Public { choice } => choice.as_byte().into(),
+        }
+    }
+}
+
 // TODO can we use jormungandr-lib Certificate ?
 enum Certificate {
     StakeDelegation(StakeDelegation),
@@ -746,6 +1198,8 @@ enum Certificate {
     PoolRegistration(PoolRegistration),
     PoolRetirement(PoolRetirement),
     PoolUpdate(PoolUpdate),
+    VotePlan(VotePlan),
+    VoteCast(VoteCast),
 }
 
 impl TryFrom<chain_impl_mockchain::certificate::Certificate> for Certificate {
@@ -769,8 +1223,10 @@ impl TryFrom<chain_impl_mockchain::certificate::Certificate> for Certificate {
             certificate::Certificate::PoolUpdate(c) => {
                 Ok(Certificate::PoolUpdate(PoolUpdate::from(c)))
             }
-            certificate::Certificate::VotePlan(_c) => todo!("Vote plans are not yet supported"),
-            certificate::Certificate::VoteCast(_c) => todo!("Vote casts are not yet supported"),
+            certificate::Certificate::VotePlan(c) => {
+                Ok(Certificate::VotePlan(VotePlan::from_valid_id(c.to_id())))
+            }
+            certificate::Certificate::VoteCast(c) => Ok(Certificate::VoteCast(VoteCast::from(c))),
         }
     }
 }
@@ -783,7 +1239,9 @@ graphql_union!(Certificate: Context |&self| {
         &OwnerStakeDelegation => match *self { Certificate::OwnerStakeDelegation(ref c) => Some(c), _ => None },
         &PoolRegistration => match *self { Certificate::PoolRegistration(ref c) => Some(c), _ => None },
         &PoolUpdate => match *self { Certificate::PoolUpdate(ref c) => Some(c), _ => None},
-        &PoolRetirement => match *self { Certificate::PoolRetirement(ref c) => Some(c), _ => None}
+        &PoolRetirement => match *self { Certificate::PoolRetirement(ref c) => Some(c), _ => None},
+        &VotePlan => match *self { Certificate::VotePlan(ref c) => Some(c), _ => None},
+        &VoteCast => match *self { Certificate::VoteCast(ref c) => Some(c), _ => None}
     }
 });
 
@@ -904,15 +1362,233 @@ impl Pool {
     }
 }
 
+#[derive(Clone)]
+pub struct VotePlan {
+    id: certificate::VotePlanId,
+    data: Option<ExplorerVotePlan>,
+}
+
+impl VotePlan {
+    fn from_string_id(id: &String, db: &ExplorerDB) -> FieldResult<VotePlan> {
+        let id = certificate::VotePlanId::from_str(&id)?;
+        let data = block_on(db.get_vote_plan(&id))
+            .ok_or(ErrorKind::NotFound("Vote plan not found".to_owned()))?;
+
+        Ok(VotePlan {
+            id,
+            data: Some(data),
+        })
+    }
+
+    fn from_valid_id(id: certificate::VotePlanId) -> VotePlan {
+        VotePlan { id, data: None }
+    }
+
+    fn new_with_data(id: certificate::VotePlanId, data: ExplorerVotePlan) -> Self {
+        VotePlan {
+            id,
+            data: Some(data),
+        }
+    }
+
+    fn get_data(&self, context: &Context) -> FieldResult<ExplorerVotePlan> {
+        match &self.data {
+            Some(data) => Ok(data.clone()),
+            None => block_on(context.db.get_vote_plan(&self.id))
+                .ok_or(ErrorKind::NotFound("Vote plan not found".to_owned()).into()),
+        }
+    }
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl VotePlan {
+    pub fn id(&self) -> VotePlanId {
+        VotePlanId(format!("{}", &self.id))
+    }
+
+    /// The transaction holding the vote plan certificate
+    pub fn transaction(&self, context: &Context) -> FieldResult<Transaction> {
+        Ok(Transaction::from_valid_id(
+            self.get_data(context)?.transaction,
+        ))
+    }
+
+    pub fn vote_start(&self, context: &Context) -> FieldResult<BlockDate> {
+        Ok(self.get_data(context)?.vote_start.into())
+    }
+
+    pub fn vote_end(&self, context: &Context) -> FieldResult<BlockDate> {
+        Ok(self.get_data(context)?.vote_end.into())
+    }
+
+    pub fn committee_end(&self, context: &Context) -> FieldResult<BlockDate> {
+        Ok(self.get_data(context)?.committee_end.into())
+    }
+
+    pub fn proposals(&self, context: &Context) -> FieldResult<Vec<Proposal>> {
+        Ok(self
+            .get_data(context)?
+            .proposals
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| Proposal {
+                vote_plan: self.id.clone(),
+                index: index
+                    .try_into()
+                    .expect("at most 256 proposals in a vote plan"),
+                data,
+            })
+            .collect())
+    }
+}
+
+struct Proposal {
+    vote_plan: certificate::VotePlanId,
+    index: u8,
+    data: ExplorerProposal,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl Proposal {
+    pub fn vote_plan(&self) -> VotePlan {
+        VotePlan::from_valid_id(self.vote_plan.clone())
+    }
+
+    /// The index of the proposal in the vote plan, used by the vote casts
+    pub fn index(&self) -> i32 {
+        self.index.into()
+    }
+
+    pub fn external_id(&self) -> ExternalProposalId {
+        ExternalProposalId(self.data.external_id.to_string())
+    }
+
+    /// The number of options the voters can choose from
+    pub fn options(&self) -> i32 {
+        self.data.options.into()
+    }
+
+    /// The votes cast for the proposal until the tip of the explorer
+    pub fn tally(&self) -> Tally {
+        Tally {
+            results: self.data.tally.clone(),
+        }
+    }
+
+    pub fn votes(
+        &self,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<IndexCursor>,
+        after: Option<IndexCursor>,
+    ) -> FieldResult<VoteConnection> {
+        let votes = &self.data.votes;
+
+        let bounds = if votes.len() > 0 {
+            PaginationInterval::Inclusive(InclusivePaginationInterval {
+                lower_bound: 0u32,
+                upper_bound: votes
+                    .len()
+                    .checked_sub(1)
+                    .unwrap()
+                    .try_into()
+                    .expect("Tried to paginate more than 2^32 votes"),
+            })
+        } else {
+            PaginationInterval::Empty
+        };
+
+        let pagination_arguments = PaginationArguments {
+            first,
+            last,
+            before: before.map(u32::try_from).transpose()?,
+            after: after.map(u32::try_from).transpose()?,
+        }
+        .validate()?;
+
+        VoteConnection::new(bounds, pagination_arguments, |range| match range {
+            PaginationInterval::Empty => vec![],
+            PaginationInterval::Inclusive(range) => (range.lower_bound..=range.upper_bound)
+                .filter_map(|i| votes.get(i).map(|vote| (Vote::from(vote.as_ref()), i)))
+                .collect(),
+        })
+    }
+}
+
+struct Tally {
+    results: Vec<u64>,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl Tally {
+    /// The number of votes for each of the options, indexed by choice
+    pub fn results(&self) -> Vec<VoteCount> {
+        self.results.iter().map(|count| (*count).into()).collect()
+    }
+
+    pub fn total(&self) -> VoteCount {
+        self.results.iter().sum::<u64>().into()
+    }
+}
+
+#[derive(Clone)]
+pub struct Vote {
+    vote: ExplorerVote,
+}
+
+impl From<&ExplorerVote> for Vote {
+    fn from(vote: &ExplorerVote) -> Vote {
+        Vote { vote: vote.clone() }
+    }
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl Vote {
+    pub fn transaction(&self) -> Transaction {
+        Transaction::from_valid_id(self.vote.transaction)
+    }
+
+    pub fn voter(&self) -> Option<Address> {
+        self.vote.voter.as_ref().map(Address::from)
+    }
+
+    pub fn choice(&self) -> i32 {
+        self.vote.choice.into()
+    }
+}
+
 struct Status {}
 
 #[juniper::object(
     Context = Context
 )]
 impl Status {
-    pub fn current_epoch(&self) -> FieldResult<Epoch> {
-        // TODO: Would this be the Epoch of last block or a timeframe reference?
-        Err(ErrorKind::Unimplemented.into())
+    /// The epoch of the current time, according to the time frame of the blockchain
+    pub fn current_epoch(&self, context: &Context) -> FieldResult<Epoch> {
+        let tip = latest_ref(context)?;
+        let era = tip.epoch_leadership_schedule().era();
+        let now = std::time::SystemTime::now();
+
+        tip.time_frame()
+            .slot_at(&now)
+            .and_then(|slot| era.from_slot_to_era(slot))
+            .map(|position| Epoch {
+                id: position.epoch.0,
+            })
+            .ok_or_else(|| {
+                ErrorKind::InternalError(
+                    "the current time is outside of the blockchain time frame".to_owned(),
+                )
+                .into()
+            })
     }
 
     pub fn latest_block(&self, context: &Context) -> FieldResult<Block> {
@@ -1275,6 +1951,63 @@ impl Query {
         })
     }
 
+    pub fn vote_plan(id: VotePlanId, context: &Context) -> FieldResult<VotePlan> {
+        VotePlan::from_string_id(&id.0, &context.db)
+    }
+
+    pub fn all_vote_plans(
+        &self,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<IndexCursor>,
+        after: Option<IndexCursor>,
+        context: &Context,
+    ) -> FieldResult<VotePlanConnection> {
+        let mut vote_plans = block_on(context.db.get_vote_plans());
+
+        vote_plans.sort_unstable_by_key(|(id, _)| id.clone());
+
+        let boundaries = if vote_plans.len() > 0 {
+            PaginationInterval::Inclusive(InclusivePaginationInterval {
+                lower_bound: 0u32,
+                upper_bound: vote_plans
+                    .len()
+                    .checked_sub(1)
+                    .unwrap()
+                    .try_into()
+                    .expect("tried to paginate more than 2^32 elements"),
+            })
+        } else {
+            PaginationInterval::Empty
+        };
+
+        let pagination_arguments = PaginationArguments {
+            first,
+            last,
+            before: before.map(u32::try_from).transpose()?,
+            after: after.map(u32::try_from).transpose()?,
+        }
+        .validate()?;
+
+        VotePlanConnection::new(boundaries, pagination_arguments, |range| match range {
+            PaginationInterval::Empty => vec![],
+            PaginationInterval::Inclusive(range) => {
+                let from = range.lower_bound.into();
+                let to = range.upper_bound.into();
+
+                (from..=to)
+                    .map(|i: u32| {
+                        let (id, data) = &vote_plans[usize::try_from(i).unwrap()];
+                        (
+                            VotePlan::new_with_data(id.clone(), ExplorerVotePlan::clone(data)),
+                            i,
+                        )
+                    })
+                    .collect::<Vec<(VotePlan, u32)>>()
+            }
+        })
+    }
+
     pub fn status() -> FieldResult<Status> {
         Ok(Status {})
     }
@@ -1293,6 +2026,18 @@ pub fn create_schema() -> Schema {
     Schema::new(Query {}, EmptyMutation::new())
 }
 
+/// the blockchain state at the tip of the explorer
+fn latest_ref(context: &Context) -> FieldResult<Arc<blockchain::Ref>> {
+    block_on(async {
+        let hash = context.db.get_latest_block_hash().await;
+        context.db.blockchain().get_ref(hash).await
+    })
+    .map_err(|err| ErrorKind::InternalError(err.to_string()))?
+    .ok_or_else(|| {
+        ErrorKind::InternalError("tip of the explorer is not in the blockchain".to_owned()).into()
+    })
+}
+
 fn latest_block(context: &Context) -> FieldResult<ExplorerBlock> {
     block_on(async {
         let hash = context.db.get_latest_block_hash().await;
@@ -32,6 +32,21 @@ pub struct TransactionCount(pub String);
 #[derive(juniper::GraphQLScalarValue)]
 pub struct PoolCount(pub String);
 
+#[derive(juniper::GraphQLScalarValue)]
+pub struct VotePlanId(pub String);
+
+#[derive(juniper::GraphQLScalarValue)]
+pub struct ExternalProposalId(pub String);
+
+#[derive(juniper::GraphQLScalarValue)]
+pub struct VotePlanCount(pub String);
+
+#[derive(juniper::GraphQLScalarValue)]
+pub struct VoteCount(pub String);
+
+#[derive(juniper::GraphQLScalarValue)]
+pub struct SpendingCounter(pub String);
+
 #[derive(juniper::GraphQLScalarValue)]
 pub struct PublicKey(pub String);
 
@@ -129,6 +144,12 @@ impl From<chain_time::TimeOffsetSeconds> for TimeOffsetSeconds {
     }
 }
 
+impl From<u32> for SpendingCounter {
+    fn from(counter: u32) -> SpendingCounter {
+        SpendingCounter(format!("{}", counter))
+    }
+}
+
 impl From<u64> for TransactionCount {
     fn from(n: u64) -> TransactionCount {
         TransactionCount(format!("{}", n))
@@ -141,6 +162,18 @@ impl From<u64> for PoolCount {
     }
 }
 
+impl From<u64> for VotePlanCount {
+    fn from(n: u64) -> VotePlanCount {
+        VotePlanCount(format!("{}", n))
+    }
+}
+
+impl From<u64> for VoteCount {
+    fn from(n: u64) -> VoteCount {
+        VoteCount(format!("{}", n))
+    }
+}
+
 impl From<u32> for IndexCursor {
     fn from(number: u32) -> IndexCursor {
         IndexCursor(number.into())
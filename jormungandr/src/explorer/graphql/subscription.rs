@@ -0,0 +1,577 @@
+//! Subscriptions of the explorer.
+//!
+//! Juniper executes query and mutation operations only, so the operation types
+//! of the documents sent by the clients are parsed and their subscription
+//! operations are executed as queries on the `Subscription` root, once for
+//! every new block indexed by the explorer. The fields of the
+//! `Subscription` root resolve to `null` when the block is not relevant to
+//! them, and nothing is sent to the client when all the fields requested are
+//! `null`.
+
+use super::error::ErrorKind;
+use super::{Address, Block, Context, Pool, Transaction};
+use crate::explorer::indexing::{BlockProducer, ExplorerTransaction};
+use crate::explorer::NewBlockEvent;
+use chain_impl_mockchain::certificate::{self, Certificate};
+use juniper::{http::GraphQLRequest, EmptyMutation, FieldResult, FromContext, RootNode};
+use std::str::FromStr;
+use thiserror::Error;
+
+pub struct SubscriptionContext {
+    pub context: Context,
+    pub event: NewBlockEvent,
+}
+
+impl juniper::Context for SubscriptionContext {}
+
+impl FromContext<SubscriptionContext> for Context {
+    fn from(value: &SubscriptionContext) -> &Context {
+        &value.context
+    }
+}
+
+pub type SubscriptionSchema = RootNode<'static, Subscription, EmptyMutation<SubscriptionContext>>;
+
+pub fn create_subscription_schema() -> SubscriptionSchema {
+    SubscriptionSchema::new(Subscription {}, EmptyMutation::new())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, juniper::GraphQLEnum)]
+pub enum StakePoolEventKind {
+    Registration,
+    Retirement,
+    BlockCreated,
+}
+
+/// Something that happened to a stake pool in a new block
+pub struct StakePoolEvent {
+    kind: StakePoolEventKind,
+    pool: certificate::PoolId,
+    block: Block,
+    transaction: Option<Transaction>,
+}
+
+#[juniper::object(
+    Context = Context
+)]
+impl StakePoolEvent {
+    pub fn kind(&self) -> StakePoolEventKind {
+        self.kind
+    }
+
+    pub fn pool(&self) -> Pool {
+        Pool::from_valid_id(self.pool.clone())
+    }
+
+    /// The block the event happened in
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
+    /// The transaction holding the certificate, if the event is a registration or
+    /// a retirement
+    pub fn transaction(&self) -> Option<&Transaction> {
+        self.transaction.as_ref()
+    }
+}
+
+pub struct Subscription;
+
+#[juniper::object(
+    Context = SubscriptionContext,
+)]
+impl Subscription {
+    /// The new tip of the longest chain, every time it changes
+    fn tip(context: &SubscriptionContext) -> Option<Block> {
+        if context.event.is_tip {
+            Some(Block::from(context.event.block.as_ref()))
+        } else {
+            None
+        }
+    }
+
+    /// Every block indexed by the explorer, including the ones that are not
+    /// (or not yet) on the longest chain
+    fn block(context: &SubscriptionContext) -> Block {
+        Block::from(context.event.block.as_ref())
+    }
+
+    /// The transactions of the new blocks involving the given address, either as
+    /// an input or an output
+    fn transactions_by_address(
+        bech32: String,
+        context: &SubscriptionContext,
+    ) -> FieldResult<Option<Vec<Transaction>>> {
+        let address = Address::from_bech32(&bech32)?;
+        let block = &context.event.block;
+
+        let mut transactions: Vec<&ExplorerTransaction> = block
+            .transactions
+            .values()
+            .filter(|tx| {
+                tx.outputs()
+                    .iter()
+                    .map(|output| &output.address)
+                    .chain(tx.inputs().iter().map(|input| &input.address))
+                    .any(|tx_address| tx_address == &address.id)
+            })
+            .collect();
+        transactions.sort_by_key(|tx| tx.offset_in_block);
+
+        if transactions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            transactions
+                .into_iter()
+                .map(|tx| Transaction {
+                    id: tx.id(),
+                    block_hash: Some(block.id()),
+                    contents: Some(tx.clone()),
+                })
+                .collect(),
+        ))
+    }
+
+    /// The registrations and retirements of stake pools, and the blocks they
+    /// create. All the stake pools are followed if no id is given.
+    fn stake_pool_events(
+        id: Option<String>,
+        context: &SubscriptionContext,
+    ) -> FieldResult<Option<Vec<StakePoolEvent>>> {
+        let pool_filter = id
+            .map(|id| certificate::PoolId::from_str(&id))
+            .transpose()
+            .map_err(|_| ErrorKind::ArgumentError("invalid stake pool id".to_owned()))?;
+        let block = &context.event.block;
+
+        let mut transactions: Vec<&ExplorerTransaction> = block.transactions.values().collect();
+        transactions.sort_by_key(|tx| tx.offset_in_block);
+
+        let mut events: Vec<StakePoolEvent> = transactions
+            .into_iter()
+            .filter_map(|tx| {
+                let (kind, pool) = match &tx.certificate {
+                    Some(Certificate::PoolRegistration(registration)) => {
+                        (StakePoolEventKind::Registration, registration.to_id())
+                    }
+                    Some(Certificate::PoolRetirement(retirement)) => {
+                        (StakePoolEventKind::Retirement, retirement.pool_id.clone())
+                    }
+                    _ => return None,
+                };
+                Some(StakePoolEvent {
+                    kind,
+                    pool,
+                    block: Block::from(block.as_ref()),
+                    transaction: Some(Transaction {
+                        id: tx.id(),
+                        block_hash: Some(block.id()),
+                        contents: Some(tx.clone()),
+                    }),
+                })
+            })
+            .collect();
+
+        if let BlockProducer::StakePool(pool) = block.producer() {
+            events.push(StakePoolEvent {
+                kind: StakePoolEventKind::BlockCreated,
+                pool: pool.clone(),
+                block: Block::from(block.as_ref()),
+                transaction: None,
+            });
+        }
+
+        if let Some(pool_filter) = pool_filter {
+            events.retain(|event| event.pool == pool_filter);
+        }
+
+        if events.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(events))
+        }
+    }
+}
+
+/// A subscription operation requested by a client
+pub struct SubscriptionRequest(GraphQLRequest);
+
+/// The result of the execution of a subscription for a new block
+pub enum SubscriptionResponse {
+    /// the response to send to the client
+    Data(serde_json::Value),
+    /// none of the fields of the subscription are concerned by the block
+    Nothing,
+    /// the subscription cannot be executed, it has to be stopped
+    Error(serde_json::Value),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SubscriptionRequestError {
+    #[error("invalid GraphQL document at offset {0}")]
+    Syntax(usize),
+    #[error("the document has no operation")]
+    NoOperation,
+    #[error("the document has several operations, the operation name is required")]
+    OperationNameRequired,
+    #[error("the document has no operation named `{0}`")]
+    UnknownOperation(String),
+    #[error("only the subscription operations can be executed, not a {0}")]
+    NotASubscription(OperationType),
+}
+
+impl SubscriptionRequest {
+    pub fn new(
+        query: String,
+        operation_name: Option<String>,
+        variables: Option<juniper::InputValue>,
+    ) -> Result<Self, SubscriptionRequestError> {
+        let query = subscription_as_query(&query, operation_name.as_deref())?;
+        Ok(SubscriptionRequest(GraphQLRequest::new(
+            query,
+            operation_name,
+            variables,
+        )))
+    }
+
+    pub fn execute(
+        &self,
+        schema: &SubscriptionSchema,
+        context: &SubscriptionContext,
+    ) -> SubscriptionResponse {
+        let response = self.0.execute(schema, context);
+        let is_ok = response.is_ok();
+        let value = match serde_json::to_value(&response) {
+            Ok(value) => value,
+            Err(err) => {
+                return SubscriptionResponse::Error(serde_json::json!({
+                    "errors": [{ "message": err.to_string() }]
+                }))
+            }
+        };
+
+        if !is_ok {
+            return SubscriptionResponse::Error(value);
+        }
+
+        let has_errors = value.get("errors").is_some();
+        let has_data = value
+            .get("data")
+            .and_then(|data| data.as_object())
+            .map_or(false, |fields| {
+                fields.values().any(|field| !field.is_null())
+            });
+        if has_errors || has_data {
+            SubscriptionResponse::Data(value)
+        } else {
+            SubscriptionResponse::Nothing
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl std::fmt::Display for OperationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OperationType::Query => f.write_str("query"),
+            OperationType::Mutation => f.write_str("mutation"),
+            OperationType::Subscription => f.write_str("subscription"),
+        }
+    }
+}
+
+/// An operation definition of a GraphQL document
+struct Operation<'a> {
+    operation_type: OperationType,
+    name: Option<&'a str>,
+    /// the position of the operation type keyword in the document, `None` for
+    /// the query shorthand
+    keyword: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Name(&'a str),
+    Punctuator(char),
+    String,
+}
+
+/// Split a GraphQL document in tokens with their position, the strings,
+/// comments, whitespaces and commas are skipped
+fn tokenize(document: &str) -> Result<Vec<(usize, usize, Token)>, SubscriptionRequestError> {
+    let bytes = document.as_bytes();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let start = index;
+        match bytes[index] {
+            b' ' | b'\t' | b'\n' | b'\r' | b',' => index += 1,
+            b'#' => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    index += 1;
+                }
+            }
+            b'"' if bytes[index..].starts_with(b"\"\"\"") => {
+                index += 3;
+                loop {
+                    if index >= bytes.len() {
+                        return Err(SubscriptionRequestError::Syntax(start));
+                    }
+                    if bytes[index..].starts_with(b"\\\"\"\"") {
+                        index += 4;
+                    } else if bytes[index..].starts_with(b"\"\"\"") {
+                        index += 3;
+                        break;
+                    } else {
+                        index += 1;
+                    }
+                }
+                tokens.push((start, index, Token::String));
+            }
+            b'"' => {
+                index += 1;
+                loop {
+                    match bytes.get(index) {
+                        None | Some(b'\n') => return Err(SubscriptionRequestError::Syntax(start)),
+                        Some(b'\\') => index += 2,
+                        Some(b'"') => {
+                            index += 1;
+                            break;
+                        }
+                        Some(_) => index += 1,
+                    }
+                }
+                tokens.push((start, index, Token::String));
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                while index < bytes.len()
+                    && (bytes[index].is_ascii_alphanumeric() || bytes[index] == b'_')
+                {
+                    index += 1;
+                }
+                tokens.push((start, index, Token::Name(&document[start..index])));
+            }
+            c if c.is_ascii_digit() || c == b'-' || c == b'.' => {
+                // numbers and the spread operator are not needed to find the
+                // operations, they are checked by juniper
+                index += 1;
+            }
+            c if b"!$()[]{}:=@|&".contains(&c) => {
+                index += 1;
+                tokens.push((start, index, Token::Punctuator(c as char)));
+            }
+            _ => return Err(SubscriptionRequestError::Syntax(start)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Find the operation definitions of a GraphQL document
+fn parse_operations(document: &str) -> Result<Vec<Operation>, SubscriptionRequestError> {
+    let tokens = tokenize(document)?;
+    let mut operations = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some((start, end, token)) = tokens.next() {
+        let operation = match token {
+            Token::Punctuator('{') => Some(Operation {
+                operation_type: OperationType::Query,
+                name: None,
+                keyword: None,
+            }),
+            Token::Name(keyword) => {
+                let operation_type = match keyword {
+                    "query" => Some(OperationType::Query),
+                    "mutation" => Some(OperationType::Mutation),
+                    "subscription" => Some(OperationType::Subscription),
+                    "fragment" => None,
+                    _ => return Err(SubscriptionRequestError::Syntax(start)),
+                };
+                let name = match tokens.peek() {
+                    Some((_, _, Token::Name(name))) => {
+                        let name = *name;
+                        tokens.next();
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                operation_type.map(|operation_type| Operation {
+                    operation_type,
+                    name,
+                    keyword: Some((start, end)),
+                })
+            }
+            _ => return Err(SubscriptionRequestError::Syntax(start)),
+        };
+
+        // skip the rest of the definition, until the end of its selection set
+        let mut depth: usize = match token {
+            Token::Punctuator('{') => 1,
+            _ => 0,
+        };
+        let mut parentheses: usize = 0;
+        let mut in_selection_set = depth > 0;
+        while !in_selection_set || depth > 0 {
+            let (position, _, token) = tokens
+                .next()
+                .ok_or(SubscriptionRequestError::Syntax(document.len()))?;
+            match token {
+                Token::Punctuator('(') => parentheses += 1,
+                Token::Punctuator(')') => {
+                    parentheses = parentheses
+                        .checked_sub(1)
+                        .ok_or(SubscriptionRequestError::Syntax(position))?
+                }
+                Token::Punctuator('{') => {
+                    if parentheses == 0 {
+                        in_selection_set = true;
+                    }
+                    depth += 1;
+                }
+                Token::Punctuator('}') => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or(SubscriptionRequestError::Syntax(position))?
+                }
+                _ => (),
+            }
+        }
+
+        operations.extend(operation);
+    }
+
+    Ok(operations)
+}
+
+/// Juniper only executes the query and mutation operations: the operation
+/// types of the document are parsed, the operation to execute is checked to
+/// be a subscription, and the subscription operations are turned into queries
+/// to be executed on the `Subscription` root.
+fn subscription_as_query(
+    document: &str,
+    operation_name: Option<&str>,
+) -> Result<String, SubscriptionRequestError> {
+    let operations = parse_operations(document)?;
+
+    let operation = match operation_name {
+        Some(operation_name) => operations
+            .iter()
+            .find(|operation| operation.name == Some(operation_name))
+            .ok_or_else(|| SubscriptionRequestError::UnknownOperation(operation_name.to_owned()))?,
+        None => match operations.as_slice() {
+            [] => return Err(SubscriptionRequestError::NoOperation),
+            [operation] => operation,
+            _ => return Err(SubscriptionRequestError::OperationNameRequired),
+        },
+    };
+    if operation.operation_type != OperationType::Subscription {
+        return Err(SubscriptionRequestError::NotASubscription(
+            operation.operation_type,
+        ));
+    }
+
+    let mut result = String::with_capacity(document.len());
+    let mut copied = 0;
+    for operation in operations {
+        if let (OperationType::Subscription, Some((start, end))) =
+            (operation.operation_type, operation.keyword)
+        {
+            result.push_str(&document[copied..start]);
+            result.push_str("query");
+            copied = end;
+        }
+    }
+    result.push_str(&document[copied..]);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{subscription_as_query, OperationType, SubscriptionRequestError};
+
+    #[test]
+    fn subscription_operation_is_executed_as_query() {
+        assert_eq!(
+            subscription_as_query("subscription { tip { id } }", None).unwrap(),
+            "query { tip { id } }"
+        );
+        assert_eq!(
+            subscription_as_query(
+                "subscription NewBlocks($a: String = \"{\") { block { id } }",
+                None
+            )
+            .unwrap(),
+            "query NewBlocks($a: String = \"{\") { block { id } }"
+        );
+    }
+
+    #[test]
+    fn subscription_in_selections_strings_and_comments_is_kept() {
+        assert_eq!(
+            subscription_as_query(
+                "# subscription\nsubscription { subscription: block { id } }",
+                None
+            )
+            .unwrap(),
+            "# subscription\nquery { subscription: block { id } }"
+        );
+        assert_eq!(
+            subscription_as_query(
+                r#"subscription { transactionsByAddress(bech32: """subscription""") { id } }"#,
+                None
+            )
+            .unwrap(),
+            r#"query { transactionsByAddress(bech32: """subscription""") { id } }"#
+        );
+    }
+
+    #[test]
+    fn only_subscription_operations_are_executed() {
+        assert_eq!(
+            subscription_as_query("{ tip { id } }", None),
+            Err(SubscriptionRequestError::NotASubscription(
+                OperationType::Query
+            ))
+        );
+        assert_eq!(
+            subscription_as_query("mutation { tip { id } }", None),
+            Err(SubscriptionRequestError::NotASubscription(
+                OperationType::Mutation
+            ))
+        );
+    }
+
+    #[test]
+    fn operation_is_selected_by_name() {
+        let document = "query A { tip { id } } subscription B { tip { id } }";
+        assert_eq!(
+            subscription_as_query(document, None),
+            Err(SubscriptionRequestError::OperationNameRequired)
+        );
+        assert_eq!(
+            subscription_as_query(document, Some("A")),
+            Err(SubscriptionRequestError::NotASubscription(
+                OperationType::Query
+            ))
+        );
+        assert_eq!(
+            subscription_as_query(document, Some("B")).unwrap(),
+            "query A { tip { id } } query B { tip { id } }"
+        );
+        assert_eq!(
+            subscription_as_query(document, Some("C")),
+            Err(SubscriptionRequestError::UnknownOperation("C".to_owned()))
+        );
+    }
+}
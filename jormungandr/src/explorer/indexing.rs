@@ -8,10 +8,14 @@ use chain_addr::{Address, Discrimination};
 use chain_core::property::Block as _;
 use chain_core::property::Fragment as _;
 use chain_impl_mockchain::block::Proof;
-use chain_impl_mockchain::certificate::{Certificate, PoolId, PoolRegistration, PoolRetirement};
+use chain_impl_mockchain::certificate::{
+    Certificate, PoolId, PoolRegistration, PoolRetirement, VoteCast, VotePlan, VotePlanId,
+};
 use chain_impl_mockchain::key::BftLeaderId;
 use chain_impl_mockchain::transaction::{InputEnum, TransactionSlice, Witness};
 use chain_impl_mockchain::value::Value;
+use chain_impl_mockchain::vote;
+use jormungandr_lib::crypto::hash::Hash;
 use std::{convert::TryInto, sync::Arc};
 
 pub type Hamt<K, V> = imhamt::Hamt<DefaultHasher, K, Arc<V>>;
@@ -21,11 +25,15 @@ pub type Blocks = Hamt<HeaderHash, ExplorerBlock>;
 pub type ChainLengths = Hamt<ChainLength, HeaderHash>;
 
 pub type Addresses = Hamt<ExplorerAddress, PersistentSequence<FragmentId>>;
+/// The totals of each address after each of its transactions, in the order of `Addresses`
+pub type AddressTotals = Hamt<ExplorerAddress, PersistentSequence<BalanceTotals>>;
 pub type Epochs = Hamt<Epoch, EpochData>;
 
 pub type StakePoolBlocks = Hamt<PoolId, PersistentSequence<HeaderHash>>;
 pub type StakePool = Hamt<PoolId, StakePoolData>;
 
+pub type VotePlans = Hamt<VotePlanId, ExplorerVotePlan>;
+
 #[derive(Clone)]
 pub struct StakePoolData {
     pub registration: PoolRegistration,
@@ -33,6 +41,37 @@ pub struct StakePoolData {
     // TODO: Track updates here too?
 }
 
+/// A vote plan with the votes cast for its proposals until the indexed block
+#[derive(Clone)]
+pub struct ExplorerVotePlan {
+    pub id: VotePlanId,
+    pub vote_start: BlockDate,
+    pub vote_end: BlockDate,
+    pub committee_end: BlockDate,
+    pub proposals: Vec<ExplorerProposal>,
+    /// The transaction holding the vote plan certificate
+    pub transaction: FragmentId,
+}
+
+#[derive(Clone)]
+pub struct ExplorerProposal {
+    pub external_id: Hash,
+    pub options: u8,
+    /// The number of votes cast for each of the options
+    pub tally: Vec<u64>,
+    pub votes: PersistentSequence<ExplorerVote>,
+}
+
+#[derive(Clone)]
+pub struct ExplorerVote {
+    pub transaction: FragmentId,
+    /// The address of the first input paying for the vote cast. Every vote cast
+    /// is counted in the tally, the votes cast again by the same voter are not
+    /// deduplicated
+    pub voter: Option<ExplorerAddress>,
+    pub choice: u8,
+}
+
 /// Block with unified inputs the metadata needed in the queries
 #[derive(Clone)]
 pub struct ExplorerBlock {
@@ -76,6 +115,35 @@ pub struct ExplorerOutput {
     pub value: Value,
 }
 
+/// The total value received by and sent from an address, from its first transaction up
+/// to one of its transactions
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BalanceTotals {
+    pub received: u64,
+    pub sent: u64,
+}
+
+impl BalanceTotals {
+    /// the totals after a transaction with the given amounts
+    pub fn add(self, received: u64, sent: u64) -> Self {
+        BalanceTotals {
+            received: self.received.saturating_add(received),
+            sent: self.sent.saturating_add(sent),
+        }
+    }
+
+    /// the totals of the transactions counted in `self` followed by the ones counted
+    /// in `other`
+    pub fn chain(self, other: BalanceTotals) -> Self {
+        self.add(other.received, other.sent)
+    }
+
+    /// the value held by the address, only the inputs and outputs being counted
+    pub fn balance(&self) -> u64 {
+        self.received.saturating_sub(self.sent)
+    }
+}
+
 #[derive(Clone)]
 pub struct EpochData {
     pub first_block: HeaderHash,
@@ -98,8 +166,7 @@ impl ExplorerBlock {
     pub fn resolve_from(
         block: &Block,
         discrimination: Discrimination,
-        prev_transactions: &Transactions,
-        prev_blocks: &Blocks,
+        resolve_output: &dyn Fn(&FragmentId, u8) -> Option<ExplorerOutput>,
     ) -> ExplorerBlock {
         let fragments = block.contents.iter();
         let id = block.id();
@@ -116,8 +183,7 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             None,
                             offset.try_into().unwrap(),
                             &current_block_txs,
@@ -129,8 +195,7 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             Some(Certificate::OwnerStakeDelegation(
                                 tx.payload().into_payload(),
                             )),
@@ -144,8 +209,7 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             Some(Certificate::StakeDelegation(tx.payload().into_payload())),
                             offset.try_into().unwrap(),
                             &current_block_txs,
@@ -157,8 +221,7 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             Some(Certificate::PoolRegistration(tx.payload().into_payload())),
                             offset.try_into().unwrap(),
                             &current_block_txs,
@@ -170,8 +233,7 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             Some(Certificate::PoolRetirement(tx.payload().into_payload())),
                             offset.try_into().unwrap(),
                             &current_block_txs,
@@ -183,13 +245,36 @@ impl ExplorerBlock {
                             &fragment_id,
                             &tx,
                             discrimination,
-                            prev_transactions,
-                            prev_blocks,
+                            resolve_output,
                             Some(Certificate::PoolUpdate(tx.payload().into_payload())),
                             offset.try_into().unwrap(),
                             &current_block_txs,
                         ))
                     }
+                    Fragment::VotePlan(tx) => {
+                        let tx = tx.as_slice();
+                        Some(ExplorerTransaction::from(
+                            &fragment_id,
+                            &tx,
+                            discrimination,
+                            resolve_output,
+                            Some(Certificate::VotePlan(tx.payload().into_payload())),
+                            offset.try_into().unwrap(),
+                            &current_block_txs,
+                        ))
+                    }
+                    Fragment::VoteCast(tx) => {
+                        let tx = tx.as_slice();
+                        Some(ExplorerTransaction::from(
+                            &fragment_id,
+                            &tx,
+                            discrimination,
+                            resolve_output,
+                            Some(Certificate::VoteCast(tx.payload().into_payload())),
+                            offset.try_into().unwrap(),
+                            &current_block_txs,
+                        ))
+                    }
                     Fragment::OldUtxoDeclaration(decl) => {
                         let outputs = decl
                             .addrs
@@ -270,12 +355,101 @@ impl ExplorerBlock {
     }
 }
 
+/// List the utxo inputs (as transaction id and output index) spent by the
+/// transactions of the given block, this is used to prefetch the outputs that
+/// are not indexed in memory before resolving the block
+pub fn utxo_inputs(block: &Block) -> Vec<(FragmentId, u8)> {
+    fn pointers<'a, P>(tx: TransactionSlice<'a, P>) -> Vec<(FragmentId, u8)> {
+        tx.inputs()
+            .iter()
+            .filter_map(|input| match input.to_enum() {
+                InputEnum::UtxoInput(pointer) => {
+                    Some((pointer.transaction_id, pointer.output_index))
+                }
+                InputEnum::AccountInput(..) => None,
+            })
+            .collect()
+    }
+
+    block
+        .contents
+        .iter()
+        .flat_map(|fragment| match fragment {
+            Fragment::Transaction(tx) => pointers(tx.as_slice()),
+            Fragment::OwnerStakeDelegation(tx) => pointers(tx.as_slice()),
+            Fragment::StakeDelegation(tx) => pointers(tx.as_slice()),
+            Fragment::PoolRegistration(tx) => pointers(tx.as_slice()),
+            Fragment::PoolRetirement(tx) => pointers(tx.as_slice()),
+            Fragment::PoolUpdate(tx) => pointers(tx.as_slice()),
+            Fragment::VotePlan(tx) => pointers(tx.as_slice()),
+            Fragment::VoteCast(tx) => pointers(tx.as_slice()),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+impl ExplorerVotePlan {
+    pub fn new(vote_plan: &VotePlan, transaction: FragmentId) -> Self {
+        let proposals = vote_plan
+            .proposals()
+            .iter()
+            .map(|proposal| {
+                let options = proposal.options().as_byte();
+                ExplorerProposal {
+                    external_id: Hash::from(proposal.external_id().clone()),
+                    options,
+                    tally: vec![0; options as usize],
+                    votes: PersistentSequence::new(),
+                }
+            })
+            .collect();
+
+        ExplorerVotePlan {
+            id: vote_plan.to_id(),
+            vote_start: vote_plan.vote_start(),
+            vote_end: vote_plan.vote_end(),
+            committee_end: vote_plan.committee_end(),
+            proposals,
+            transaction,
+        }
+    }
+
+    /// Count the vote cast by the given transaction, the votes for unknown proposals or
+    /// options are ignored
+    pub fn cast(&self, vote_cast: &VoteCast, transaction: &ExplorerTransaction) -> Self {
+        let mut vote_plan = self.clone();
+        let choice = match vote_cast.payload() {
+            vote::Payload::Public { choice } => choice.as_byte(),
+        };
+
+        if let Some(proposal) = vote_plan
+            .proposals
+            .get_mut(vote_cast.proposal_index() as usize)
+        {
+            if let Some(count) = proposal.tally.get_mut(choice as usize) {
+                *count += 1;
+                proposal.votes = proposal.votes.append(ExplorerVote {
+                    transaction: transaction.id(),
+                    voter: transaction
+                        .inputs()
+                        .first()
+                        .map(|input| input.address.clone()),
+                    choice,
+                });
+            }
+        }
+
+        vote_plan
+    }
+}
+
 impl ExplorerTransaction {
     /// Map the given AuthenticatedTransaction to the ExplorerTransaction API representation
     /// type.
     /// the fragment id is the associated to the given AuthenticatedTransaction before 'unwrapping'
     /// The discrimination is needed to get addresses from account inputs.
-    /// The transactions and blocks are used to resolve utxo inputs
+    /// The `resolve_output` lookup (and the transactions of the current block) are used
+    /// to resolve utxo inputs
 
     // TODO: The signature of this got too long, using a builder may be a good idea
     // It's called only from one place, though, so it is not that bothersome
@@ -283,8 +457,7 @@ impl ExplorerTransaction {
         id: &FragmentId,
         tx: &TransactionSlice<'a, T>,
         discrimination: Discrimination,
-        transactions: &Transactions,
-        blocks: &Blocks,
+        resolve_output: &dyn Fn(&FragmentId, u8) -> Option<ExplorerOutput>,
         certificate: Option<Certificate>,
         offset_in_block: u32,
         current_block: &HashMap<FragmentId, ExplorerTransaction>,
@@ -327,17 +500,11 @@ impl ExplorerTransaction {
                     let tx = utxo_pointer.transaction_id;
                     let index = utxo_pointer.output_index;
 
-                    let output = transactions
-                        .lookup(&tx)
-                        .and_then(|block_id| {
-                            blocks
-                                .lookup(&block_id)
-                                .map(|block| &block.transactions[&tx].outputs[index as usize])
-                        })
+                    let output = resolve_output(&tx, index)
                         .or_else(|| {
                             current_block
                                 .get(&tx)
-                                .map(|fragment| &fragment.outputs[index as usize])
+                                .map(|fragment| fragment.outputs[index as usize].clone())
                         })
                         .expect("transaction not found for utxo input");
 
@@ -370,4 +537,22 @@ impl ExplorerTransaction {
     pub fn outputs(&self) -> &Vec<ExplorerOutput> {
         &self.outputs
     }
+
+    /// the total of the outputs sent to and of the inputs spent from the given
+    /// address in this transaction
+    pub fn received_and_sent(&self, address: &ExplorerAddress) -> (u64, u64) {
+        let received = self
+            .outputs
+            .iter()
+            .filter(|output| &output.address == address)
+            .map(|output| output.value.0)
+            .sum();
+        let sent = self
+            .inputs
+            .iter()
+            .filter(|input| &input.address == address)
+            .map(|input| input.value.0)
+            .sum();
+        (received, sent)
+    }
 }
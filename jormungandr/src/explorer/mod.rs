@@ -2,14 +2,17 @@ pub mod error;
 pub mod graphql;
 mod indexing;
 mod persistent_sequence;
+mod stable_index;
 
 use self::error::{Error, ErrorKind, Result};
 use self::graphql::Context;
 use self::indexing::{
-    Addresses, Blocks, ChainLengths, EpochData, Epochs, ExplorerAddress, ExplorerBlock, StakePool,
-    StakePoolBlocks, StakePoolData, Transactions,
+    AddressTotals, Addresses, BalanceTotals, Blocks, ChainLengths, EpochData, Epochs,
+    ExplorerAddress, ExplorerBlock, ExplorerOutput, ExplorerTransaction, ExplorerVotePlan,
+    StakePool, StakePoolBlocks, StakePoolData, Transactions, VotePlans,
 };
 use self::persistent_sequence::PersistentSequence;
+use self::stable_index::{StableIndex, StableTip};
 
 use crate::blockcfg::{
     Block, ChainLength, ConfigParam, ConfigParams, ConsensusVersion, Epoch, Fragment, FragmentId,
@@ -21,18 +24,39 @@ use crate::utils::async_msg::MessageQueue;
 use crate::utils::task::TokioServiceInfo;
 use chain_addr::Discrimination;
 use chain_core::property::Block as _;
-use chain_impl_mockchain::certificate::{Certificate, PoolId};
+use chain_impl_mockchain::certificate::{Certificate, PoolId, VotePlanId};
 use chain_impl_mockchain::fee::LinearFee;
 use chain_impl_mockchain::multiverse;
 use futures::prelude::*;
+use slog::Logger;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+
+/// number of blocks to let accumulate beyond the epoch stability depth before
+/// moving the stable blocks from memory to the on-disk index
+const STABLE_BLOCKS_FLUSH_INTERVAL: u32 = 100;
+
+/// number of events kept for the subscribers that are too slow to follow, the
+/// subscribers lagging further behind miss the oldest events
+const SUBSCRIPTION_EVENTS_BUFFER: usize = 64;
 
 #[derive(Clone)]
 pub struct Explorer {
     pub db: ExplorerDB,
     pub schema: Arc<graphql::Schema>,
+    pub subscription_schema: Arc<graphql::SubscriptionSchema>,
+    events: broadcast::Sender<NewBlockEvent>,
+}
+
+/// Notification sent to the GraphQL subscriptions every time a block is indexed
+#[derive(Clone)]
+pub struct NewBlockEvent {
+    pub block: Arc<ExplorerBlock>,
+    /// the block became the tip of the longest chain
+    pub is_tip: bool,
 }
 
 struct Branch {
@@ -53,8 +77,19 @@ pub struct ExplorerDB {
     /// performed using the state of this branch, the HeaderHash is used as key for the
     /// multiverse, and the ChainLength is used in the updating process.
     longest_chain_tip: Tip,
+    /// The hashes of the tips of all the branches indexed in the multiverse, used to
+    /// rebuild the unstable states when the stable blocks are moved to disk.
+    branch_tips: Arc<RwLock<HashSet<HeaderHash>>>,
+    /// The on-disk indexes of the stable blocks, if the explorer has a storage
+    /// configured. In that case, the multiverse only keeps the states of the blocks
+    /// that come after the stable tip.
+    stable_index: Option<StableIndex>,
+    /// Held for writing while the stable blocks are moved to disk, so that the
+    /// queries never see the indexes in between the memory and the disk.
+    rebase_lock: Arc<RwLock<()>>,
     pub blockchain_config: BlockchainConfig,
     blockchain: Blockchain,
+    logger: Logger,
 }
 
 #[derive(Clone)]
@@ -75,10 +110,14 @@ struct State {
     transactions: Transactions,
     blocks: Blocks,
     addresses: Addresses,
+    /// relative to the root of the in-memory states, the totals of the transactions
+    /// moved to the on-disk index are not counted
+    address_totals: AddressTotals,
     epochs: Epochs,
     chain_lengths: ChainLengths,
     stake_pool_data: StakePool,
     stake_pool_blocks: StakePoolBlocks,
+    vote_plans: VotePlans,
 }
 
 #[derive(Clone)]
@@ -92,12 +131,20 @@ pub struct Settings {
 
 impl Explorer {
     pub fn new(db: ExplorerDB, schema: graphql::Schema) -> Explorer {
+        let (events, _) = broadcast::channel(SUBSCRIPTION_EVENTS_BUFFER);
         Explorer {
             db,
             schema: Arc::new(schema),
+            subscription_schema: Arc::new(graphql::create_subscription_schema()),
+            events,
         }
     }
 
+    /// get notified of the blocks indexed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<NewBlockEvent> {
+        self.events.subscribe()
+    }
+
     pub fn context(&self) -> Context {
         Context {
             db: self.db.clone(),
@@ -108,22 +155,42 @@ impl Explorer {
         }
     }
 
+    pub fn subscription_context(&self, event: NewBlockEvent) -> graphql::SubscriptionContext {
+        graphql::SubscriptionContext {
+            context: self.context(),
+            event,
+        }
+    }
+
     pub async fn start(&mut self, info: TokioServiceInfo, messages: MessageQueue<ExplorerMsg>) {
         messages
             .for_each(|input| async {
                 match input {
                     ExplorerMsg::NewBlock(block) => {
                         let mut explorer_db = self.db.clone();
+                        let events = self.events.clone();
                         let logger = info.logger().clone();
                         info.spawn_fallible("apply block", async move {
-                            explorer_db
-                                .apply_block(block)
-                                .map(move |result| match result {
-                                    // XXX: There is no garbage collection now, so the GCRoot is not used
-                                    Ok(_gc_root) => Ok(()),
-                                    Err(err) => Err(error!(logger, "Explorer error: {}", err)),
-                                })
-                                .await
+                            let block_id = block.id();
+                            let result = match explorer_db.apply_block(block).await {
+                                // XXX: There is no garbage collection now other than moving
+                                // the stable states to disk, so the GCRoot is not used
+                                Ok(state_ref) => {
+                                    let is_tip =
+                                        explorer_db.get_latest_block_hash().await == block_id;
+                                    if let Some(block) = state_ref.state().blocks.lookup(&block_id)
+                                    {
+                                        // no error if nobody is subscribed
+                                        let _ = events.send(NewBlockEvent {
+                                            block: block.clone(),
+                                            is_tip,
+                                        });
+                                    }
+                                    explorer_db.persist_stable_blocks().await
+                                }
+                                Err(err) => Err(err),
+                            };
+                            result.map_err(|err| error!(logger, "Explorer error: {}", err))
                         });
                     }
                 }
@@ -135,8 +202,15 @@ impl Explorer {
 impl ExplorerDB {
     /// Apply all the blocks in the [block0, MAIN_BRANCH_TAG], also extract the static
     /// Blockchain settings from the Block0 (Discrimination)
+    /// When a storage directory is given, the blocks already moved to the on-disk
+    /// index are not applied again, only the ones after the stable tip are.
     /// This function is only called once on the node's bootstrap phase
-    pub async fn bootstrap(block0: Block, blockchain: &Blockchain) -> Result<Self> {
+    pub async fn bootstrap(
+        block0: Block,
+        blockchain: &Blockchain,
+        storage: Option<PathBuf>,
+        logger: Logger,
+    ) -> Result<Self> {
         let blockchain_config = BlockchainConfig::from_config_params(
             block0
                 .contents
@@ -149,67 +223,96 @@ impl ExplorerDB {
                 .expect("the Initial fragment to be present in the genesis block"),
         );
 
-        let block = ExplorerBlock::resolve_from(
-            &block0,
-            blockchain_config.discrimination,
-            &Transactions::new(),
-            &Blocks::new(),
-        );
+        let block0_id = block0.id();
+        let block =
+            ExplorerBlock::resolve_from(&block0, blockchain_config.discrimination, &|_, _| None);
 
-        let blocks = apply_block_to_blocks(Blocks::new(), &block)?;
-        let epochs = apply_block_to_epochs(Epochs::new(), &block);
-        let chain_lengths = apply_block_to_chain_lengths(ChainLengths::new(), &block)?;
-        let transactions = apply_block_to_transactions(Transactions::new(), &block)?;
-        let addresses = apply_block_to_addresses(Addresses::new(), &block)?;
-        let (stake_pool_data, stake_pool_blocks) =
-            apply_block_to_stake_pools(StakePool::new(), StakePoolBlocks::new(), &block);
+        let stable_index = match storage {
+            Some(dir) => Some(StableIndex::open(dir, block0_id, logger.clone()).await?),
+            None => None,
+        };
 
-        let initial_state = State {
-            blocks,
-            epochs,
-            chain_lengths,
-            transactions,
-            addresses,
-            stake_pool_data,
-            stake_pool_blocks,
-            parent_ref: None,
+        let (initial_state, initial_hash, initial_length) = match &stable_index {
+            None => (State::from_block0(&block)?, block0_id, block.chain_length()),
+            Some(stable_index) => {
+                let stable_tip = match stable_index.stable_tip().await? {
+                    Some(stable_tip) => stable_tip,
+                    None => {
+                        stable_index.commit(vec![block.clone()]).await?;
+                        StableTip {
+                            hash: block0_id,
+                            chain_length: block.chain_length(),
+                        }
+                    }
+                };
+                info!(
+                    logger,
+                    "explorer indexes are stored on disk up to block {} ({})",
+                    stable_tip.hash,
+                    stable_tip.chain_length,
+                );
+                let stake_pool_data =
+                    load_stable_stake_pools(stable_index, blockchain, &blockchain_config).await?;
+                let vote_plans =
+                    load_stable_vote_plans(stable_index, blockchain, &blockchain_config).await?;
+                (
+                    State::stable_root(stake_pool_data, vote_plans),
+                    stable_tip.hash,
+                    stable_tip.chain_length,
+                )
+            }
         };
 
         let multiverse = Multiverse::<State>::new();
-        let block0_id = block0.id();
         let initial_state_ref = multiverse
-            .insert(block0.chain_length(), block0_id, initial_state)
+            .insert(initial_length, initial_hash, initial_state)
             .await;
 
         let bootstraped_db = ExplorerDB {
             multiverse,
             longest_chain_tip: Tip::new(Branch {
                 state_ref: initial_state_ref,
-                length: block0.header.chain_length(),
+                length: initial_length,
             }),
+            branch_tips: Arc::new(RwLock::new(std::iter::once(initial_hash).collect())),
+            stable_index,
+            rebase_lock: Arc::new(RwLock::new(())),
             blockchain_config,
             blockchain: blockchain.clone(),
+            logger,
         };
 
         let maybe_head = blockchain
             .storage()
             .get_tag(MAIN_BRANCH_TAG.to_owned())
             .await?;
-        let stream = match maybe_head {
-            Some(head) => blockchain.storage().stream_from_to(block0_id, head).await?,
+        let head = match maybe_head {
+            Some(head) => head,
             None => {
                 return Err(Error::from(ErrorKind::BootstrapError(
                     "Couldn't read the HEAD tag from storage".to_owned(),
                 )))
             }
         };
-        stream
+        if head == initial_hash {
+            return Ok(bootstraped_db);
+        }
+
+        let stream = blockchain
+            .storage()
+            .stream_from_to(initial_hash, head)
+            .await?;
+        let db = stream
             .map_err(|err| Error::from(err))
             .try_fold(bootstraped_db, |mut db, block| async move {
                 db.apply_block(block).await?;
                 Ok(db)
             })
-            .await
+            .await?;
+
+        db.persist_stable_blocks().await?;
+
+        Ok(db)
     }
 
     /// Try to add a new block to the indexes, this can fail if the parent of the block is
@@ -218,34 +321,76 @@ impl ExplorerDB {
     /// This doesn't perform any validation on the given block and the previous state, it
     /// is assumed that the Block is valid
     async fn apply_block(&mut self, block: Block) -> Result<multiverse::Ref<State>> {
+        let _guard = self.rebase_lock.read().await;
+
         let previous_block = block.header.block_parent_hash();
-        let chain_length = block.header.chain_length();
-        let block_id = block.header.hash();
-        let multiverse = self.multiverse.clone();
-        let current_tip = self.longest_chain_tip.clone();
         let discrimination = self.blockchain_config.discrimination.clone();
 
-        let previous_state = multiverse
+        let previous_state = self
+            .multiverse
             .get_ref(previous_block)
             .await
             .ok_or_else(|| Error::from(ErrorKind::AncestorNotFound(format!("{}", block.id()))))?;
+
+        let stable_outputs = match &self.stable_index {
+            Some(stable_index) => {
+                let state = previous_state.state();
+                let pointers: Vec<_> = indexing::utxo_inputs(&block)
+                    .into_iter()
+                    .filter(|(transaction_id, _)| {
+                        state.transactions.lookup(transaction_id).is_none()
+                    })
+                    .collect();
+                if pointers.is_empty() {
+                    HashMap::new()
+                } else {
+                    stable_index.outputs(pointers).await?
+                }
+            }
+            None => HashMap::new(),
+        };
+
+        let explorer_block = {
+            let state = previous_state.state();
+            ExplorerBlock::resolve_from(&block, discrimination, &|transaction_id, index| {
+                resolve_output(state, &stable_outputs, transaction_id, index)
+            })
+        };
+
+        self.apply_explorer_block(previous_state, explorer_block)
+            .await
+    }
+
+    /// Index the already resolved block on top of the given state
+    async fn apply_explorer_block(
+        &self,
+        previous_state: multiverse::Ref<State>,
+        explorer_block: ExplorerBlock,
+    ) -> Result<multiverse::Ref<State>> {
+        let chain_length = explorer_block.chain_length();
+        let block_id = explorer_block.id();
+        let parent_id = explorer_block.parent_hash;
+
         let State {
             parent_ref: _,
             transactions,
             blocks,
             addresses,
+            address_totals,
             epochs,
             chain_lengths,
             stake_pool_data,
             stake_pool_blocks,
+            vote_plans,
         } = previous_state.state().clone();
 
-        let explorer_block =
-            ExplorerBlock::resolve_from(&block, discrimination, &transactions, &blocks);
         let (stake_pool_data, stake_pool_blocks) =
             apply_block_to_stake_pools(stake_pool_data, stake_pool_blocks, &explorer_block);
+        let (addresses, address_totals) =
+            apply_block_to_addresses(addresses, address_totals, &explorer_block);
 
-        let state_ref = multiverse
+        let state_ref = self
+            .multiverse
             .insert(
                 chain_length,
                 block_id,
@@ -253,16 +398,24 @@ impl ExplorerDB {
                     parent_ref: Some(previous_state),
                     transactions: apply_block_to_transactions(transactions, &explorer_block)?,
                     blocks: apply_block_to_blocks(blocks, &explorer_block)?,
-                    addresses: apply_block_to_addresses(addresses, &explorer_block)?,
+                    addresses,
+                    address_totals,
                     epochs: apply_block_to_epochs(epochs, &explorer_block),
                     chain_lengths: apply_block_to_chain_lengths(chain_lengths, &explorer_block)?,
                     stake_pool_data,
                     stake_pool_blocks,
+                    vote_plans: apply_block_to_vote_plans(vote_plans, &explorer_block)?,
                 },
             )
             .await;
 
-        current_tip
+        {
+            let mut branch_tips = self.branch_tips.write().await;
+            branch_tips.remove(&parent_id);
+            branch_tips.insert(block_id);
+        }
+
+        self.longest_chain_tip
             .compare_and_replace(Branch {
                 state_ref: state_ref.clone(),
                 length: chain_length,
@@ -272,63 +425,308 @@ impl ExplorerDB {
         Ok(state_ref)
     }
 
+    /// Move the blocks of the longest chain that are deeper than the epoch stability
+    /// depth from the in-memory states to the on-disk index, then rebuild the
+    /// in-memory states on top of the new stable tip. The branches forking before
+    /// the new stable tip are dropped.
+    /// This does nothing if the explorer doesn't have a storage configured.
+    async fn persist_stable_blocks(&self) -> Result<()> {
+        if self.stable_index.is_none() {
+            return Ok(());
+        }
+
+        let tip_hash = self.longest_chain_tip.get_block_id().await;
+        let epoch_stability_depth = match self.blockchain.get_ref(tip_hash).await {
+            Ok(Some(tip_ref)) => tip_ref.epoch_ledger_parameters().epoch_stability_depth,
+            // the tip of the explorer is not known by the blockchain anymore, the
+            // next block will tell
+            Ok(None) | Err(_) => return Ok(()),
+        };
+
+        self.persist_blocks_deeper_than(epoch_stability_depth).await
+    }
+
+    /// Move the blocks of the longest chain that are deeper than the given depth to
+    /// the on-disk index, see `persist_stable_blocks`
+    async fn persist_blocks_deeper_than(&self, epoch_stability_depth: u32) -> Result<()> {
+        let stable_index = match &self.stable_index {
+            Some(stable_index) => stable_index,
+            None => return Ok(()),
+        };
+
+        let _guard = self.rebase_lock.write().await;
+
+        let stable_tip = stable_index
+            .stable_tip()
+            .await?
+            .expect("the explorer index to have a stable tip");
+        let (tip_hash, tip_length) = self.longest_chain_tip.get_block_id_and_length().await;
+
+        let stable_length = u32::from(stable_tip.chain_length);
+        let tip_length = u32::from(tip_length);
+        if tip_length < stable_length + epoch_stability_depth + STABLE_BLOCKS_FLUSH_INTERVAL {
+            return Ok(());
+        }
+        let new_stable_length = tip_length - epoch_stability_depth;
+
+        let tip_state = self
+            .multiverse
+            .get(tip_hash)
+            .await
+            .expect("the longest chain to be indexed");
+
+        let mut stable_blocks = Vec::new();
+        let mut current = tip_hash;
+        loop {
+            let block = tip_state
+                .blocks
+                .lookup(&current)
+                .expect("the unstable blocks of the longest chain to be indexed in memory");
+            let length = u32::from(block.chain_length());
+            if length <= new_stable_length {
+                stable_blocks.push(block.as_ref().clone());
+            }
+            if length == stable_length + 1 {
+                break;
+            }
+            current = block.parent_hash;
+        }
+        stable_blocks.reverse();
+        let new_stable_hash = stable_blocks
+            .last()
+            .expect("at least one block to become stable")
+            .id();
+
+        // collect the blocks of all the branches built on top of the new stable tip
+        let mut unstable_blocks = HashMap::new();
+        let branch_tips: Vec<HeaderHash> = self.branch_tips.read().await.iter().cloned().collect();
+        for branch_tip in branch_tips {
+            let state = match self.multiverse.get(branch_tip).await {
+                Some(state) => state,
+                None => continue,
+            };
+            let mut branch = Vec::new();
+            let mut current = branch_tip;
+            let connected = loop {
+                match state.blocks.lookup(&current) {
+                    Some(block) if u32::from(block.chain_length()) > new_stable_length => {
+                        current = block.parent_hash;
+                        branch.push(block.clone());
+                    }
+                    _ => break current == new_stable_hash,
+                }
+            };
+            if connected {
+                unstable_blocks.extend(branch.into_iter().map(|block| (block.id(), block)));
+            }
+        }
+
+        let new_root_state = self
+            .multiverse
+            .get(new_stable_hash)
+            .await
+            .expect("the new stable block to be indexed");
+
+        let stable_blocks_count = stable_blocks.len();
+        stable_index.commit(stable_blocks).await?;
+
+        self.multiverse.clear().await;
+        let root_ref = self
+            .multiverse
+            .insert(
+                new_stable_length.into(),
+                new_stable_hash,
+                State::stable_root(new_root_state.stake_pool_data, new_root_state.vote_plans),
+            )
+            .await;
+        *self.branch_tips.write().await = std::iter::once(new_stable_hash).collect();
+        self.longest_chain_tip
+            .replace(Branch {
+                state_ref: root_ref,
+                length: new_stable_length.into(),
+            })
+            .await;
+
+        let mut unstable_blocks: Vec<Arc<ExplorerBlock>> = unstable_blocks
+            .into_iter()
+            .map(|(_, block)| block)
+            .collect();
+        unstable_blocks.sort_by_key(|block| block.chain_length());
+        for block in unstable_blocks {
+            let parent = self
+                .multiverse
+                .get_ref(block.parent_hash)
+                .await
+                .expect("the parent block to be indexed before its children");
+            self.apply_explorer_block(parent, block.as_ref().clone())
+                .await?;
+        }
+
+        // keep the same tip as before in case of branches of the same length
+        let tip_ref = self
+            .multiverse
+            .get_ref(tip_hash)
+            .await
+            .expect("the longest chain to be indexed");
+        self.longest_chain_tip
+            .replace(Branch {
+                state_ref: tip_ref,
+                length: tip_length.into(),
+            })
+            .await;
+
+        debug!(
+            self.logger,
+            "moved {} stable blocks to the explorer index, up to block {} ({})",
+            stable_blocks_count,
+            new_stable_hash,
+            new_stable_length,
+        );
+
+        Ok(())
+    }
+
     pub async fn get_latest_block_hash(&self) -> HeaderHash {
         self.longest_chain_tip.get_block_id().await
     }
 
     pub async fn get_block(&self, block_id: &HeaderHash) -> Option<ExplorerBlock> {
+        let _guard = self.rebase_lock.read().await;
         let block_id = block_id.clone();
-        self.with_latest_state(move |state| {
-            state.blocks.lookup(&block_id).map(|b| b.as_ref().clone())
-        })
-        .await
+        let block = self
+            .with_latest_state(move |state| {
+                state.blocks.lookup(&block_id).map(|b| b.as_ref().clone())
+            })
+            .await;
+        match (block, &self.stable_index) {
+            (None, Some(stable_index)) => self.get_stable_block(stable_index, block_id).await,
+            (block, _) => block,
+        }
     }
 
     pub async fn get_epoch(&self, epoch: Epoch) -> Option<EpochData> {
+        let _guard = self.rebase_lock.read().await;
         let epoch = epoch.clone();
-        self.with_latest_state(move |state| state.epochs.lookup(&epoch).map(|e| e.as_ref().clone()))
-            .await
+        let unstable = self
+            .with_latest_state(move |state| state.epochs.lookup(&epoch).map(|e| e.as_ref().clone()))
+            .await;
+        let stable = match &self.stable_index {
+            Some(stable_index) => self.or_log(stable_index.epoch(epoch).await).flatten(),
+            None => None,
+        };
+        match (stable, unstable) {
+            (Some(stable), Some(unstable)) => Some(EpochData {
+                first_block: stable.first_block,
+                last_block: unstable.last_block,
+                total_blocks: stable.total_blocks + unstable.total_blocks + 1,
+            }),
+            (stable, unstable) => unstable.or(stable),
+        }
     }
 
     pub async fn find_block_by_chain_length(
         &self,
         chain_length: ChainLength,
     ) -> Option<HeaderHash> {
-        self.with_latest_state(move |state| {
-            state
-                .chain_lengths
-                .lookup(&chain_length)
-                .map(|b| b.as_ref().clone())
-        })
-        .await
+        let _guard = self.rebase_lock.read().await;
+        let block = self
+            .with_latest_state(move |state| {
+                state
+                    .chain_lengths
+                    .lookup(&chain_length)
+                    .map(|b| b.as_ref().clone())
+            })
+            .await;
+        match (block, &self.stable_index) {
+            (None, Some(stable_index)) => self
+                .or_log(stable_index.find_block_by_chain_length(chain_length).await)
+                .flatten(),
+            (block, _) => block,
+        }
     }
 
     pub async fn find_block_hash_by_transaction(
         &self,
         transaction_id: &FragmentId,
     ) -> Option<HeaderHash> {
+        let _guard = self.rebase_lock.read().await;
         let transaction_id = transaction_id.clone();
-        self.with_latest_state(move |state| {
-            state
-                .transactions
-                .lookup(&transaction_id)
-                .map(|id| id.as_ref().clone())
-        })
-        .await
+        let block = self
+            .with_latest_state(move |state| {
+                state
+                    .transactions
+                    .lookup(&transaction_id)
+                    .map(|id| id.as_ref().clone())
+            })
+            .await;
+        match (block, &self.stable_index) {
+            (None, Some(stable_index)) => self
+                .or_log(
+                    stable_index
+                        .find_block_hash_by_transaction(transaction_id)
+                        .await,
+                )
+                .flatten(),
+            (block, _) => block,
+        }
     }
 
     pub async fn get_transactions_by_address(
         &self,
         address: &ExplorerAddress,
     ) -> Option<PersistentSequence<FragmentId>> {
+        let _guard = self.rebase_lock.read().await;
         let address = address.clone();
-        self.with_latest_state(move |state| {
-            state
-                .addresses
-                .lookup(&address)
-                .map(|set| set.as_ref().clone())
-        })
-        .await
+        let unstable = {
+            let address = address.clone();
+            self.with_latest_state(move |state| {
+                state
+                    .addresses
+                    .lookup(&address)
+                    .map(|set| set.as_ref().clone())
+            })
+            .await
+        };
+        let stable = match &self.stable_index {
+            Some(stable_index) => self
+                .or_log(stable_index.transactions_by_address(address).await)
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        merge_sequences(stable, unstable)
+    }
+
+    /// Get the totals of the given address after its transaction at the given
+    /// position in the sequence returned by `get_transactions_by_address`. `None`
+    /// if the address has fewer transactions.
+    pub async fn get_address_totals(
+        &self,
+        address: &ExplorerAddress,
+        index: u64,
+    ) -> Option<BalanceTotals> {
+        let _guard = self.rebase_lock.read().await;
+        let address = address.clone();
+        let (stable_count, stable_totals) = match &self.stable_index {
+            Some(stable_index) => match self
+                .or_log(stable_index.address_totals(address.clone(), index).await)
+                .flatten()
+            {
+                Some((seq, totals)) if seq == index => return Some(totals),
+                // the last transaction of the address in the on-disk index
+                Some((seq, totals)) => (seq + 1, totals),
+                None => (0, BalanceTotals::default()),
+            },
+            None => (0, BalanceTotals::default()),
+        };
+        let unstable = self
+            .with_latest_state(move |state| {
+                state
+                    .address_totals
+                    .lookup(&address)
+                    .and_then(|sequence| sequence.get(index - stable_count).map(|t| **t))
+            })
+            .await?;
+        Some(stable_totals.chain(unstable))
     }
 
     // Get the hashes of all blocks in the range [from, to)
@@ -339,51 +737,101 @@ impl ExplorerDB {
         from: ChainLength,
         to: ChainLength,
     ) -> Vec<(HeaderHash, ChainLength)> {
+        let _guard = self.rebase_lock.read().await;
+
+        // the on-disk index only has blocks before the ones kept in memory
+        let mut hashes = match &self.stable_index {
+            Some(stable_index) => self
+                .or_log(stable_index.block_hash_range(from, to).await)
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
         let from = u32::from(from);
         let to = u32::from(to);
 
-        self.with_latest_state(move |state| {
-            (from..to)
-                .filter_map(|i| {
-                    state
-                        .chain_lengths
-                        .lookup(&i.into())
-                        .map(|b| (b.as_ref().clone(), i.into()))
-                })
-                .collect()
-        })
-        .await
+        let unstable: Vec<_> = self
+            .with_latest_state(move |state| {
+                (from..to)
+                    .filter_map(|i| {
+                        state
+                            .chain_lengths
+                            .lookup(&i.into())
+                            .map(|b| (b.as_ref().clone(), i.into()))
+                    })
+                    .collect()
+            })
+            .await;
+        hashes.extend(unstable);
+        hashes
     }
 
     pub async fn get_stake_pool_blocks(
         &self,
         pool: &PoolId,
     ) -> Option<PersistentSequence<HeaderHash>> {
+        let _guard = self.rebase_lock.read().await;
+        let pool = pool.clone();
+        let unstable = {
+            let pool = pool.clone();
+            self.with_latest_state(move |state| {
+                state
+                    .stake_pool_blocks
+                    .lookup(&pool)
+                    .map(|i| i.as_ref().clone())
+            })
+            .await
+        };
+        let stable = match &self.stable_index {
+            Some(stable_index) => self
+                .or_log(stable_index.stake_pool_blocks(pool).await)
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        merge_sequences(stable, unstable)
+    }
+
+    pub async fn get_stake_pool_data(&self, pool: &PoolId) -> Option<StakePoolData> {
+        let _guard = self.rebase_lock.read().await;
         let pool = pool.clone();
         self.with_latest_state(move |state| {
             state
-                .stake_pool_blocks
+                .stake_pool_data
                 .lookup(&pool)
                 .map(|i| i.as_ref().clone())
         })
         .await
     }
 
-    pub async fn get_stake_pool_data(&self, pool: &PoolId) -> Option<StakePoolData> {
-        let pool = pool.clone();
+    pub async fn get_stake_pools(&self) -> Vec<(PoolId, Arc<StakePoolData>)> {
+        let _guard = self.rebase_lock.read().await;
         self.with_latest_state(move |state| {
             state
                 .stake_pool_data
-                .lookup(&pool)
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn get_vote_plan(&self, vote_plan: &VotePlanId) -> Option<ExplorerVotePlan> {
+        let _guard = self.rebase_lock.read().await;
+        let vote_plan = vote_plan.clone();
+        self.with_latest_state(move |state| {
+            state
+                .vote_plans
+                .lookup(&vote_plan)
                 .map(|i| i.as_ref().clone())
         })
         .await
     }
 
-    pub async fn get_stake_pools(&self) -> Vec<(PoolId, Arc<StakePoolData>)> {
+    pub async fn get_vote_plans(&self) -> Vec<(VotePlanId, Arc<ExplorerVotePlan>)> {
+        let _guard = self.rebase_lock.read().await;
         self.with_latest_state(move |state| {
             state
-                .stake_pool_data
+                .vote_plans
                 .iter()
                 .map(|(k, v)| (k.clone(), v.clone()))
                 .collect()
@@ -400,11 +848,304 @@ impl ExplorerDB {
         f(state)
     }
 
+    /// recreate a block that was moved to the on-disk index from the node's storage
+    async fn get_stable_block(
+        &self,
+        stable_index: &StableIndex,
+        block_id: HeaderHash,
+    ) -> Option<ExplorerBlock> {
+        // the node's storage also has blocks of the abandoned branches
+        self.or_log(stable_index.block_chain_length(block_id).await)
+            .flatten()?;
+        let block = self
+            .or_log(self.blockchain.storage().get(block_id).await)
+            .flatten()?;
+        let outputs = self.or_log(stable_index.outputs(indexing::utxo_inputs(&block)).await)?;
+        Some(ExplorerBlock::resolve_from(
+            &block,
+            self.blockchain_config.discrimination,
+            &|transaction_id, index| outputs.get(&(*transaction_id, index)).cloned(),
+        ))
+    }
+
+    /// the queries have no way to report an error of the on-disk index, the error is
+    /// logged and the query answers as if the data was not found
+    fn or_log<T, E: Into<Error>>(&self, result: std::result::Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let err: Error = err.into();
+                error!(self.logger, "error reading the explorer index: {}", err);
+                None
+            }
+        }
+    }
+
     fn blockchain(&self) -> &Blockchain {
         &self.blockchain
     }
 }
 
+impl State {
+    fn from_block0(block0: &ExplorerBlock) -> Result<Self> {
+        let blocks = apply_block_to_blocks(Blocks::new(), block0)?;
+        let epochs = apply_block_to_epochs(Epochs::new(), block0);
+        let chain_lengths = apply_block_to_chain_lengths(ChainLengths::new(), block0)?;
+        let transactions = apply_block_to_transactions(Transactions::new(), block0)?;
+        let (addresses, address_totals) =
+            apply_block_to_addresses(Addresses::new(), AddressTotals::new(), block0);
+        let (stake_pool_data, stake_pool_blocks) =
+            apply_block_to_stake_pools(StakePool::new(), StakePoolBlocks::new(), block0);
+        let vote_plans = apply_block_to_vote_plans(VotePlans::new(), block0)?;
+
+        Ok(State {
+            blocks,
+            epochs,
+            chain_lengths,
+            transactions,
+            addresses,
+            address_totals,
+            stake_pool_data,
+            stake_pool_blocks,
+            vote_plans,
+            parent_ref: None,
+        })
+    }
+
+    /// The state at the stable tip of the on-disk index. Everything is found on disk
+    /// except the stake pools data and the vote plans, that are kept in memory for the
+    /// whole chain. The blocks of the stake pools only have the blocks created after the
+    /// stable tip.
+    fn stable_root(stake_pool_data: StakePool, vote_plans: VotePlans) -> Self {
+        let stake_pool_blocks =
+            stake_pool_data
+                .iter()
+                .fold(StakePoolBlocks::new(), |blocks, (id, _)| {
+                    blocks
+                        .insert(id.clone(), Arc::new(PersistentSequence::new()))
+                        .expect("stake pools to be unique")
+                });
+
+        State {
+            parent_ref: None,
+            transactions: Transactions::new(),
+            blocks: Blocks::new(),
+            addresses: Addresses::new(),
+            address_totals: AddressTotals::new(),
+            epochs: Epochs::new(),
+            chain_lengths: ChainLengths::new(),
+            stake_pool_data,
+            stake_pool_blocks,
+            vote_plans,
+        }
+    }
+}
+
+/// get the output of an indexed transaction from the in-memory state or, if not
+/// there, from the outputs prefetched from the on-disk index
+fn resolve_output(
+    state: &State,
+    stable_outputs: &HashMap<(FragmentId, u8), ExplorerOutput>,
+    transaction_id: &FragmentId,
+    index: u8,
+) -> Option<ExplorerOutput> {
+    state
+        .transactions
+        .lookup(transaction_id)
+        .and_then(|block_id| state.blocks.lookup(&block_id))
+        .map(|block| block.transactions[transaction_id].outputs[index as usize].clone())
+        .or_else(|| stable_outputs.get(&(*transaction_id, index)).cloned())
+}
+
+fn merge_sequences<T>(
+    stable: Vec<T>,
+    unstable: Option<PersistentSequence<T>>,
+) -> Option<PersistentSequence<T>>
+where
+    T: Clone,
+{
+    if stable.is_empty() {
+        return unstable;
+    }
+    let sequence = stable
+        .into_iter()
+        .fold(PersistentSequence::new(), |sequence, t| sequence.append(t));
+    match unstable {
+        None => Some(sequence),
+        Some(unstable) => Some((0..unstable.len()).fold(sequence, |sequence, i| {
+            sequence.append(
+                unstable
+                    .get(i)
+                    .expect("sequence elements to be contiguous")
+                    .as_ref()
+                    .clone(),
+            )
+        })),
+    }
+}
+
+/// rebuild the stake pools data of the stable part of the blockchain, the
+/// certificates are read from the blocks in the node's storage
+async fn load_stable_stake_pools(
+    stable_index: &StableIndex,
+    blockchain: &Blockchain,
+    blockchain_config: &BlockchainConfig,
+) -> Result<StakePool> {
+    let mut blocks: HashMap<HeaderHash, ExplorerBlock> = HashMap::new();
+    let mut stake_pools = StakePool::new();
+
+    for pool in stable_index.stake_pools().await? {
+        let registration = match stable_certificate(
+            stable_index,
+            blockchain,
+            blockchain_config,
+            &mut blocks,
+            pool.registration,
+        )
+        .await?
+        {
+            Certificate::PoolRegistration(registration) => registration,
+            _ => {
+                return Err(ErrorKind::BootstrapError(format!(
+                    "transaction {} is not a stake pool registration",
+                    pool.registration
+                ))
+                .into())
+            }
+        };
+        let retirement = match pool.retirement {
+            None => None,
+            Some(retirement) => match stable_certificate(
+                stable_index,
+                blockchain,
+                blockchain_config,
+                &mut blocks,
+                retirement,
+            )
+            .await?
+            {
+                Certificate::PoolRetirement(retirement) => Some(retirement),
+                _ => {
+                    return Err(ErrorKind::BootstrapError(format!(
+                        "transaction {} is not a stake pool retirement",
+                        retirement
+                    ))
+                    .into())
+                }
+            },
+        };
+
+        stake_pools = stake_pools
+            .insert(
+                pool.id,
+                Arc::new(StakePoolData {
+                    registration,
+                    retirement,
+                }),
+            )
+            .expect("stake pools to be unique");
+    }
+
+    Ok(stake_pools)
+}
+
+/// rebuild the vote plans of the stable part of the blockchain, the votes are
+/// counted again from the certificates in the node's storage
+async fn load_stable_vote_plans(
+    stable_index: &StableIndex,
+    blockchain: &Blockchain,
+    blockchain_config: &BlockchainConfig,
+) -> Result<VotePlans> {
+    let mut blocks: HashMap<HeaderHash, ExplorerBlock> = HashMap::new();
+    let mut vote_plans = VotePlans::new();
+
+    for transaction_id in stable_index.vote_transactions().await? {
+        let transaction = stable_transaction(
+            stable_index,
+            blockchain,
+            blockchain_config,
+            &mut blocks,
+            transaction_id,
+        )
+        .await?;
+        vote_plans = apply_transaction_to_vote_plans(vote_plans, &transaction);
+    }
+
+    Ok(vote_plans)
+}
+
+async fn stable_certificate(
+    stable_index: &StableIndex,
+    blockchain: &Blockchain,
+    blockchain_config: &BlockchainConfig,
+    blocks: &mut HashMap<HeaderHash, ExplorerBlock>,
+    transaction_id: FragmentId,
+) -> Result<Certificate> {
+    stable_transaction(
+        stable_index,
+        blockchain,
+        blockchain_config,
+        blocks,
+        transaction_id,
+    )
+    .await?
+    .certificate
+    .ok_or_else(|| {
+        ErrorKind::BootstrapError(format!(
+            "transaction {} does not have a certificate",
+            transaction_id
+        ))
+        .into()
+    })
+}
+
+/// recreate a transaction of the on-disk index from the node's storage, the blocks
+/// already read are kept in the given cache
+async fn stable_transaction(
+    stable_index: &StableIndex,
+    blockchain: &Blockchain,
+    blockchain_config: &BlockchainConfig,
+    blocks: &mut HashMap<HeaderHash, ExplorerBlock>,
+    transaction_id: FragmentId,
+) -> Result<ExplorerTransaction> {
+    let block_id = stable_index
+        .find_block_hash_by_transaction(transaction_id)
+        .await?
+        .ok_or_else(|| {
+            ErrorKind::BootstrapError(format!(
+                "transaction {} is not in the explorer index",
+                transaction_id
+            ))
+        })?;
+
+    if !blocks.contains_key(&block_id) {
+        let block = blockchain
+            .storage()
+            .get(block_id)
+            .await?
+            .ok_or_else(|| ErrorKind::BlockNotFound(format!("{}", block_id)))?;
+        let outputs = stable_index.outputs(indexing::utxo_inputs(&block)).await?;
+        let block = ExplorerBlock::resolve_from(
+            &block,
+            blockchain_config.discrimination,
+            &|transaction_id, index| outputs.get(&(*transaction_id, index)).cloned(),
+        );
+        blocks.insert(block_id, block);
+    }
+
+    blocks[&block_id]
+        .transactions
+        .get(&transaction_id)
+        .cloned()
+        .ok_or_else(|| {
+            ErrorKind::BootstrapError(format!(
+                "transaction {} is not in block {}",
+                transaction_id, block_id
+            ))
+            .into()
+        })
+}
+
 fn apply_block_to_transactions(
     mut transactions: Transactions,
     block: &ExplorerBlock,
@@ -428,8 +1169,15 @@ fn apply_block_to_blocks(blocks: Blocks, block: &ExplorerBlock) -> Result<Blocks
         .map_err(|_| Error::from(ErrorKind::BlockAlreadyExists(format!("{}", block_id))))
 }
 
-fn apply_block_to_addresses(mut addresses: Addresses, block: &ExplorerBlock) -> Result<Addresses> {
-    let transactions = block.transactions.values();
+fn apply_block_to_addresses(
+    mut addresses: Addresses,
+    mut address_totals: AddressTotals,
+    block: &ExplorerBlock,
+) -> (Addresses, AddressTotals) {
+    // keep the order of the block, as in the on-disk index, so the totals are the
+    // same wherever the transactions are indexed
+    let mut transactions: Vec<_> = block.transactions.values().collect();
+    transactions.sort_by_key(|tx| tx.offset_in_block);
 
     for tx in transactions {
         let id = tx.id();
@@ -445,6 +1193,19 @@ fn apply_block_to_addresses(mut addresses: Addresses, block: &ExplorerBlock) ->
             .collect();
 
         for address in included_addresses {
+            let (received, sent) = tx.received_and_sent(&address);
+            let totals = BalanceTotals::default().add(received, sent);
+            address_totals = address_totals.insert_or_update_simple(
+                address.clone(),
+                Arc::new(PersistentSequence::new().append(totals)),
+                |sequence| {
+                    let previous = sequence
+                        .get(sequence.len() - 1)
+                        .expect("the sequence to have the totals of the previous transaction");
+                    let new_sequence = sequence.append(previous.add(received, sent));
+                    Some(Arc::new(new_sequence))
+                },
+            );
             addresses = addresses.insert_or_update_simple(
                 address,
                 Arc::new(PersistentSequence::new().append(id.clone())),
@@ -455,7 +1216,7 @@ fn apply_block_to_addresses(mut addresses: Addresses, block: &ExplorerBlock) ->
             )
         }
     }
-    Ok(addresses)
+    (addresses, address_totals)
 }
 
 fn apply_block_to_epochs(epochs: Epochs, block: &ExplorerBlock) -> Epochs {
@@ -551,6 +1312,44 @@ fn apply_block_to_stake_pools(
     (data, blocks)
 }
 
+fn apply_block_to_vote_plans(vote_plans: VotePlans, block: &ExplorerBlock) -> Result<VotePlans> {
+    // the votes may be cast in the same block as the vote plan
+    let mut transactions: Vec<_> = block.transactions.values().collect();
+    transactions.sort_by_key(|tx| tx.offset_in_block);
+
+    transactions
+        .into_iter()
+        .try_fold(vote_plans, apply_transaction_to_vote_plans)
+}
+
+fn apply_transaction_to_vote_plans(
+    vote_plans: VotePlans,
+    transaction: &ExplorerTransaction,
+) -> Result<VotePlans> {
+    match &transaction.certificate {
+        Some(Certificate::VotePlan(vote_plan)) => {
+            let id = vote_plan.to_id();
+            vote_plans
+                .insert(
+                    id.clone(),
+                    Arc::new(ExplorerVotePlan::new(vote_plan, transaction.id())),
+                )
+                .map_err(|_| Error::from(ErrorKind::VotePlanAlreadyExists(format!("{}", id))))
+        }
+        Some(Certificate::VoteCast(vote_cast)) => vote_plans
+            .update::<_, Infallible>(vote_cast.vote_plan(), |vote_plan| {
+                Ok(Some(Arc::new(vote_plan.cast(vote_cast, transaction))))
+            })
+            .map_err(|_| {
+                Error::from(ErrorKind::VotePlanNotFound(format!(
+                    "{}",
+                    vote_cast.vote_plan()
+                )))
+            }),
+        _ => Ok(vote_plans),
+    }
+}
+
 impl BlockchainConfig {
     fn from_config_params(params: &ConfigParams) -> BlockchainConfig {
         let discrimination = params
@@ -604,8 +1403,357 @@ impl Tip {
         }
     }
 
+    async fn replace(&self, other: Branch) {
+        let mut current = self.0.write().await;
+        *current = other;
+    }
+
     async fn get_block_id(&self) -> HeaderHash {
         let guard = self.0.read().await;
         *guard.state_ref.id()
     }
+
+    async fn get_block_id_and_length(&self) -> (HeaderHash, ChainLength) {
+        let guard = self.0.read().await;
+        (*guard.state_ref.id(), guard.length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockDate, BlockVersion, Contents, ContentsBuilder, HeaderBuilderNew};
+    use crate::blockchain::Storage;
+    use chain_core::property::Fragment as _;
+    use chain_impl_mockchain::account::SpendingCounter;
+    use chain_impl_mockchain::transaction::{Input, Output, TxBuilder, Witness};
+    use chain_impl_mockchain::value::Value;
+    use chain_storage::BlockStoreBuilder;
+    use jormungandr_lib::{
+        crypto::account::SigningKey,
+        interfaces::{block0_configuration_documented_example, Block0Configuration},
+    };
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng as _;
+
+    const EPOCH_STABILITY_DEPTH: u32 = 5;
+
+    const SLOTS_PER_EPOCH: u32 = 50;
+
+    /// a temporary directory, removed when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "jormungandr-explorer-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    struct Keys {
+        sender: SigningKey,
+        receiver: SigningKey,
+    }
+
+    impl Keys {
+        fn new() -> Self {
+            Keys {
+                sender: SigningKey::generate(ChaChaRng::from_seed([1; 32])),
+                receiver: SigningKey::generate(ChaChaRng::from_seed([2; 32])),
+            }
+        }
+
+        fn addresses(&self) -> Vec<ExplorerAddress> {
+            vec![&self.sender, &self.receiver]
+                .into_iter()
+                .map(|key| ExplorerAddress::New(key.identifier().to_address(Discrimination::Test)))
+                .collect()
+        }
+
+        /// a transaction from the sender's account to the receiver, the
+        /// counter tells the transactions apart
+        fn transaction(&self, counter: u32) -> Fragment {
+            let input = Input::from_account_single(self.sender.identifier().to_inner(), Value(10));
+            let output = Output::from_address(
+                self.receiver.identifier().to_address(Discrimination::Test),
+                Value(10),
+            );
+            let builder = TxBuilder::new()
+                .set_nopayload()
+                .set_ios(&[input], &[output]);
+            let sign_data_hash = builder.get_auth_data_for_witness().hash();
+            let witness = Witness::new_account(
+                &HeaderHash::zero_hash(),
+                &sign_data_hash,
+                SpendingCounter::from(counter),
+                |data| self.sender.as_ref().sign(data),
+            );
+            Fragment::Transaction(builder.set_witnesses(&[witness]).set_payload_auth(&()))
+        }
+    }
+
+    fn block0() -> Block {
+        let config: Block0Configuration =
+            serde_yaml::from_str(&block0_configuration_documented_example()).unwrap();
+        config.to_block()
+    }
+
+    /// the child of `parent` holding the given fragments, the blocks are
+    /// spread over epochs of `SLOTS_PER_EPOCH` blocks
+    fn child(parent: &Block, fragments: Vec<Fragment>) -> Block {
+        let mut builder = ContentsBuilder::new();
+        for fragment in fragments {
+            builder.push(fragment);
+        }
+        let contents: Contents = builder.into();
+        let chain_length = parent.header.chain_length().increase();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_parent(&parent.header.id(), chain_length)
+            .set_date(BlockDate {
+                epoch: u32::from(chain_length) / SLOTS_PER_EPOCH,
+                slot_id: u32::from(chain_length) % SLOTS_PER_EPOCH,
+            })
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    /// the blocks of the longest chain up to the given chain length, every
+    /// tenth block has a transaction
+    fn chain(block0: &Block, keys: &Keys, length: u32) -> Vec<Block> {
+        let mut blocks = vec![block0.clone()];
+        for i in 1..=length {
+            let fragments = if i % 10 == 0 {
+                vec![keys.transaction(i)]
+            } else {
+                Vec::new()
+            };
+            let block = child(blocks.last().unwrap(), fragments);
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    async fn bootstrap(block0: &Block, storage: Option<PathBuf>) -> ExplorerDB {
+        let logger = Logger::root(slog::Discard, o!());
+        let block_store = Storage::new(BlockStoreBuilder::memory().build(), logger.clone());
+        block_store.put_block(block0.clone()).await.unwrap();
+        block_store
+            .put_tag(MAIN_BRANCH_TAG.to_owned(), block0.header.hash())
+            .await
+            .unwrap();
+        let blockchain = Blockchain::new(block0.header.hash(), block_store, 100, false, None);
+        ExplorerDB::bootstrap(block0.clone(), &blockchain, storage, logger)
+            .await
+            .unwrap()
+    }
+
+    async fn stable_length(db: &ExplorerDB) -> u32 {
+        let stable_tip = db
+            .stable_index
+            .as_ref()
+            .unwrap()
+            .stable_tip()
+            .await
+            .unwrap()
+            .unwrap();
+        u32::from(stable_tip.chain_length)
+    }
+
+    fn epoch_fields(epoch: Option<EpochData>) -> Option<(HeaderHash, HeaderHash, u32)> {
+        epoch.map(|epoch| (epoch.first_block, epoch.last_block, epoch.total_blocks))
+    }
+
+    fn sequence_items<T: Clone>(sequence: Option<PersistentSequence<T>>) -> Vec<T> {
+        sequence
+            .map(|sequence| {
+                (0..sequence.len())
+                    .map(|i| sequence.get(i).unwrap().as_ref().clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn stable_blocks_are_flushed_past_the_stability_depth() {
+        let dir = TestDir::new("flush");
+        let keys = Keys::new();
+        let block0 = block0();
+        let blocks = chain(&block0, &keys, 110);
+        let mut db = bootstrap(&block0, Some(dir.0.clone())).await;
+
+        let threshold = EPOCH_STABILITY_DEPTH + STABLE_BLOCKS_FLUSH_INTERVAL;
+        for block in blocks[1..threshold as usize].iter() {
+            db.apply_block(block.clone()).await.unwrap();
+        }
+        db.persist_blocks_deeper_than(EPOCH_STABILITY_DEPTH)
+            .await
+            .unwrap();
+        assert_eq!(stable_length(&db).await, 0);
+
+        for block in blocks[threshold as usize..].iter() {
+            db.apply_block(block.clone()).await.unwrap();
+        }
+        db.persist_blocks_deeper_than(EPOCH_STABILITY_DEPTH)
+            .await
+            .unwrap();
+        assert_eq!(stable_length(&db).await, 110 - EPOCH_STABILITY_DEPTH);
+
+        // the stable blocks are only found on disk
+        let tip = blocks.last().unwrap().header.hash();
+        assert_eq!(db.get_latest_block_hash().await, tip);
+        let tip_state = db.multiverse.get(tip).await.unwrap();
+        assert!(tip_state.blocks.lookup(&blocks[50].header.hash()).is_none());
+        assert!(tip_state
+            .blocks
+            .lookup(&blocks[106].header.hash())
+            .is_some());
+        assert_eq!(
+            db.find_block_by_chain_length(50.into()).await,
+            Some(blocks[50].header.hash())
+        );
+    }
+
+    #[tokio::test]
+    async fn queries_spanning_disk_and_memory_match_the_in_memory_index() {
+        let dir = TestDir::new("queries");
+        let keys = Keys::new();
+        let block0 = block0();
+        let blocks = chain(&block0, &keys, 110);
+        let mut stored = bootstrap(&block0, Some(dir.0.clone())).await;
+        let mut in_memory = bootstrap(&block0, None).await;
+
+        for block in blocks[1..].iter() {
+            stored.apply_block(block.clone()).await.unwrap();
+            in_memory.apply_block(block.clone()).await.unwrap();
+        }
+        stored
+            .persist_blocks_deeper_than(EPOCH_STABILITY_DEPTH)
+            .await
+            .unwrap();
+        // the last epoch has blocks on disk and in memory
+        assert_eq!(stable_length(&stored).await, 105);
+
+        for epoch in 0..=3 {
+            assert_eq!(
+                epoch_fields(stored.get_epoch(epoch).await),
+                epoch_fields(in_memory.get_epoch(epoch).await),
+                "epoch {}",
+                epoch
+            );
+        }
+
+        for &(from, to) in &[(0, 120), (100, 108), (106, 110), (0, 50)] {
+            assert_eq!(
+                stored.get_block_hash_range(from.into(), to.into()).await,
+                in_memory.get_block_hash_range(from.into(), to.into()).await,
+                "range [{}, {})",
+                from,
+                to
+            );
+        }
+
+        for address in keys.addresses() {
+            let transactions = sequence_items(stored.get_transactions_by_address(&address).await);
+            assert_eq!(transactions.len(), 11);
+            assert_eq!(
+                transactions,
+                sequence_items(in_memory.get_transactions_by_address(&address).await)
+            );
+
+            for index in 0..=11 {
+                assert_eq!(
+                    stored.get_address_totals(&address, index).await,
+                    in_memory.get_address_totals(&address, index).await,
+                    "transaction {}",
+                    index
+                );
+            }
+            assert_eq!(stored.get_address_totals(&address, 11).await, None);
+        }
+
+        // each transaction moves 10 from the sender to the receiver
+        let addresses = keys.addresses();
+        assert_eq!(
+            stored.get_address_totals(&addresses[0], 10).await,
+            Some(BalanceTotals {
+                received: 0,
+                sent: 110
+            })
+        );
+        assert_eq!(
+            stored.get_address_totals(&addresses[1], 4).await,
+            Some(BalanceTotals {
+                received: 50,
+                sent: 0
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn forks_above_the_stable_tip_survive_the_rebase() {
+        let dir = TestDir::new("forks");
+        let keys = Keys::new();
+        let block0 = block0();
+        let blocks = chain(&block0, &keys, 110);
+        let mut db = bootstrap(&block0, Some(dir.0.clone())).await;
+
+        for block in blocks[1..].iter() {
+            db.apply_block(block.clone()).await.unwrap();
+        }
+        // the counters tell the forks apart from the blocks of the longest chain
+        let stable_fork = child(&blocks[103], vec![keys.transaction(1000)]);
+        let unstable_fork = child(&blocks[107], vec![keys.transaction(1001)]);
+        let unstable_fork_child = child(&unstable_fork, Vec::new());
+        for block in vec![&stable_fork, &unstable_fork, &unstable_fork_child] {
+            db.apply_block(block.clone()).await.unwrap();
+        }
+
+        db.persist_blocks_deeper_than(EPOCH_STABILITY_DEPTH)
+            .await
+            .unwrap();
+        assert_eq!(stable_length(&db).await, 105);
+
+        let tip = blocks.last().unwrap().header.hash();
+        assert_eq!(db.get_latest_block_hash().await, tip);
+        assert!(db.multiverse.get(stable_fork.header.hash()).await.is_none());
+
+        let fork_state = db
+            .multiverse
+            .get(unstable_fork_child.header.hash())
+            .await
+            .unwrap();
+        for block in &[&blocks[106], &blocks[107], &unstable_fork] {
+            assert!(fork_state.blocks.lookup(&block.header.hash()).is_some());
+        }
+        assert!(fork_state
+            .transactions
+            .lookup(&keys.transaction(1001).id())
+            .is_some());
+
+        // the fork can still grow and become the longest chain
+        let mut parent = unstable_fork_child;
+        for _ in 0..2 {
+            let block = child(&parent, Vec::new());
+            db.apply_block(block.clone()).await.unwrap();
+            parent = block;
+        }
+        assert_eq!(db.get_latest_block_hash().await, parent.header.hash());
+        assert_eq!(
+            db.find_block_by_chain_length(108.into()).await,
+            Some(unstable_fork.header.hash())
+        );
+    }
 }
@@ -0,0 +1,622 @@
+//! On-disk indexes of the stable part of the blockchain.
+//!
+//! Blocks deeper than the epoch stability depth in the longest chain cannot be
+//! rolled back, so when the explorer is configured with a storage directory
+//! their indexes are moved from the in-memory states to this database. Only
+//! the data that cannot be recovered cheaply from the node's block storage is
+//! kept here, the blocks themselves are re-read from the node's storage when
+//! queried.
+
+use super::indexing::{
+    BalanceTotals, BlockProducer, EpochData, ExplorerAddress, ExplorerBlock, ExplorerOutput,
+};
+use crate::{
+    blockcfg::{ChainLength, Epoch, FragmentId, HeaderHash},
+    utils::storage_db,
+};
+use cardano_legacy_address::Addr as OldAddress;
+use chain_addr::Address;
+use chain_impl_mockchain::certificate::{Certificate, PoolId};
+use chain_impl_mockchain::value::Value;
+use jormungandr_lib::crypto::hash::Hash;
+use rusqlite::{params, Connection, OptionalExtension as _, Transaction, NO_PARAMS};
+use slog::Logger;
+use thiserror::Error;
+use tokio::task::spawn_blocking;
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    io,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// name of the database file in the explorer storage directory
+const DATABASE_FILE: &str = "explorer.sqlite";
+
+/// version of the database schema. The index created with another version is
+/// dropped.
+const SCHEMA_VERSION: u32 = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS blocks (
+    hash BLOB PRIMARY KEY,
+    chain_length INTEGER NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    id BLOB PRIMARY KEY,
+    block BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS outputs (
+    tx BLOB NOT NULL,
+    idx INTEGER NOT NULL,
+    address BLOB NOT NULL,
+    value INTEGER NOT NULL,
+    PRIMARY KEY (tx, idx)
+);
+CREATE TABLE IF NOT EXISTS address_transactions (
+    address BLOB NOT NULL,
+    seq INTEGER NOT NULL,
+    tx BLOB NOT NULL,
+    received INTEGER NOT NULL,
+    sent INTEGER NOT NULL,
+    PRIMARY KEY (address, seq)
+);
+CREATE TABLE IF NOT EXISTS epochs (
+    epoch INTEGER PRIMARY KEY,
+    first_block BLOB NOT NULL,
+    last_block BLOB NOT NULL,
+    total_blocks INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS stake_pools (
+    pool BLOB PRIMARY KEY,
+    registration BLOB NOT NULL,
+    retirement BLOB
+);
+CREATE TABLE IF NOT EXISTS stake_pool_blocks (
+    pool BLOB NOT NULL,
+    seq INTEGER NOT NULL,
+    block BLOB NOT NULL,
+    PRIMARY KEY (pool, seq)
+);
+CREATE TABLE IF NOT EXISTS vote_transactions (
+    seq INTEGER PRIMARY KEY,
+    tx BLOB NOT NULL
+);
+";
+
+const META_STABLE_TIP: &str = "stable_tip";
+const META_STABLE_TIP_LENGTH: &str = "stable_tip_length";
+
+const ADDRESS_TAG_NEW: u8 = 0;
+const ADDRESS_TAG_OLD: u8 = 1;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot create the explorer storage directory")]
+    Io(#[from] io::Error),
+    #[error("explorer index database error")]
+    Database(#[from] rusqlite::Error),
+    #[error("the explorer index contains an invalid {0}")]
+    InvalidData(&'static str),
+}
+
+/// the last block of the longest chain moved to the on-disk index
+#[derive(Clone, Copy)]
+pub struct StableTip {
+    pub hash: HeaderHash,
+    pub chain_length: ChainLength,
+}
+
+/// the transactions holding the certificates of a stake pool registered
+/// in the stable part of the blockchain
+pub struct StablePool {
+    pub id: PoolId,
+    pub registration: FragmentId,
+    pub retirement: Option<FragmentId>,
+}
+
+#[derive(Clone)]
+pub struct StableIndex {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl StableIndex {
+    /// open (or create) the index in the given directory. The index created
+    /// with another schema version or for another block0 is dropped, the
+    /// blocks are indexed again from the node storage.
+    pub async fn open(dir: PathBuf, block0: HeaderHash, logger: Logger) -> Result<Self, Error> {
+        spawn_blocking(move || {
+            let connection = storage_db::open::<Error>(
+                &dir,
+                DATABASE_FILE,
+                SCHEMA,
+                SCHEMA_VERSION,
+                block0,
+                &logger,
+            )?;
+            Ok(StableIndex {
+                connection: Arc::new(Mutex::new(connection)),
+            })
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn run<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, Error>,
+        F: Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = self.connection.clone();
+        spawn_blocking(move || {
+            let mut connection = connection.lock().unwrap();
+            f(&mut connection)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// the last block moved to the index, `None` if the index is empty
+    pub async fn stable_tip(&self) -> Result<Option<StableTip>, Error> {
+        self.run(|connection| {
+            let hash: Option<Vec<u8>> = connection
+                .query_row(
+                    "SELECT value FROM meta WHERE key = ?1",
+                    params![META_STABLE_TIP],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let chain_length: Option<u32> = connection
+                .query_row(
+                    "SELECT value FROM meta WHERE key = ?1",
+                    params![META_STABLE_TIP_LENGTH],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match (hash, chain_length) {
+                (Some(hash), Some(chain_length)) => Ok(Some(StableTip {
+                    hash: hash_from_sql(hash)?.into_hash(),
+                    chain_length: chain_length.into(),
+                })),
+                _ => Ok(None),
+            }
+        })
+        .await
+    }
+
+    /// add the given blocks to the index and make the last one the new stable
+    /// tip. The blocks must be given in the chain order, starting with the
+    /// child of the current stable tip.
+    pub async fn commit(&self, blocks: Vec<ExplorerBlock>) -> Result<(), Error> {
+        self.run(move |connection| {
+            let db_tx = connection.transaction()?;
+            for block in blocks.iter() {
+                commit_block(&db_tx, block)?;
+            }
+            if let Some(block) = blocks.last() {
+                db_tx.execute(
+                    "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                    params![META_STABLE_TIP, hash_to_sql(block.id())],
+                )?;
+                db_tx.execute(
+                    "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+                    params![META_STABLE_TIP_LENGTH, u32::from(block.chain_length())],
+                )?;
+            }
+            db_tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn block_chain_length(
+        &self,
+        block_id: HeaderHash,
+    ) -> Result<Option<ChainLength>, Error> {
+        self.run(move |connection| {
+            let chain_length: Option<u32> = connection
+                .query_row(
+                    "SELECT chain_length FROM blocks WHERE hash = ?1",
+                    params![hash_to_sql(block_id)],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            Ok(chain_length.map(ChainLength::from))
+        })
+        .await
+    }
+
+    pub async fn find_block_by_chain_length(
+        &self,
+        chain_length: ChainLength,
+    ) -> Result<Option<HeaderHash>, Error> {
+        self.run(move |connection| {
+            let hash: Option<Vec<u8>> = connection
+                .query_row(
+                    "SELECT hash FROM blocks WHERE chain_length = ?1",
+                    params![u32::from(chain_length)],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            hash.map(|hash| hash_from_sql(hash).map(Hash::into_hash))
+                .transpose()
+        })
+        .await
+    }
+
+    /// get the hashes of the indexed blocks in the range [from, to)
+    pub async fn block_hash_range(
+        &self,
+        from: ChainLength,
+        to: ChainLength,
+    ) -> Result<Vec<(HeaderHash, ChainLength)>, Error> {
+        self.run(move |connection| {
+            let mut statement = connection.prepare(
+                "SELECT hash, chain_length FROM blocks
+                 WHERE chain_length >= ?1 AND chain_length < ?2
+                 ORDER BY chain_length",
+            )?;
+            let rows = statement
+                .query_map(params![u32::from(from), u32::from(to)], |row| {
+                    Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, u32>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.into_iter()
+                .map(|(hash, chain_length)| {
+                    Ok((hash_from_sql(hash)?.into_hash(), chain_length.into()))
+                })
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn find_block_hash_by_transaction(
+        &self,
+        transaction_id: FragmentId,
+    ) -> Result<Option<HeaderHash>, Error> {
+        self.run(move |connection| {
+            let hash: Option<Vec<u8>> = connection
+                .query_row(
+                    "SELECT block FROM transactions WHERE id = ?1",
+                    params![hash_to_sql(transaction_id)],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            hash.map(|hash| hash_from_sql(hash).map(Hash::into_hash))
+                .transpose()
+        })
+        .await
+    }
+
+    /// get the given transaction outputs, the ones that are not indexed are
+    /// not present in the result
+    pub async fn outputs(
+        &self,
+        pointers: Vec<(FragmentId, u8)>,
+    ) -> Result<HashMap<(FragmentId, u8), ExplorerOutput>, Error> {
+        self.run(move |connection| {
+            let mut statement = connection
+                .prepare_cached("SELECT address, value FROM outputs WHERE tx = ?1 AND idx = ?2")?;
+            let mut outputs = HashMap::new();
+            for (transaction_id, index) in pointers {
+                let row: Option<(Vec<u8>, i64)> = statement
+                    .query_row(params![hash_to_sql(transaction_id), index], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .optional()?;
+                if let Some((address, value)) = row {
+                    let output = ExplorerOutput {
+                        address: address_from_sql(&address)?,
+                        value: Value(value as u64),
+                    };
+                    outputs.insert((transaction_id, index), output);
+                }
+            }
+            Ok(outputs)
+        })
+        .await
+    }
+
+    pub async fn epoch(&self, epoch: Epoch) -> Result<Option<EpochData>, Error> {
+        self.run(move |connection| {
+            let row: Option<(Vec<u8>, Vec<u8>, u32)> = connection
+                .query_row(
+                    "SELECT first_block, last_block, total_blocks FROM epochs WHERE epoch = ?1",
+                    params![epoch],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            row.map(|(first_block, last_block, total_blocks)| {
+                Ok(EpochData {
+                    first_block: hash_from_sql(first_block)?.into_hash(),
+                    last_block: hash_from_sql(last_block)?.into_hash(),
+                    total_blocks,
+                })
+            })
+            .transpose()
+        })
+        .await
+    }
+
+    /// the transactions involving the given address, in the chain order
+    pub async fn transactions_by_address(
+        &self,
+        address: ExplorerAddress,
+    ) -> Result<Vec<FragmentId>, Error> {
+        self.run(move |connection| {
+            let mut statement = connection
+                .prepare("SELECT tx FROM address_transactions WHERE address = ?1 ORDER BY seq")?;
+            let rows = statement
+                .query_map(params![address_to_sql(&address)], |row| row.get(0))?
+                .collect::<Result<Vec<Vec<u8>>, _>>()?;
+            rows.into_iter()
+                .map(|id| hash_from_sql(id).map(Hash::into_hash))
+                .collect()
+        })
+        .await
+    }
+
+    /// the position and the totals of the transaction of the given address at the
+    /// given position or, if the address has fewer transactions in the index, of
+    /// its last one. `None` if the address has no transaction in the index
+    pub async fn address_totals(
+        &self,
+        address: ExplorerAddress,
+        index: u64,
+    ) -> Result<Option<(u64, BalanceTotals)>, Error> {
+        self.run(move |connection| {
+            let row: Option<(i64, i64, i64)> = connection
+                .query_row(
+                    "SELECT seq, received, sent FROM address_transactions
+                     WHERE address = ?1 AND seq <= ?2
+                     ORDER BY seq DESC LIMIT 1",
+                    params![address_to_sql(&address), index as i64],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            Ok(row.map(|(seq, received, sent)| {
+                let totals = BalanceTotals {
+                    received: received as u64,
+                    sent: sent as u64,
+                };
+                (seq as u64, totals)
+            }))
+        })
+        .await
+    }
+
+    /// the blocks created by the given stake pool, in the chain order
+    pub async fn stake_pool_blocks(&self, pool: PoolId) -> Result<Vec<HeaderHash>, Error> {
+        self.run(move |connection| {
+            let mut statement = connection
+                .prepare("SELECT block FROM stake_pool_blocks WHERE pool = ?1 ORDER BY seq")?;
+            let rows = statement
+                .query_map(params![pool_to_sql(pool)], |row| row.get(0))?
+                .collect::<Result<Vec<Vec<u8>>, _>>()?;
+            rows.into_iter()
+                .map(|id| hash_from_sql(id).map(Hash::into_hash))
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn stake_pools(&self) -> Result<Vec<StablePool>, Error> {
+        self.run(|connection| {
+            let mut statement =
+                connection.prepare("SELECT pool, registration, retirement FROM stake_pools")?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, Vec<u8>>(1)?,
+                        row.get::<_, Option<Vec<u8>>>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.into_iter()
+                .map(|(id, registration, retirement)| {
+                    Ok(StablePool {
+                        id: hash_from_sql(id)?.into_digest_of(),
+                        registration: hash_from_sql(registration)?.into_hash(),
+                        retirement: retirement
+                            .map(|id| hash_from_sql(id).map(Hash::into_hash))
+                            .transpose()?,
+                    })
+                })
+                .collect()
+        })
+        .await
+    }
+
+    /// the transactions holding vote plan and vote cast certificates, in the
+    /// chain order
+    pub async fn vote_transactions(&self) -> Result<Vec<FragmentId>, Error> {
+        self.run(|connection| {
+            let mut statement =
+                connection.prepare("SELECT tx FROM vote_transactions ORDER BY seq")?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| row.get(0))?
+                .collect::<Result<Vec<Vec<u8>>, _>>()?;
+            rows.into_iter()
+                .map(|id| hash_from_sql(id).map(Hash::into_hash))
+                .collect()
+        })
+        .await
+    }
+}
+
+fn commit_block(db_tx: &Transaction, block: &ExplorerBlock) -> Result<(), Error> {
+    let block_id = hash_to_sql(block.id());
+
+    db_tx.execute(
+        "INSERT INTO blocks (hash, chain_length) VALUES (?1, ?2)",
+        params![block_id, u32::from(block.chain_length())],
+    )?;
+
+    // keep the order of the block so the address histories are deterministic
+    let mut transactions: Vec<_> = block.transactions.values().collect();
+    transactions.sort_by_key(|tx| tx.offset_in_block);
+
+    for tx in transactions {
+        let id = hash_to_sql(tx.id());
+
+        db_tx.execute(
+            "INSERT INTO transactions (id, block) VALUES (?1, ?2)",
+            params![id, block_id],
+        )?;
+
+        for (index, output) in tx.outputs().iter().enumerate() {
+            db_tx.execute(
+                "INSERT INTO outputs (tx, idx, address, value) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    id,
+                    index as i64,
+                    address_to_sql(&output.address),
+                    output.value.0 as i64
+                ],
+            )?;
+        }
+
+        // A Hashset is used for preventing duplicates when the address is both an
+        // input and an output in the given transaction
+        let included_addresses: HashSet<&ExplorerAddress> = tx
+            .outputs()
+            .iter()
+            .map(|output| &output.address)
+            .chain(tx.inputs().iter().map(|input| &input.address))
+            .collect();
+
+        for address in included_addresses {
+            let address_id = address_to_sql(address);
+            let last: Option<(i64, i64, i64)> = db_tx
+                .query_row(
+                    "SELECT seq, received, sent FROM address_transactions
+                     WHERE address = ?1 ORDER BY seq DESC LIMIT 1",
+                    params![address_id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            let (seq, totals) = match last {
+                Some((seq, received, sent)) => {
+                    let totals = BalanceTotals {
+                        received: received as u64,
+                        sent: sent as u64,
+                    };
+                    (seq + 1, totals)
+                }
+                None => (0, BalanceTotals::default()),
+            };
+            let (received, sent) = tx.received_and_sent(address);
+            let totals = totals.add(received, sent);
+            db_tx.execute(
+                "INSERT INTO address_transactions (address, seq, tx, received, sent)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    address_id,
+                    seq,
+                    id,
+                    totals.received as i64,
+                    totals.sent as i64
+                ],
+            )?;
+        }
+
+        match &tx.certificate {
+            Some(Certificate::PoolRegistration(registration)) => {
+                db_tx.execute(
+                    "INSERT INTO stake_pools (pool, registration) VALUES (?1, ?2)",
+                    params![pool_to_sql(registration.to_id()), id],
+                )?;
+            }
+            Some(Certificate::PoolRetirement(retirement)) => {
+                db_tx.execute(
+                    "UPDATE stake_pools SET retirement = ?2 WHERE pool = ?1",
+                    params![pool_to_sql(retirement.pool_id.clone()), id],
+                )?;
+            }
+            Some(Certificate::VotePlan(_)) | Some(Certificate::VoteCast(_)) => {
+                db_tx.execute(
+                    "INSERT INTO vote_transactions (seq, tx)
+                     SELECT COALESCE(MAX(seq) + 1, 0), ?1 FROM vote_transactions",
+                    params![id],
+                )?;
+            }
+            _ => (),
+        }
+    }
+
+    let epoch = block.date().epoch;
+    let updated = db_tx.execute(
+        "UPDATE epochs SET last_block = ?2, total_blocks = total_blocks + 1 WHERE epoch = ?1",
+        params![epoch, block_id],
+    )?;
+    if updated == 0 {
+        db_tx.execute(
+            "INSERT INTO epochs (epoch, first_block, last_block, total_blocks)
+             VALUES (?1, ?2, ?2, 0)",
+            params![epoch, block_id],
+        )?;
+    }
+
+    if let BlockProducer::StakePool(pool) = block.producer() {
+        db_tx.execute(
+            "INSERT INTO stake_pool_blocks (pool, seq, block)
+             SELECT ?1, COALESCE(MAX(seq) + 1, 0), ?2
+             FROM stake_pool_blocks WHERE pool = ?1",
+            params![pool_to_sql(pool.clone()), block_id],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn hash_to_sql(hash: HeaderHash) -> Vec<u8> {
+    let bytes: [u8; 32] = Hash::from(hash).into();
+    bytes.to_vec()
+}
+
+fn pool_to_sql(pool: PoolId) -> Vec<u8> {
+    let bytes: [u8; 32] = Hash::from(pool).into();
+    bytes.to_vec()
+}
+
+fn hash_from_sql(bytes: Vec<u8>) -> Result<Hash, Error> {
+    let bytes: [u8; 32] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidData("hash"))?;
+    Ok(Hash::from(bytes))
+}
+
+fn address_to_sql(address: &ExplorerAddress) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match address {
+        ExplorerAddress::New(address) => {
+            bytes.push(ADDRESS_TAG_NEW);
+            bytes.extend(address.to_bytes());
+        }
+        ExplorerAddress::Old(address) => {
+            bytes.push(ADDRESS_TAG_OLD);
+            bytes.extend(address.to_string().into_bytes());
+        }
+    }
+    bytes
+}
+
+fn address_from_sql(bytes: &[u8]) -> Result<ExplorerAddress, Error> {
+    match bytes.split_first() {
+        Some((&ADDRESS_TAG_NEW, address)) => Address::from_bytes(address)
+            .map(ExplorerAddress::New)
+            .map_err(|_| Error::InvalidData("address")),
+        Some((&ADDRESS_TAG_OLD, address)) => std::str::from_utf8(address)
+            .ok()
+            .and_then(|address| OldAddress::from_str(address).ok())
+            .map(ExplorerAddress::Old)
+            .ok_or(Error::InvalidData("legacy address")),
+        _ => Err(Error::InvalidData("address")),
+    }
+}
@@ -1,4 +1,7 @@
-use crate::fragment::FragmentId;
+use crate::fragment::{
+    persistent_logs::{Error as PersistentLogsError, LogWriter, PersistentLogs},
+    FragmentId,
+};
 use jormungandr_lib::{
     crypto::hash::Hash,
     interfaces::{FragmentLog, FragmentOrigin, FragmentStatus},
@@ -7,13 +10,45 @@ use lru::LruCache;
 
 pub struct Logs {
     entries: LruCache<Hash, FragmentLog>,
+    persistent: Option<LogWriter>,
+    /// the logs changed since the last write to the storage
+    dirty: Vec<Hash>,
 }
 
 impl Logs {
     pub fn new(max_entries: usize) -> Self {
         Logs {
             entries: LruCache::new(max_entries),
+            persistent: None,
+            dirty: Vec::new(),
+        }
+    }
+
+    /// create the logs from the ones stored on disk, the changes of the logs
+    /// are then written to the storage as well when `flush` is called, from a
+    /// dedicated thread
+    pub fn persistent(
+        max_entries: usize,
+        mut persistent: PersistentLogs,
+    ) -> Result<Self, PersistentLogsError> {
+        let mut entries = LruCache::new(max_entries);
+        let mut dropped = Vec::new();
+        for log in persistent.load()? {
+            // the pool is not persisted: the fragments still pending when
+            // the node stopped would never be resolved and their logs would
+            // refuse them as duplicates if they are submitted again
+            if log.is_pending() {
+                dropped.push(*log.fragment_id());
+                continue;
+            }
+            entries.put(*log.fragment_id(), log);
         }
+        persistent.remove(&dropped)?;
+        Ok(Logs {
+            entries,
+            persistent: Some(LogWriter::spawn(persistent)?),
+            dirty: Vec::new(),
+        })
     }
 
     pub fn exists(&self, fragment_id: FragmentId) -> bool {
@@ -28,27 +63,51 @@ impl Logs {
             .collect()
     }
 
+    /// Returns the log of the fragment without changing its position in the logs
+    pub fn get(&self, fragment_id: &FragmentId) -> Option<&FragmentLog> {
+        let fragment_id: Hash = fragment_id.clone().into();
+        self.entries.peek(&fragment_id)
+    }
+
     /// Returns true if fragment was registered
     pub fn insert(&mut self, log: FragmentLog) -> bool {
-        let fragment_id = *log.fragment_id();
-        if self.entries.contains(&fragment_id) {
-            false
-        } else {
-            self.entries.put(fragment_id, log);
-            true
-        }
+        self.insert_all(Some(log)) == 1
     }
 
     /// Returns number of registered fragments
     pub fn insert_all(&mut self, logs: impl IntoIterator<Item = FragmentLog>) -> usize {
-        logs.into_iter()
-            .map(|log| self.insert(log))
-            .filter(|was_modified| *was_modified)
-            .count()
+        let mut inserted = Vec::new();
+        for log in logs {
+            let fragment_id = *log.fragment_id();
+            if !self.entries.contains(&fragment_id) {
+                self.entries.put(fragment_id, log);
+                inserted.push(fragment_id);
+            }
+        }
+        let count = inserted.len();
+        self.mark_dirty(inserted);
+        count
     }
 
     pub fn modify(&mut self, fragment_id: FragmentId, status: FragmentStatus) {
-        let fragment_id: Hash = fragment_id.into();
+        self.modify_all(Some(fragment_id), status)
+    }
+
+    pub fn modify_all(
+        &mut self,
+        fragment_ids: impl IntoIterator<Item = FragmentId>,
+        status: FragmentStatus,
+    ) {
+        let mut modified = Vec::new();
+        for fragment_id in fragment_ids {
+            let fragment_id: Hash = fragment_id.into();
+            self.modify_entry(fragment_id, status.clone());
+            modified.push(fragment_id);
+        }
+        self.mark_dirty(modified);
+    }
+
+    fn modify_entry(&mut self, fragment_id: Hash, status: FragmentStatus) {
         match self.entries.get_mut(&fragment_id) {
             Some(entry) => {
                 entry.modify(status);
@@ -67,13 +126,31 @@ impl Logs {
         }
     }
 
-    pub fn modify_all(
-        &mut self,
-        fragment_ids: impl IntoIterator<Item = FragmentId>,
-        status: FragmentStatus,
-    ) {
-        for fragment_id in fragment_ids {
-            self.modify(fragment_id, status.clone());
+    fn mark_dirty(&mut self, fragment_ids: Vec<Hash>) {
+        if self.persistent.is_some() {
+            self.dirty.extend(fragment_ids);
+        }
+    }
+
+    /// write the logs changed since the last call to the storage in a single
+    /// transaction, if the logs are persistent. This does not wait for the
+    /// write to complete
+    pub fn flush(&mut self) {
+        let Logs {
+            entries,
+            persistent,
+            dirty,
+        } = self;
+        if let Some(persistent) = persistent {
+            if !dirty.is_empty() {
+                persistent.store(
+                    dirty
+                        .drain(..)
+                        .filter_map(|fragment_id| entries.peek(&fragment_id))
+                        .cloned()
+                        .collect(),
+                );
+            }
         }
     }
 
@@ -81,3 +158,30 @@ impl Logs {
         self.entries.iter().map(|(_, v)| v)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_impl_mockchain::key;
+    use slog::Logger;
+
+    #[test]
+    fn pending_logs_are_dropped_on_reload() {
+        let fragment_id = key::Hash::hash_bytes(b"pending fragment");
+        let mut persistent = PersistentLogs::in_memory(16, Logger::root(slog::Discard, o!()));
+        persistent.store(Some(&FragmentLog::new(fragment_id, FragmentOrigin::Rest)));
+
+        let mut logs = Logs::persistent(16, persistent).unwrap();
+        assert!(!logs.exists(fragment_id));
+        let persistent = logs.persistent.take().unwrap().close();
+        assert!(persistent.load().unwrap().is_empty());
+
+        // the fragment can be submitted again
+        let mut logs = Logs::persistent(16, persistent).unwrap();
+        assert!(logs.insert(FragmentLog::new(fragment_id, FragmentOrigin::Rest)));
+        logs.flush();
+        let stored = logs.persistent.take().unwrap().close().load().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(stored[0].is_pending());
+    }
+}
@@ -1,11 +1,13 @@
 mod entry;
 mod logs;
+mod persistent_logs;
 mod pool;
 mod process;
 pub mod selection;
 
 pub use self::entry::PoolEntry;
 pub use self::logs::Logs;
+pub use self::persistent_logs::{Error as PersistentLogsError, PersistentLogs};
 pub use self::pool::Pool;
 pub use self::process::Process;
 
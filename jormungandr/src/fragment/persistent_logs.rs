@@ -0,0 +1,206 @@
+//! On-disk copy of the fragment logs.
+//!
+//! When enabled, every change of the in-memory logs is written to a database
+//! in the node storage directory, so the statuses of the fragments submitted
+//! to the node are still known after a restart. The database keeps the same
+//! logs as the in-memory cache: the `log_max_entries` most recently updated.
+
+use crate::{blockcfg::HeaderHash, utils::storage_db};
+use jormungandr_lib::{crypto::hash::Hash, interfaces::FragmentLog};
+use rusqlite::{params, Connection, NO_PARAMS};
+use slog::Logger;
+use thiserror::Error;
+
+use std::{
+    convert::TryFrom,
+    io,
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+/// name of the database file in the node storage directory
+const DATABASE_FILE: &str = "fragment_logs.sqlite";
+
+/// version of the database schema. The logs stored with another version are
+/// dropped.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS fragment_logs (
+    fragment_id TEXT PRIMARY KEY,
+    seq INTEGER NOT NULL,
+    log TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS fragment_logs_seq ON fragment_logs (seq);
+";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot create the storage directory")]
+    Io(#[from] io::Error),
+    #[error("fragment logs database error")]
+    Database(#[from] rusqlite::Error),
+    #[error("cannot encode or decode a fragment log")]
+    Encoding(#[from] serde_json::Error),
+    #[error("cannot start the thread writing the fragment logs")]
+    Writer(#[source] io::Error),
+}
+
+pub struct PersistentLogs {
+    connection: Connection,
+    max_entries: i64,
+    /// sequence number of the last write, the logs written with a sequence
+    /// number older than `max_entries` writes are dropped
+    seq: i64,
+    logger: Logger,
+}
+
+impl PersistentLogs {
+    /// open (or create) the database in the given node storage directory.
+    /// The logs stored for another block0 are dropped.
+    pub fn open(
+        storage_dir: &Path,
+        block0: HeaderHash,
+        max_entries: usize,
+        logger: Logger,
+    ) -> Result<Self, Error> {
+        let connection = storage_db::open::<Error>(
+            storage_dir,
+            DATABASE_FILE,
+            SCHEMA,
+            SCHEMA_VERSION,
+            block0,
+            &logger,
+        )?;
+        Self::init(connection, max_entries, logger)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory(max_entries: usize, logger: Logger) -> Self {
+        let connection = storage_db::in_memory(SCHEMA, SCHEMA_VERSION);
+        Self::init(connection, max_entries, logger).unwrap()
+    }
+
+    fn init(connection: Connection, max_entries: usize, logger: Logger) -> Result<Self, Error> {
+        let seq: Option<i64> =
+            connection.query_row("SELECT MAX(seq) FROM fragment_logs", NO_PARAMS, |row| {
+                row.get(0)
+            })?;
+
+        Ok(PersistentLogs {
+            connection,
+            max_entries: i64::try_from(max_entries).unwrap_or(i64::MAX),
+            seq: seq.unwrap_or(0),
+            logger,
+        })
+    }
+
+    /// Returns the stored logs, the least recently updated first
+    pub fn load(&self) -> Result<Vec<FragmentLog>, Error> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT log FROM fragment_logs ORDER BY seq DESC LIMIT ?1")?;
+        let rows = statement.query_map(params![self.max_entries], |row| row.get::<_, String>(0))?;
+        let mut logs = Vec::new();
+        for row in rows {
+            logs.push(serde_json::from_str(&row?)?);
+        }
+        logs.reverse();
+        Ok(logs)
+    }
+
+    /// drop the logs of the given fragments
+    pub fn remove(&mut self, fragment_ids: &[Hash]) -> Result<(), Error> {
+        if fragment_ids.is_empty() {
+            return Ok(());
+        }
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement =
+                transaction.prepare_cached("DELETE FROM fragment_logs WHERE fragment_id = ?1")?;
+            for fragment_id in fragment_ids {
+                statement.execute(params![fragment_id.to_string()])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// write the given logs as the most recently updated ones, the failures
+    /// are logged and do not affect the in-memory logs
+    pub fn store<'a>(&mut self, logs: impl IntoIterator<Item = &'a FragmentLog>) {
+        if let Err(err) = self.try_store(logs) {
+            warn!(self.logger, "cannot write the fragment logs to the storage"; "reason" => %err);
+        }
+    }
+
+    fn try_store<'a>(
+        &mut self,
+        logs: impl IntoIterator<Item = &'a FragmentLog>,
+    ) -> Result<(), Error> {
+        let PersistentLogs {
+            connection,
+            max_entries,
+            seq,
+            ..
+        } = self;
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO fragment_logs (fragment_id, seq, log) VALUES (?1, ?2, ?3)",
+            )?;
+            for log in logs {
+                *seq += 1;
+                statement.execute(params![
+                    log.fragment_id().to_string(),
+                    *seq,
+                    serde_json::to_string(log)?
+                ])?;
+            }
+        }
+        transaction.execute(
+            "DELETE FROM fragment_logs WHERE seq <= ?1",
+            params![*seq - *max_entries],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Writes the logs to the database from a dedicated thread, so the fragment
+/// process does not wait for the storage. The logs are written in the order
+/// they are given.
+pub struct LogWriter {
+    sender: Sender<Vec<FragmentLog>>,
+    thread: JoinHandle<PersistentLogs>,
+}
+
+impl LogWriter {
+    pub fn spawn(mut persistent: PersistentLogs) -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::channel::<Vec<FragmentLog>>();
+        let thread = thread::Builder::new()
+            .name("fragment logs writer".to_owned())
+            .spawn(move || {
+                // the thread stops when the fragment logs are dropped
+                for logs in receiver {
+                    persistent.store(&logs);
+                }
+                persistent
+            })
+            .map_err(Error::Writer)?;
+        Ok(LogWriter { sender, thread })
+    }
+
+    pub fn store(&self, logs: Vec<FragmentLog>) {
+        // the thread only stops once the sender is dropped
+        let _ = self.sender.send(logs);
+    }
+
+    /// wait for the pending writes and get the database back
+    #[cfg(test)]
+    pub fn close(self) -> PersistentLogs {
+        drop(self.sender);
+        self.thread.join().unwrap()
+    }
+}
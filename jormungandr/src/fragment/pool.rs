@@ -1,30 +1,142 @@
 use crate::{
-    blockcfg::{BlockDate, Ledger, LedgerParameters},
+    blockcfg::{BlockDate, Ledger, LedgerParameters, Value},
+    blockchain::{Ref, Tip},
     fragment::{
-        selection::{FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams, OldestFirst},
+        selection::{
+            fragment_fee, FeeDensity, FragmentSelectionAlgorithm, FragmentSelectionAlgorithmParams,
+            OldestFirst,
+        },
         Fragment, FragmentId, Logs,
     },
     intercom::{NetworkMsg, PropagateMsg},
     utils::async_msg::MessageBox,
 };
 use chain_core::property::Fragment as _;
-use chain_impl_mockchain::{fragment::Contents, transaction::Transaction};
+use chain_crypto::Verification;
+use chain_impl_mockchain::{
+    account::{self, SpendingCounter},
+    certificate::{VoteCast, VotePlan},
+    fragment::Contents,
+    transaction::{InputEnum, Transaction, Witness, WitnessAccountData},
+    value::ValueError,
+};
 use futures::sink::SinkExt;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use jormungandr_lib::{
+    crypto::hash::Hash,
+    interfaces::{
+        AccountPendingFragment, FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission,
+        FragmentTtl, OriginMaxEntries, PendingFragment,
+    },
+};
 use slog::Logger;
+use std::net::IpAddr;
+use std::time::{Instant, SystemTime};
+use thiserror::Error;
+
+use self::internal::AccountCounter;
+
+/// number of spending counters, from the current one of the account, accepted
+/// for the account inputs of the fragments entering the pool. This lets an
+/// account have a few transactions waiting in the pool at once.
+const SPENDING_COUNTER_LOOKAHEAD: u32 = 16;
+
+/// number of signature verifications, beyond the one at the current spending
+/// counter of each account input, made to find the counters signed by the
+/// witnesses of a fragment. This bounds the cost of checking a fragment with
+/// many account inputs.
+const SPENDING_COUNTER_LOOKAHEAD_VERIFICATIONS: u32 = 16;
 
 pub struct Pool {
     logs: Logs,
     pool: internal::Pool,
+    ttl: Option<FragmentTtl>,
     network_msg_box: MessageBox<NetworkMsg>,
+    tip: Tip,
+}
+
+#[derive(Debug, Error)]
+enum VoteFragmentError {
+    #[error("the vote plan signature is invalid")]
+    InvalidVotePlanSignature,
+    #[error("the vote plan {0} already exists")]
+    VotePlanAlreadyExists(Hash),
+    #[error("the vote plan {id} starts at {vote_start}, it cannot be submitted at {date}")]
+    VotePlanAlreadyStarted {
+        id: Hash,
+        vote_start: BlockDate,
+        date: BlockDate,
+    },
+    #[error("the vote plan {id} ends before it starts ({vote_start} to {vote_end})")]
+    InvalidVotePlanPeriod {
+        id: Hash,
+        vote_start: BlockDate,
+        vote_end: BlockDate,
+    },
+    #[error("the vote plan {0} does not exist or is no longer active")]
+    VotePlanNotFound(Hash),
+    #[error("the vote plan {id} has no proposal at index {index}")]
+    ProposalNotFound { id: Hash, index: u8 },
+    #[error("the vote plan {id} accepts votes from {vote_start} to {vote_end}, not at {date}")]
+    OutsideVoteWindow {
+        id: Hash,
+        vote_start: BlockDate,
+        vote_end: BlockDate,
+        date: BlockDate,
+    },
+}
+
+#[derive(Debug, Error)]
+enum LedgerFragmentError {
+    #[error("input {index} spends an output that does not exist or is already spent")]
+    UtxoNotFound { index: usize },
+    #[error("input {index} does not have the value of the output it spends")]
+    UtxoValueMismatch { index: usize },
+    #[error("the account of input {index} does not exist")]
+    AccountNotFound { index: usize },
+    #[error("the account of input {index} has {balance}, not enough to spend {value}")]
+    InsufficientFunds {
+        index: usize,
+        balance: Value,
+        value: Value,
+    },
+    #[error(
+        "the witness of input {index} is not valid for the spending counter {counter} or the next ones tried"
+    )]
+    ImplausibleSpendingCounter { index: usize, counter: u32 },
+    #[error("the inputs ({inputs}) do not pay for the outputs ({outputs}) and the fee ({fee})")]
+    NotBalanced {
+        inputs: Value,
+        outputs: Value,
+        fee: Value,
+    },
+    #[error("invalid transaction value")]
+    InvalidValue(#[from] ValueError),
+}
+
+/// reason for a fragment to be refused in the pool
+#[derive(Debug, Error)]
+enum AdmissionError {
+    #[error(transparent)]
+    Ledger(#[from] LedgerFragmentError),
+    #[error(transparent)]
+    Vote(#[from] VoteFragmentError),
 }
 
 impl Pool {
-    pub fn new(max_entries: usize, logs: Logs, network_msg_box: MessageBox<NetworkMsg>) -> Self {
+    pub fn new(
+        max_entries: usize,
+        ttl: Option<FragmentTtl>,
+        origin_max_entries: OriginMaxEntries,
+        logs: Logs,
+        network_msg_box: MessageBox<NetworkMsg>,
+        tip: Tip,
+    ) -> Self {
         Pool {
             logs,
-            pool: internal::Pool::new(max_entries),
+            pool: internal::Pool::new(max_entries, origin_max_entries),
+            ttl,
             network_msg_box,
+            tip,
         }
     }
 
@@ -32,27 +144,83 @@ impl Pool {
         &mut self.logs
     }
 
-    /// Returns number of registered fragments
+    /// Returns the verdict on each of the fragments, in the order they were
+    /// given. `client` is the address of the REST client or of the peer the
+    /// fragments were received from, the quotas of the origins are counted
+    /// for each address.
     pub async fn insert_and_propagate_all(
         &mut self,
         origin: FragmentOrigin,
-        mut fragments: Vec<Fragment>,
+        client: Option<IpAddr>,
+        fragments: Vec<Fragment>,
         logger: Logger,
-    ) -> Result<usize, ()> {
-        fragments.retain(is_fragment_valid);
+    ) -> Result<Vec<FragmentSubmission>, ()> {
         if fragments.is_empty() {
-            return Ok(0);
+            return Ok(Vec::new());
         }
         let mut network_msg_box = self.network_msg_box.clone();
-        let fragment_ids = fragments.iter().map(Fragment::id).collect::<Vec<_>>();
-        let fragments_exist_in_logs = self.logs.exist_all(fragment_ids);
-        let new_fragments = fragments
-            .into_iter()
-            .zip(fragments_exist_in_logs)
-            .filter(|(_, exists_in_logs)| !exists_in_logs)
-            .map(|(fragment, _)| fragment);
-        let new_fragments = self.pool.insert_all(new_fragments);
-        let count = new_fragments.len();
+        let tip = self.tip.get_ref().await;
+        let received_slot = current_slot(&tip);
+        let mut submissions = Vec::with_capacity(fragments.len());
+        let mut rejected_logs = Vec::new();
+        let mut new_fragments = Vec::new();
+        let mut evicted_fragments = Vec::new();
+        for fragment in fragments {
+            let fragment_id = fragment.id();
+            if !is_fragment_valid(&fragment) {
+                submissions.push(FragmentSubmission::rejected(
+                    fragment_id,
+                    "the fragment is malformed or not accepted in the pool".to_owned(),
+                ));
+                continue;
+            }
+            if self.logs.exists(fragment_id) {
+                submissions.push(FragmentSubmission::rejected(
+                    fragment_id,
+                    "the fragment was already received".to_owned(),
+                ));
+                continue;
+            }
+            let account_counters = match check_fragment(&tip, &fragment) {
+                Ok(account_counters) => account_counters,
+                Err(error) => {
+                    debug!(logger, "rejecting fragment"; "fragment_id" => %fragment_id, "reason" => %error);
+                    let mut log = FragmentLog::new(fragment_id.into(), origin);
+                    log.modify(FragmentStatus::Rejected {
+                        reason: error.to_string(),
+                    });
+                    rejected_logs.push(log);
+                    submissions.push(FragmentSubmission::rejected(fragment_id, error.to_string()));
+                    continue;
+                }
+            };
+            let entry = internal::PoolEntry::new(
+                fragment.clone(),
+                origin,
+                client,
+                received_slot,
+                account_counters,
+            );
+            match self.pool.insert(entry) {
+                Ok(evicted) => {
+                    if let Some(evicted) = evicted {
+                        evicted_fragments.push(evicted.fragment.id());
+                    }
+                    new_fragments.push(fragment);
+                    submissions.push(FragmentSubmission::accepted(fragment_id));
+                }
+                Err(error) => {
+                    debug!(logger, "rejecting fragment"; "fragment_id" => %fragment_id, "reason" => %error);
+                    let mut log = FragmentLog::new(fragment_id.into(), origin);
+                    log.modify(FragmentStatus::Rejected {
+                        reason: error.to_string(),
+                    });
+                    rejected_logs.push(log);
+                    submissions.push(FragmentSubmission::rejected(fragment_id, error.to_string()))
+                }
+            }
+        }
+        self.logs.insert_all(rejected_logs);
         let fragment_logs = new_fragments
             .iter()
             .map(move |fragment| FragmentLog::new(fragment.id().into(), origin))
@@ -65,7 +233,79 @@ impl Pool {
                 .map_err(|e| error!(logger, "cannot propagate fragment to network: {}", e))?;
         }
         self.logs.insert_all(fragment_logs);
-        Ok(count)
+        if !evicted_fragments.is_empty() {
+            debug!(logger, "evicted fragments from the full pool"; "count" => evicted_fragments.len());
+            let reason = format!(
+                "the pool reached its maximum of {} fragments",
+                self.pool.max_entries()
+            );
+            self.logs
+                .modify_all(evicted_fragments, FragmentStatus::Evicted { reason });
+        }
+        Ok(submissions)
+    }
+
+    /// Removes the fragments which stayed in the pool longer than the time
+    /// to live, if one is set
+    pub async fn remove_expired(&mut self, logger: &Logger) {
+        let ttl = match self.ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        let tip = self.tip.get_ref().await;
+        let expired = self
+            .pool
+            .remove_expired(ttl, Instant::now(), current_slot(&tip));
+        if !expired.is_empty() {
+            debug!(logger, "expired fragments removed from the pool"; "count" => expired.len());
+            self.logs.modify_all(expired, FragmentStatus::Expired);
+        }
+    }
+
+    /// Returns the fragment with the given id if it is in the pool
+    pub fn get(&self, fragment_id: &FragmentId) -> Option<&Fragment> {
+        self.pool.get(fragment_id)
+    }
+
+    /// Returns the fragments waiting in the pool, the oldest first
+    pub async fn pending(&self, include_fragment: bool) -> Vec<PendingFragment> {
+        let tip = self.tip.get_ref().await;
+        let fees = &tip.epoch_ledger_parameters().fees;
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+        self.pool
+            .entries()
+            .map(|entry| {
+                let fragment = &entry.fragment;
+                let raw = fragment.to_raw();
+                let age = now.saturating_duration_since(entry.received_at());
+                let received_at = system_now.checked_sub(age).unwrap_or(system_now);
+                let pending = PendingFragment::new(
+                    fragment.id(),
+                    entry.origin(),
+                    received_at.into(),
+                    age.into(),
+                    raw.size_bytes_plus_size() as u32,
+                    fragment_fee(fees, fragment).into(),
+                );
+                if include_fragment {
+                    pending.with_fragment(hex::encode(raw.as_ref()))
+                } else {
+                    pending
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the fragments of the pool spending from the given account, in
+    /// the order of their spending counters
+    pub fn account_pending(&self, account: &account::Identifier) -> Vec<AccountPendingFragment> {
+        self.pool
+            .account_queue(account)
+            .map(|(counter, fragment_id)| {
+                AccountPendingFragment::new(fragment_id.clone().into(), counter)
+            })
+            .collect()
     }
 
     pub fn remove_added_to_block(&mut self, fragment_ids: Vec<FragmentId>, status: FragmentStatus) {
@@ -87,10 +327,24 @@ impl Pool {
                 selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
                 selection_alg.finalize()
             }
+            FragmentSelectionAlgorithmParams::FeeDensity => {
+                let mut selection_alg = FeeDensity::new();
+                selection_alg.select(&ledger, &ledger_params, block_date, logs, pool);
+                selection_alg.finalize()
+            }
         }
     }
 }
 
+/// the slot of the current time, or the slot of the tip if the current time
+/// is outside of the time frame of the blockchain
+fn current_slot(tip: &Ref) -> u64 {
+    tip.time_frame()
+        .slot_at(&SystemTime::now())
+        .unwrap_or_else(|| tip.slot())
+        .into()
+}
+
 fn is_fragment_valid(fragment: &Fragment) -> bool {
     match fragment {
         // never valid in the pool, only acceptable in genesis
@@ -102,12 +356,12 @@ fn is_fragment_valid(fragment: &Fragment) -> bool {
         Fragment::OwnerStakeDelegation(ref tx) => is_transaction_valid(tx),
         Fragment::PoolRegistration(ref tx) => is_transaction_valid(tx),
         Fragment::PoolRetirement(ref tx) => is_transaction_valid(tx),
+        Fragment::VotePlan(ref tx) => is_transaction_valid(tx),
+        Fragment::VoteCast(ref tx) => is_transaction_valid(tx),
         // disabled for now
         Fragment::PoolUpdate(_) => false,
         Fragment::UpdateProposal(_) => false,
         Fragment::UpdateVote(_) => false,
-        Fragment::VotePlan(_) => false,
-        Fragment::VoteCast(_) => false,
     }
 }
 
@@ -115,51 +369,794 @@ fn is_transaction_valid<E>(tx: &Transaction<E>) -> bool {
     tx.verify_possibly_balanced().is_ok()
 }
 
+/// check the fragment against the state of the current tip, so the fragments
+/// that cannot be applied are not kept in the pool nor propagated.
+///
+/// Returns the spending counters the account inputs of the fragment use.
+fn check_fragment(tip: &Ref, fragment: &Fragment) -> Result<Vec<AccountCounter>, AdmissionError> {
+    let counters = check_ledger_fragment(tip, fragment)?;
+    check_vote_fragment(tip, fragment)?;
+    Ok(counters)
+}
+
+/// check that the inputs of a transaction can be spent in the ledger of the
+/// current tip and that they pay exactly for the outputs and the fee.
+///
+/// The fragments are checked independently of each other, so the exact spending
+/// counter of an account input cannot be known: the witness only has to be valid
+/// for one of the next few counters of the account, within a budget of signature
+/// verifications for the whole fragment. The ledger does the complete validation
+/// when the fragment is selected for a block.
+fn check_ledger_fragment(
+    tip: &Ref,
+    fragment: &Fragment,
+) -> Result<Vec<AccountCounter>, LedgerFragmentError> {
+    let ledger = tip.ledger();
+    let fee = fragment_fee(&tip.epoch_ledger_parameters().fees, fragment);
+    match fragment {
+        Fragment::Transaction(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::OwnerStakeDelegation(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::StakeDelegation(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::PoolRegistration(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::PoolRetirement(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::PoolUpdate(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::VotePlan(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::VoteCast(tx) => check_transaction(&ledger, fee, tx),
+        Fragment::Initial(_)
+        | Fragment::OldUtxoDeclaration(_)
+        | Fragment::UpdateProposal(_)
+        | Fragment::UpdateVote(_) => Ok(Vec::new()),
+    }
+}
+
+fn check_transaction<P>(
+    ledger: &Ledger,
+    fee: Value,
+    tx: &Transaction<P>,
+) -> Result<Vec<AccountCounter>, LedgerFragmentError> {
+    let tx = tx.as_slice();
+    let block0_hash = ledger.get_static_parameters().block0_initial_hash;
+    let sign_data_hash = tx.transaction_sign_data_hash();
+    let mut counters: Vec<AccountCounter> = Vec::new();
+    let mut lookahead_verifications = SPENDING_COUNTER_LOOKAHEAD_VERIFICATIONS;
+
+    let inputs = tx.inputs().iter().zip(tx.witnesses().iter()).enumerate();
+    for (index, (input, witness)) in inputs {
+        match input.to_enum() {
+            InputEnum::UtxoInput(pointer) => {
+                let output = ledger
+                    .utxo_out(pointer.transaction_id, pointer.output_index)
+                    .ok_or(LedgerFragmentError::UtxoNotFound { index })?;
+                if output.value != pointer.value {
+                    return Err(LedgerFragmentError::UtxoValueMismatch { index });
+                }
+            }
+            InputEnum::AccountInput(account, value) => {
+                // the multisig accounts are left to the ledger
+                let account = match account.to_single_account() {
+                    Some(account) => account,
+                    None => continue,
+                };
+                let state = ledger
+                    .accounts()
+                    .get_state(&account)
+                    .map_err(|_| LedgerFragmentError::AccountNotFound { index })?;
+                if state.value() < value {
+                    return Err(LedgerFragmentError::InsufficientFunds {
+                        index,
+                        balance: state.value(),
+                        value,
+                    });
+                }
+
+                let counter = state.get_counter();
+                let signed_counter = match witness {
+                    Witness::Account(signature) => {
+                        let mut signed_counter = None;
+                        for offset in 0..SPENDING_COUNTER_LOOKAHEAD {
+                            let candidate = match counter.checked_add(offset) {
+                                Some(candidate) => candidate,
+                                None => break,
+                            };
+                            if offset > 0 {
+                                if lookahead_verifications == 0 {
+                                    break;
+                                }
+                                lookahead_verifications -= 1;
+                            }
+                            let data = WitnessAccountData::new(
+                                &block0_hash,
+                                &sign_data_hash,
+                                &SpendingCounter::from(candidate),
+                            );
+                            if let Verification::Success = signature.verify(account.as_ref(), &data)
+                            {
+                                signed_counter = Some(candidate);
+                                break;
+                            }
+                        }
+                        signed_counter
+                    }
+                    _ => None,
+                };
+                let signed_counter = signed_counter
+                    .ok_or(LedgerFragmentError::ImplausibleSpendingCounter { index, counter })?;
+                // only the first input of an account is tracked, the ledger
+                // checks the others
+                if !counters.iter().any(|used| used.account == account) {
+                    counters.push(AccountCounter {
+                        account,
+                        counter: signed_counter,
+                    });
+                }
+            }
+        }
+    }
+
+    let inputs = tx.total_input()?;
+    let outputs = tx.total_output()?;
+    if inputs != (outputs + fee)? {
+        return Err(LedgerFragmentError::NotBalanced {
+            inputs,
+            outputs,
+            fee,
+        });
+    }
+
+    Ok(counters)
+}
+
+/// check the vote plans and vote casts against the state of the current tip.
+///
+/// The witnesses of the vote casts are verified by the ledger when the
+/// fragment is applied, as they depend on the spending counter of the
+/// voter's account.
+fn check_vote_fragment(tip: &Ref, fragment: &Fragment) -> Result<(), VoteFragmentError> {
+    match fragment {
+        Fragment::VotePlan(tx) => check_vote_plan(tip, tx),
+        Fragment::VoteCast(tx) => check_vote_cast(tip, tx),
+        _ => Ok(()),
+    }
+}
+
+fn check_vote_plan(tip: &Ref, tx: &Transaction<VotePlan>) -> Result<(), VoteFragmentError> {
+    let tx = tx.as_slice();
+    let vote_plan = tx.payload().into_payload();
+    let proof = tx.payload_auth().into_payload_auth();
+    let id = Hash::from(vote_plan.to_id());
+    let date = tip.block_date();
+
+    if let Verification::Failed = proof.verify(&tx.transaction_binding_auth_data()) {
+        return Err(VoteFragmentError::InvalidVotePlanSignature);
+    }
+
+    if vote_plan.vote_start() >= vote_plan.vote_end() {
+        return Err(VoteFragmentError::InvalidVotePlanPeriod {
+            id,
+            vote_start: vote_plan.vote_start(),
+            vote_end: vote_plan.vote_end(),
+        });
+    }
+
+    if vote_plan.vote_start() <= date {
+        return Err(VoteFragmentError::VotePlanAlreadyStarted {
+            id,
+            vote_start: vote_plan.vote_start(),
+            date,
+        });
+    }
+
+    let exists = tip
+        .active_vote_plans()
+        .iter()
+        .any(|active| Hash::from(active.to_id()) == id);
+    if exists {
+        return Err(VoteFragmentError::VotePlanAlreadyExists(id));
+    }
+
+    Ok(())
+}
+
+fn check_vote_cast(tip: &Ref, tx: &Transaction<VoteCast>) -> Result<(), VoteFragmentError> {
+    let vote_cast = tx.as_slice().payload().into_payload();
+    let id = Hash::from(vote_cast.vote_plan().clone());
+    let date = tip.block_date();
+
+    let vote_plan = tip
+        .active_vote_plans()
+        .into_iter()
+        .find(|active| Hash::from(active.to_id()) == id)
+        .ok_or_else(|| VoteFragmentError::VotePlanNotFound(id))?;
+
+    if date < vote_plan.vote_start() || vote_plan.vote_end() <= date {
+        return Err(VoteFragmentError::OutsideVoteWindow {
+            id,
+            vote_start: vote_plan.vote_start(),
+            vote_end: vote_plan.vote_end(),
+            date,
+        });
+    }
+
+    let index = vote_cast.proposal_index();
+    if usize::from(index) >= vote_plan.proposals().len() {
+        return Err(VoteFragmentError::ProposalNotFound { id, index });
+    }
+
+    Ok(())
+}
+
 pub(super) mod internal {
     use super::*;
-    use lru::LruCache;
+    use std::collections::{BTreeMap, HashMap};
+
+    /// the spending counter an account input of a fragment is signed for
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct AccountCounter {
+        pub account: account::Identifier,
+        pub counter: u32,
+    }
+
+    /// a fragment of the pool, with what is needed to expire it, to count it
+    /// in the quota of its origin and to order it in the queues of the
+    /// accounts it spends from
+    pub struct PoolEntry {
+        pub fragment: Fragment,
+        origin: FragmentOrigin,
+        /// the address of the REST client or of the peer the fragment was
+        /// received from
+        client: Option<IpAddr>,
+        received_at: Instant,
+        received_slot: u64,
+        account_counters: Vec<AccountCounter>,
+        /// the position of the fragment in the order the pool received the
+        /// fragments, kept when the entry is put back
+        sequence: u64,
+    }
+
+    #[derive(Debug, Error)]
+    pub enum InsertError {
+        #[error("the fragment is already in the pool")]
+        AlreadyInPool,
+        #[error("the pool already holds the maximum of {max_entries} fragments received from this {origin:?} client")]
+        QuotaReached {
+            origin: FragmentOrigin,
+            max_entries: usize,
+        },
+        #[error("the spending counter {counter} of the account is already used by the fragment {fragment_id} of the pool")]
+        SpendingCounterInUse {
+            counter: u32,
+            fragment_id: FragmentId,
+        },
+    }
 
     pub struct Pool {
-        entries: LruCache<FragmentId, Fragment>,
+        entries: HashMap<FragmentId, PoolEntry>,
+        /// the fragments of the pool in the order they were received
+        order: BTreeMap<u64, FragmentId>,
+        next_sequence: u64,
+        max_entries: usize,
+        origin_max_entries: OriginMaxEntries,
+        /// the number of entries received from each client of each origin
+        origin_entries: HashMap<(FragmentOrigin, Option<IpAddr>), usize>,
+        /// the fragments spending from each account, by spending counter
+        accounts: HashMap<account::Identifier, BTreeMap<u32, FragmentId>>,
+    }
+
+    impl PoolEntry {
+        pub fn new(
+            fragment: Fragment,
+            origin: FragmentOrigin,
+            client: Option<IpAddr>,
+            received_slot: u64,
+            account_counters: Vec<AccountCounter>,
+        ) -> Self {
+            PoolEntry {
+                fragment,
+                origin,
+                client,
+                received_at: Instant::now(),
+                received_slot,
+                account_counters,
+                sequence: 0,
+            }
+        }
+
+        pub fn origin(&self) -> FragmentOrigin {
+            self.origin
+        }
+
+        pub fn received_at(&self) -> Instant {
+            self.received_at
+        }
+
+        /// the spending counters the account inputs of the fragment are
+        /// signed for
+        pub fn account_counters(&self) -> &[AccountCounter] {
+            &self.account_counters
+        }
+
+        /// the fragment spends from an account with a spending counter the
+        /// given ledger has not reached yet: it has to wait for the fragments
+        /// using the previous counters of the account
+        pub fn is_waiting(&self, ledger: &Ledger) -> bool {
+            self.account_counters.iter().any(|account_counter| {
+                match ledger.accounts().get_state(&account_counter.account) {
+                    Ok(state) => state.get_counter() < account_counter.counter,
+                    Err(_) => false,
+                }
+            })
+        }
+
+        fn is_expired(&self, ttl: FragmentTtl, now: Instant, current_slot: u64) -> bool {
+            match ttl {
+                FragmentTtl::Slots(slots) => {
+                    current_slot.saturating_sub(self.received_slot) >= slots
+                }
+                FragmentTtl::Duration(duration) => {
+                    now.saturating_duration_since(self.received_at) >= duration.into()
+                }
+            }
+        }
     }
 
     impl Pool {
-        pub fn new(max_entries: usize) -> Self {
+        pub fn new(max_entries: usize, origin_max_entries: OriginMaxEntries) -> Self {
             Pool {
-                entries: LruCache::new(max_entries),
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                next_sequence: 0,
+                max_entries,
+                origin_max_entries,
+                origin_entries: HashMap::new(),
+                accounts: HashMap::new(),
             }
         }
 
-        /// Returns clone of fragment if it was registered
-        pub fn insert(&mut self, fragment: Fragment) -> Option<Fragment> {
-            let fragment_id = fragment.id();
-            if self.entries.contains(&fragment_id) {
-                None
+        pub fn max_entries(&self) -> usize {
+            self.max_entries
+        }
+
+        /// Returns the oldest fragment of the pool if it had to be evicted to
+        /// make room for the new one
+        pub fn insert(&mut self, mut entry: PoolEntry) -> Result<Option<PoolEntry>, InsertError> {
+            let fragment_id = entry.fragment.id();
+            if self.entries.contains_key(&fragment_id) {
+                return Err(InsertError::AlreadyInPool);
+            }
+            if let Some(max_entries) = self.origin_max_entries.get(entry.origin) {
+                let max_entries = usize::from(max_entries);
+                if self.origin_len(entry.origin, entry.client) >= max_entries {
+                    return Err(InsertError::QuotaReached {
+                        origin: entry.origin,
+                        max_entries,
+                    });
+                }
+            }
+            for account_counter in entry.account_counters.iter() {
+                let in_use = self
+                    .accounts
+                    .get(&account_counter.account)
+                    .and_then(|queue| queue.get(&account_counter.counter));
+                if let Some(fragment_id) = in_use {
+                    return Err(InsertError::SpendingCounterInUse {
+                        counter: account_counter.counter,
+                        fragment_id: fragment_id.clone(),
+                    });
+                }
+            }
+            let evicted = if self.entries.len() >= self.max_entries {
+                self.remove_oldest()
             } else {
-                self.entries.put(fragment_id, fragment.clone());
-                Some(fragment)
+                None
+            };
+            entry.sequence = self.next_sequence;
+            self.next_sequence += 1;
+            self.put_back(entry);
+            Ok(evicted)
+        }
+
+        /// put back an entry taken from the pool, regardless of the size of
+        /// the pool and of the quota of its origin. The entry takes back its
+        /// place in the order the fragments were received.
+        pub fn put_back(&mut self, entry: PoolEntry) {
+            let fragment_id = entry.fragment.id();
+            *self
+                .origin_entries
+                .entry((entry.origin, entry.client))
+                .or_insert(0) += 1;
+            for account_counter in entry.account_counters.iter() {
+                self.accounts
+                    .entry(account_counter.account.clone())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(account_counter.counter, fragment_id.clone());
             }
+            self.order.insert(entry.sequence, fragment_id.clone());
+            self.entries.insert(fragment_id, entry);
         }
 
-        /// Returns clones of registered fragments
-        pub fn insert_all(
-            &mut self,
-            fragments: impl IntoIterator<Item = Fragment>,
-        ) -> Vec<Fragment> {
-            fragments
-                .into_iter()
-                .filter_map(|fragment| self.insert(fragment))
-                .collect()
+        /// Returns the entries of the pool, the oldest first
+        pub fn entries(&self) -> impl Iterator<Item = &PoolEntry> {
+            let entries = &self.entries;
+            self.order
+                .values()
+                .filter_map(move |fragment_id| entries.get(fragment_id))
+        }
+
+        pub fn get(&self, fragment_id: &FragmentId) -> Option<&Fragment> {
+            self.entries.get(fragment_id).map(|entry| &entry.fragment)
         }
 
         pub fn remove_all(&mut self, fragment_ids: impl IntoIterator<Item = FragmentId>) {
             for fragment_id in fragment_ids {
-                self.entries.pop(&fragment_id);
+                self.remove(&fragment_id);
             }
         }
 
-        pub fn remove_oldest(&mut self) -> Option<Fragment> {
-            self.entries.pop_lru().map(|(_, value)| value)
+        /// Returns the ids of the fragments spending from the given account
+        /// with their spending counters, in the order of the counters
+        pub fn account_queue<'a>(
+            &'a self,
+            account: &account::Identifier,
+        ) -> impl Iterator<Item = (u32, &'a FragmentId)> + 'a {
+            self.accounts.get(account).into_iter().flat_map(|queue| {
+                queue
+                    .iter()
+                    .map(|(counter, fragment_id)| (*counter, fragment_id))
+            })
         }
+
+        pub fn remove_oldest(&mut self) -> Option<PoolEntry> {
+            let fragment_id = self.order.values().next()?.clone();
+            self.remove(&fragment_id)
+        }
+
+        /// Returns the ids of the fragments removed because they outlived the
+        /// time to live
+        pub fn remove_expired(
+            &mut self,
+            ttl: FragmentTtl,
+            now: Instant,
+            current_slot: u64,
+        ) -> Vec<FragmentId> {
+            let expired: Vec<FragmentId> = self
+                .entries()
+                .filter(|entry| entry.is_expired(ttl, now, current_slot))
+                .map(|entry| entry.fragment.id())
+                .collect();
+            for fragment_id in expired.iter() {
+                self.remove(fragment_id);
+            }
+            expired
+        }
+
+        fn remove(&mut self, fragment_id: &FragmentId) -> Option<PoolEntry> {
+            let entry = self.entries.remove(fragment_id)?;
+            self.order.remove(&entry.sequence);
+            self.forget(&entry);
+            Some(entry)
+        }
+
+        fn origin_len(&self, origin: FragmentOrigin, client: Option<IpAddr>) -> usize {
+            self.origin_entries
+                .get(&(origin, client))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// remove the entry from the counts of its origin and from the queues
+        /// of its accounts
+        fn forget(&mut self, entry: &PoolEntry) {
+            let key = (entry.origin, entry.client);
+            if let Some(count) = self.origin_entries.get_mut(&key) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.origin_entries.remove(&key);
+                }
+            }
+            for account_counter in entry.account_counters.iter() {
+                if let Some(queue) = self.accounts.get_mut(&account_counter.account) {
+                    queue.remove(&account_counter.counter);
+                    if queue.is_empty() {
+                        self.accounts.remove(&account_counter.account);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::internal::{self, AccountCounter, InsertError, PoolEntry};
+    use super::*;
+    use crate::{
+        blockcfg::{Block, HeaderHash, Leadership},
+        fragment::selection::OldestFirst,
+    };
+    use chain_addr::Discrimination;
+    use chain_impl_mockchain::transaction::{Input, Output, TxBuilder};
+    use jormungandr_lib::{
+        crypto::account::SigningKey,
+        interfaces::{
+            block0_configuration_documented_example, Block0Configuration, Initial, InitialUTxO,
+            PoolMaxEntries,
+        },
+    };
+    use rand_chacha::ChaChaRng;
+    use rand_core::SeedableRng as _;
+    use std::time::Duration;
+
+    pub(in crate::fragment) const FUNDS: u64 = 1_000_000;
+
+    /// the fee of a transaction with one input and one output with the fees
+    /// of the documented block0
+    pub(in crate::fragment) const FEE: u64 = 4;
+
+    /// the ledger of the documented block0, with an account funded
+    pub(in crate::fragment) struct TestLedger {
+        pub ledger: Ledger,
+        block0_hash: HeaderHash,
+        key: SigningKey,
+    }
+
+    impl TestLedger {
+        pub fn new() -> Self {
+            let key = SigningKey::generate(ChaChaRng::from_seed([1; 32]));
+            let mut config: Block0Configuration =
+                serde_yaml::from_str(&block0_configuration_documented_example()).unwrap();
+            config.initial.push(Initial::Fund(vec![InitialUTxO {
+                address: key.identifier().to_address(Discrimination::Test).into(),
+                value: FUNDS.into(),
+            }]));
+            let block0: Block = config.to_block();
+            let block0_hash = block0.header.hash();
+            let ledger = Ledger::new(block0_hash, block0.contents.iter()).unwrap();
+            TestLedger {
+                ledger,
+                block0_hash,
+                key,
+            }
+        }
+
+        pub fn account(&self) -> account::Identifier {
+            self.key.identifier().to_inner()
+        }
+
+        /// a transaction from the account to itself, sending `value` and
+        /// paying `fee`, signed for the given spending counter
+        pub fn transaction(&self, counter: u32, value: u64, fee: u64) -> Fragment {
+            self.transaction_to_outputs(counter, &[value], fee)
+        }
+
+        /// a transaction from the account to itself with one output for
+        /// each of the given values
+        pub fn transaction_to_outputs(&self, counter: u32, values: &[u64], fee: u64) -> Fragment {
+            let total: u64 = values.iter().sum();
+            let input = Input::from_account_single(self.account(), Value(total + fee));
+            let outputs: Vec<_> = values
+                .iter()
+                .map(|value| {
+                    Output::from_address(
+                        self.key.identifier().to_address(Discrimination::Test),
+                        Value(*value),
+                    )
+                })
+                .collect();
+            let builder = TxBuilder::new().set_nopayload().set_ios(&[input], &outputs);
+            let sign_data_hash = builder.get_auth_data_for_witness().hash();
+            let witness = Witness::new_account(
+                &self.block0_hash,
+                &sign_data_hash,
+                SpendingCounter::from(counter),
+                |data| self.key.as_ref().sign(data),
+            );
+            Fragment::Transaction(builder.set_witnesses(&[witness]).set_payload_auth(&()))
+        }
+
+        pub fn counters(&self, counter: u32) -> Vec<AccountCounter> {
+            vec![AccountCounter {
+                account: self.account(),
+                counter,
+            }]
+        }
+
+        fn check(&self, fragment: &Fragment) -> Result<Vec<AccountCounter>, LedgerFragmentError> {
+            match fragment {
+                Fragment::Transaction(tx) => check_transaction(&self.ledger, Value(FEE), tx),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    pub(in crate::fragment) fn entry(
+        fragment: &Fragment,
+        counters: Vec<AccountCounter>,
+    ) -> PoolEntry {
+        PoolEntry::new(fragment.clone(), FragmentOrigin::Rest, None, 0, counters)
+    }
+
+    pub(in crate::fragment) fn ids<'a>(
+        entries: impl Iterator<Item = &'a PoolEntry>,
+    ) -> Vec<FragmentId> {
+        entries.map(|entry| entry.fragment.id()).collect()
+    }
+
+    #[test]
+    fn transaction_is_checked_against_the_ledger() {
+        let test = TestLedger::new();
+
+        let counters = test.check(&test.transaction(0, 10, FEE)).unwrap();
+        assert_eq!(counters, test.counters(0));
+
+        assert!(matches!(
+            test.check(&test.transaction(0, 10, FEE - 1)),
+            Err(LedgerFragmentError::NotBalanced { .. })
+        ));
+        assert!(matches!(
+            test.check(&test.transaction(0, FUNDS, FEE)),
+            Err(LedgerFragmentError::InsufficientFunds { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn spending_counter_lookahead() {
+        let test = TestLedger::new();
+
+        let last = SPENDING_COUNTER_LOOKAHEAD - 1;
+        let counters = test.check(&test.transaction(last, 10, FEE)).unwrap();
+        assert_eq!(counters, test.counters(last));
+
+        assert!(matches!(
+            test.check(&test.transaction(SPENDING_COUNTER_LOOKAHEAD, 10, FEE)),
+            Err(LedgerFragmentError::ImplausibleSpendingCounter {
+                index: 0,
+                counter: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn quota_per_origin_and_client() {
+        let test = TestLedger::new();
+        let max_entries = OriginMaxEntries {
+            rest: Some(PoolMaxEntries::from(2)),
+            network: None,
+        };
+        let mut pool = internal::Pool::new(10, max_entries);
+        let client = Some("10.0.0.1".parse().unwrap());
+        let other_client = Some("10.0.0.2".parse().unwrap());
+        let mut fragments = (1..).map(|value| test.transaction(0, value, FEE));
+        let mut insert = |origin, client| {
+            let fragment = fragments.next().unwrap();
+            pool.insert(PoolEntry::new(fragment, origin, client, 0, Vec::new()))
+        };
+
+        insert(FragmentOrigin::Rest, client).unwrap();
+        insert(FragmentOrigin::Rest, client).unwrap();
+        assert!(matches!(
+            insert(FragmentOrigin::Rest, client),
+            Err(InsertError::QuotaReached {
+                origin: FragmentOrigin::Rest,
+                max_entries: 2,
+            })
+        ));
+        // the quota is counted for each client, and only for the origins
+        // with a quota set
+        insert(FragmentOrigin::Rest, other_client).unwrap();
+        for _ in 0..3 {
+            insert(FragmentOrigin::Network, client).unwrap();
+        }
+    }
+
+    #[test]
+    fn expired_fragments_are_removed() {
+        let test = TestLedger::new();
+        let mut pool = internal::Pool::new(10, OriginMaxEntries::default());
+        let first = test.transaction(0, 10, FEE);
+        let second = test.transaction(0, 20, FEE);
+        pool.insert(PoolEntry::new(
+            first.clone(),
+            FragmentOrigin::Rest,
+            None,
+            5,
+            Vec::new(),
+        ))
+        .unwrap();
+        pool.insert(PoolEntry::new(
+            second.clone(),
+            FragmentOrigin::Rest,
+            None,
+            8,
+            Vec::new(),
+        ))
+        .unwrap();
+
+        let now = Instant::now();
+        let ttl = FragmentTtl::Slots(5);
+        assert!(pool.remove_expired(ttl, now, 9).is_empty());
+        assert_eq!(pool.remove_expired(ttl, now, 10), vec![first.id()]);
+
+        let ttl = FragmentTtl::Duration(Duration::from_secs(30).into());
+        assert!(pool.remove_expired(ttl, now, 10).is_empty());
+        let later = now + Duration::from_secs(60);
+        assert_eq!(pool.remove_expired(ttl, later, 10), vec![second.id()]);
+        assert_eq!(pool.entries().count(), 0);
+    }
+
+    #[test]
+    fn fragments_wait_for_the_previous_spending_counters() {
+        let test = TestLedger::new();
+        let mut pool = internal::Pool::new(10, OriginMaxEntries::default());
+        let second = test.transaction(1, 10, FEE);
+        let first = test.transaction(0, 20, FEE);
+
+        pool.insert(entry(&second, test.counters(1))).unwrap();
+        assert!(matches!(
+            pool.insert(entry(&test.transaction(1, 30, FEE), test.counters(1))),
+            Err(InsertError::SpendingCounterInUse { counter: 1, .. })
+        ));
+        pool.insert(entry(&first, test.counters(0))).unwrap();
+
+        let queue: Vec<(u32, FragmentId)> = pool
+            .account_queue(&test.account())
+            .map(|(counter, fragment_id)| (counter, fragment_id.clone()))
+            .collect();
+        assert_eq!(queue, vec![(0, first.id()), (1, second.id())]);
+        let waiting: Vec<bool> = pool
+            .entries()
+            .map(|entry| entry.is_waiting(&test.ledger))
+            .collect();
+        assert_eq!(waiting, vec![true, false]);
+
+        // the fragment received first is held back until the fragment using
+        // the previous counter is applied
+        let parameters = Leadership::new(0, &test.ledger).ledger_parameters().clone();
+        let mut logs = Logs::new(10);
+        let mut selection = OldestFirst::new();
+        selection.select(
+            &test.ledger,
+            &parameters,
+            BlockDate {
+                epoch: 0,
+                slot_id: 1,
+            },
+            &mut logs,
+            &mut pool,
+        );
+        let selected: Vec<FragmentId> = selection
+            .finalize()
+            .iter()
+            .map(|fragment| fragment.id())
+            .collect();
+        assert_eq!(selected, vec![first.id(), second.id()]);
+        assert_eq!(pool.account_queue(&test.account()).count(), 0);
+    }
+
+    #[test]
+    fn put_back_keeps_the_age_of_the_fragment() {
+        let test = TestLedger::new();
+        let mut pool = internal::Pool::new(3, OriginMaxEntries::default());
+        let fragments: Vec<Fragment> = (1..=4)
+            .map(|value| test.transaction(0, value, FEE))
+            .collect();
+        for fragment in &fragments[..3] {
+            assert!(pool.insert(entry(fragment, Vec::new())).unwrap().is_none());
+        }
+
+        let oldest = pool.remove_oldest().unwrap();
+        assert_eq!(oldest.fragment.id(), fragments[0].id());
+        pool.put_back(oldest);
+        assert_eq!(
+            ids(pool.entries()),
+            vec![fragments[0].id(), fragments[1].id(), fragments[2].id()]
+        );
+
+        // the fragment put back is still the oldest, it is evicted first
+        let evicted = pool.insert(entry(&fragments[3], Vec::new())).unwrap();
+        assert_eq!(evicted.unwrap().fragment.id(), fragments[0].id());
+        assert_eq!(
+            ids(pool.entries()),
+            vec![fragments[1].id(), fragments[2].id(), fragments[3].id()]
+        );
     }
 }
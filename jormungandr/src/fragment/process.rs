@@ -1,27 +1,53 @@
 use crate::{
+    blockchain::Tip,
     fragment::{Logs, Pool},
-    intercom::{NetworkMsg, TransactionMsg},
+    intercom::{Error, NetworkMsg, ReplySendError, TransactionMsg},
     stats_counter::StatsCounter,
     utils::{
         async_msg::{MessageBox, MessageQueue},
         task::TokioServiceInfo,
     },
 };
-use tokio::stream::StreamExt;
+use futures::{
+    future,
+    sink::SinkExt,
+    stream::{self, StreamExt},
+};
+use jormungandr_lib::interfaces::{FragmentTtl, OriginMaxEntries};
+use std::time::Duration;
+
+/// how often the pool is checked for the fragments which outlived their
+/// time to live
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct Process {
     pool: Pool,
 }
 
+enum Input {
+    Message(TransactionMsg),
+    ExpiryCheck,
+    Shutdown,
+}
+
 impl Process {
     pub fn new(
         pool_max_entries: usize,
-        logs_max_entries: usize,
+        logs: Logs,
+        fragment_ttl: Option<FragmentTtl>,
+        origin_max_entries: OriginMaxEntries,
         network_msg_box: MessageBox<NetworkMsg>,
+        blockchain_tip: Tip,
     ) -> Self {
-        let logs = Logs::new(logs_max_entries);
         Process {
-            pool: Pool::new(pool_max_entries, logs, network_msg_box),
+            pool: Pool::new(
+                pool_max_entries,
+                fragment_ttl,
+                origin_max_entries,
+                logs,
+                network_msg_box,
+                blockchain_tip,
+            ),
         }
     }
 
@@ -29,19 +55,36 @@ impl Process {
         self,
         service_info: TokioServiceInfo,
         stats_counter: StatsCounter,
-        mut input: MessageQueue<TransactionMsg>,
+        input: MessageQueue<TransactionMsg>,
     ) -> Result<(), ()> {
         let mut pool = self.pool;
 
-        while let Some(input_result) = input.next().await {
+        // the messages are followed by a shutdown marker, so the process
+        // stops once the queue is closed even though the expiry checks go on
+        let messages = input
+            .map(Input::Message)
+            .chain(stream::once(future::ready(Input::Shutdown)));
+        let expiry_checks =
+            tokio::time::interval(EXPIRY_CHECK_INTERVAL).map(|_| Input::ExpiryCheck);
+        let mut inputs = stream::select(messages, expiry_checks);
+
+        while let Some(input) = inputs.next().await {
+            let input_result = match input {
+                Input::Message(msg) => msg,
+                Input::ExpiryCheck => {
+                    pool.remove_expired(service_info.logger()).await;
+                    pool.logs().flush();
+                    continue;
+                }
+                Input::Shutdown => break,
+            };
             match input_result {
-                TransactionMsg::SendTransaction(origin, txs) => {
+                TransactionMsg::SendTransaction(origin, client, txs, reply_handle) => {
                     // Note that we cannot use apply_block here, since we don't have a valid context to which to apply
-                    // those blocks. one valid tx in a given context, could be invalid in another. for example
-                    // fee calculations, existence utxo / account solvency.
-
-                    // FIXME/TODO check that the txs are valid within themselves with basic requirements (e.g. inputs >= outputs).
-                    // we also want to keep a basic capability to filter away repetitive queries or definitely discarded txid.
+                    // those blocks. one valid tx in a given context, could be invalid in another. The pool checks
+                    // the fragments against the ledger of the current tip (existence of the utxos, solvency of the
+                    // accounts, fees) and rejects the ones that cannot be applied there, the ledger still has the
+                    // last word when the fragments are selected for a block.
 
                     // This interface only makes sense for messages coming from arbitrary users (like transaction, certificates),
                     // for other message we don't want to receive them through this interface, and possibly
@@ -49,9 +92,23 @@ impl Process {
 
                     let stats_counter = stats_counter.clone();
 
-                    pool.insert_and_propagate_all(origin, txs, service_info.logger().clone())
-                        .await
-                        .map(move |count| stats_counter.add_tx_recv_cnt(count))?;
+                    let submissions = pool
+                        .insert_and_propagate_all(
+                            origin,
+                            client,
+                            txs,
+                            service_info.logger().clone(),
+                        )
+                        .await?;
+                    stats_counter.add_tx_recv_cnt(
+                        submissions
+                            .iter()
+                            .filter(|submission| submission.is_accepted())
+                            .count(),
+                    );
+                    if let Some(reply_handle) = reply_handle {
+                        reply_handle.reply_ok(submissions);
+                    }
                 }
                 TransactionMsg::RemoveTransactions(fragment_ids, status) => {
                     pool.remove_added_to_block(fragment_ids, status);
@@ -60,6 +117,43 @@ impl Process {
                     let logs = pool.logs().logs().cloned().collect();
                     reply_handle.reply_ok(logs);
                 }
+                TransactionMsg::GetStatuses(fragment_ids, reply_handle) => {
+                    let statuses = fragment_ids
+                        .into_iter()
+                        .filter_map(|fragment_id| {
+                            let status = pool.logs().get(&fragment_id)?.status().clone();
+                            Some((fragment_id, status))
+                        })
+                        .collect();
+                    reply_handle.reply_ok(statuses);
+                }
+                TransactionMsg::GetPending(include_fragment, reply_handle) => {
+                    reply_handle.reply_ok(pool.pending(include_fragment).await);
+                }
+                TransactionMsg::GetAccountPending(account, reply_handle) => {
+                    reply_handle.reply_ok(pool.account_pending(&account));
+                }
+                TransactionMsg::GetFragments(fragment_ids, mut reply_handle) => {
+                    let replies = fragment_ids
+                        .into_iter()
+                        .map(|fragment_id| match pool.get(&fragment_id) {
+                            Some(fragment) => Ok(fragment.clone()),
+                            None => Err(Error::not_found(format!(
+                                "fragment {} is not in the mempool of this node",
+                                fragment_id
+                            ))),
+                        })
+                        .collect::<Vec<_>>();
+
+                    // the replies are sent from another task so a slow
+                    // reader does not hold the mempool
+                    service_info.spawn_fallible("get fragments", async move {
+                        for reply in replies {
+                            reply_handle.send(reply).await?;
+                        }
+                        Ok::<(), ReplySendError>(())
+                    });
+                }
                 TransactionMsg::SelectTransactions {
                     ledger,
                     block_date,
@@ -71,6 +165,10 @@ impl Process {
                     reply_handle.reply_ok(contents);
                 }
             }
+
+            // the logs changed while handling the message are written to
+            // the storage at once
+            pool.logs().flush();
         }
 
         Ok(())
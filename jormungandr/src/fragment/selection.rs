@@ -1,11 +1,17 @@
 use super::logs::Logs;
-use super::pool::internal::Pool;
+use super::pool::internal::{Pool, PoolEntry};
 use crate::{
-    blockcfg::{BlockDate, Contents, ContentsBuilder, Ledger, LedgerParameters},
+    blockcfg::{BlockDate, Contents, ContentsBuilder, Fragment, Ledger, LedgerParameters, Value},
     fragment::FragmentId,
 };
 use chain_core::property::Fragment as _;
-use jormungandr_lib::interfaces::FragmentStatus;
+use chain_impl_mockchain::{
+    fee::{FeeAlgorithm, LinearFee},
+    transaction::{Payload, Transaction},
+};
+use jormungandr_lib::interfaces::{FragmentSelection, FragmentStatus};
+
+use std::cmp::Ordering;
 
 pub enum SelectionOutput {
     Commit { fragment_id: FragmentId },
@@ -27,9 +33,19 @@ pub trait FragmentSelectionAlgorithm {
     fn finalize(self) -> Contents;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum FragmentSelectionAlgorithmParams {
     OldestFirst,
+    FeeDensity,
+}
+
+impl From<FragmentSelection> for FragmentSelectionAlgorithmParams {
+    fn from(selection: FragmentSelection) -> Self {
+        match selection {
+            FragmentSelection::OldestFirst => FragmentSelectionAlgorithmParams::OldestFirst,
+            FragmentSelection::FeeDensity => FragmentSelectionAlgorithmParams::FeeDensity,
+        }
+    }
 }
 
 pub struct OldestFirst {
@@ -60,8 +76,21 @@ impl FragmentSelectionAlgorithm for OldestFirst {
         pool: &mut Pool,
     ) {
         let mut ledger_simulation = ledger.clone();
+        let mut waiting = Waiting::new();
 
-        while let Some(fragment) = pool.remove_oldest() {
+        loop {
+            let entry = match waiting
+                .take_ready(&ledger_simulation)
+                .or_else(|| pool.remove_oldest())
+            {
+                Some(entry) => entry,
+                None => break,
+            };
+            let entry = match waiting.hold(&ledger_simulation, entry) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let fragment = entry.fragment;
             let id = fragment.id();
             let fragment_raw = fragment.to_raw(); // TODO: replace everything to FragmentRaw in the node
             let fragment_size = fragment_raw.size_bytes_plus_size() as u32;
@@ -91,5 +120,280 @@ impl FragmentSelectionAlgorithm for OldestFirst {
                 }
             }
         }
+
+        for entry in waiting.into_entries() {
+            pool.put_back(entry);
+        }
+    }
+}
+
+/// select the fragments paying the highest fee per byte first.
+///
+/// Fragments paying the same fee per byte are selected in the order they
+/// were received. The fragments which do not fit in the block, or which wait
+/// for a fragment of the same account with a lower spending counter, are put
+/// back in the pool, to be considered for the next block.
+pub struct FeeDensity {
+    builder: ContentsBuilder,
+    current_total_size: u32,
+}
+
+struct FeeDensityEntry {
+    age: usize,
+    entry: PoolEntry,
+    size: u32,
+    fee: Value,
+}
+
+impl FeeDensity {
+    pub fn new() -> Self {
+        FeeDensity {
+            builder: ContentsBuilder::new(),
+            current_total_size: 0,
+        }
+    }
+}
+
+impl FragmentSelectionAlgorithm for FeeDensity {
+    fn finalize(self) -> Contents {
+        self.builder.into()
+    }
+
+    fn select(
+        &mut self,
+        ledger: &Ledger,
+        ledger_params: &LedgerParameters,
+        block_date: BlockDate,
+        logs: &mut Logs,
+        pool: &mut Pool,
+    ) {
+        let mut ledger_simulation = ledger.clone();
+
+        let mut entries = Vec::new();
+        while let Some(entry) = pool.remove_oldest() {
+            let size = entry.fragment.to_raw().size_bytes_plus_size() as u32;
+            let fee = fragment_fee(&ledger_params.fees, &entry.fragment);
+            entries.push(FeeDensityEntry {
+                age: entries.len(),
+                entry,
+                size,
+                fee,
+            });
+        }
+
+        // the sort is stable so the oldest fragment comes first on equal density
+        entries.sort_by(|a, b| b.cmp_fee_density(a));
+
+        let mut not_selected = Vec::new();
+        let mut waiting = Waiting::new();
+        let mut entries = entries.into_iter();
+
+        loop {
+            let entry = match waiting
+                .take_ready(&ledger_simulation)
+                .or_else(|| entries.next())
+            {
+                Some(entry) => entry,
+                None => break,
+            };
+            let entry = match waiting.hold(&ledger_simulation, entry) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let total_size = self.current_total_size + entry.size;
+
+            if total_size > ledger_params.block_content_max_size {
+                not_selected.push(entry);
+                continue;
+            }
+
+            let fragment = entry.entry.fragment;
+            let id = fragment.id();
+            match ledger_simulation.apply_fragment(ledger_params, &fragment, block_date) {
+                Ok(ledger_new) => {
+                    self.builder.push(fragment);
+                    ledger_simulation = ledger_new;
+                    self.current_total_size = total_size;
+                }
+                Err(error) => {
+                    use std::error::Error as _;
+                    let error = if let Some(source) = error.source() {
+                        format!("{}: {}", error, source)
+                    } else {
+                        error.to_string()
+                    };
+                    logs.modify(id, FragmentStatus::Rejected { reason: error })
+                }
+            }
+        }
+
+        not_selected.extend(waiting.into_entries());
+        not_selected.sort_by_key(|entry| entry.age);
+        for entry in not_selected {
+            pool.put_back(entry.entry);
+        }
+    }
+}
+
+impl AsRef<PoolEntry> for FeeDensityEntry {
+    fn as_ref(&self) -> &PoolEntry {
+        &self.entry
+    }
+}
+
+impl FeeDensityEntry {
+    /// compare the fee per byte of the two entries, without loss of precision
+    fn cmp_fee_density(&self, other: &Self) -> Ordering {
+        let lhs = u128::from(self.fee.0) * u128::from(other.size);
+        let rhs = u128::from(other.fee.0) * u128::from(self.size);
+        lhs.cmp(&rhs)
+    }
+}
+
+/// the entries held back by a selection because they spend from an account
+/// with a spending counter the simulated ledger has not reached yet. They are
+/// released as soon as the fragments using the previous counters are applied.
+struct Waiting<T> {
+    entries: Vec<T>,
+}
+
+impl<T: AsRef<PoolEntry>> Waiting<T> {
+    fn new() -> Self {
+        Waiting {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the entry back if it does not have to wait
+    fn hold(&mut self, ledger: &Ledger, entry: T) -> Option<T> {
+        if entry.as_ref().is_waiting(ledger) {
+            self.entries.push(entry);
+            None
+        } else {
+            Some(entry)
+        }
+    }
+
+    /// take the first of the held entries which no longer has to wait
+    fn take_ready(&mut self, ledger: &Ledger) -> Option<T> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| !entry.as_ref().is_waiting(ledger))?;
+        Some(self.entries.remove(index))
+    }
+
+    fn into_entries(self) -> Vec<T> {
+        self.entries
+    }
+}
+
+impl AsRef<PoolEntry> for PoolEntry {
+    fn as_ref(&self) -> &PoolEntry {
+        self
+    }
+}
+
+/// fee expected by the ledger for the given fragment, fragments which are
+/// not transactions do not pay any fee
+pub(super) fn fragment_fee(fees: &LinearFee, fragment: &Fragment) -> Value {
+    fn transaction_fee<P: Payload>(fees: &LinearFee, tx: &Transaction<P>) -> Value {
+        let tx = tx.as_slice();
+        fees.calculate(
+            tx.payload().into_certificate_slice(),
+            tx.nb_inputs(),
+            tx.nb_outputs(),
+        )
+    }
+
+    match fragment {
+        Fragment::Transaction(tx) => transaction_fee(fees, tx),
+        Fragment::OwnerStakeDelegation(tx) => transaction_fee(fees, tx),
+        Fragment::StakeDelegation(tx) => transaction_fee(fees, tx),
+        Fragment::PoolRegistration(tx) => transaction_fee(fees, tx),
+        Fragment::PoolRetirement(tx) => transaction_fee(fees, tx),
+        Fragment::PoolUpdate(tx) => transaction_fee(fees, tx),
+        Fragment::VotePlan(tx) => transaction_fee(fees, tx),
+        Fragment::VoteCast(tx) => transaction_fee(fees, tx),
+        Fragment::Initial(_)
+        | Fragment::OldUtxoDeclaration(_)
+        | Fragment::UpdateProposal(_)
+        | Fragment::UpdateVote(_) => Value::zero(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        blockcfg::Leadership,
+        fragment::pool::{
+            internal::Pool,
+            tests::{entry, ids, TestLedger},
+        },
+    };
+    use jormungandr_lib::interfaces::OriginMaxEntries;
+
+    fn size(fragment: &Fragment) -> u32 {
+        fragment.to_raw().size_bytes_plus_size() as u32
+    }
+
+    #[test]
+    fn fee_density_selects_the_highest_fee_per_byte_first() {
+        let test = TestLedger::new();
+        let mut parameters = Leadership::new(0, &test.ledger).ledger_parameters().clone();
+        // every input and output pays much more than the bytes it takes, so
+        // the transaction with more outputs pays more per byte
+        parameters.fees = LinearFee::new(0, 100, 0);
+
+        // received first, but paying less per byte
+        let low = test.transaction(1, 10, 200);
+        let high = test.transaction_to_outputs(0, &[10, 20], 300);
+        assert_eq!(fragment_fee(&parameters.fees, &low), Value(200));
+        assert_eq!(fragment_fee(&parameters.fees, &high), Value(300));
+        let fee_density = |fragment: &Fragment| FeeDensityEntry {
+            age: 0,
+            entry: entry(fragment, Vec::new()),
+            size: size(fragment),
+            fee: fragment_fee(&parameters.fees, fragment),
+        };
+        assert_eq!(
+            fee_density(&high).cmp_fee_density(&fee_density(&low)),
+            Ordering::Greater
+        );
+
+        // only one of the fragments fits in the block
+        parameters.block_content_max_size = size(&high);
+
+        let mut pool = Pool::new(10, OriginMaxEntries::default());
+        pool.insert(entry(&low, test.counters(1))).unwrap();
+        pool.insert(entry(&high, test.counters(0))).unwrap();
+        let mut logs = Logs::new(10);
+        let mut selection = FeeDensity::new();
+        selection.select(
+            &test.ledger,
+            &parameters,
+            BlockDate {
+                epoch: 0,
+                slot_id: 1,
+            },
+            &mut logs,
+            &mut pool,
+        );
+
+        let selected: Vec<FragmentId> = selection
+            .finalize()
+            .iter()
+            .map(|fragment| fragment.id())
+            .collect();
+        assert_eq!(selected, vec![high.id()]);
+        // the fragment which did not fit is put back for the next block
+        assert_eq!(ids(pool.entries()), vec![low.id()]);
+        assert_eq!(
+            pool.account_queue(&test.account())
+                .map(|(counter, _)| counter)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
     }
 }
@@ -3,19 +3,24 @@ use crate::blockcfg::{
 };
 use crate::blockchain::{Checkpoints, StorageError};
 use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-use crate::network::p2p::{comm::PeerInfo, Address};
+use crate::network::p2p::{comm::PeerInfo, Address, PeerEvent};
 use crate::utils::async_msg::{self, MessageBox, MessageQueue};
-use chain_impl_mockchain::fragment::Contents as FragmentContents;
+use chain_impl_mockchain::{account, fragment::Contents as FragmentContents};
 use chain_network::error as net_error;
-use jormungandr_lib::interfaces::{FragmentLog, FragmentOrigin, FragmentStatus};
+use jormungandr_lib::interfaces::{
+    AccountPendingFragment, FragmentLog, FragmentOrigin, FragmentStatus, FragmentSubmission,
+    PendingFragment,
+};
 
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
 use slog::Logger;
 use std::{
+    collections::HashMap,
     error,
     fmt::{self, Debug, Display},
     marker::PhantomData,
+    net::IpAddr,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -455,9 +460,33 @@ pub fn stream_request<T, R>(
 /// ...
 #[derive(Debug)]
 pub enum TransactionMsg {
-    SendTransaction(FragmentOrigin, Vec<Fragment>),
+    /// submit fragments to the pool, the reply handle gets the verdict on each
+    /// of the fragments once they are processed. The IP address of the REST
+    /// client or of the peer the fragments come from, if known, is the key of
+    /// the quota of the origin.
+    SendTransaction(
+        FragmentOrigin,
+        Option<IpAddr>,
+        Vec<Fragment>,
+        Option<ReplyHandle<Vec<FragmentSubmission>>>,
+    ),
     RemoveTransactions(Vec<FragmentId>, FragmentStatus),
     GetLogs(ReplyHandle<Vec<FragmentLog>>),
+    /// get the statuses of the given fragments, the fragments missing from the
+    /// logs are left out
+    GetStatuses(
+        Vec<FragmentId>,
+        ReplyHandle<HashMap<FragmentId, FragmentStatus>>,
+    ),
+    /// get the fragments waiting in the pool, optionally with their contents
+    GetPending(bool, ReplyHandle<Vec<PendingFragment>>),
+    GetFragments(Vec<FragmentId>, ReplyStreamHandle<Fragment>),
+    /// get the fragments of the pool spending from an account, in the order
+    /// of their spending counters
+    GetAccountPending(
+        account::Identifier,
+        ReplyHandle<Vec<AccountPendingFragment>>,
+    ),
     SelectTransactions {
         ledger: Ledger,
         block_date: BlockDate,
@@ -516,12 +545,13 @@ pub enum BlockMsg {
     LeadershipBlock(Block),
     /// A untrusted block Header has been received from the network task
     AnnouncedBlock(Header, Address),
-    /// A stream of untrusted blocks has been received from the network task.
-    NetworkBlocks(RequestStreamHandle<Block, ()>),
+    /// A stream of untrusted blocks has been received from the network task,
+    /// from the given node if it is known.
+    NetworkBlocks(RequestStreamHandle<Block, ()>, Option<Address>),
     /// The stream of headers for missing chain blocks has been received
     /// from the network in response to a PullHeaders request or a Missing
-    /// solicitation event.
-    ChainHeaders(RequestStreamHandle<Header, ()>),
+    /// solicitation event, from the given node if it is known.
+    ChainHeaders(RequestStreamHandle<Header, ()>, Option<Address>),
 }
 
 /// Propagation requests for the network task.
@@ -543,6 +573,8 @@ pub enum NetworkMsg {
         to: HeaderHash,
     },
     PeerInfo(ReplyHandle<Vec<PeerInfo>>),
+    /// update the reputation of the node
+    ReportPeer(Address, PeerEvent),
 }
 
 /// Messages to the explorer task
@@ -1,19 +1,52 @@
 pub use crate::secure::enclave::LeaderEvent;
 use crate::{
     blockcfg::{
-        HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
-        HeaderSetConsensusSignature, Leadership,
+        BlockDate, HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
+        HeaderSetConsensusSignature, Leadership, Ledger,
+    },
+    secure::{
+        enclave::{Enclave as SecureEnclave, SigningError},
+        signer::Error as SignerError,
+        signing_history::Error as SigningHistoryError,
     },
-    secure::enclave::Enclave as SecureEnclave,
 };
-use jormungandr_lib::interfaces::EnclaveLeaderId as LeaderId;
-use std::sync::Arc;
+use jormungandr_lib::interfaces::{EnclaveLeaderId as LeaderId, LeaderKesStatus};
+use std::{sync::Arc, time::SystemTime};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Error)]
+#[derive(Debug, Error)]
 pub enum EnclaveError {
     #[error("This leader {id} is not in the enclave")]
     NotInEnclave { id: LeaderId },
+    #[error("The external signer failed")]
+    ExternalSigner(#[from] SignerError),
+    #[error(
+        "The leader {id} already signed a block for {last_signed}, refusing to sign for {date}"
+    )]
+    AlreadySigned {
+        id: LeaderId,
+        date: BlockDate,
+        last_signed: BlockDate,
+    },
+    #[error("Cannot record the signing in the signing history")]
+    SigningHistory(#[source] SigningHistoryError),
+}
+
+impl EnclaveError {
+    fn signing(id: LeaderId, error: SigningError) -> Self {
+        match error {
+            SigningError::SigningHistory(SigningHistoryError::AlreadySigned {
+                date,
+                last_signed,
+            }) => EnclaveError::AlreadySigned {
+                id,
+                date,
+                last_signed,
+            },
+            SigningError::SigningHistory(error) => EnclaveError::SigningHistory(error),
+            SigningError::ExternalSigner(error) => EnclaveError::ExternalSigner(error),
+        }
+    }
 }
 
 /// represent the client side of an enclave. From there we will query the
@@ -21,10 +54,8 @@ pub enum EnclaveError {
 ///
 #[derive(Clone)]
 pub struct Enclave {
-    /// TODO: we will need to remove this to instead query data to an
-    /// external service (running in the secure enclave). For now we
-    /// hold on the `SecureEnclave` . But it will need to be separated
-    /// when we have the necessary crypto done.
+    /// the `SecureEnclave` either holds the leaders' keys or delegates
+    /// to an external signer holding them.
     inner: Arc<SecureEnclave>,
 }
 
@@ -40,22 +71,30 @@ impl Enclave {
     /// ask the enclave to attempt computing some leadership schedule for the
     /// given settings
     ///
-    /// TODO: for now we are utilizing the Leadership object fully but on the long
-    ///       run this might be limited to only the required data.
+    /// `leadership_ledger` is the ledger state the leadership was created
+    /// from, it is sent to the external signer to recreate the leadership.
     pub async fn query_schedules(
         &self,
         leadership: Arc<Leadership>,
+        leadership_ledger: Arc<Ledger>,
         slot_start: u32,
         nb_slots: u32,
     ) -> Result<Vec<LeaderEvent>, EnclaveError> {
         Ok(self
             .inner
-            .leadership_evaluate(&leadership, slot_start, nb_slots)
-            .await)
+            .leadership_evaluate(&leadership, &leadership_ledger, slot_start, nb_slots)
+            .await?)
+    }
+
+    /// ask the enclave for the state of the KES keys of its Genesis-Praos
+    /// leaders
+    pub async fn query_kes_status(&self, ledger: &Ledger, now: SystemTime) -> Vec<LeaderKesStatus> {
+        self.inner.kes_status(ledger, now).await
     }
 
     /// ask the leader associated to the `LeaderEvent` to finalize the given
-    /// block by providing the proof.
+    /// block by providing the proof. The enclave refuses if the leader
+    /// already signed a block for this date or a later one.
     ///
     /// TODO: for now we are querying the whole with the block builder but on the long
     ///       run we will only need the block signing data.
@@ -63,8 +102,14 @@ impl Enclave {
         &self,
         block_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
+        date: BlockDate,
     ) -> Result<HeaderBft, EnclaveError> {
-        if let Some(block) = self.inner.create_header_bft(block_builder, id).await {
+        if let Some(block) = self
+            .inner
+            .create_header_bft(block_builder, id, date)
+            .await
+            .map_err(|error| EnclaveError::signing(id, error))?
+        {
             Ok(block)
         } else {
             Err(EnclaveError::NotInEnclave { id })
@@ -72,7 +117,8 @@ impl Enclave {
     }
 
     /// ask the leader associated to the `LeaderEvent` to finalize the given
-    /// block by providing the proof.
+    /// block by providing the proof. The enclave refuses if the leader
+    /// already signed a block for this date or a later one.
     ///
     /// TODO: for now we are querying the whole with the block builder but on the long
     ///       run we will only need the block signing data.
@@ -80,11 +126,13 @@ impl Enclave {
         &self,
         block_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
+        date: BlockDate,
     ) -> Result<HeaderGenesisPraos, EnclaveError> {
         if let Some(block) = self
             .inner
-            .create_header_genesis_praos(block_builder, id)
+            .create_header_genesis_praos(block_builder, id, date)
             .await
+            .map_err(|error| EnclaveError::signing(id, error))?
         {
             Ok(block)
         } else {
@@ -1,5 +1,5 @@
 pub use jormungandr_lib::interfaces::LeadershipLogStatus;
-use jormungandr_lib::interfaces::{LeadershipLog, LeadershipLogId};
+use jormungandr_lib::interfaces::{LeadershipLog, LeadershipLogId, LeadershipScheduleEntry};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -68,6 +68,22 @@ impl Logs {
         })
     }
 
+    /// set the slots assigned to the leaders for the whole given epoch, as
+    /// computed by the leadership task at the beginning of the epoch
+    pub async fn set_schedule(&self, epoch: u32, schedule: Vec<LeadershipScheduleEntry>) {
+        self.0.write().await.set_schedule(epoch, schedule);
+    }
+
+    /// the epoch scheduled by the leadership task and the slots assigned to
+    /// the leaders for this epoch. `None` until the first epoch is scheduled.
+    pub async fn schedule(&self) -> Option<(u32, Vec<LeadershipScheduleEntry>)> {
+        self.0
+            .read()
+            .await
+            .schedule()
+            .map(|(epoch, schedule)| (epoch, schedule.to_vec()))
+    }
+
     async fn mark_wake(&self, leadership_log_id: LeadershipLogId) {
         let inner = self.0.clone();
         inner.write().await.mark_wake(&leadership_log_id.into());
@@ -94,20 +110,32 @@ impl Logs {
 }
 
 pub(super) mod internal {
-    use super::{LeadershipLog, LeadershipLogId, LeadershipLogStatus};
+    use super::{LeadershipLog, LeadershipLogId, LeadershipLogStatus, LeadershipScheduleEntry};
     use lru::LruCache;
 
     pub struct Logs {
         entries: LruCache<LeadershipLogId, LeadershipLog>,
+        schedule: Option<(u32, Vec<LeadershipScheduleEntry>)>,
     }
 
     impl Logs {
         pub fn new(cap: usize) -> Self {
             Logs {
                 entries: LruCache::new(cap),
+                schedule: None,
             }
         }
 
+        pub fn set_schedule(&mut self, epoch: u32, schedule: Vec<LeadershipScheduleEntry>) {
+            self.schedule = Some((epoch, schedule));
+        }
+
+        pub fn schedule(&self) -> Option<(u32, &[LeadershipScheduleEntry])> {
+            self.schedule
+                .as_ref()
+                .map(|(epoch, schedule)| (*epoch, schedule.as_slice()))
+        }
+
         pub fn insert(&mut self, log: LeadershipLog) -> LeadershipLogId {
             let id = log.leadership_log_id();
 
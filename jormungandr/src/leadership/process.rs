@@ -4,6 +4,7 @@ use crate::{
         Ledger, LedgerParameters,
     },
     blockchain::{new_epoch_leadership_from, Ref, Tip},
+    fragment::selection::FragmentSelectionAlgorithmParams,
     intercom::{unary_reply, BlockMsg, Error as IntercomError, TransactionMsg},
     leadership::{
         enclave::{Enclave, EnclaveError, LeaderEvent},
@@ -17,7 +18,7 @@ use chain_time::{
 };
 use futures::{future::TryFutureExt, sink::SinkExt};
 use jormungandr_lib::{
-    interfaces::{LeadershipLog, LeadershipLogStatus},
+    interfaces::{LeadershipLog, LeadershipLogStatus, LeadershipScheduleEntry},
     time::SystemTime,
 };
 use slog::Logger;
@@ -25,6 +26,10 @@ use std::{collections::VecDeque, sync::Arc, time::Instant};
 use thiserror::Error;
 use tokio::time::{delay_until, timeout_at, Instant as TokioInstant};
 
+/// the leaders whose KES key expires in less than this duration are reported
+/// at every new schedule
+const KES_EXPIRY_WARNING: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 3600);
+
 #[derive(Error, Debug)]
 pub enum LeadershipError {
     #[error("The blockchain Timeline hasn't started yet")]
@@ -72,6 +77,7 @@ pub struct Module {
     pool: MessageBox<TransactionMsg>,
     enclave: Enclave,
     block_message: MessageBox<BlockMsg>,
+    selection_alg: FragmentSelectionAlgorithmParams,
 }
 
 impl Module {
@@ -82,6 +88,7 @@ impl Module {
         pool: MessageBox<TransactionMsg>,
         enclave: Enclave,
         block_message: MessageBox<BlockMsg>,
+        selection_alg: FragmentSelectionAlgorithmParams,
     ) -> Result<Self, LeadershipError> {
         let tip_ref = tip.get_ref().await;
 
@@ -94,6 +101,7 @@ impl Module {
             pool,
             enclave,
             block_message,
+            selection_alg,
         })
     }
 
@@ -422,10 +430,18 @@ impl Module {
             return Ok(());
         };
 
-        let contents =
-            prepare_block(pool, event.date, ledger, ledger_parameters, logger.clone()).await?;
+        let contents = prepare_block(
+            pool,
+            event.date,
+            ledger,
+            ledger_parameters,
+            self.selection_alg,
+            logger.clone(),
+        )
+        .await?;
 
         let event_logs_error = event_logs.clone();
+        let logger_error = logger.clone();
         let signing = {
             let ver = match event.output {
                 LeaderOutput::None => BlockVersion::Genesis,
@@ -451,7 +467,7 @@ impl Module {
                         .expect("Valid Header Builder")
                         .set_consensus_data(&leader_id);
                     enclave
-                        .query_header_bft_finalize(final_builder, event.id)
+                        .query_header_bft_finalize(final_builder, event.id, event.date)
                         .map_ok(|h| {
                             Some(Block {
                                 header: h.generalize(),
@@ -459,11 +475,7 @@ impl Module {
                             })
                         })
                         .or_else(|e| async move {
-                            event_logs_error
-                                .set_status(LeadershipLogStatus::Rejected {
-                                    reason: format!("Cannot sign the block: {}", e),
-                                })
-                                .await;
+                            signing_failed(event_logs_error, e, &logger_error).await;
                             Ok(None)
                         })
                         .await
@@ -474,7 +486,7 @@ impl Module {
                         .expect("Valid Header Builder")
                         .set_consensus_data(&node_id, &vrfproof.into());
                     enclave
-                        .query_header_genesis_praos_finalize(final_builder, event.id)
+                        .query_header_genesis_praos_finalize(final_builder, event.id, event.date)
                         .map_ok(|h| {
                             Some(Block {
                                 header: h.generalize(),
@@ -482,11 +494,7 @@ impl Module {
                             })
                         })
                         .or_else(|e| async move {
-                            event_logs_error
-                                .set_status(LeadershipLogStatus::Rejected {
-                                    reason: format!("Cannot sign the block: {}", e),
-                                })
-                                .await;
+                            signing_failed(event_logs_error, e, &logger_error).await;
                             Ok(None)
                         })
                         .await
@@ -529,41 +537,34 @@ impl Module {
             "current_slot" => current_slot_position.slot.0,
         ));
 
+        self.check_kes_status(&logger).await;
+
         if epoch_tip < current_slot_position.epoch {
-            let (_, leadership, _, _, _, _) = new_epoch_leadership_from(
+            let (_, leadership, leadership_ledger, _, _, _, _) = new_epoch_leadership_from(
                 current_slot_position.epoch.0,
                 Arc::clone(&self.tip_ref),
                 false,
             );
 
             let slot_start = current_slot_position.slot.0 + 1;
-            let nb_slots = leadership.era().slots_per_epoch() - slot_start;
-            let running_ref = leadership;
 
             debug!(logger, "scheduling events" ;
                 "slot_start" => slot_start,
-                "nb_slots" => nb_slots,
             );
 
-            self.action_run_schedule(running_ref, slot_start, nb_slots)
+            self.action_run_schedule(leadership, leadership_ledger, slot_start)
                 .await
         } else if epoch_tip == current_slot_position.epoch {
             // check for current epoch
             let slot_start = current_slot_position.slot.0 + 1;
-            let nb_slots = self
-                .tip_ref
-                .epoch_leadership_schedule()
-                .era()
-                .slots_per_epoch()
-                - slot_start;
             let running_ref = Arc::clone(self.tip_ref.epoch_leadership_schedule());
+            let leadership_ledger = Arc::clone(self.tip_ref.epoch_leadership_ledger());
 
             debug!(logger, "scheduling events" ;
                 "slot_start" => slot_start,
-                "nb_slots" => nb_slots,
             );
 
-            self.action_run_schedule(running_ref, slot_start, nb_slots)
+            self.action_run_schedule(running_ref, leadership_ledger, slot_start)
                 .await
         } else {
             // The only reason this would happen is if we had accepted a block
@@ -577,23 +578,85 @@ impl Module {
         }
     }
 
+    /// warn about the KES keys of the leaders expiring soon, expired or not
+    /// matching the key registered for their stake pool
+    async fn check_kes_status(&self, logger: &Logger) {
+        let now = std::time::SystemTime::now();
+        let statuses = self
+            .enclave
+            .query_kes_status(&self.tip_ref.ledger(), now)
+            .await;
+        for status in statuses {
+            let logger = logger.new(o!(
+                "leader_id" => status.enclave_leader_id().to_string(),
+                "pool_id" => status.pool_id().to_owned(),
+            ));
+            let (remaining_evolutions, expiry_time) =
+                match (status.remaining_evolutions(), status.expiry_time()) {
+                    (Some(remaining_evolutions), Some(expiry_time)) => {
+                        (remaining_evolutions, *expiry_time.as_ref())
+                    }
+                    _ => {
+                        warn!(logger, "the stake pool of the leader is not registered");
+                        continue;
+                    }
+                };
+            if !status.key_registered() {
+                warn!(
+                    logger,
+                    "the KES key of the leader is not the one registered for its stake pool"
+                );
+            }
+            match expiry_time.duration_since(now) {
+                Err(_) => error!(logger, "the KES key of the leader has expired"),
+                Ok(remaining) if remaining < KES_EXPIRY_WARNING => warn!(
+                    logger,
+                    "the KES key of the leader expires soon";
+                    "expiry_time" => %SystemTime::from(expiry_time),
+                    "remaining_evolutions" => remaining_evolutions,
+                ),
+                Ok(_) => debug!(
+                    logger,
+                    "KES key status";
+                    "period" => status.period(),
+                    "expiry_time" => %SystemTime::from(expiry_time),
+                    "remaining_evolutions" => remaining_evolutions,
+                ),
+            }
+        }
+    }
+
+    /// evaluate the leadership of the whole epoch, so the schedule served
+    /// through the REST API includes the slots already passed, and only
+    /// queue the events from `slot_start` onward
     async fn action_run_schedule(
         self,
         leadership: Arc<Leadership>,
+        leadership_ledger: Arc<Ledger>,
         slot_start: u32,
-        nb_slots: u32,
     ) -> Result<Self, LeadershipError> {
+        let scheduled_epoch = leadership.date_at_slot(0).epoch;
+        let nb_slots = leadership.era().slots_per_epoch();
         let schedules = self
             .enclave
-            .query_schedules(leadership, slot_start, nb_slots)
+            .query_schedules(leadership, leadership_ledger, 0, nb_slots)
             .map_err(|e| LeadershipError::CannotScheduleWithEnclave { source: e })
             .await?;
 
         let mut module = self;
+        let mut schedule_entries = Vec::with_capacity(schedules.len());
         for schedule in schedules.into_iter() {
             let epoch = Epoch(schedule.date.epoch);
             let slot = EpochSlotOffset(schedule.date.slot_id);
             let scheduled_at_time = module.slot_time(epoch, slot);
+            schedule_entries.push(LeadershipScheduleEntry::new(
+                schedule.id,
+                schedule.date.into(),
+                scheduled_at_time,
+            ));
+            if schedule.date.slot_id < slot_start {
+                continue;
+            }
             let log = LeadershipLog::new(schedule.id, schedule.date.into(), scheduled_at_time);
 
             match module.logs.insert(log).await {
@@ -604,6 +667,10 @@ impl Module {
                 Err(()) => return Err(LeadershipError::CannotUpdateLogs),
             }
         }
+        module
+            .logs
+            .set_schedule(scheduled_epoch, schedule_entries)
+            .await;
 
         Ok(module)
     }
@@ -636,17 +703,16 @@ async fn prepare_block(
     block_date: BlockDate,
     ledger: Arc<Ledger>,
     epoch_parameters: Arc<LedgerParameters>,
+    selection_alg: FragmentSelectionAlgorithmParams,
     logger: Logger,
 ) -> Result<Contents, LeadershipError> {
-    use crate::fragment::selection::FragmentSelectionAlgorithmParams;
-
     let (reply_handle, reply_future) = unary_reply(logger.clone());
 
     let msg = TransactionMsg::SelectTransactions {
         ledger: ledger.as_ref().clone(),
         block_date,
         ledger_params: epoch_parameters.as_ref().clone(),
-        selection_alg: FragmentSelectionAlgorithmParams::OldestFirst,
+        selection_alg,
         reply_handle,
     };
 
@@ -661,6 +727,28 @@ async fn prepare_block(
     }
 }
 
+/// update the leadership log of an event the enclave did not sign the block
+/// of. A refusal from the signing history is logged as critical: the node,
+/// or another one with the same keys, already signed a block for this slot.
+async fn signing_failed(event_logs: LeadershipLogHandle, error: EnclaveError, logger: &Logger) {
+    let status = match &error {
+        EnclaveError::AlreadySigned { last_signed, .. } => {
+            crit!(
+                logger,
+                "refused to sign a second block for a slot, is another node running with the same leader keys?";
+                "reason" => %error,
+            );
+            LeadershipLogStatus::SigningRefused {
+                last_signed: (*last_signed).into(),
+            }
+        }
+        _ => LeadershipLogStatus::Rejected {
+            reason: format!("Cannot sign the block: {}", error),
+        },
+    };
+    event_logs.set_status(status).await;
+}
+
 fn too_late(now: SystemTime, event_end: SystemTime) -> bool {
     event_end <= now
 }
@@ -22,8 +22,8 @@ use crate::{
     blockcfg::{HeaderHash, Leader},
     blockchain::Blockchain,
     diagnostic::Diagnostic,
-    network::p2p::P2pTopology,
-    secure::enclave::Enclave,
+    network::p2p::{P2pTopology, PeerTable},
+    secure::{enclave::Enclave, signer::ExternalSigner, signing_history::SigningHistory},
     settings::start::Settings,
     utils::{async_msg, task::Services},
 };
@@ -73,6 +73,8 @@ pub struct BootstrappedNode {
     logger: Logger,
     explorer_db: Option<explorer::ExplorerDB>,
     rest_context: Option<rest::ContextLock>,
+    stats_counter: StatsCounter,
+    peer_table: Option<PeerTable>,
     services: Services,
 }
 
@@ -83,6 +85,65 @@ const EXPLORER_TASK_QUEUE_LEN: usize = 32;
 const CLIENT_TASK_QUEUE_LEN: usize = 32;
 const BOOTSTRAP_RETRY_WAIT: Duration = Duration::from_secs(5);
 
+/// load the leaders' secrets, or connect to the external signer holding them
+fn load_enclave(
+    settings: &Settings,
+    block0_hash: HeaderHash,
+    logger: &Logger,
+) -> Result<Enclave, start_up::Error> {
+    let enclave = if let Some(path) = &settings.leadership.external_signer {
+        let external_signer = ExternalSigner::connect(path)?;
+        info!(
+            logger,
+            "connected to the external signer";
+            "path" => %path.display(),
+            "leaders" => external_signer.leader_ids().len(),
+        );
+        Enclave::with_external_signer(external_signer)
+    } else {
+        let leader_secrets: Result<Vec<Leader>, start_up::Error> = settings
+            .secrets
+            .iter()
+            .map(|secret_path| {
+                let secret = secure::NodeSecret::load_from_file(secret_path.as_path())?;
+                Ok(Leader {
+                    bft_leader: secret.bft(),
+                    genesis_leader: secret.genesis(),
+                })
+            })
+            .collect();
+        block_on(Enclave::from_vec(leader_secrets?))
+    };
+
+    Ok(enclave.with_signing_history(load_signing_history(settings, block0_hash, logger)?))
+}
+
+fn load_signing_history(
+    settings: &Settings,
+    block0_hash: HeaderHash,
+    logger: &Logger,
+) -> Result<SigningHistory, start_up::Error> {
+    let mut signing_history = match settings.storage.as_ref() {
+        Some(dir) => SigningHistory::open(dir, block0_hash, logger)?,
+        None => {
+            warn!(
+                logger,
+                "the signing history is kept in memory only, no storage directory is set: a restarted node may sign twice for the same slot"
+            );
+            SigningHistory::in_memory()
+        }
+    };
+    if settings.reset_signing_history && signing_history.nb_leaders() > 0 {
+        crit!(
+            logger,
+            "forgetting the slots signed by the leaders, the node may sign a second block for a slot already signed";
+            "leaders" => signing_history.nb_leaders(),
+        );
+        signing_history.reset()?;
+    }
+    Ok(signing_history)
+}
+
 fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::Error> {
     if let Some(context) = bootstrapped_node.rest_context.as_ref() {
         block_on(async {
@@ -111,15 +172,26 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
             .logger
             .new(o!(log::KEY_TASK => "poldercast")),
     );
+    if let Some(peer_table) = bootstrapped_node.peer_table.as_ref() {
+        topology.restore_quarantine_records(&peer_table.load()?);
+    }
 
-    let stats_counter = StatsCounter::default();
+    let stats_counter = bootstrapped_node.stats_counter;
 
     {
         let stats_counter = stats_counter.clone();
+        let logs = load_fragment_logs(
+            &bootstrapped_node.settings,
+            bootstrapped_node.block0_hash,
+            &bootstrapped_node.logger,
+        )?;
         let process = fragment::Process::new(
             bootstrapped_node.settings.mempool.pool_max_entries.into(),
-            bootstrapped_node.settings.mempool.log_max_entries.into(),
+            logs,
+            bootstrapped_node.settings.mempool.fragment_ttl,
+            bootstrapped_node.settings.mempool.origin_max_entries,
             network_msgbox.clone(),
+            blockchain_tip.clone(),
         );
 
         services.spawn_try_future("fragment", move |info| {
@@ -190,6 +262,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         bootstrapped_node.settings.network.clone(),
         topology,
         stats_counter.clone(),
+        bootstrapped_node.peer_table,
         bootstrapped_node
             .logger
             .new(o!(crate::log::KEY_TASK => "network")),
@@ -215,20 +288,11 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         });
     }
 
-    let leader_secrets: Result<Vec<Leader>, start_up::Error> = bootstrapped_node
-        .settings
-        .secrets
-        .iter()
-        .map(|secret_path| {
-            let secret = secure::NodeSecret::load_from_file(secret_path.as_path())?;
-            Ok(Leader {
-                bft_leader: secret.bft(),
-                genesis_leader: secret.genesis(),
-            })
-        })
-        .collect();
-    let leader_secrets = leader_secrets?;
-    let enclave = block_on(Enclave::from_vec(leader_secrets));
+    let enclave = load_enclave(
+        &bootstrapped_node.settings,
+        bootstrapped_node.block0_hash,
+        &bootstrapped_node.logger,
+    )?;
 
     {
         let leadership_logs = leadership_logs.clone();
@@ -236,6 +300,9 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         let blockchain_tip = blockchain_tip.clone();
         let enclave = leadership::Enclave::new(enclave.clone());
         let fragment_msgbox = fragment_msgbox.clone();
+        let selection_alg = fragment::selection::FragmentSelectionAlgorithmParams::from(
+            bootstrapped_node.settings.mempool.fragment_selection,
+        );
 
         services.spawn_try_future("leadership", move |info| {
             let fut = leadership::Module::new(
@@ -245,6 +312,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 fragment_msgbox,
                 enclave,
                 block_msgbox,
+                selection_alg,
             )
             .and_then(|module| module.run())
             .map_err(|e| {
@@ -339,6 +407,8 @@ fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, star
         block0_hash,
         explorer_db,
         rest_context,
+        stats_counter,
+        peer_table,
         settings,
     } = services.block_on_task("bootstrap", |info| {
         bootstrap_internal(
@@ -358,6 +428,8 @@ fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, star
         logger,
         explorer_db,
         rest_context,
+        stats_counter,
+        peer_table,
         services,
     })
 }
@@ -368,6 +440,8 @@ struct BootstrapData {
     block0_hash: HeaderHash,
     explorer_db: Option<explorer::ExplorerDB>,
     rest_context: Option<rest::ContextLock>,
+    stats_counter: StatsCounter,
+    peer_table: Option<PeerTable>,
     settings: Settings,
 }
 
@@ -399,11 +473,18 @@ async fn bootstrap_internal(
 
     let cache_capacity = 102_400;
 
+    // ledger snapshots are only kept along a persistent storage
+    let ledger_snapshots = settings
+        .storage
+        .as_ref()
+        .map(|dir| blockchain::LedgerSnapshots::in_storage_dir(dir));
+
     let (blockchain, blockchain_tip) = start_up::load_blockchain(
         block0,
         storage,
         cache_capacity,
         settings.rewards_report_all,
+        ledger_snapshots,
         &logger,
     )
     .await?;
@@ -416,6 +497,22 @@ async fn bootstrap_internal(
         }
     });
 
+    let stats_counter = StatsCounter::default();
+
+    let peer_table = load_peer_table(&settings, block0_hash, &logger)?;
+    let known_peers = match peer_table.as_ref() {
+        Some(peer_table) => {
+            let known_peers = peer_table.load()?;
+            info!(
+                logger,
+                "{} peers known from the previous runs of the node",
+                known_peers.len()
+            );
+            known_peers
+        }
+        None => Vec::new(),
+    };
+
     let mut bootstrap_attempt: usize = 0;
 
     let (shutdown_tx, shutdown_rx) = channel();
@@ -450,9 +547,11 @@ async fn bootstrap_internal(
         // Will return true if we successfully bootstrap or there are no trusted peers defined.
         if network::bootstrap(
             &settings.network,
+            &known_peers,
             blockchain.clone(),
             blockchain_tip.clone(),
             shutdown_rx.clone(),
+            stats_counter.clone(),
             &logger,
         )
         .await?
@@ -483,7 +582,13 @@ async fn bootstrap_internal(
 
     let explorer_db = if settings.explorer {
         match select(
-            explorer::ExplorerDB::bootstrap(block0_explorer, &blockchain).boxed(),
+            explorer::ExplorerDB::bootstrap(
+                block0_explorer,
+                &blockchain,
+                settings.explorer_storage.clone(),
+                logger.new(o!(log::KEY_TASK => "explorer")),
+            )
+            .boxed(),
             shutdown_rx,
         )
         .await
@@ -504,10 +609,56 @@ async fn bootstrap_internal(
         blockchain_tip,
         explorer_db,
         rest_context,
+        stats_counter,
+        peer_table,
         settings,
     })
 }
 
+/// the peers saved by a previous run of the node, if there is a storage
+/// directory to save them in
+fn load_peer_table(
+    settings: &Settings,
+    block0_hash: HeaderHash,
+    logger: &Logger,
+) -> Result<Option<PeerTable>, start_up::Error> {
+    match settings.storage.as_ref() {
+        Some(dir) => Ok(Some(PeerTable::open(dir, block0_hash, logger)?)),
+        None => Ok(None),
+    }
+}
+
+/// the fragment logs, loaded from the storage directory if they are persistent
+fn load_fragment_logs(
+    settings: &Settings,
+    block0_hash: HeaderHash,
+    logger: &Logger,
+) -> Result<fragment::Logs, start_up::Error> {
+    let mempool = &settings.mempool;
+    let max_entries = mempool.log_max_entries.into();
+    if !mempool.persistent_logs {
+        return Ok(fragment::Logs::new(max_entries));
+    }
+    match settings.storage.as_ref() {
+        Some(dir) => {
+            let persistent = fragment::PersistentLogs::open(
+                dir,
+                block0_hash,
+                max_entries,
+                logger.new(o!(log::KEY_TASK => "fragment")),
+            )?;
+            Ok(fragment::Logs::persistent(max_entries, persistent)?)
+        }
+        None => {
+            warn!(
+                logger,
+                "the fragment logs are kept in memory only, no storage directory is set"
+            );
+            Ok(fragment::Logs::new(max_entries))
+        }
+    }
+}
+
 pub struct InitializedNode {
     pub settings: Settings,
     pub block0: blockcfg::Block,
@@ -1,22 +1,28 @@
+use super::convert::{Decode, Encode};
 use super::grpc;
 use crate::blockcfg::{Block, HeaderDesc, HeaderHash};
 use crate::blockchain::{self, Blockchain, Error as BlockchainError, PreCheckedHeader, Ref, Tip};
+use crate::network::tls::TlsConfig;
 use crate::settings::start::network::Peer;
+use crate::stats_counter::StatsCounter;
 use chain_core::property::{Deserialize, HasHeader};
 use chain_network::data as net_data;
 use chain_network::error::Error as NetworkError;
 use futures::{
     channel::oneshot::Receiver,
-    future::{Either, Shared},
+    future::{self, Either, Shared},
     prelude::*,
     stream,
     task::Poll,
 };
 use slog::Logger;
+use tokio::time;
 
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -26,10 +32,25 @@ pub enum Error {
     ClientNotReady(#[source] NetworkError),
     #[error("peers not available")]
     PeersNotAvailable(#[source] NetworkError),
+    #[error("failed to get the tip of the bootstrap peer")]
+    TipRequestFailed(#[source] NetworkError),
     #[error("bootstrap pull request failed")]
     PullRequestFailed(#[source] NetworkError),
     #[error("bootstrap pull stream failed")]
     PullStreamFailed(#[source] NetworkError),
+    #[error("received header {0} does not follow the previous header")]
+    HeaderChainBroken(HeaderHash),
+    #[error("block range request failed")]
+    GetBlocksFailed(#[source] NetworkError),
+    #[error("block range download stalled")]
+    BlockRangeStalled,
+    #[error("received {received} blocks of a range of {expected}")]
+    BlockRangeIncomplete { expected: usize, received: usize },
+    #[error("received block {received} instead of {expected}")]
+    UnexpectedBlock {
+        expected: HeaderHash,
+        received: HeaderHash,
+    },
     #[error("decoding of a block failed")]
     BlockDecodingFailed(#[source] <Block as Deserialize>::Error),
     #[error("block header check failed")]
@@ -52,13 +73,27 @@ pub enum Error {
 
 const MAX_BOOTSTRAP_PEERS: u32 = 32;
 
-pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<Peer>, Error> {
+/// number of blocks requested at once from a peer during bootstrap
+const BLOCK_RANGE_SIZE: usize = 500;
+
+/// number of block ranges requested at a time from each peer
+const RANGES_IN_FLIGHT_PER_PEER: usize = 2;
+
+/// a peer not delivering a block range in this time is considered stalled
+/// and the range is requested from another peer
+const BLOCK_RANGE_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+pub async fn peers_from_trusted_peer(
+    peer: &Peer,
+    tls: Option<&TlsConfig>,
+    logger: Logger,
+) -> Result<Vec<Peer>, Error> {
     info!(
         logger,
         "getting peers from bootstrap peer {}", peer.connection
     );
 
-    let mut client = grpc::connect(&peer).await.map_err(Error::Connect)?;
+    let mut client = grpc::connect(&peer, tls).await.map_err(Error::Connect)?;
     let peers = client
         .peers(MAX_BOOTSTRAP_PEERS)
         .await
@@ -74,11 +109,18 @@ pub async fn peers_from_trusted_peer(peer: &Peer, logger: Logger) -> Result<Vec<
     Ok(peers)
 }
 
+/// Bootstraps from the peer: the headers of the missing blocks are pulled
+/// from it, then the blocks are downloaded by ranges from this peer and the
+/// `block_peers` concurrently, and applied in order. A range a peer fails to
+/// deliver in time is requested from the next one.
 pub async fn bootstrap_from_peer(
     peer: &Peer,
+    block_peers: &[Peer],
+    tls: Option<&TlsConfig>,
     blockchain: Blockchain,
     tip: Tip,
     bootstrap_stopper: Shared<Receiver<()>>,
+    stats_counter: StatsCounter,
     logger: Logger,
 ) -> Result<(), Error> {
     use futures::future::select;
@@ -89,27 +131,55 @@ pub async fn bootstrap_from_peer(
     let tip1 = tip.clone();
     let logger1 = logger.clone();
 
-    let stream_future = async move {
-        let mut client = grpc::connect(&peer).await.map_err(|e| Error::Connect(e))?;
-
-        let checkpoints = blockchain1.get_checkpoints(tip1.branch()).await;
-        let checkpoints = net_data::block::try_ids_from_iter(checkpoints).unwrap();
-
-        info!(
-            logger1,
-            "pulling blocks starting from checkpoints: {:?}", checkpoints
-        );
-
-        client
-            .pull_blocks_to_tip(checkpoints)
+    let download_future = async move {
+        let mut client = grpc::connect(&peer, tls)
             .await
-            .map_err(|e| Error::PullRequestFailed(e))
+            .map_err(|e| Error::Connect(e))?;
+
+        let block_ids = pull_block_ids(&mut client, &blockchain1, &tip1, &logger1).await?;
+
+        let mut download_peers = vec![(peer.connection, client)];
+        let block_peers = block_peers
+            .iter()
+            .filter(|block_peer| block_peer.connection != peer.connection);
+        let connections = future::join_all(block_peers.map(|block_peer| async move {
+            (block_peer.connection, grpc::connect(block_peer, tls).await)
+        }))
+        .await;
+        for (addr, connection) in connections {
+            match connection {
+                Ok(client) => download_peers.push((addr, client)),
+                Err(e) => {
+                    info!(logger1, "unable to reach peer for block download"; "download_peer" => %addr, "reason" => %e)
+                }
+            }
+        }
+
+        Ok((block_ids, download_peers))
     };
 
-    // process a signal from the stopper if it arrives before the stream is ready
-    match select(stream_future.boxed(), bootstrap_stopper).await {
-        Either::Left((stream_result, bootstrap_stopper)) => {
-            bootstrap_from_stream(blockchain, tip, stream_result?, bootstrap_stopper, logger).await
+    // process a signal from the stopper if it arrives before the download starts
+    match select(download_future.boxed(), bootstrap_stopper).await {
+        Either::Left((download_result, bootstrap_stopper)) => {
+            let (block_ids, download_peers) = download_result?;
+            info!(
+                logger,
+                "downloading {} blocks from {} peers",
+                block_ids.len(),
+                download_peers.len()
+            );
+            let nb_blocks = block_ids.len() as u64;
+            let stream = download_blocks(block_ids, download_peers, logger.clone());
+            bootstrap_from_stream(
+                blockchain,
+                tip,
+                stream,
+                nb_blocks,
+                bootstrap_stopper,
+                stats_counter,
+                logger,
+            )
+            .await
         }
         Either::Right((bootstrap_stopper_result, _)) => match bootstrap_stopper_result {
             Ok(()) => Err(Error::Interrupted),
@@ -118,16 +188,159 @@ pub async fn bootstrap_from_peer(
     }
 }
 
+/// Returns the hashes of the blocks from our checkpoints to the tip of the
+/// peer, in chain order.
+async fn pull_block_ids(
+    client: &mut grpc::Client,
+    blockchain: &Blockchain,
+    tip: &Tip,
+    logger: &Logger,
+) -> Result<Vec<HeaderHash>, Error> {
+    let peer_tip = client.tip().await.map_err(Error::TipRequestFailed)?;
+    let peer_tip = peer_tip.decode().map_err(Error::TipRequestFailed)?;
+
+    let checkpoints = blockchain.get_checkpoints(tip.branch()).await;
+    let checkpoints = net_data::block::try_ids_from_iter(checkpoints).unwrap();
+
+    info!(
+        logger,
+        "pulling headers up to {} starting from checkpoints: {:?}",
+        peer_tip.description(),
+        checkpoints
+    );
+
+    let block0 = blockchain.block0().clone();
+    let mut headers = client
+        .pull_headers(checkpoints, peer_tip.hash().encode())
+        .await
+        .map_err(Error::PullRequestFailed)?;
+
+    let mut block_ids = Vec::new();
+    while let Some(header) = headers.next().await {
+        let header = header
+            .and_then(|header| header.decode())
+            .map_err(Error::PullStreamFailed)?;
+        let hash = header.hash();
+        if hash == block0 {
+            continue;
+        }
+        if let Some(previous) = block_ids.last() {
+            if header.block_parent_hash() != *previous {
+                return Err(Error::HeaderChainBroken(hash));
+            }
+        }
+        block_ids.push(hash);
+    }
+    Ok(block_ids)
+}
+
+/// Downloads the blocks by ranges, with up to `RANGES_IN_FLIGHT_PER_PEER`
+/// ranges requested from each peer at a time. The blocks are returned in
+/// the order of the given hashes.
+fn download_blocks(
+    block_ids: Vec<HeaderHash>,
+    download_peers: Vec<(SocketAddr, grpc::Client)>,
+    logger: Logger,
+) -> impl Stream<Item = Result<Block, Error>> {
+    let nb_in_flight = download_peers.len() * RANGES_IN_FLIGHT_PER_PEER;
+    let download_peers = Arc::new(download_peers);
+    let ranges: Vec<Vec<HeaderHash>> = block_ids
+        .chunks(BLOCK_RANGE_SIZE)
+        .map(|range| range.to_vec())
+        .collect();
+
+    stream::iter(ranges.into_iter().enumerate())
+        .map(move |(index, range)| {
+            // the ranges are downloaded in the background while the blocks
+            // already received are applied, the download is cancelled if
+            // the handle is dropped
+            let (download, handle) =
+                download_range(index, range, Arc::clone(&download_peers), logger.clone())
+                    .remote_handle();
+            tokio::spawn(download);
+            handle
+        })
+        .buffered(nb_in_flight)
+        .map_ok(|blocks| stream::iter(blocks.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+/// Downloads a range of blocks from the peer it is assigned to, or from the
+/// next peers in turn if the download fails or stalls.
+async fn download_range(
+    index: usize,
+    range: Vec<HeaderHash>,
+    download_peers: Arc<Vec<(SocketAddr, grpc::Client)>>,
+    logger: Logger,
+) -> Result<Vec<Block>, Error> {
+    let mut last_error = Error::BlockRangeStalled;
+    for attempt in 0..download_peers.len() {
+        let (addr, client) = &download_peers[(index + attempt) % download_peers.len()];
+        let mut client = client.clone();
+        match time::timeout(BLOCK_RANGE_TIMEOUT, fetch_range(&mut client, &range)).await {
+            Ok(Ok(blocks)) => return Ok(blocks),
+            Ok(Err(e)) => {
+                warn!(logger, "block range download failed, trying another peer"; "download_peer" => %addr, "range" => index, "error" => ?e);
+                last_error = e;
+            }
+            Err(_) => {
+                warn!(logger, "block range download stalled, trying another peer"; "download_peer" => %addr, "range" => index);
+                last_error = Error::BlockRangeStalled;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+async fn fetch_range(client: &mut grpc::Client, range: &[HeaderHash]) -> Result<Vec<Block>, Error> {
+    let ids = net_data::block::try_ids_from_iter(range.iter().cloned()).unwrap();
+    let blocks: Vec<net_data::Block> = client
+        .get_blocks(ids)
+        .await
+        .map_err(Error::GetBlocksFailed)?
+        .try_collect()
+        .await
+        .map_err(Error::PullStreamFailed)?;
+    decode_range(&blocks, range)
+}
+
+/// decode the blocks received for the range, which must be the blocks of the
+/// range in the same order
+fn decode_range(blocks: &[net_data::Block], range: &[HeaderHash]) -> Result<Vec<Block>, Error> {
+    if blocks.len() != range.len() {
+        return Err(Error::BlockRangeIncomplete {
+            expected: range.len(),
+            received: blocks.len(),
+        });
+    }
+    blocks
+        .iter()
+        .zip(range)
+        .map(|(block, expected)| {
+            let block = Block::deserialize(block.as_bytes()).map_err(Error::BlockDecodingFailed)?;
+            let received = block.header.hash();
+            if received != *expected {
+                return Err(Error::UnexpectedBlock {
+                    expected: *expected,
+                    received,
+                });
+            }
+            Ok(block)
+        })
+        .collect()
+}
+
 struct BootstrapInfo {
     last_reported: std::time::SystemTime,
     last_bytes_received: u64,
     bytes_received: u64,
     block_received: u64,
+    block_expected: u64,
     last_block_description: Option<HeaderDesc>,
 }
 
 impl BootstrapInfo {
-    pub fn new() -> Self {
+    pub fn new(block_expected: u64) -> Self {
         let now = std::time::SystemTime::now();
         let lbd: Option<HeaderDesc> = None;
         BootstrapInfo {
@@ -135,6 +348,7 @@ impl BootstrapInfo {
             last_bytes_received: 0,
             bytes_received: 0,
             block_received: 0,
+            block_expected,
             last_block_description: lbd,
         }
     }
@@ -172,13 +386,15 @@ impl BootstrapInfo {
         self.last_bytes_received = self.bytes_received;
         info!(
             logger,
-            "receiving from network bytes={} {}/s, blockchain {}",
+            "receiving from network bytes={} {}/s, blockchain {}, blocks {}/{}",
             bytes,
             kbs,
             self.last_block_description
                 .as_ref()
                 .map(|lbd| lbd.to_string())
-                .expect("append_block should always be called before report")
+                .expect("append_block should always be called before report"),
+            self.block_received,
+            self.block_expected,
         )
     }
 }
@@ -187,19 +403,21 @@ async fn bootstrap_from_stream<S>(
     mut blockchain: Blockchain,
     branch: Tip,
     stream: S,
+    block_expected: u64,
     bootstrap_stopper: Shared<Receiver<()>>,
+    stats_counter: StatsCounter,
     logger: Logger,
 ) -> Result<(), Error>
 where
-    S: Stream<Item = Result<net_data::Block, NetworkError>> + Unpin,
+    S: Stream<Item = Result<Block, Error>> + Send,
 {
     const PROCESS_LOGGING_DISTANCE: u64 = 2500;
     let block0 = blockchain.block0().clone();
 
-    let mut bootstrap_info = BootstrapInfo::new();
+    let mut bootstrap_info = BootstrapInfo::new(block_expected);
     let mut maybe_parent_tip = None;
 
-    let mut stream = stream.map_err(Error::PullStreamFailed);
+    let mut stream = stream.boxed();
     let mut bootstrap_stopper = bootstrap_stopper.map(|res| match res {
         Ok(()) => Err(Error::Interrupted),
         Err(_) => panic!("failed to wait for SIGINT"),
@@ -223,9 +441,6 @@ where
     while let Some(block_result) = stream.next().await {
         let result = match block_result {
             Ok(block) => {
-                let block =
-                    Block::deserialize(block.as_bytes()).map_err(Error::BlockDecodingFailed)?;
-
                 if block.header.hash() == block0 {
                     continue;
                 }
@@ -252,6 +467,7 @@ where
                         &mut blockchain,
                         branch.clone(),
                         parent_tip.clone(),
+                        &stats_counter,
                     )
                     .await
                     {
@@ -264,11 +480,11 @@ where
     }
 
     if let Some(parent_tip) = maybe_parent_tip {
-        blockchain::process_new_ref(&logger, &mut blockchain, branch, parent_tip)
+        blockchain::process_new_ref(&logger, &mut blockchain, branch, parent_tip, &stats_counter)
             .await
             .map_err(|e| Error::ChainSelectionFailed(e))
     } else {
-        info!(logger, "no new blocks in bootstrap stream");
+        info!(logger, "no new blocks to bootstrap from the peer");
         Ok(())
     }
 }
@@ -315,3 +531,109 @@ async fn handle_block(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockDate, BlockVersion, Contents, ContentsBuilder, HeaderBuilderNew};
+
+    fn block0() -> Block {
+        let contents: Contents = ContentsBuilder::new().into();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_genesis()
+            .set_date(BlockDate::first())
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    fn child(parent: &Block) -> Block {
+        let contents: Contents = ContentsBuilder::new().into();
+        let date = parent.header.block_date();
+        let header = HeaderBuilderNew::new(BlockVersion::Genesis, &contents)
+            .set_parent(&parent.header.id(), parent.header.chain_length().increase())
+            .set_date(BlockDate {
+                epoch: date.epoch,
+                slot_id: date.slot_id + 1,
+            })
+            .into_unsigned_header()
+            .unwrap()
+            .generalize();
+        Block { header, contents }
+    }
+
+    /// a range of three blocks following the block0
+    fn range() -> Vec<Block> {
+        let mut blocks = vec![child(&block0())];
+        for _ in 0..2 {
+            let block = child(blocks.last().unwrap());
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn ids(blocks: &[Block]) -> Vec<HeaderHash> {
+        blocks.iter().map(|block| block.header.hash()).collect()
+    }
+
+    fn encode(blocks: &[&Block]) -> Vec<net_data::Block> {
+        blocks.iter().map(|block| block.encode()).collect()
+    }
+
+    #[test]
+    fn range_is_decoded_in_order() {
+        let blocks = range();
+        let decoded = decode_range(
+            &encode(&[&blocks[0], &blocks[1], &blocks[2]]),
+            &ids(&blocks),
+        )
+        .unwrap();
+        assert_eq!(ids(&decoded), ids(&blocks));
+    }
+
+    #[test]
+    fn incomplete_range_is_rejected() {
+        let blocks = range();
+        match decode_range(&encode(&[&blocks[0], &blocks[1]]), &ids(&blocks)) {
+            Err(Error::BlockRangeIncomplete {
+                expected: 3,
+                received: 2,
+            }) => (),
+            other => panic!("unexpected result {:?}", other.map(|blocks| ids(&blocks))),
+        }
+        match decode_range(
+            &encode(&[&blocks[0], &blocks[1], &blocks[2], &blocks[2]]),
+            &ids(&blocks),
+        ) {
+            Err(Error::BlockRangeIncomplete {
+                expected: 3,
+                received: 4,
+            }) => (),
+            other => panic!("unexpected result {:?}", other.map(|blocks| ids(&blocks))),
+        }
+    }
+
+    #[test]
+    fn unexpected_block_is_rejected() {
+        let blocks = range();
+        match decode_range(
+            &encode(&[&blocks[0], &blocks[2], &blocks[1]]),
+            &ids(&blocks),
+        ) {
+            Err(Error::UnexpectedBlock { expected, received }) => {
+                assert_eq!(expected, blocks[1].header.hash());
+                assert_eq!(received, blocks[2].header.hash());
+            }
+            other => panic!("unexpected result {:?}", other.map(|blocks| ids(&blocks))),
+        }
+
+        let invalid = net_data::Block::from_bytes(Vec::new());
+        let mut encoded = encode(&[&blocks[0], &blocks[1]]);
+        encoded.push(invalid);
+        assert!(matches!(
+            decode_range(&encoded, &ids(&blocks)),
+            Err(Error::BlockDecodingFailed(_))
+        ));
+    }
+}
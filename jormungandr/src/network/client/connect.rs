@@ -15,6 +15,7 @@ use futures::ready;
 
 use std::pin::Pin;
 use std::task::{Context, Poll};
+use std::time::Instant;
 
 /// Initiates a client connection, returning a connection handle and
 /// the connection future that must be polled to complete the connection.
@@ -31,13 +32,20 @@ pub fn connect(state: ConnectionState, channels: Channels) -> (ConnectHandle, Co
         logger: state.logger.clone(),
     };
     let cf = async move {
-        let mut grpc_client = grpc::connect(&peer)
+        let mut grpc_client = grpc::connect(&peer, state.global.config.tls.as_ref())
             .await
             .map_err(ConnectError::Transport)?;
+        let node_id = Address::new(peer.connection).unwrap();
+        let handshake_started = Instant::now();
         let block0 = grpc_client
             .handshake()
             .await
             .map_err(ConnectError::Handshake)?;
+        state
+            .global
+            .topology
+            .reputation()
+            .record_latency(&node_id, handshake_started.elapsed());
         let mut buf = ReadBuf::from(block0.as_bytes());
         let block0_hash = HeaderHash::read(&mut buf).map_err(ConnectError::DecodeBlock0)?;
         let expected = state.global.block0_hash;
@@ -57,7 +65,7 @@ pub fn connect(state: ConnectionState, channels: Channels) -> (ConnectHandle, Co
         .await
         .map_err(ConnectError::Subscription)?;
         let inbound = InboundSubscriptions {
-            node_id: Address::new(peer.connection).unwrap(),
+            node_id,
             block_events: block_sub,
             fragments: fragment_sub,
             gossip: gossip_sub,
@@ -115,7 +123,7 @@ pub enum ConnectError {
     #[error("connection has been canceled")]
     Canceled,
     #[error(transparent)]
-    Transport(tonic::transport::Error),
+    Transport(grpc::ConnectError),
     #[error("protocol handshake failed: {0}")]
     Handshake(#[source] HandshakeError),
     #[error("failed to decode genesis block in response")]
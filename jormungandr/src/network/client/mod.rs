@@ -341,8 +341,11 @@ impl Client {
         // TODO: make sure that back pressure on the number of requests
         // in flight prevents unlimited spawning of these tasks.
         // https://github.com/input-output-hk/jormungandr/issues/1034
+        let node_id = self.inbound.node_id.clone();
         self.global_state.spawn(async move {
-            let res = block_box.send(BlockMsg::ChainHeaders(handle)).await;
+            let res = block_box
+                .send(BlockMsg::ChainHeaders(handle, Some(node_id)))
+                .await;
             if let Err(e) = res {
                 error!(
                     logger,
@@ -386,8 +389,11 @@ impl Client {
         // TODO: make sure that back pressure on the number of requests
         // in flight prevents unlimited spawning of these tasks.
         // https://github.com/input-output-hk/jormungandr/issues/1034
+        let node_id = self.inbound.node_id.clone();
         self.global_state.spawn(async move {
-            let res = block_box.send(BlockMsg::NetworkBlocks(handle)).await;
+            let res = block_box
+                .send(BlockMsg::NetworkBlocks(handle, Some(node_id)))
+                .await;
             if let Err(e) = res {
                 error!(
                     logger,
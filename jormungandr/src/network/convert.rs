@@ -1,5 +1,5 @@
 use super::p2p::Gossip;
-use crate::blockcfg::{Block, Fragment, Header, HeaderId};
+use crate::blockcfg::{Block, Fragment, FragmentId, Header, HeaderId};
 use crate::intercom;
 use chain_core::mempack::{ReadBuf, Readable};
 use chain_core::property::{Deserialize, Serialize};
@@ -79,6 +79,14 @@ impl Decode for net_data::Header {
     }
 }
 
+impl Decode for net_data::FragmentId {
+    type Object = FragmentId;
+
+    fn decode(self) -> Result<Self::Object, Error> {
+        read(&self)
+    }
+}
+
 impl Decode for net_data::Fragment {
     type Object = Fragment;
 
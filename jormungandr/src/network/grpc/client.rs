@@ -2,6 +2,7 @@ use crate::{
     blockcfg::{Block, HeaderHash},
     network::concurrency_limits,
     network::convert::Decode,
+    network::tls::{self, TlsConfig},
     settings::start::network::{Peer, Protocol},
 };
 use chain_network::data as net_data;
@@ -32,23 +33,35 @@ pub enum FetchBlockError {
     NoBlocks,
 }
 
-pub type ConnectError = transport::Error;
+#[derive(Error, Debug)]
+pub enum ConnectError {
+    #[error(transparent)]
+    Transport(#[from] transport::Error),
+    #[error("invalid TLS settings for the connection")]
+    Tls(#[from] tls::Error),
+}
 
 pub type Client = chain_network::grpc::Client<tonic::transport::Channel>;
 
-pub async fn connect(peer: &Peer) -> Result<Client, ConnectError> {
+/// Connects to the peer, over TLS if the TLS settings are given. The peer
+/// must then present the identity set in the peer details, if any.
+pub async fn connect(peer: &Peer, tls: Option<&TlsConfig>) -> Result<Client, ConnectError> {
     assert!(peer.protocol == Protocol::Grpc);
-    let endpoint = destination_endpoint(peer.connection)
+    let mut endpoint = destination_endpoint(peer.connection, tls.is_some())
         .concurrency_limit(concurrency_limits::CLIENT_REQUESTS)
         .timeout(peer.timeout);
-    Client::connect(endpoint).await
+    if let Some(tls) = tls {
+        endpoint = endpoint.tls_config(tls.client_tls(peer.identity)?);
+    }
+    Client::connect(endpoint).await.map_err(Into::into)
 }
 
-fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
+fn destination_endpoint(addr: SocketAddr, tls: bool) -> transport::Endpoint {
+    let scheme = if tls { "https" } else { "http" };
     let ip = addr.ip();
     let uri = match ip {
-        IpAddr::V4(ip) => format!("http://{}:{}", ip, addr.port()),
-        IpAddr::V6(ip) => format!("http://[{}]:{}", ip, addr.port()),
+        IpAddr::V4(ip) => format!("{}://{}:{}", scheme, ip, addr.port()),
+        IpAddr::V6(ip) => format!("{}://[{}]:{}", scheme, ip, addr.port()),
     };
     transport::Endpoint::try_from(uri).unwrap()
 }
@@ -57,11 +70,12 @@ fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
 // This function is used during node bootstrap to fetch the genesis block.
 pub async fn fetch_block(
     peer: &Peer,
+    tls: Option<&TlsConfig>,
     hash: HeaderHash,
     logger: &Logger,
 ) -> Result<Block, FetchBlockError> {
     info!(logger, "fetching block {}", hash);
-    let mut client = connect(peer)
+    let mut client = connect(peer, tls)
         .await
         .map_err(|err| FetchBlockError::Connect { source: err })?;
     let block_id = net_data::BlockId::try_from(hash.as_bytes()).unwrap();
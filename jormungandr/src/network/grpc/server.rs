@@ -12,11 +12,23 @@ pub async fn run_listen_socket(
     let sockaddr = listen.address();
 
     let logger = state.logger().new(o!("local_addr" => sockaddr.to_string()));
-    info!(logger, "listening and accepting gRPC connections");
+    let mut builder = Server::builder();
+    match &state.config.tls {
+        Some(tls) => {
+            info!(
+                logger,
+                "listening and accepting gRPC connections over TLS";
+                "node_identity" => %tls.identity(),
+                "mutual" => tls.mutual(),
+            );
+            builder = builder.tls_config(tls.server_tls());
+        }
+        None => info!(logger, "listening and accepting gRPC connections"),
+    }
 
     let service = grpc::Server::new(grpc::NodeService::new(NodeService::new(channels, state)));
 
-    Server::builder()
+    builder
         .add_service(service)
         .serve(sockaddr)
         .await
@@ -12,6 +12,7 @@ mod grpc;
 pub mod p2p;
 mod service;
 mod subscription;
+pub mod tls;
 
 use self::convert::Encode;
 use futures::{future, prelude::*};
@@ -45,6 +46,10 @@ mod buffer_sizes {
         // (GetBlocks response or an UploadBlocks request)
         // before the client request task producing them gets preempted.
         pub const BLOCKS: usize = 8;
+
+        // The maximum number of fragments to buffer for an outbound
+        // GetFragments response stream.
+        pub const FRAGMENTS: usize = 32;
     }
 }
 
@@ -53,8 +58,14 @@ mod concurrency_limits {
     pub const CLIENT_REQUESTS: usize = 256;
 }
 
+/// How often the peers of the topology are saved in the peer table
+const PEER_TABLE_SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// number of other peers the blocks are downloaded from during bootstrap
+const MAX_BOOTSTRAP_BLOCK_PEERS: usize = 3;
+
 use self::client::ConnectError;
-use self::p2p::{comm::Peers, P2pTopology};
+use self::p2p::{comm::Peers, P2pTopology, PeerEvent, PeerRecord, PeerTable};
 use crate::blockcfg::{Block, HeaderHash};
 use crate::blockchain::{Blockchain as NewBlockchain, Tip};
 use crate::intercom::{BlockMsg, ClientMsg, NetworkMsg, PropagateMsg, TransactionMsg};
@@ -129,6 +140,7 @@ pub struct GlobalState {
     stats_counter: StatsCounter,
     topology: P2pTopology,
     peers: Peers,
+    peer_table: Option<Arc<PeerTable>>,
     logger: Logger,
 }
 
@@ -141,6 +153,7 @@ impl GlobalState {
         config: Configuration,
         topology: P2pTopology,
         stats_counter: StatsCounter,
+        peer_table: Option<PeerTable>,
         logger: Logger,
     ) -> Self {
         let peers = Peers::new(config.max_connections, logger.clone());
@@ -151,6 +164,7 @@ impl GlobalState {
             stats_counter,
             topology,
             peers,
+            peer_table: peer_table.map(Arc::new),
             logger,
         }
     }
@@ -167,6 +181,11 @@ impl GlobalState {
         &self.topology
     }
 
+    /// the peers allowed in permissioned mode, if the node runs in this mode
+    pub fn allow_list(&self) -> Option<&p2p::AllowList> {
+        self.config.allow_list.as_deref()
+    }
+
     pub fn spawn<F>(&self, f: F)
     where
         F: Future<Output = ()> + Send + 'static,
@@ -209,6 +228,9 @@ pub struct ConnectionState {
     /// the local (to the task) connection details
     pub connection: Connection,
 
+    /// the identity the peer must present over TLS, if pinned
+    identity: Option<tls::NodeIdentity>,
+
     logger: Logger,
 }
 
@@ -217,13 +239,14 @@ impl ConnectionState {
         ConnectionState {
             timeout: peer.timeout,
             connection: peer.connection.clone(),
+            identity: peer.identity,
             logger: global.logger().new(o!("peer_addr" => peer.connection)),
             global,
         }
     }
 
     fn peer(&self) -> Peer {
-        Peer::with_timeout(self.connection, self.timeout)
+        Peer::with_timeout(self.connection, self.timeout).with_identity(self.identity)
     }
 
     fn logger(&self) -> &Logger {
@@ -276,6 +299,24 @@ pub async fn start(service_info: TokioServiceInfo, params: TaskParams) {
 
     let reset_state = global_state.clone();
 
+    if let Some(peer_table) = global_state.peer_table.clone() {
+        let save_state = global_state.clone();
+        service_info.run_periodic_fallible(
+            "save peer table",
+            PEER_TABLE_SAVE_INTERVAL,
+            move || {
+                let state = save_state.clone();
+                let peer_table = peer_table.clone();
+                async move {
+                    let peers = state.topology.known_peers().await;
+                    tokio::task::spawn_blocking(move || peer_table.save(&peers))
+                        .await
+                        .unwrap()
+                }
+            },
+        );
+    }
+
     if let Some(interval) = global_state.config.topology_force_reset_interval.clone() {
         service_info.run_periodic("force reset topology", interval, move || {
             let state = reset_state.clone();
@@ -320,6 +361,13 @@ async fn handle_network_input(
             NetworkMsg::PeerInfo(reply) => {
                 state.peers.infos().map(|infos| reply.reply_ok(infos)).await;
             }
+            NetworkMsg::ReportPeer(node_id, event) => {
+                let reputation = state.topology.reputation();
+                reputation.record(&node_id, event);
+                if reputation.is_banned(&node_id) {
+                    state.peers.remove_peer(node_id).await;
+                }
+            }
         }
     }
 }
@@ -461,13 +509,31 @@ fn connect_and_propagate(
             return;
         }
     };
+    if let Some(allow_list) = state.allow_list() {
+        if !allow_list.allows_ip(addr.ip()) {
+            debug!(
+                state.logger(),
+                "ignoring P2P node outside of the allow-list" ;
+                "address" => %node
+            );
+            return;
+        }
+    }
+    if state.topology.reputation().is_banned(&node) {
+        debug!(
+            state.logger(),
+            "ignoring banned P2P node" ;
+            "address" => %node
+        );
+        return;
+    }
     options.evict_clients = state.num_clients_to_bump();
     assert_ne!(
         Some(&node),
         state.node_address(),
         "topology tells the node to connect to itself"
     );
-    let peer = Peer::new(addr);
+    let peer = Peer::new(addr).with_identity(state.config.trusted_peer_identity(addr));
     let conn_state = ConnectionState::new(state.clone(), &peer);
     let conn_logger = conn_state.logger().new(o!("address" => node.to_string()));
     info!(conn_logger, "connecting to peer");
@@ -499,6 +565,10 @@ fn connect_and_propagate(
                     }
                 };
                 if !benign {
+                    state
+                        .topology
+                        .reputation()
+                        .record(&node, PeerEvent::CannotConnect);
                     future::join(
                         state
                             .topology
@@ -523,11 +593,14 @@ fn connect_and_propagate(
     spawn_state.spawn(cf);
 }
 
-fn trusted_peers_shuffled(config: &Configuration) -> Vec<SocketAddr> {
+fn trusted_peers_shuffled(config: &Configuration) -> Vec<Peer> {
     let mut peers = config
         .trusted_peers
         .iter()
-        .filter_map(|peer| multiaddr_to_socket_addr(peer.address.multi_address()))
+        .filter_map(|peer| {
+            multiaddr_to_socket_addr(peer.address.multi_address())
+                .map(|addr| Peer::new(addr).with_identity(peer.identity))
+        })
         .collect::<Vec<_>>();
     let mut rng = rand::thread_rng();
     peers.shuffle(&mut rng);
@@ -561,6 +634,10 @@ impl BootstrapPeers {
         self.0.len()
     }
 
+    pub fn contains(&self, peer: &Peer) -> bool {
+        self.0.contains_key(&peer.address().to_string())
+    }
+
     pub fn randomly(&self) -> Vec<&Peer> {
         let mut peers = self.0.iter().map(|(_, peer)| peer).collect::<Vec<_>>();
         let mut rng = rand::thread_rng();
@@ -578,7 +655,8 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
         .trusted_peers
         .iter()
         .filter_map(|tp| {
-            multiaddr_to_socket_addr(tp.address.multi_address()).map(|sa| Peer::new(sa.clone()))
+            multiaddr_to_socket_addr(tp.address.multi_address())
+                .map(|sa| Peer::new(sa.clone()).with_identity(tp.identity))
         })
         .collect::<Vec<_>>();
     if config.bootstrap_from_trusted_peers {
@@ -590,16 +668,24 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
         for tpeer in trusted_peers {
             // let peer = Peer::new(peer, Protocol::Grpc);
             let tp_logger = logger.new(o!("peer_addr" => tpeer.address().to_string()));
-            let received_peers = bootstrap::peers_from_trusted_peer(&tpeer, tp_logger.clone())
-                .await
-                .unwrap_or_else(|e| {
-                    warn!(
-                        tp_logger,
-                        "failed to retrieve the list of bootstrap peers from trusted peer";
-                        "reason" => %e,
-                    );
-                    vec![tpeer]
-                });
+            let received_peers =
+                bootstrap::peers_from_trusted_peer(&tpeer, config.tls.as_ref(), tp_logger.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            tp_logger,
+                            "failed to retrieve the list of bootstrap peers from trusted peer";
+                            "reason" => %e,
+                        );
+                        vec![tpeer]
+                    });
+            let received_peers = match &config.allow_list {
+                Some(allow_list) => received_peers
+                    .into_iter()
+                    .filter(|peer| allow_list.allows_ip(peer.connection.ip()))
+                    .collect(),
+                None => received_peers,
+            };
             let added = peers.add_peers(&received_peers);
             info!(logger, "adding {} peers from peer", added);
 
@@ -611,11 +697,32 @@ async fn netboot_peers(config: &Configuration, logger: &Logger) -> BootstrapPeer
     peers
 }
 
+/// The peers of the peer table saved by a previous run of the node that can be
+/// tried for bootstrap, the most recently seen first
+fn known_bootstrap_peers(config: &Configuration, known_peers: &[PeerRecord]) -> Vec<Peer> {
+    known_peers
+        .iter()
+        .filter(|peer| !peer.quarantined)
+        .filter_map(|peer| multiaddr_to_socket_addr(peer.address.multi_address()))
+        .filter(|addr| {
+            config
+                .allow_list
+                .as_ref()
+                .map_or(true, |allow_list| allow_list.allows_ip(addr.ip()))
+        })
+        .map(|addr| Peer::new(addr).with_identity(config.trusted_peer_identity(addr)))
+        .collect()
+}
+
+/// Bootstraps the blockchain from the trusted peers, or the peers they know,
+/// then from the peers saved in the peer table.
 pub async fn bootstrap(
     config: &Configuration,
+    known_peers: &[PeerRecord],
     blockchain: NewBlockchain,
     branch: Tip,
     bootstrap_stopper: Shared<Receiver<()>>,
+    stats_counter: StatsCounter,
     logger: &Logger,
 ) -> Result<bool, bootstrap::Error> {
     use futures::future::{select, Either, FutureExt};
@@ -628,7 +735,7 @@ pub async fn bootstrap(
         return Ok(true);
     }
 
-    if config.trusted_peers.is_empty() {
+    if config.trusted_peers.is_empty() && known_peers.is_empty() {
         error!(
             logger,
             "trusted-peers cannot be empty. to avoid bootstrap use 'skip_bootstrap: true'"
@@ -647,13 +754,34 @@ pub async fn bootstrap(
             },
         };
 
-    for peer in netboot_peers.randomly() {
+    // the trusted peers, and the peers they know, take precedence
+    let known_bootstrap_peers = known_bootstrap_peers(config, known_peers)
+        .into_iter()
+        .filter(|peer| !netboot_peers.contains(peer))
+        .collect::<Vec<_>>();
+    let candidates = netboot_peers
+        .randomly()
+        .into_iter()
+        .chain(known_bootstrap_peers.iter())
+        .collect::<Vec<_>>();
+
+    for (index, peer) in candidates.iter().enumerate() {
         let logger = logger.new(o!("peer_addr" => peer.address().to_string()));
+        // the blocks are also downloaded from the next candidates
+        let block_peers = candidates
+            .iter()
+            .skip(index + 1)
+            .take(MAX_BOOTSTRAP_BLOCK_PEERS)
+            .map(|peer| (*peer).clone())
+            .collect::<Vec<_>>();
         let res = bootstrap::bootstrap_from_peer(
             peer,
+            &block_peers,
+            config.tls.as_ref(),
             blockchain.clone(),
             branch.clone(),
             bootstrap_stopper.clone(),
+            stats_counter.clone(),
             logger.clone(),
         )
         .await;
@@ -701,10 +829,9 @@ pub async fn fetch_block(
 
     let logger = logger.new(o!("block" => hash.to_string()));
 
-    for address in trusted_peers_shuffled(&config) {
-        let logger = logger.new(o!("peer_address" => address.to_string()));
-        let peer = Peer::new(address);
-        match grpc::fetch_block(&peer, hash, &logger).await {
+    for peer in trusted_peers_shuffled(&config) {
+        let logger = logger.new(o!("peer_address" => peer.address().to_string()));
+        match grpc::fetch_block(&peer, config.tls.as_ref(), hash, &logger).await {
             Err(grpc::FetchBlockError::Connect { source: e }) => {
                 warn!(logger, "unable to reach peer for block download"; "reason" => %e);
             }
@@ -0,0 +1,252 @@
+//! Allow-list of the peers of a permissioned network.
+//!
+//! In permissioned mode, the node only exchanges with the peers of the
+//! allow-list: the subscriptions of other peers are refused, they are dropped
+//! from the received gossip so they do not enter the topology, and with mutual
+//! TLS the connections of nodes presenting another identity are refused during
+//! the handshake.
+//!
+//! The peers are matched by the IP address of their P2P address, the port of
+//! an inbound connection being an ephemeral one, and by node identity. Each
+//! criterion only applies when the allow-list has entries of its kind, and the
+//! trusted peers are always allowed.
+
+use super::{Address, Gossip};
+use crate::network::tls::NodeIdentity;
+use crate::settings::start::network::TrustedPeer;
+use jormungandr_lib::{interfaces::PeerAllowList, multiaddr::multiaddr_to_socket_addr};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeSet, HashSet};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct AllowListConfig {
+    #[serde(default)]
+    addresses: Vec<Address>,
+    #[serde(default)]
+    identities: Vec<NodeIdentity>,
+}
+
+impl AllowListConfig {
+    /// an allow-list without entries would allow every peer
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.identities.is_empty()
+    }
+
+    pub fn has_identities(&self) -> bool {
+        !self.identities.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct AllowList {
+    addresses: Vec<Address>,
+    ips: HashSet<IpAddr>,
+    identities: BTreeSet<NodeIdentity>,
+    rejected_connections: AtomicU64,
+    rejected_subscriptions: AtomicU64,
+    rejected_gossip_nodes: AtomicU64,
+}
+
+impl AllowList {
+    pub fn new(config: AllowListConfig, trusted_peers: &[TrustedPeer]) -> Self {
+        let mut addresses = config.addresses;
+        let mut identities: BTreeSet<_> = config.identities.into_iter().collect();
+        if !addresses.is_empty() {
+            addresses.extend(trusted_peers.iter().map(|peer| peer.address.clone()));
+        }
+        if !identities.is_empty() {
+            identities.extend(trusted_peers.iter().filter_map(|peer| peer.identity));
+        }
+        let ips = addresses
+            .iter()
+            .filter_map(|address| multiaddr_to_socket_addr(address.multi_address()))
+            .map(|addr| addr.ip())
+            .collect();
+
+        AllowList {
+            addresses,
+            ips,
+            identities,
+            rejected_connections: AtomicU64::new(0),
+            rejected_subscriptions: AtomicU64::new(0),
+            rejected_gossip_nodes: AtomicU64::new(0),
+        }
+    }
+
+    /// the peer connecting from or listening on this IP address is allowed
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.addresses.is_empty() || self.ips.contains(&ip)
+    }
+
+    pub fn allows_address(&self, address: &Address) -> bool {
+        self.addresses.is_empty()
+            || multiaddr_to_socket_addr(address.multi_address())
+                .map_or(false, |addr| self.ips.contains(&addr.ip()))
+    }
+
+    pub fn allows_identity(&self, identity: &NodeIdentity) -> bool {
+        self.identities.is_empty() || self.identities.contains(identity)
+    }
+
+    /// a node of the received gossip may enter the topology. The nodes
+    /// without an address cannot be checked and are not allowed when the
+    /// peers are filtered by address.
+    pub fn allows_gossip_node(&self, node: &Gossip) -> bool {
+        match node.address() {
+            Some(address) => self.allows_address(address),
+            None => self.addresses.is_empty(),
+        }
+    }
+
+    pub fn reject_connection(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reject_subscription(&self) {
+        self.rejected_subscriptions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reject_gossip_nodes(&self, count: usize) {
+        self.rejected_gossip_nodes
+            .fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> PeerAllowList {
+        PeerAllowList {
+            addresses: self
+                .addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+            identities: self
+                .identities
+                .iter()
+                .map(|identity| identity.to_string())
+                .collect(),
+            rejected_connections: self.rejected_connections.load(Ordering::Relaxed),
+            rejected_subscriptions: self.rejected_subscriptions.load(Ordering::Relaxed),
+            rejected_gossip_nodes: self.rejected_gossip_nodes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poldercast::NodeProfileBuilder;
+
+    fn address(host: u8, port: u16) -> Address {
+        format!("/ip4/127.0.0.{}/tcp/{}", host, port)
+            .parse()
+            .unwrap()
+    }
+
+    fn ip(host: u8) -> IpAddr {
+        format!("127.0.0.{}", host).parse().unwrap()
+    }
+
+    fn identity(byte: u8) -> NodeIdentity {
+        format!("{:02x}", byte).repeat(32).parse().unwrap()
+    }
+
+    fn gossip(address: Option<Address>) -> Gossip {
+        let mut builder = NodeProfileBuilder::new();
+        if let Some(address) = address {
+            builder.address(address);
+        }
+        Gossip::from(builder.build())
+    }
+
+    fn trusted_peer(host: u8, identity: Option<NodeIdentity>) -> TrustedPeer {
+        TrustedPeer {
+            address: address(host, 3000),
+            identity,
+        }
+    }
+
+    #[test]
+    fn empty_allow_list_allows_every_peer() {
+        let allow_list = AllowList::new(AllowListConfig::default(), &[]);
+        assert!(allow_list.allows_ip(ip(1)));
+        assert!(allow_list.allows_address(&address(1, 3000)));
+        assert!(allow_list.allows_identity(&identity(1)));
+        assert!(allow_list.allows_gossip_node(&gossip(Some(address(1, 3000)))));
+        assert!(allow_list.allows_gossip_node(&gossip(None)));
+    }
+
+    #[test]
+    fn peers_are_matched_by_ip() {
+        let config = AllowListConfig {
+            addresses: vec![address(1, 3000)],
+            identities: Vec::new(),
+        };
+        let allow_list = AllowList::new(config, &[]);
+
+        assert!(allow_list.allows_ip(ip(1)));
+        assert!(!allow_list.allows_ip(ip(2)));
+        // the port of an inbound connection is an ephemeral one
+        assert!(allow_list.allows_address(&address(1, 49152)));
+        assert!(!allow_list.allows_address(&address(2, 3000)));
+        assert!(allow_list.allows_gossip_node(&gossip(Some(address(1, 3001)))));
+        assert!(!allow_list.allows_gossip_node(&gossip(Some(address(2, 3000)))));
+        // a node without an address cannot be checked
+        assert!(!allow_list.allows_gossip_node(&gossip(None)));
+
+        // there is no identity in the allow-list
+        assert!(allow_list.allows_identity(&identity(1)));
+    }
+
+    #[test]
+    fn peers_are_matched_by_identity() {
+        let config = AllowListConfig {
+            addresses: Vec::new(),
+            identities: vec![identity(1)],
+        };
+        let allow_list = AllowList::new(config, &[]);
+
+        assert!(allow_list.allows_identity(&identity(1)));
+        assert!(!allow_list.allows_identity(&identity(2)));
+
+        // there is no address in the allow-list
+        assert!(allow_list.allows_ip(ip(2)));
+        assert!(allow_list.allows_gossip_node(&gossip(Some(address(2, 3000)))));
+        assert!(allow_list.allows_gossip_node(&gossip(None)));
+    }
+
+    #[test]
+    fn trusted_peers_are_always_allowed() {
+        let config = AllowListConfig {
+            addresses: vec![address(1, 3000)],
+            identities: vec![identity(1)],
+        };
+        let trusted_peers = [trusted_peer(9, Some(identity(9))), trusted_peer(10, None)];
+        let allow_list = AllowList::new(config, &trusted_peers);
+
+        assert!(allow_list.allows_ip(ip(9)));
+        assert!(allow_list.allows_ip(ip(10)));
+        assert!(allow_list.allows_gossip_node(&gossip(Some(address(9, 3000)))));
+        assert!(allow_list.allows_identity(&identity(9)));
+        assert!(!allow_list.allows_ip(ip(2)));
+        assert!(!allow_list.allows_identity(&identity(2)));
+    }
+
+    #[test]
+    fn trusted_peers_do_not_enable_a_criterion() {
+        let config = AllowListConfig {
+            addresses: Vec::new(),
+            identities: vec![identity(1)],
+        };
+        let allow_list = AllowList::new(config, &[trusted_peer(9, Some(identity(9)))]);
+
+        assert!(allow_list.allows_identity(&identity(9)));
+        assert!(!allow_list.allows_identity(&identity(2)));
+        // the addresses of the trusted peers do not filter the other peers
+        assert!(allow_list.allows_ip(ip(2)));
+        assert!(allow_list.allows_gossip_node(&gossip(None)));
+        assert!(allow_list.status().addresses.is_empty());
+    }
+}
@@ -1,11 +1,17 @@
+mod allow_list;
 pub mod comm;
 mod gossip;
 pub mod layers;
+mod peer_table;
 mod policy;
+mod reputation;
 mod topology;
 
+pub use self::allow_list::{AllowList, AllowListConfig};
 pub use self::gossip::{Gossip, Gossips, Peer, Peers};
-pub use self::policy::{Policy, PolicyConfig};
+pub use self::peer_table::{Error as PeerTableError, PeerRecord, PeerTable};
+pub use self::policy::{Policy, PolicyConfig, QuarantineRecords};
+pub use self::reputation::{PeerEvent, Reputation};
 pub use self::topology::P2pTopology;
 
 pub use poldercast::Address;
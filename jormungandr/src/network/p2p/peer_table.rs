@@ -0,0 +1,253 @@
+//! On-disk copy of the peers known to the node.
+//!
+//! The topology starts empty at every launch, so the node has to go through
+//! its trusted peers to meet the network again. The nodes of the topology are
+//! periodically saved in a database in the node storage directory, with the
+//! time they were last seen and the number of times they were quarantined,
+//! and are reused as bootstrap candidates on the next start. The peers not
+//! seen for `MAX_AGE` are dropped, and at most `MAX_PEERS` of the most recently
+//! seen peers are kept.
+
+use super::Address;
+use crate::{blockcfg::HeaderHash, utils::storage_db};
+use rusqlite::{params, Connection, NO_PARAMS};
+use slog::Logger;
+use thiserror::Error;
+
+use std::{
+    convert::TryFrom,
+    io,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// name of the database file in the node storage directory
+const DATABASE_FILE: &str = "peers.sqlite";
+
+/// the peers not seen for a week are dropped
+const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+const MAX_PEERS: u32 = 1024;
+
+/// version of the database schema. The peers stored with another version
+/// are dropped.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS peers (
+    address TEXT PRIMARY KEY,
+    last_seen INTEGER NOT NULL,
+    quarantine_count INTEGER NOT NULL,
+    quarantined INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot create the storage directory")]
+    Io(#[from] io::Error),
+    #[error("peer table database error")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// a peer known to the node
+#[derive(Debug, Clone)]
+pub struct PeerRecord {
+    pub address: Address,
+    pub last_seen: SystemTime,
+    /// the number of times the peer has been quarantined
+    pub quarantine_count: u32,
+    /// the peer was in quarantine when the table was saved
+    pub quarantined: bool,
+}
+
+pub struct PeerTable {
+    connection: Mutex<Connection>,
+}
+
+impl PeerTable {
+    /// open (or create) the database in the given node storage directory.
+    /// The peers stored for another block0 are dropped.
+    pub fn open(storage_dir: &Path, block0: HeaderHash, logger: &Logger) -> Result<Self, Error> {
+        let connection = storage_db::open::<Error>(
+            storage_dir,
+            DATABASE_FILE,
+            SCHEMA,
+            SCHEMA_VERSION,
+            block0,
+            logger,
+        )?;
+
+        Ok(PeerTable {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        PeerTable {
+            connection: Mutex::new(storage_db::in_memory(SCHEMA, SCHEMA_VERSION)),
+        }
+    }
+
+    /// Returns the stored peers, the most recently seen first
+    pub fn load(&self) -> Result<Vec<PeerRecord>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT address, last_seen, quarantine_count, quarantined FROM peers
+             ORDER BY last_seen DESC",
+        )?;
+        let rows = statement.query_map(NO_PARAMS, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, u32>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?;
+        let mut peers = Vec::new();
+        for row in rows {
+            let (address, last_seen, quarantine_count, quarantined) = row?;
+            // the addresses are written by `save`, skip rather than fail on
+            // an entry edited by hand
+            if let Ok(address) = address.parse() {
+                peers.push(PeerRecord {
+                    address,
+                    last_seen: UNIX_EPOCH + Duration::from_secs(last_seen.max(0) as u64),
+                    quarantine_count,
+                    quarantined,
+                });
+            }
+        }
+        Ok(peers)
+    }
+
+    /// save the peers, the peers already stored are kept until they are too
+    /// old or there are too many peers
+    pub fn save(&self, peers: &[PeerRecord]) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR REPLACE INTO peers (address, last_seen, quarantine_count, quarantined)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for peer in peers {
+                statement.execute(params![
+                    peer.address.to_string(),
+                    seconds_since_epoch(peer.last_seen),
+                    peer.quarantine_count,
+                    peer.quarantined,
+                ])?;
+            }
+        }
+        transaction.execute(
+            "DELETE FROM peers WHERE last_seen < ?1",
+            params![seconds_since_epoch(SystemTime::now() - MAX_AGE)],
+        )?;
+        transaction.execute(
+            "DELETE FROM peers WHERE address NOT IN
+             (SELECT address FROM peers ORDER BY last_seen DESC LIMIT ?1)",
+            params![MAX_PEERS],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+fn seconds_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| i64::try_from(elapsed.as_secs()).unwrap_or(i64::MAX))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(index: u32, last_seen: SystemTime) -> PeerRecord {
+        PeerRecord {
+            address: format!("/ip4/10.0.{}.{}/tcp/3000", index / 256, index % 256)
+                .parse()
+                .unwrap(),
+            last_seen,
+            quarantine_count: 0,
+            quarantined: false,
+        }
+    }
+
+    /// the time the given number of seconds ago, rounded to the second as
+    /// stored in the table
+    fn seconds_ago(seconds: u64) -> SystemTime {
+        let now = UNIX_EPOCH + Duration::from_secs(seconds_since_epoch(SystemTime::now()) as u64);
+        now - Duration::from_secs(seconds)
+    }
+
+    fn addresses(peers: &[PeerRecord]) -> Vec<String> {
+        peers.iter().map(|peer| peer.address.to_string()).collect()
+    }
+
+    #[test]
+    fn peers_are_loaded_as_saved() {
+        let table = PeerTable::in_memory();
+        let quarantined = PeerRecord {
+            quarantine_count: 3,
+            quarantined: true,
+            ..peer(1, seconds_ago(60))
+        };
+        table
+            .save(&[peer(0, seconds_ago(120)), quarantined.clone()])
+            .unwrap();
+        // the peers already stored are kept
+        table.save(&[peer(2, seconds_ago(10))]).unwrap();
+
+        let peers = table.load().unwrap();
+        assert_eq!(
+            addresses(&peers),
+            addresses(&[
+                peer(2, seconds_ago(10)),
+                quarantined.clone(),
+                peer(0, seconds_ago(120))
+            ])
+        );
+        assert_eq!(peers[1].last_seen, quarantined.last_seen);
+        assert_eq!(peers[1].quarantine_count, 3);
+        assert!(peers[1].quarantined);
+        assert_eq!(peers[2].quarantine_count, 0);
+        assert!(!peers[2].quarantined);
+
+        // a peer saved again is updated
+        table.save(&[peer(0, seconds_ago(0))]).unwrap();
+        let peers = table.load().unwrap();
+        assert_eq!(peers.len(), 3);
+        assert_eq!(
+            addresses(&peers[..1]),
+            addresses(&[peer(0, seconds_ago(0))])
+        );
+    }
+
+    #[test]
+    fn old_peers_are_dropped() {
+        let table = PeerTable::in_memory();
+        let too_old = seconds_ago(MAX_AGE.as_secs() + 3600);
+        let old = seconds_ago(MAX_AGE.as_secs() - 3600);
+        table.save(&[peer(0, too_old), peer(1, old)]).unwrap();
+
+        let peers = table.load().unwrap();
+        assert_eq!(addresses(&peers), addresses(&[peer(1, old)]));
+    }
+
+    #[test]
+    fn only_the_most_recently_seen_peers_are_kept() {
+        let table = PeerTable::in_memory();
+        let peers: Vec<_> = (0..MAX_PEERS + 10)
+            .map(|index| peer(index, seconds_ago(u64::from(index))))
+            .collect();
+        table.save(&peers).unwrap();
+
+        let kept = table.load().unwrap();
+        assert_eq!(kept.len(), MAX_PEERS as usize);
+        assert_eq!(addresses(&kept), addresses(&peers[..MAX_PEERS as usize]));
+    }
+}
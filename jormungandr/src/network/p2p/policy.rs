@@ -4,6 +4,7 @@ use poldercast::{Address, Node, PolicyReport};
 use serde::{Deserialize, Serialize};
 use slog::Logger;
 use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use std::time::Duration as StdDuration;
 
 /// default quarantine duration is 10min
@@ -22,7 +23,7 @@ const DEFAULT_MAX_NUM_QUARANTINE_RECORDS: usize = 24_000;
 pub struct Policy {
     quarantine_duration: StdDuration,
     max_quarantine: StdDuration,
-    records: LruCache<Address, Records>,
+    records: QuarantineRecords,
     quarantine_whitelist: HashSet<Address>,
     logger: Logger,
 }
@@ -33,6 +34,11 @@ pub struct Records {
     quarantine: u32,
 }
 
+/// the quarantine records of the policy, shared with the topology so they
+/// are saved in the peer table and restored on the next start
+#[derive(Clone, Debug)]
+pub struct QuarantineRecords(Arc<Mutex<LruCache<Address, Records>>>);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub struct PolicyConfig {
@@ -53,32 +59,59 @@ impl Policy {
                 .max_quarantine
                 .unwrap_or(DEFAULT_MAX_QUARANTINE_DURATION.into())
                 .into(),
-            records: LruCache::new(
+            records: QuarantineRecords(Arc::new(Mutex::new(LruCache::new(
                 pc.max_num_quarantine_records
                     .unwrap_or(DEFAULT_MAX_NUM_QUARANTINE_RECORDS),
-            ),
+            )))),
             quarantine_whitelist: pc.quarantine_whitelist,
             logger,
         }
     }
 
+    pub fn quarantine_records(&self) -> QuarantineRecords {
+        self.records.clone()
+    }
+
     fn quarantine_duration_for(&mut self, id: Address) -> StdDuration {
-        if let Some(r) = self.records.get_mut(&id) {
+        let mut records = self.records.0.lock().unwrap();
+        if let Some(r) = records.get_mut(&id) {
             r.quarantine_for(self.quarantine_duration, self.max_quarantine)
         } else {
             let r = Records::new();
             let t = r.quarantine_for(self.quarantine_duration, self.max_quarantine);
-            self.records.put(id, r);
+            records.put(id, r);
             t
         }
     }
 
     fn update(&mut self, id: Address) {
-        if let Some(r) = self.records.get_mut(&id) {
+        let mut records = self.records.0.lock().unwrap();
+        if let Some(r) = records.get_mut(&id) {
             r.update();
         } else {
             let r = Records::new();
-            self.records.put(id, r);
+            records.put(id, r);
+        }
+    }
+}
+
+impl QuarantineRecords {
+    /// the number of times the node has been quarantined
+    pub fn quarantine_count(&self, id: &Address) -> u32 {
+        self.0
+            .lock()
+            .unwrap()
+            .peek(id)
+            .map(|r| r.quarantine)
+            .unwrap_or(0)
+    }
+
+    /// restore the number of times the node has been quarantined, as saved
+    /// before the node restarted
+    pub fn restore(&self, id: Address, quarantine: u32) {
+        let mut records = self.0.lock().unwrap();
+        if !records.contains(&id) {
+            records.put(id, Records { quarantine });
         }
     }
 }
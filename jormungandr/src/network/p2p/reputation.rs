@@ -0,0 +1,353 @@
+//! Reputation of the peers.
+//!
+//! The quarantine of the topology policy only tells whether a peer could be
+//! reached lately. The reputation scores the peers by what they send to the
+//! node: the blocks they deliver first raise the score, the protocol
+//! violations, such as invalid blocks or headers and malformed messages,
+//! lower it. A peer whose score falls to `BAN_SCORE` is banned for
+//! `BAN_DURATION`: the node does not connect to it, refuses its
+//! subscriptions and leaves it out of the topology view. The peers of the
+//! view are ordered by score, then by the latency of their handshake.
+//!
+//! The reputation is kept by IP address: the inbound connections come from
+//! ephemeral ports, a peer reconnecting from another port must not start
+//! over with a clean record.
+
+use super::Address;
+use jormungandr_lib::{interfaces::PeerReputation, multiaddr::multiaddr_to_socket_addr};
+use lru::LruCache;
+use slog::Logger;
+
+use std::cmp::Ordering;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// the score is capped so a peer cannot build up enough credit to commit
+/// many violations before it is banned
+const MAX_SCORE: i32 = 100;
+
+const BAN_SCORE: i32 = -200;
+
+const BAN_DURATION: Duration = Duration::from_secs(24 * 3600);
+
+/// number of peers the reputation is kept for, the least recently updated
+/// peers are forgotten first
+const MAX_NUM_RECORDS: usize = 24_000;
+
+/// something a peer did, which changes its reputation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerEvent {
+    /// the peer delivered a block the node did not have yet
+    BlockDelivered,
+    /// the peer could not be reached
+    CannotConnect,
+    /// the peer sent a header failing the verification
+    InvalidHeader,
+    /// the peer sent a block failing the verification
+    InvalidBlock,
+    MalformedHeader,
+    MalformedFragment,
+    MalformedGossip,
+}
+
+impl PeerEvent {
+    fn score(self) -> i32 {
+        match self {
+            PeerEvent::BlockDelivered => 5,
+            PeerEvent::CannotConnect => -2,
+            PeerEvent::InvalidHeader | PeerEvent::InvalidBlock => -100,
+            PeerEvent::MalformedHeader
+            | PeerEvent::MalformedFragment
+            | PeerEvent::MalformedGossip => -50,
+        }
+    }
+
+    fn is_violation(self) -> bool {
+        match self {
+            PeerEvent::BlockDelivered | PeerEvent::CannotConnect => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Record {
+    score: i32,
+    latency: Option<Duration>,
+    violations: u32,
+    banned_until: Option<SystemTime>,
+}
+
+impl Record {
+    /// lifts the ban once it expired, the peer starts over with a low score
+    fn is_banned(&mut self, now: SystemTime) -> bool {
+        match self.banned_until {
+            Some(until) if until > now => true,
+            Some(_) => {
+                self.banned_until = None;
+                self.score = BAN_SCORE / 2;
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn compare(&self, other: &Record) -> Ordering {
+        other
+            .score
+            .cmp(&self.score)
+            .then_with(|| match (self.latency, other.latency) {
+                (Some(latency), Some(other_latency)) => latency.cmp(&other_latency),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            })
+    }
+}
+
+/// the reputation of the peers, shared between the network tasks
+#[derive(Clone)]
+pub struct Reputation {
+    records: Arc<Mutex<LruCache<IpAddr, Record>>>,
+    logger: Logger,
+}
+
+impl Reputation {
+    pub fn new(logger: Logger) -> Self {
+        Reputation {
+            records: Arc::new(Mutex::new(LruCache::new(MAX_NUM_RECORDS))),
+            logger,
+        }
+    }
+
+    pub fn record(&self, address: &Address, event: PeerEvent) {
+        let ip = match peer_ip(address) {
+            Some(ip) => ip,
+            None => return,
+        };
+        let mut records = self.records.lock().unwrap();
+        let record = get_or_insert(&mut records, ip);
+        let now = SystemTime::now();
+        if record.is_banned(now) {
+            return;
+        }
+        record.score = (record.score + event.score()).min(MAX_SCORE);
+        if !event.is_violation() {
+            // only the protocol violations can get a peer banned, a peer
+            // that is often unreachable is left in the quarantine
+            record.score = record.score.max(BAN_SCORE / 2);
+        } else {
+            record.violations += 1;
+            debug!(self.logger, "protocol violation"; "peer" => %ip, "event" => ?event, "score" => record.score);
+            if record.score <= BAN_SCORE {
+                info!(self.logger, "banning peer for protocol violations"; "peer" => %ip, "violations" => record.violations);
+                record.banned_until = Some(now + BAN_DURATION);
+            }
+        }
+    }
+
+    /// record the round trip time of a request to the peer
+    pub fn record_latency(&self, address: &Address, latency: Duration) {
+        let ip = match peer_ip(address) {
+            Some(ip) => ip,
+            None => return,
+        };
+        let mut records = self.records.lock().unwrap();
+        let record = get_or_insert(&mut records, ip);
+        record.latency = Some(match record.latency {
+            Some(previous) => (previous * 3 + latency) / 4,
+            None => latency,
+        });
+    }
+
+    pub fn is_banned(&self, address: &Address) -> bool {
+        match peer_ip(address) {
+            Some(ip) => self.is_ip_banned(ip),
+            None => false,
+        }
+    }
+
+    pub fn is_ip_banned(&self, ip: IpAddr) -> bool {
+        let mut records = self.records.lock().unwrap();
+        records
+            .get_mut(&ip)
+            .map_or(false, |record| record.is_banned(SystemTime::now()))
+    }
+
+    /// drops the banned peers and orders the others, the best first. The
+    /// peers without a reputation keep their relative order.
+    pub fn prioritize(&self, peers: &mut Vec<Address>) {
+        let mut records = self.records.lock().unwrap();
+        let now = SystemTime::now();
+        peers.retain(|address| {
+            !peer_ip(address)
+                .and_then(|ip| records.get_mut(&ip))
+                .map_or(false, |record| record.is_banned(now))
+        });
+        let default = Record::default();
+        peers.sort_by(|a, b| {
+            let a = peer_ip(a)
+                .and_then(|ip| records.peek(&ip))
+                .unwrap_or(&default);
+            let b = peer_ip(b)
+                .and_then(|ip| records.peek(&ip))
+                .unwrap_or(&default);
+            a.compare(b)
+        });
+    }
+
+    /// the reputation of the peers, the best first
+    pub fn status(&self) -> Vec<PeerReputation> {
+        let mut records = self.records.lock().unwrap();
+        let now = SystemTime::now();
+        let mut peers = records
+            .iter_mut()
+            .map(|(ip, record)| {
+                record.is_banned(now);
+                (ip, &*record)
+            })
+            .collect::<Vec<_>>();
+        peers.sort_by(|(_, a), (_, b)| a.compare(b));
+        peers
+            .into_iter()
+            .map(|(ip, record)| PeerReputation {
+                address: ip.to_string(),
+                score: record.score,
+                latency: record.latency.map(Into::into),
+                violations: record.violations,
+                banned_until: record.banned_until.map(Into::into),
+            })
+            .collect()
+    }
+}
+
+fn peer_ip(address: &Address) -> Option<IpAddr> {
+    multiaddr_to_socket_addr(address.multi_address()).map(|addr| addr.ip())
+}
+
+fn get_or_insert(records: &mut LruCache<IpAddr, Record>, ip: IpAddr) -> &mut Record {
+    if !records.contains(&ip) {
+        records.put(ip, Record::default());
+    }
+    records.get_mut(&ip).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(host: u8) -> Address {
+        address_with_port(host, 3000)
+    }
+
+    fn address_with_port(host: u8, port: u16) -> Address {
+        format!("/ip4/127.0.0.{}/tcp/{}", host, port)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn ban_after_violations() {
+        let reputation = Reputation::new(Logger::root(slog::Discard, o!()));
+        let peer = address(1);
+        for _ in 0..10 {
+            reputation.record(&peer, PeerEvent::BlockDelivered);
+        }
+        reputation.record(&peer, PeerEvent::InvalidBlock);
+        reputation.record(&peer, PeerEvent::InvalidHeader);
+        assert!(!reputation.is_banned(&peer));
+        reputation.record(&peer, PeerEvent::MalformedFragment);
+        reputation.record(&peer, PeerEvent::InvalidBlock);
+        assert!(reputation.is_banned(&peer));
+
+        let mut peers = vec![address(2), peer, address(3)];
+        reputation.record(&address(3), PeerEvent::BlockDelivered);
+        reputation.prioritize(&mut peers);
+        assert_eq!(peers, vec![address(3), address(2)]);
+    }
+
+    #[test]
+    fn ban_expires() {
+        let reputation = Reputation::new(Logger::root(slog::Discard, o!()));
+        let peer = address(1);
+        reputation.record(&peer, PeerEvent::InvalidBlock);
+        reputation.record(&peer, PeerEvent::InvalidBlock);
+        assert!(reputation.is_banned(&peer));
+
+        // the events are ignored while the peer is banned
+        reputation.record(&peer, PeerEvent::BlockDelivered);
+        assert!(reputation.is_banned(&peer));
+
+        let expired = SystemTime::now() - Duration::from_secs(1);
+        reputation
+            .records
+            .lock()
+            .unwrap()
+            .get_mut(&peer)
+            .unwrap()
+            .banned_until = Some(expired);
+        assert!(!reputation.is_banned(&peer));
+        assert_eq!(reputation.status()[0].score, BAN_SCORE / 2);
+
+        // the peer starts over with a low score, it is banned again sooner
+        reputation.record(&peer, PeerEvent::InvalidHeader);
+        assert!(reputation.is_banned(&peer));
+    }
+
+    #[test]
+    fn unreachable_peer_is_not_banned() {
+        let reputation = Reputation::new(Logger::root(slog::Discard, o!()));
+        let peer = address(1);
+        for _ in 0..1000 {
+            reputation.record(&peer, PeerEvent::CannotConnect);
+        }
+        assert!(!reputation.is_banned(&peer));
+        assert_eq!(reputation.status()[0].score, BAN_SCORE / 2);
+    }
+
+    #[test]
+    fn latency_orders_peers_with_the_same_score() {
+        let reputation = Reputation::new(Logger::root(slog::Discard, o!()));
+        let slow = address(2);
+        let fast = address(3);
+        let unknown = address(4);
+        reputation.record_latency(&slow, Duration::from_millis(400));
+        reputation.record_latency(&fast, Duration::from_millis(100));
+
+        let mut peers = vec![unknown.clone(), slow.clone(), fast.clone()];
+        reputation.prioritize(&mut peers);
+        assert_eq!(peers, vec![fast.clone(), slow.clone(), unknown.clone()]);
+
+        // the latency is averaged, a single slow request does not make the
+        // fast peer slower than the slow one
+        reputation.record_latency(&fast, Duration::from_millis(800));
+        let mut peers = vec![slow.clone(), fast.clone()];
+        reputation.prioritize(&mut peers);
+        assert_eq!(peers, vec![fast.clone(), slow.clone()]);
+
+        // the score comes before the latency
+        reputation.record(&slow, PeerEvent::BlockDelivered);
+        let mut peers = vec![fast.clone(), slow.clone()];
+        reputation.prioritize(&mut peers);
+        assert_eq!(peers, vec![slow, fast]);
+    }
+
+    #[test]
+    fn reputation_is_kept_by_ip() {
+        let reputation = Reputation::new(Logger::root(slog::Discard, o!()));
+        let listening = address_with_port(1, 3000);
+        let inbound = address_with_port(1, 49152);
+        let reconnected = address_with_port(1, 49153);
+        reputation.record(&inbound, PeerEvent::InvalidBlock);
+        reputation.record(&reconnected, PeerEvent::InvalidBlock);
+
+        // the violations add up across the connections of the peer and the
+        // ban applies to all of them
+        assert!(reputation.is_banned(&listening));
+        assert!(reputation.is_banned(&address_with_port(1, 49154)));
+        assert!(reputation.is_ip_banned("127.0.0.1".parse().unwrap()));
+        assert!(!reputation.is_banned(&address_with_port(2, 49152)));
+        assert_eq!(reputation.status().len(), 1);
+    }
+}
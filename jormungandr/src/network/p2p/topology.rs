@@ -3,7 +3,10 @@
 
 use crate::{
     log::KEY_SUB_TASK,
-    network::p2p::{layers::PreferredListLayer, Address, Gossips, Policy, PolicyConfig},
+    network::p2p::{
+        layers::PreferredListLayer, Address, Gossips, PeerRecord, Policy, QuarantineRecords,
+        Reputation,
+    },
     settings::start::network::Configuration,
 };
 use poldercast::{
@@ -22,28 +25,25 @@ pub struct View {
 /// object holding the P2pTopology of the Node
 pub struct P2pTopology {
     lock: RwLock<Topology>,
+    quarantine_records: QuarantineRecords,
+    reputation: Reputation,
 }
 
 /// Builder object used to initialize the `P2pTopology`
 struct Builder {
     topology: Topology,
-    logger: Logger,
 }
 
 impl Builder {
     /// Create a new topology for the given node profile
-    fn new(node: poldercast::NodeProfile, logger: Logger) -> Self {
+    fn new(node: poldercast::NodeProfile) -> Self {
         Builder {
             topology: Topology::new(node),
-            logger,
         }
     }
 
-    fn set_policy(mut self, policy: PolicyConfig) -> Self {
-        self.topology.set_policy(Policy::new(
-            policy,
-            self.logger.new(o!(KEY_SUB_TASK => "policy")),
-        ));
+    fn set_policy(mut self, policy: Policy) -> Self {
+        self.topology.set_policy(policy);
         self
     }
 
@@ -71,27 +71,72 @@ impl Builder {
         self
     }
 
-    fn build(self) -> P2pTopology {
+    fn build(self, quarantine_records: QuarantineRecords, reputation: Reputation) -> P2pTopology {
         P2pTopology {
             lock: RwLock::new(self.topology),
+            quarantine_records,
+            reputation,
         }
     }
 }
 
 impl P2pTopology {
     pub fn new(config: &Configuration, logger: Logger) -> Self {
-        Builder::new(config.profile.clone(), logger)
+        let policy = Policy::new(
+            config.policy.clone(),
+            logger.new(o!(KEY_SUB_TASK => "policy")),
+        );
+        let quarantine_records = policy.quarantine_records();
+        let reputation = Reputation::new(logger.new(o!(KEY_SUB_TASK => "reputation")));
+        Builder::new(config.profile.clone())
             .set_poldercast_modules()
             .set_custom_modules(&config)
-            .set_policy(config.policy.clone())
-            .build()
+            .set_policy(policy)
+            .build(quarantine_records, reputation)
+    }
+
+    /// the reputation of the peers, which orders the peers of the view
+    pub fn reputation(&self) -> &Reputation {
+        &self.reputation
+    }
+
+    /// restore the quarantine history of the peers saved in the peer table,
+    /// so the peers quarantined before the node restarted are quarantined
+    /// for longer again
+    pub fn restore_quarantine_records(&self, peers: &[PeerRecord]) {
+        for peer in peers {
+            if peer.quarantine_count > 0 {
+                self.quarantine_records
+                    .restore(peer.address.clone(), peer.quarantine_count);
+            }
+        }
+    }
+
+    /// the nodes of the topology that can be reached, with their quarantine
+    /// history, to be saved in the peer table
+    pub async fn known_peers(&self) -> Vec<PeerRecord> {
+        let topology = self.lock.read().await;
+        let nodes = topology.nodes();
+        let available = nodes.all_available_nodes().into_iter().map(|n| (n, false));
+        let quarantined = nodes.all_quarantined_nodes().into_iter().map(|n| (n, true));
+        available
+            .chain(quarantined)
+            .map(|(node, quarantined)| PeerRecord {
+                address: node.address().clone(),
+                last_seen: node.logs().last_update(),
+                quarantine_count: self.quarantine_records.quarantine_count(node.address()),
+                quarantined,
+            })
+            .collect()
     }
 
     /// Returns a list of neighbors selected in this turn
-    /// to contact for event dissemination.
+    /// to contact for event dissemination, the peers with the best
+    /// reputation first. The banned peers are left out.
     pub async fn view(&self, selection: poldercast::Selection) -> View {
         let mut topology = self.lock.write().await;
-        let peers = topology.view(None, selection).into_iter().collect();
+        let mut peers = topology.view(None, selection).into_iter().collect();
+        self.reputation.prioritize(&mut peers);
         View {
             self_node: topology.profile().clone(),
             peers,
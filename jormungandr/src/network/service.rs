@@ -6,7 +6,7 @@ use super::{
     subscription, Channels, GlobalStateR,
 };
 use crate::blockcfg as app_data;
-use crate::intercom::{self, BlockMsg, ClientMsg};
+use crate::intercom::{self, BlockMsg, ClientMsg, TransactionMsg};
 use crate::utils::async_msg::MessageBox;
 use chain_network::core::server::{BlockService, FragmentService, GossipService, Node, PushStream};
 use chain_network::data::{
@@ -20,6 +20,7 @@ use futures::try_join;
 use slog::Logger;
 
 use std::convert::TryFrom;
+use std::net::SocketAddr;
 
 #[derive(Clone)]
 pub struct NodeService {
@@ -49,6 +50,38 @@ impl NodeService {
         self.logger
             .new(o!("peer" => subscriber.to_string(), "stream" => stream_name))
     }
+
+    /// refuses the subscriptions of the peers outside of the allow-list
+    /// in permissioned mode, and of the banned peers. Returns the address
+    /// of the subscriber.
+    fn check_subscriber(&self, addr: SocketAddr, logger: &Logger) -> Result<Address, Error> {
+        if let Some(allow_list) = &self.global_state.config.allow_list {
+            if !allow_list.allows_ip(addr.ip()) {
+                allow_list.reject_subscription();
+                info!(
+                    logger,
+                    "refusing the subscription of a peer outside of the allow-list"
+                );
+                return Err(Error::new(
+                    net_error::Code::PermissionDenied,
+                    "the peer is not in the allow-list of the node",
+                ));
+            }
+        }
+        if self
+            .global_state
+            .topology
+            .reputation()
+            .is_ip_banned(addr.ip())
+        {
+            info!(logger, "refusing the subscription of a banned peer");
+            return Err(Error::new(
+                net_error::Code::PermissionDenied,
+                "the peer is banned for protocol violations",
+            ));
+        }
+        Ok(Address::new(addr).unwrap())
+    }
 }
 
 impl Node for NodeService {
@@ -166,7 +199,7 @@ impl BlockService for NodeService {
         let (handle, sink, reply) =
             intercom::stream_request(buffer_sizes::inbound::HEADERS, logger.clone());
         let block_box = self.channels.block_box.clone();
-        send_message(block_box, BlockMsg::ChainHeaders(handle), logger).await?;
+        send_message(block_box, BlockMsg::ChainHeaders(handle, None), logger).await?;
         try_join!(
             stream
                 .and_then(|header| async { header.decode() })
@@ -181,7 +214,7 @@ impl BlockService for NodeService {
         let (handle, sink, reply) =
             intercom::stream_request(buffer_sizes::inbound::BLOCKS, logger.clone());
         let block_box = self.channels.block_box.clone();
-        send_message(block_box, BlockMsg::NetworkBlocks(handle), logger).await?;
+        send_message(block_box, BlockMsg::NetworkBlocks(handle, None), logger).await?;
         try_join!(
             stream
                 .and_then(|block| async { block.decode() })
@@ -198,7 +231,7 @@ impl BlockService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "block_events");
-        let subscriber = Address::new(addr).unwrap();
+        let subscriber = self.check_subscriber(addr, &logger)?;
 
         self.global_state
             .spawn(subscription::process_block_announcements(
@@ -223,8 +256,19 @@ impl FragmentService for NodeService {
     type GetFragmentsStream = ResponseStream<app_data::Fragment>;
     type SubscriptionStream = SubscriptionStream<FragmentSubscription>;
 
-    async fn get_fragments(&self, _ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
-        Err(net_error::Error::unimplemented())
+    async fn get_fragments(&self, ids: FragmentIds) -> Result<Self::GetFragmentsStream, Error> {
+        let ids = ids.decode()?;
+        let logger = self.logger().new(o!("request" => "GetFragments"));
+        let (handle, stream) =
+            intercom::stream_reply(buffer_sizes::outbound::FRAGMENTS, logger.clone());
+        let transaction_box = self.channels.transaction_box.clone();
+        send_message(
+            transaction_box,
+            TransactionMsg::GetFragments(ids, handle),
+            logger,
+        )
+        .await?;
+        Ok(convert::response_stream(stream))
     }
 
     async fn fragment_subscription(
@@ -234,7 +278,7 @@ impl FragmentService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "fragments");
-        let subscriber = Address::new(addr).unwrap();
+        let subscriber = self.check_subscriber(addr, &logger)?;
 
         self.global_state.spawn(subscription::process_fragments(
             stream,
@@ -264,7 +308,7 @@ impl GossipService for NodeService {
     ) -> Result<Self::SubscriptionStream, Error> {
         let addr = subscriber.addr();
         let logger = self.subscription_logger(subscriber, "gossip");
-        let subscriber = Address::new(addr).unwrap();
+        let subscriber = self.check_subscriber(addr, &logger)?;
 
         self.global_state.spawn(subscription::process_gossip(
             stream,
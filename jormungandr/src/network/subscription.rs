@@ -1,7 +1,7 @@
 use super::{
     buffer_sizes,
     convert::Decode,
-    p2p::{Address, Gossip},
+    p2p::{Address, Gossip, PeerEvent},
     GlobalStateR,
 };
 use crate::{
@@ -12,7 +12,7 @@ use crate::{
 };
 use chain_network::data as net_data;
 use chain_network::error::{Code, Error};
-use jormungandr_lib::interfaces::FragmentOrigin;
+use jormungandr_lib::{interfaces::FragmentOrigin, multiaddr::multiaddr_to_socket_addr};
 
 use futures::future::BoxFuture;
 use futures::prelude::*;
@@ -242,6 +242,10 @@ impl Sink<net_data::Header> for BlockAnnouncementProcessor {
                 "failed to decode incoming block announcement header";
                 "reason" => %e.source().unwrap(),
             );
+            self.global_state
+                .topology
+                .reputation()
+                .record(&self.node_id, PeerEvent::MalformedHeader);
             e
         })?;
         let node_id = self.node_id.clone();
@@ -302,6 +306,10 @@ impl Sink<net_data::Fragment> for FragmentProcessor {
                 "failed to decode incoming fragment";
                 "reason" => %e.source().unwrap(),
             );
+            self.global_state
+                .topology
+                .reputation()
+                .record(&self.node_id, PeerEvent::MalformedFragment);
             e
         })?;
         self.buffered_fragments.push(fragment);
@@ -357,7 +365,9 @@ impl FragmentProcessor {
         self.mbox
             .start_send(TransactionMsg::SendTransaction(
                 FragmentOrigin::Network,
+                multiaddr_to_socket_addr(self.node_id.multi_address()).map(|addr| addr.ip()),
                 fragments,
+                None,
             ))
             .map_err(|e| {
                 error!(
@@ -402,8 +412,30 @@ impl Sink<net_data::Gossip> for GossipProcessor {
                 "failed to decode incoming gossip";
                 "reason" => %e.source().unwrap(),
             );
+            self.global_state
+                .topology
+                .reputation()
+                .record(&self.node_id, PeerEvent::MalformedGossip);
             e
         })?;
+        let mut nodes = nodes;
+        let reputation = self.global_state.topology.reputation();
+        nodes.retain(|node| {
+            node.address()
+                .map_or(true, |address| !reputation.is_banned(address))
+        });
+        if let Some(allow_list) = &self.global_state.config.allow_list {
+            let count = nodes.len();
+            nodes.retain(|node| allow_list.allows_gossip_node(node));
+            if nodes.len() < count {
+                allow_list.reject_gossip_nodes(count - nodes.len());
+                debug!(
+                    self.logger,
+                    "dropped {} nodes outside of the allow-list from gossip",
+                    count - nodes.len()
+                );
+            }
+        }
         let (nodes, filtered_out): (Vec<_>, Vec<_>) = nodes.into_iter().partition(|node| {
             filter_gossip_node(node, &self.global_state.config) || node.address().is_none()
         });
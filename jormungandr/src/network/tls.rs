@@ -0,0 +1,446 @@
+//! Transport security of the P2P connections.
+//!
+//! The nodes are identified by the key pair of their TLS certificate: the
+//! identity of a node is the hash of the public key of its certificate, so
+//! it does not change when the certificate is renewed with the same key.
+//! There is no certificate authority between the nodes, the certificates
+//! are accepted as long as the peer proves it holds the private key, and
+//! the identity of a peer is checked when it is pinned in the configuration
+//! of its trusted peer entry.
+
+use super::p2p::AllowList;
+use chain_crypto::Blake2b256;
+use jormungandr_lib::crypto::hash::Hash;
+use rustls::{
+    internal::pemfile, Certificate, ClientCertVerified, ClientCertVerifier, ClientConfig,
+    DistinguishedNames, PrivateKey, RootCertStore, ServerCertVerified, ServerCertVerifier,
+    ServerConfig, TLSError,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tonic::transport::{ClientTlsConfig, ServerTlsConfig};
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// name presented to the peers, the nodes are reached by IP address and
+/// are not authenticated by name
+const TLS_DOMAIN_NAME: &str = "jormungandr";
+
+const ALPN_H2: &[u8] = b"h2";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot read {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("no PEM encoded certificate in {}", path.display())]
+    NoCertificate { path: PathBuf },
+    #[error("no PEM encoded PKCS#8 private key in {}", path.display())]
+    NoPrivateKey { path: PathBuf },
+    #[error("cannot decode the public key of the node certificate")]
+    InvalidCertificate,
+    #[error("the private key does not match the certificate")]
+    KeyMismatch(#[source] TLSError),
+}
+
+/// identity of a node: the hash of the public key of its TLS certificate
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeIdentity(Hash);
+
+impl NodeIdentity {
+    /// the identity of the node presenting the given DER encoded certificate
+    pub fn from_certificate(certificate: &[u8]) -> Option<Self> {
+        subject_public_key_info(certificate)
+            .map(|public_key| NodeIdentity(Blake2b256::new(public_key).into()))
+    }
+}
+
+impl fmt::Display for NodeIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl FromStr for NodeIdentity {
+    type Err = chain_crypto::hash::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(NodeIdentity)
+    }
+}
+
+/// the TLS settings of the node, loaded from the files of the configuration
+#[derive(Clone)]
+pub struct TlsConfig {
+    certificates: Vec<Certificate>,
+    private_key: PrivateKey,
+    identity: NodeIdentity,
+    /// require a certificate from the connecting peers
+    mutual: bool,
+    /// the identities allowed in permissioned mode
+    allow_list: Option<Arc<AllowList>>,
+    server_config: ServerConfig,
+}
+
+impl TlsConfig {
+    /// load the PEM encoded certificate chain, the node certificate first,
+    /// and its PKCS#8 private key. The peers must have an identity of the
+    /// allow-list if it is given.
+    pub fn load(
+        certificate: &Path,
+        private_key: &Path,
+        mutual: bool,
+        allow_list: Option<Arc<AllowList>>,
+    ) -> Result<Self, Error> {
+        let certificates =
+            pemfile::certs(&mut open(certificate)?).map_err(|()| Error::NoCertificate {
+                path: certificate.to_path_buf(),
+            })?;
+        let identity = certificates
+            .first()
+            .ok_or_else(|| Error::NoCertificate {
+                path: certificate.to_path_buf(),
+            })
+            .and_then(|certificate| {
+                NodeIdentity::from_certificate(&certificate.0).ok_or(Error::InvalidCertificate)
+            })?;
+        let private_key = pemfile::pkcs8_private_keys(&mut open(private_key)?)
+            .ok()
+            .and_then(|keys| keys.into_iter().next())
+            .ok_or_else(|| Error::NoPrivateKey {
+                path: private_key.to_path_buf(),
+            })?;
+
+        // also checks the key pair now rather than on the first connection
+        let mut server_config = ServerConfig::new(Arc::new(PeerCertificateVerifier {
+            mandatory: mutual,
+            allow_list: allow_list.clone(),
+        }));
+        server_config
+            .set_single_cert(certificates.clone(), private_key.clone())
+            .map_err(Error::KeyMismatch)?;
+        server_config.set_protocols(&[ALPN_H2.to_vec()]);
+
+        Ok(TlsConfig {
+            certificates,
+            private_key,
+            identity,
+            mutual,
+            allow_list,
+            server_config,
+        })
+    }
+
+    pub fn identity(&self) -> NodeIdentity {
+        self.identity
+    }
+
+    pub fn mutual(&self) -> bool {
+        self.mutual
+    }
+
+    /// the TLS settings of the listening socket
+    pub fn server_tls(&self) -> ServerTlsConfig {
+        let mut tls = ServerTlsConfig::new();
+        tls.rustls_server_config(self.server_config.clone());
+        tls
+    }
+
+    /// the TLS settings of a connection to a peer, which must have the given
+    /// identity if it is set. The node presents its own certificate with
+    /// mutual TLS.
+    pub fn client_tls(&self, expected: Option<NodeIdentity>) -> Result<ClientTlsConfig, Error> {
+        let mut config = ClientConfig::new();
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(PinnedIdentityVerifier {
+                expected,
+                allow_list: self.allow_list.clone(),
+            }));
+        if self.mutual {
+            config
+                .set_single_client_cert(self.certificates.clone(), self.private_key.clone())
+                .map_err(Error::KeyMismatch)?;
+        }
+        config.set_protocols(&[ALPN_H2.to_vec()]);
+
+        let mut tls = ClientTlsConfig::new();
+        tls.rustls_client_config(config)
+            .domain_name(TLS_DOMAIN_NAME);
+        Ok(tls)
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, Error> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+/// identity of the node presenting the given certificate chain, which must be
+/// in the allow-list if any
+fn peer_identity(
+    presented_certs: &[Certificate],
+    allow_list: Option<&AllowList>,
+) -> Result<NodeIdentity, TLSError> {
+    let identity = presented_certs
+        .first()
+        .and_then(|certificate| NodeIdentity::from_certificate(&certificate.0))
+        .ok_or_else(|| TLSError::General("invalid peer certificate".to_owned()))?;
+    match allow_list {
+        Some(allow_list) if !allow_list.allows_identity(&identity) => {
+            allow_list.reject_connection();
+            Err(TLSError::General(format!(
+                "peer identity {} is not allowed",
+                identity
+            )))
+        }
+        _ => Ok(identity),
+    }
+}
+
+/// accepts the server certificates of the expected identity, or any valid
+/// certificate if no identity is expected
+struct PinnedIdentityVerifier {
+    expected: Option<NodeIdentity>,
+    allow_list: Option<Arc<AllowList>>,
+}
+
+impl ServerCertVerifier for PinnedIdentityVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        let identity = peer_identity(presented_certs, self.allow_list.as_deref())?;
+        match self.expected {
+            Some(expected) if expected != identity => Err(TLSError::General(format!(
+                "unexpected peer identity {}, expected {}",
+                identity, expected
+            ))),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+}
+
+/// accepts any valid client certificate, and requires one with mutual TLS
+struct PeerCertificateVerifier {
+    mandatory: bool,
+    allow_list: Option<Arc<AllowList>>,
+}
+
+impl ClientCertVerifier for PeerCertificateVerifier {
+    fn client_auth_mandatory(&self, _sni: Option<&webpki::DNSName>) -> Option<bool> {
+        Some(self.mandatory)
+    }
+
+    fn client_auth_root_subjects(
+        &self,
+        _sni: Option<&webpki::DNSName>,
+    ) -> Option<DistinguishedNames> {
+        Some(DistinguishedNames::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[Certificate],
+        _sni: Option<&webpki::DNSName>,
+    ) -> Result<ClientCertVerified, TLSError> {
+        peer_identity(presented_certs, self.allow_list.as_deref())
+            .map(|_| ClientCertVerified::assertion())
+    }
+}
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_EXPLICIT_VERSION: u8 = 0xa0;
+
+/// split the DER element at the start of the input, returns its tag, its
+/// content, the whole element and the remaining input
+fn der_element(input: &[u8]) -> Option<(u8, &[u8], &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let nb_bytes = (first & 0x7f) as usize;
+        if nb_bytes == 0 || nb_bytes > 4 || rest.len() < nb_bytes {
+            return None;
+        }
+        let len = rest[..nb_bytes]
+            .iter()
+            .fold(0, |len, byte| (len << 8) | *byte as usize);
+        (len, &rest[nb_bytes..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let header_len = input.len() - rest.len();
+    Some((tag, &rest[..len], &input[..header_len + len], &rest[len..]))
+}
+
+/// the DER encoded `subjectPublicKeyInfo` of an X.509 certificate
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (tag, certificate, _, _) = der_element(certificate)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+    let (tag, mut tbs_certificate, _, _) = der_element(certificate)?;
+    if tag != DER_SEQUENCE {
+        return None;
+    }
+    if tbs_certificate.first() == Some(&DER_EXPLICIT_VERSION) {
+        tbs_certificate = der_element(tbs_certificate)?.3;
+    }
+    // skip the serial number, the signature algorithm, the issuer, the
+    // validity and the subject
+    for _ in 0..5 {
+        tbs_certificate = der_element(tbs_certificate)?.3;
+    }
+    match der_element(tbs_certificate)? {
+        (DER_SEQUENCE, _, public_key_info, _) => Some(public_key_info),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// self-signed Ed25519 certificate
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBLTCB4KADAgECAgEBMAUGAytlcDAWMRQwEgYDVQQDDAtqb3JtdW5nYW5kcjAe
+Fw0yNjEwMTgwOTIzMjhaFw0yNjExMTcwOTIzMjhaMBYxFDASBgNVBAMMC2pvcm11
+bmdhbmRyMCowBQYDK2VwAyEA5oln+GpBGJx0JZvx1YrTCfK8bWg8zuPU0eet2ugc
+cNGjUzBRMB0GA1UdDgQWBBS1qUAYMily5SUSz7iDOOFUWLCo6zAfBgNVHSMEGDAW
+gBS1qUAYMily5SUSz7iDOOFUWLCo6zAPBgNVHRMBAf8EBTADAQH/MAUGAytlcANB
+AAwJd8Im14OLNgQ0iq5n9QMloJMkQwzs1V5xvCE0Fqetf1j5254zwQXZaElwF0bf
+0mBQ8G49IvtKxUxshmk57wI=
+-----END CERTIFICATE-----
+";
+
+    /// `CERTIFICATE` issued again with the same key, another serial number
+    /// and another validity
+    const REISSUED_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBLTCB4KADAgECAgECMAUGAytlcDAWMRQwEgYDVQQDDAtqb3JtdW5nYW5kcjAe
+Fw0yNjEwMTgwOTIzMjhaFw0yNzEwMTgwOTIzMjhaMBYxFDASBgNVBAMMC2pvcm11
+bmdhbmRyMCowBQYDK2VwAyEA5oln+GpBGJx0JZvx1YrTCfK8bWg8zuPU0eet2ugc
+cNGjUzBRMB0GA1UdDgQWBBS1qUAYMily5SUSz7iDOOFUWLCo6zAfBgNVHSMEGDAW
+gBS1qUAYMily5SUSz7iDOOFUWLCo6zAPBgNVHRMBAf8EBTADAQH/MAUGAytlcANB
+AFKDpOL6DmFqUnZeSTeCFPnIgYceCIpu4rQOkfIdZKlDl6R1MDxomdDG0fNewwcR
+MQJ94Kg84VyHqLwU+x4Tqw0=
+-----END CERTIFICATE-----
+";
+
+    /// same subject and serial number as `CERTIFICATE`, with another key
+    const OTHER_KEY_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBLTCB4KADAgECAgEBMAUGAytlcDAWMRQwEgYDVQQDDAtqb3JtdW5nYW5kcjAe
+Fw0yNjEwMTgwOTIzMjhaFw0yNjExMTcwOTIzMjhaMBYxFDASBgNVBAMMC2pvcm11
+bmdhbmRyMCowBQYDK2VwAyEAos0n/dtNGDVl1hATqmE0e6TojrfOG0Mvepg/M/mB
+A6GjUzBRMB0GA1UdDgQWBBSried1VrvNTBLVMTnyedEWRF4OczAfBgNVHSMEGDAW
+gBSried1VrvNTBLVMTnyedEWRF4OczAPBgNVHRMBAf8EBTADAQH/MAUGAytlcANB
+AJnPk3aUay9rQdHYJCpT1ys4WSCNqpXisJgplf+wZsCKtrJ78nTuIAU7WdvzHEOt
+C36eNfJq8H59O6MWYolyjgA=
+-----END CERTIFICATE-----
+";
+
+    /// the DER encoded `subjectPublicKeyInfo` of `CERTIFICATE`, as given by
+    /// `openssl pkey -pubout -outform DER`
+    const SUBJECT_PUBLIC_KEY_INFO: &[u8] = &[
+        0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00, 0xe6, 0x89, 0x67,
+        0xf8, 0x6a, 0x41, 0x18, 0x9c, 0x74, 0x25, 0x9b, 0xf1, 0xd5, 0x8a, 0xd3, 0x09, 0xf2, 0xbc,
+        0x6d, 0x68, 0x3c, 0xce, 0xe3, 0xd4, 0xd1, 0xe7, 0xad, 0xda, 0xe8, 0x1c, 0x70, 0xd1,
+    ];
+
+    fn der(pem: &str) -> Vec<u8> {
+        let mut certificates = pemfile::certs(&mut pem.as_bytes()).unwrap();
+        assert_eq!(certificates.len(), 1);
+        certificates.remove(0).0
+    }
+
+    #[test]
+    fn subject_public_key_info_is_found() {
+        let certificate = der(CERTIFICATE);
+        // the lengths of the certificate and of its body are in long form
+        assert_eq!(&certificate[..2], &[DER_SEQUENCE, 0x82]);
+        assert_eq!(
+            subject_public_key_info(&certificate),
+            Some(SUBJECT_PUBLIC_KEY_INFO)
+        );
+    }
+
+    #[test]
+    fn identity_is_kept_when_the_certificate_is_reissued() {
+        let identity = NodeIdentity::from_certificate(&der(CERTIFICATE)).unwrap();
+        assert_eq!(
+            NodeIdentity::from_certificate(&der(REISSUED_CERTIFICATE)),
+            Some(identity)
+        );
+        assert_ne!(
+            NodeIdentity::from_certificate(&der(OTHER_KEY_CERTIFICATE)),
+            Some(identity)
+        );
+        assert_eq!(
+            identity.to_string().parse::<NodeIdentity>().unwrap(),
+            identity
+        );
+    }
+
+    #[test]
+    fn truncated_certificate_is_rejected() {
+        let certificate = der(CERTIFICATE);
+        for len in 0..certificate.len() {
+            assert_eq!(subject_public_key_info(&certificate[..len]), None);
+        }
+
+        // a well formed certificate body ending right after the subject
+        let spki_start = certificate
+            .windows(SUBJECT_PUBLIC_KEY_INFO.len())
+            .position(|window| window == SUBJECT_PUBLIC_KEY_INFO)
+            .unwrap();
+        assert_eq!(&certificate[4..6], &[DER_SEQUENCE, 0x81]);
+        let mut without_key = certificate[..spki_start].to_vec();
+        without_key[2..4].copy_from_slice(&((spki_start - 4) as u16).to_be_bytes());
+        without_key[6] = (spki_start - 7) as u8;
+        assert_eq!(subject_public_key_info(&without_key), None);
+    }
+
+    #[test]
+    fn invalid_lengths_are_rejected() {
+        // indefinite length
+        assert!(der_element(&[DER_SEQUENCE, 0x80, 0x00, 0x00]).is_none());
+        // the length does not fit in 4 bytes
+        assert!(der_element(&[DER_SEQUENCE, 0x85, 0, 0, 0, 0, 1, 0]).is_none());
+        // the bytes of the length are missing
+        assert!(der_element(&[DER_SEQUENCE, 0x82, 0x01]).is_none());
+        // the content is shorter than the length
+        assert!(der_element(&[DER_SEQUENCE, 0x81, 0x02, 0x01]).is_none());
+        assert!(der_element(&[DER_SEQUENCE, 0x03, 0x01, 0x02]).is_none());
+
+        let (tag, content, element, rest) =
+            der_element(&[DER_SEQUENCE, 0x81, 0x02, 0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(tag, DER_SEQUENCE);
+        assert_eq!(content, &[0x01, 0x02]);
+        assert_eq!(element, &[DER_SEQUENCE, 0x81, 0x02, 0x01, 0x02]);
+        assert_eq!(rest, &[0x03]);
+
+        // a certificate which is not a sequence
+        let mut certificate = der(CERTIFICATE);
+        certificate[0] = 0x31;
+        assert_eq!(subject_public_key_info(&certificate), None);
+    }
+}
@@ -1,3 +1,4 @@
+use super::subscriptions;
 use crate::{
     explorer::graphql::GraphQLRequest,
     rest::{context, ContextLock},
@@ -5,7 +6,7 @@ use crate::{
 
 use thiserror::Error;
 use tokio::task::{spawn_blocking, JoinError};
-use warp::{reject::Reject, Rejection, Reply};
+use warp::{reject::Reject, ws::Ws, Rejection, Reply};
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -48,3 +49,22 @@ pub async fn graphql(data: GraphQLRequest, context: ContextLock) -> Result<impl
     .map_err(Error::BlockingError)
     .map_err(warp::reject::custom)?
 }
+
+pub async fn subscriptions(ws: Ws, context: ContextLock) -> Result<impl Reply, Rejection> {
+    let explorer = context
+        .read()
+        .await
+        .try_full()
+        .map_err(Error::Context)
+        .map_err(warp::reject::custom)?
+        .explorer
+        .clone()
+        .unwrap();
+
+    let reply = ws.on_upgrade(move |socket| subscriptions::serve(socket, explorer));
+    Ok(warp::reply::with_header(
+        reply,
+        "sec-websocket-protocol",
+        subscriptions::PROTOCOL,
+    ))
+}
@@ -1,4 +1,5 @@
 mod handlers;
+mod subscriptions;
 
 use crate::rest::{display_internal_server_error, ContextLock};
 
@@ -13,16 +14,22 @@ pub fn filter(
     let graphql = warp::path!("graphql")
         .and(warp::post())
         .and(warp::body::json())
-        .and(with_context)
+        .and(with_context.clone())
         .and_then(handlers::graphql)
         .boxed();
 
+    let subscriptions = warp::path!("graphql")
+        .and(warp::ws())
+        .and(with_context)
+        .and_then(handlers::subscriptions)
+        .boxed();
+
     let graphiql = warp::path!("graphiql")
         .and(warp::get())
         .and_then(handlers::graphiql)
         .boxed();
 
-    root.and(graphql.or(graphiql))
+    root.and(graphql.or(subscriptions).or(graphiql))
         .recover(handle_rejection)
         .boxed()
 }
@@ -0,0 +1,249 @@
+//! Transport of the explorer GraphQL subscriptions over websocket, following
+//! the `graphql-ws` protocol of `subscriptions-transport-ws`.
+
+use crate::explorer::{
+    graphql::{SubscriptionRequest, SubscriptionResponse},
+    Explorer, NewBlockEvent,
+};
+
+use futures::future::{AbortHandle, Abortable};
+use futures::prelude::*;
+use futures::stream::SplitStream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::spawn_blocking;
+use warp::ws::{Message, WebSocket};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// websocket sub-protocol implemented by the subscriptions endpoint
+pub const PROTOCOL: &str = "graphql-ws";
+
+/// number of messages waiting to be sent to a client, a client letting more
+/// messages pile up is disconnected
+const OUTGOING_MESSAGES_BUFFER: usize = 32;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    ConnectionInit {},
+    Start { id: String, payload: StartPayload },
+    Stop { id: String },
+    ConnectionTerminate,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartPayload {
+    query: String,
+    #[serde(default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: Option<juniper::InputValue>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    ConnectionAck,
+    ConnectionError {
+        payload: serde_json::Value,
+    },
+    Data {
+        id: &'a str,
+        payload: serde_json::Value,
+    },
+    Error {
+        id: &'a str,
+        payload: serde_json::Value,
+    },
+    Complete {
+        id: &'a str,
+    },
+}
+
+impl<'a> ServerMessage<'a> {
+    fn to_message(&self) -> Message {
+        Message::text(serde_json::to_string(self).expect("server message to be serializable"))
+    }
+}
+
+/// serve the subscriptions of a websocket connection until it is closed
+pub async fn serve(socket: WebSocket, explorer: Explorer) {
+    let (mut socket_sink, socket_stream) = socket.split();
+    let (messages, mut outgoing) = mpsc::channel::<Message>(OUTGOING_MESSAGES_BUFFER);
+
+    tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if socket_sink.send(message).await.is_err() {
+                break;
+            }
+        }
+        let _ = socket_sink.close().await;
+    });
+
+    // the connection is closed when a subscription cannot queue its messages,
+    // the subscriptions are stopped when the handling of the connection ends
+    let (connection, connection_registration) = AbortHandle::new_pair();
+    let _ = Abortable::new(
+        handle_client_messages(socket_stream, explorer, messages, connection),
+        connection_registration,
+    )
+    .await;
+}
+
+/// the running subscriptions of a connection, stopped when dropped
+struct Subscriptions(HashMap<String, AbortHandle>);
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        for subscription in self.0.values() {
+            subscription.abort();
+        }
+    }
+}
+
+async fn handle_client_messages(
+    mut socket_stream: SplitStream<WebSocket>,
+    explorer: Explorer,
+    mut messages: mpsc::Sender<Message>,
+    connection: AbortHandle,
+) {
+    let mut subscriptions = Subscriptions(HashMap::new());
+
+    while let Some(Ok(message)) = socket_stream.next().await {
+        if message.is_close() {
+            break;
+        }
+        let text = match message.to_str() {
+            Ok(text) => text,
+            // ping, pong and binary messages
+            Err(()) => continue,
+        };
+
+        let client_message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(client_message) => client_message,
+            Err(err) => {
+                let reply = ServerMessage::ConnectionError {
+                    payload: serde_json::json!({ "message": err.to_string() }),
+                };
+                if messages.send(reply.to_message()).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        match client_message {
+            ClientMessage::ConnectionInit {} => {
+                if messages
+                    .send(ServerMessage::ConnectionAck.to_message())
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            ClientMessage::Start { id, payload } => {
+                if let Some(previous) = subscriptions.0.remove(&id) {
+                    previous.abort();
+                }
+                let request = match SubscriptionRequest::new(
+                    payload.query,
+                    payload.operation_name,
+                    payload.variables,
+                ) {
+                    Ok(request) => request,
+                    Err(err) => {
+                        let payload = serde_json::json!({ "message": err.to_string() });
+                        let reply = ServerMessage::Error { id: &id, payload };
+                        if messages.send(reply.to_message()).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let (abort_handle, abort_registration) = AbortHandle::new_pair();
+                subscriptions.0.insert(id.clone(), abort_handle);
+                let subscription = run_subscription(
+                    id,
+                    Arc::new(request),
+                    explorer.clone(),
+                    explorer.subscribe(),
+                    messages.clone(),
+                    connection.clone(),
+                );
+                tokio::spawn(Abortable::new(subscription, abort_registration));
+            }
+            ClientMessage::Stop { id } => {
+                if let Some(subscription) = subscriptions.0.remove(&id) {
+                    subscription.abort();
+                    let reply = ServerMessage::Complete { id: &id };
+                    if messages.send(reply.to_message()).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            ClientMessage::ConnectionTerminate => break,
+        }
+    }
+}
+
+async fn run_subscription(
+    id: String,
+    request: Arc<SubscriptionRequest>,
+    explorer: Explorer,
+    mut events: broadcast::Receiver<NewBlockEvent>,
+    mut messages: mpsc::Sender<Message>,
+    connection: AbortHandle,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            // the events missed are not replayed, the subscription carries on
+            // with the next one
+            Err(broadcast::RecvError::Lagged(_)) => continue,
+            Err(broadcast::RecvError::Closed) => break,
+        };
+
+        let request = request.clone();
+        let explorer = explorer.clone();
+        // Run the query in a threadpool, as Juniper is synchronous
+        let response = spawn_blocking(move || {
+            let context = explorer.subscription_context(event);
+            request.execute(&explorer.subscription_schema, &context)
+        })
+        .await;
+
+        let (message, last) = match response {
+            Ok(SubscriptionResponse::Nothing) => continue,
+            Ok(SubscriptionResponse::Data(payload)) => {
+                (ServerMessage::Data { id: &id, payload }, false)
+            }
+            Ok(SubscriptionResponse::Error(payload)) => {
+                (ServerMessage::Error { id: &id, payload }, true)
+            }
+            Err(err) => {
+                let payload = serde_json::json!({ "message": err.to_string() });
+                (ServerMessage::Error { id: &id, payload }, true)
+            }
+        };
+        match messages.try_send(message.to_message()) {
+            Ok(()) if !last => {}
+            Ok(()) => return,
+            // the client does not read the messages as fast as the blocks
+            // come, it is disconnected
+            Err(TrySendError::Full(_)) => {
+                connection.abort();
+                return;
+            }
+            Err(TrySendError::Closed(_)) => return,
+        }
+    }
+
+    let _ = messages
+        .send(ServerMessage::Complete { id: &id }.to_message())
+        .await;
+}
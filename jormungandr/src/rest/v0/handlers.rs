@@ -2,6 +2,7 @@ use crate::{
     rest::{v0::logic, ContextLock},
     secure::NodeSecret,
 };
+use std::net::SocketAddr;
 use warp::{reject::Reject, Rejection, Reply};
 
 impl Reject for logic::Error {}
@@ -18,6 +19,17 @@ pub async fn get_account_state(
         .ok_or(warp::reject::not_found())
 }
 
+pub async fn get_account_pending_fragments(
+    account_id_hex: String,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_account_pending_fragments(&context, &account_id_hex)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
 pub async fn get_message_logs(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_message_logs(&context)
@@ -26,16 +38,74 @@ pub async fn get_message_logs(context: ContextLock) -> Result<impl Reply, Reject
         .map(|r| warp::reply::json(&r))
 }
 
+#[derive(Deserialize)]
+pub struct GetFragmentStatusesQuery {
+    fragment_ids: String,
+}
+
+pub async fn get_fragment_statuses(
+    query: GetFragmentStatusesQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_fragment_statuses(&context, &query.fragment_ids)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
+#[derive(Deserialize)]
+pub struct GetPendingFragmentsQuery {
+    #[serde(default)]
+    full: bool,
+}
+
+pub async fn get_pending_fragments(
+    query: GetPendingFragmentsQuery,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_pending_fragments(&context, query.full)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
 pub async fn post_message(
     message: bytes::Bytes,
+    client: Option<SocketAddr>,
     context: ContextLock,
 ) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
-    logic::post_message(&context, &message)
+    logic::post_message(&context, &message, client)
         .await
         .map_err(warp::reject::custom)
 }
 
+pub async fn post_message_sync(
+    message: bytes::Bytes,
+    client: Option<SocketAddr>,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::post_message_sync(&context, &message, client)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
+pub async fn post_fragments(
+    fragments: Vec<String>,
+    client: Option<SocketAddr>,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::post_fragments(&context, fragments, client)
+        .await
+        .map_err(warp::reject::custom)
+        .map(|r| warp::reply::json(&r))
+}
+
 pub async fn get_tip(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_tip(&context).await.map_err(warp::reject::custom)
@@ -116,7 +186,7 @@ pub async fn get_shutdown(context: ContextLock) -> Result<impl Reply, Rejection>
 
 pub async fn get_leaders(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
-    logic::get_leader_ids(&context)
+    logic::get_leaders(&context)
         .await
         .map(|r| warp::reply::json(&r))
         .map_err(warp::reject::custom)
@@ -150,6 +220,22 @@ pub async fn get_leaders_logs(context: ContextLock) -> Result<impl Reply, Reject
         .map_err(warp::reject::custom)
 }
 
+pub async fn get_leaders_kes(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_leaders_kes(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+pub async fn get_leaders_schedule(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_leaders_schedule(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_stake_pools(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_stake_pools(&context)
@@ -254,6 +340,23 @@ pub async fn get_network_p2p_view(context: ContextLock) -> Result<impl Reply, Re
         .map_err(warp::reject::custom)
 }
 
+pub async fn get_network_p2p_allow_list(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_network_p2p_allow_list(&context)
+        .await
+        .map_err(warp::reject::custom)?
+        .map(|r| warp::reply::json(&r))
+        .ok_or(warp::reject::not_found())
+}
+
+pub async fn get_network_p2p_reputation(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_network_p2p_reputation(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_network_p2p_view_topic(
     topic: String,
     context: ContextLock,
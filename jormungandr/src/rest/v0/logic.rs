@@ -7,11 +7,11 @@
 //   they are 400 or 500.
 
 use crate::{
-    blockchain::StorageError,
+    blockchain::{new_epoch_leadership_from, Ref, StorageError},
     diagnostic::Diagnostic,
     intercom::{self, NetworkMsg, TransactionMsg},
     rest::Context,
-    secure::NodeSecret,
+    secure::{signer::Error as SignerError, NodeSecret},
 };
 use chain_core::property::{
     Block as _, Deserialize, Fragment as fragment_property, FromStr, Serialize,
@@ -23,6 +23,7 @@ use chain_crypto::{
 use chain_impl_mockchain::{
     account::{AccountAlg, Identifier},
     block::Block as ChainBlock,
+    chaintypes::ConsensusVersion,
     fragment::{Fragment, FragmentId},
     key::Hash,
     leadership::{Leader, LeadershipConsensus},
@@ -31,14 +32,22 @@ use chain_impl_mockchain::{
 };
 use jormungandr_lib::{
     interfaces::{
-        AccountState, EnclaveLeaderId, EpochRewardsInfo, FragmentLog, FragmentOrigin,
-        LeadershipLog, NodeStats, NodeStatsDto, PeerStats, Rewards as StakePoolRewards,
-        SettingsDto, StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde,
-        TransactionOutput, VotePlanWithId,
+        AccountPendingFragment, AccountState, EnclaveLeaderId, EpochRewardsInfo, FragmentLog,
+        FragmentOrigin, FragmentStatus, FragmentSubmission, LeaderKesStatus, LeaderStatus,
+        LeadershipLog, LeadershipScheduleEntry, NodeStats, NodeStatsDto, PeerAllowList,
+        PeerReputation, PeerStats, PendingFragment, Rewards as StakePoolRewards, SettingsDto,
+        StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde, TransactionOutput,
+        VotePlanWithId,
     },
     time::SystemTime,
 };
 
+use chain_time::{
+    era::{EpochPosition, EpochSlotOffset},
+    Epoch,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use futures::{channel::mpsc::SendError, channel::mpsc::TrySendError, prelude::*};
@@ -71,6 +80,16 @@ pub enum Error {
     Storage(#[from] StorageError),
     #[error("Invalid topic")]
     InvalidTopic,
+    #[error("The leaders are held by an external signer, they cannot be added or removed")]
+    ExternalSignerLeaders,
+    #[error(transparent)]
+    ExternalSigner(#[from] SignerError),
+    #[error("Fragment {index} is not hex-encoded")]
+    Hex {
+        index: usize,
+        #[source]
+        source: hex::FromHexError,
+    },
 }
 
 fn parse_account_id(id_hex: &str) -> Result<Identifier, Error> {
@@ -107,6 +126,25 @@ pub async fn get_account_state(
         .map(Into::into))
 }
 
+pub async fn get_account_pending_fragments(
+    context: &Context,
+    account_id_hex: &str,
+) -> Result<Vec<AccountPendingFragment>, Error> {
+    let account = parse_account_id(account_id_hex)?;
+    let logger = context
+        .logger()?
+        .new(o!("request" => "account_pending_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetAccountPending(account, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting account pending fragments"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
 pub async fn get_message_logs(context: &Context) -> Result<Vec<FragmentLog>, Error> {
     let logger = context.logger()?.new(o!("request" => "message_logs"));
     let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
@@ -120,14 +158,117 @@ pub async fn get_message_logs(context: &Context) -> Result<Vec<FragmentLog>, Err
     reply_future.await.map_err(Into::into)
 }
 
-pub async fn post_message(context: &Context, message: &[u8]) -> Result<String, Error> {
+pub async fn get_fragment_statuses(
+    context: &Context,
+    fragment_ids_hex: &str,
+) -> Result<HashMap<String, FragmentStatus>, Error> {
+    let fragment_ids = fragment_ids_hex
+        .split(',')
+        .filter(|id_hex| !id_hex.is_empty())
+        .map(parse_fragment_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    let logger = context.logger()?.new(o!("request" => "fragment_statuses"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetStatuses(fragment_ids, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting fragment statuses"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    let statuses = reply_future.await?;
+    Ok(statuses
+        .into_iter()
+        .map(|(fragment_id, status)| (fragment_id.to_string(), status))
+        .collect())
+}
+
+pub async fn get_pending_fragments(
+    context: &Context,
+    include_fragment: bool,
+) -> Result<Vec<PendingFragment>, Error> {
+    let logger = context.logger()?.new(o!("request" => "pending_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::GetPending(include_fragment, reply_handle))
+        .await
+        .map_err(|e| {
+            debug!(&logger, "error getting pending fragments"; "reason" => %e);
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
+pub async fn post_message(
+    context: &Context,
+    message: &[u8],
+    client: Option<SocketAddr>,
+) -> Result<String, Error> {
     let fragment = Fragment::deserialize(message).map_err(Error::Deserialize)?;
     let fragment_id = fragment.id().to_string();
-    let msg = TransactionMsg::SendTransaction(FragmentOrigin::Rest, vec![fragment]);
+    let msg = TransactionMsg::SendTransaction(
+        FragmentOrigin::Rest,
+        client.map(|addr| addr.ip()),
+        vec![fragment],
+        None,
+    );
     context.try_full()?.transaction_task.clone().try_send(msg)?;
     Ok(fragment_id)
 }
 
+/// submit the fragment and wait for the verdict of the fragment task
+pub async fn post_message_sync(
+    context: &Context,
+    message: &[u8],
+    client: Option<SocketAddr>,
+) -> Result<FragmentSubmission, Error> {
+    let fragment = Fragment::deserialize(message).map_err(Error::Deserialize)?;
+    let mut submissions = submit_fragments(context, vec![fragment], client).await?;
+    Ok(submissions
+        .pop()
+        .expect("the fragment task to reply for every submitted fragment"))
+}
+
+/// submit the hex-encoded fragments and wait for the verdict of the fragment task
+/// on each of them
+pub async fn post_fragments(
+    context: &Context,
+    fragments_hex: Vec<String>,
+    client: Option<SocketAddr>,
+) -> Result<Vec<FragmentSubmission>, Error> {
+    let fragments = fragments_hex
+        .iter()
+        .enumerate()
+        .map(|(index, fragment_hex)| {
+            let bytes = hex::decode(fragment_hex).map_err(|source| Error::Hex { index, source })?;
+            Fragment::deserialize(bytes.as_slice()).map_err(Error::Deserialize)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    submit_fragments(context, fragments, client).await
+}
+
+async fn submit_fragments(
+    context: &Context,
+    fragments: Vec<Fragment>,
+    client: Option<SocketAddr>,
+) -> Result<Vec<FragmentSubmission>, Error> {
+    let logger = context.logger()?.new(o!("request" => "submit_fragments"));
+    let (reply_handle, reply_future) = intercom::unary_reply(logger.clone());
+    let mut mbox = context.try_full()?.transaction_task.clone();
+    mbox.send(TransactionMsg::SendTransaction(
+        FragmentOrigin::Rest,
+        client.map(|addr| addr.ip()),
+        fragments,
+        Some(reply_handle),
+    ))
+    .await
+    .map_err(|e| {
+        debug!(&logger, "error submitting fragments"; "reason" => %e);
+        Error::MsgSendError(e)
+    })?;
+    reply_future.await.map_err(Into::into)
+}
+
 pub async fn get_tip(context: &Context) -> Result<String, Error> {
     Ok(context.blockchain_tip()?.get_ref().await.hash().to_string())
 }
@@ -209,6 +350,7 @@ async fn create_stats(context: &Context) -> Result<Option<NodeStats>, Error> {
     let stats = &full_context.stats_counter;
     let node_stats = NodeStats {
         block_recv_cnt: stats.block_recv_cnt(),
+        deep_fork_rejected_cnt: stats.deep_fork_rejected_cnt(),
         last_block_content_size: tip_header.block_content_size(),
         last_block_date: tip_header.block_date().to_string().into(),
         last_block_fees: block_fee_sum.0,
@@ -382,8 +524,14 @@ pub async fn get_shutdown(context: &Context) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn get_leader_ids(context: &Context) -> Result<Vec<EnclaveLeaderId>, Error> {
-    Ok(context.try_full()?.enclave.get_leader_ids().await)
+/// the leaders of the node, with the state of the KES keys of the
+/// Genesis-Praos leaders computed with the stake pool registrations of the tip
+pub async fn get_leaders(context: &Context) -> Result<Vec<LeaderStatus>, Error> {
+    let enclave = &context.try_full()?.enclave;
+    let ledger = context.blockchain_tip()?.get_ref().await.ledger();
+    Ok(enclave
+        .leaders_status(&ledger, std::time::SystemTime::now())
+        .await)
 }
 
 pub async fn post_leaders(context: &Context, secret: NodeSecret) -> Result<EnclaveLeaderId, Error> {
@@ -391,7 +539,11 @@ pub async fn post_leaders(context: &Context, secret: NodeSecret) -> Result<Encla
         bft_leader: secret.bft(),
         genesis_leader: secret.genesis(),
     };
-    let leader_id = context.try_full()?.enclave.add_leader(leader).await;
+    let enclave = &context.try_full()?.enclave;
+    if enclave.has_external_signer() {
+        return Err(Error::ExternalSignerLeaders);
+    }
+    let leader_id = enclave.add_leader(leader).await;
     Ok(leader_id)
 }
 
@@ -399,7 +551,11 @@ pub async fn delete_leaders(
     context: &Context,
     leader_id: EnclaveLeaderId,
 ) -> Result<Option<()>, Error> {
-    let removed = context.try_full()?.enclave.remove_leader(leader_id).await;
+    let enclave = &context.try_full()?.enclave;
+    if enclave.has_external_signer() {
+        return Err(Error::ExternalSignerLeaders);
+    }
+    let removed = enclave.remove_leader(leader_id).await;
 
     if removed {
         Ok(Some(()))
@@ -408,10 +564,84 @@ pub async fn delete_leaders(
     }
 }
 
+/// the state of the KES keys of the Genesis-Praos leaders of the node, with
+/// the stake pool registrations of the tip
+pub async fn get_leaders_kes(context: &Context) -> Result<Vec<LeaderKesStatus>, Error> {
+    let enclave = &context.try_full()?.enclave;
+    let ledger = context.blockchain_tip()?.get_ref().await.ledger();
+    Ok(enclave
+        .kes_status(&ledger, std::time::SystemTime::now())
+        .await)
+}
+
 pub async fn get_leaders_logs(context: &Context) -> Result<Vec<LeadershipLog>, Error> {
     Ok(context.try_full()?.leadership_logs.logs().await)
 }
 
+/// the slots assigned to the leaders of the enclave for the whole epoch
+/// scheduled by the leadership task, and for the next epoch once its
+/// leadership can no longer change
+pub async fn get_leaders_schedule(
+    context: &Context,
+) -> Result<Vec<LeadershipScheduleEntry>, Error> {
+    let full_context = context.try_full()?;
+    let (scheduled_epoch, mut schedule) = match full_context.leadership_logs.schedule().await {
+        Some(scheduled) => scheduled,
+        None => return Ok(Vec::new()),
+    };
+
+    // the leadership of the epoch following the one of the tip is only known
+    // for sure once the tip is deep enough in its epoch
+    let tip_ref = context.blockchain_tip()?.get_ref().await;
+    let tip_epoch = tip_ref.block_date().epoch;
+    if scheduled_epoch != tip_epoch || !next_epoch_leadership_is_stable(&tip_ref) {
+        return Ok(schedule);
+    }
+
+    let (_, leadership, leadership_ledger, ..) =
+        new_epoch_leadership_from(tip_epoch + 1, Arc::clone(&tip_ref), false);
+    let era = leadership.era();
+    let time_frame = tip_ref.time_frame();
+    let events = full_context
+        .enclave
+        .leadership_evaluate(&leadership, &leadership_ledger, 0, era.slots_per_epoch())
+        .await?;
+    for event in events {
+        let slot = era.from_era_to_slot(EpochPosition {
+            epoch: Epoch(event.date.epoch),
+            slot: EpochSlotOffset(event.date.slot_id),
+        });
+        let scheduled_at_time = time_frame
+            .slot_to_systemtime(slot)
+            .expect("the slots of the era are after the start of the time frame");
+        schedule.push(LeadershipScheduleEntry::new(
+            event.id,
+            event.date.into(),
+            scheduled_at_time.into(),
+        ));
+    }
+    Ok(schedule)
+}
+
+/// With the genesis praos consensus the leadership of the next epoch is
+/// computed from the state at the end of the previous epoch, it cannot change
+/// anymore once the last block of the previous epoch cannot be rolled back.
+fn next_epoch_leadership_is_stable(tip_ref: &Ref) -> bool {
+    if tip_ref.ledger().consensus_version() != ConsensusVersion::GenesisPraos {
+        return false;
+    }
+
+    match tip_ref.last_ref_previous_epoch() {
+        Some(previous_epoch) => {
+            let tip_length: u32 = tip_ref.chain_length().into();
+            let previous_epoch_length: u32 = previous_epoch.chain_length().into();
+            tip_length - previous_epoch_length
+                >= tip_ref.epoch_ledger_parameters().epoch_stability_depth
+        }
+        None => false,
+    }
+}
+
 pub async fn get_stake_pools(context: &Context) -> Result<Vec<String>, Error> {
     Ok(context
         .blockchain_tip()?
@@ -608,6 +838,23 @@ pub async fn get_network_p2p_view(context: &Context) -> Result<Vec<poldercast::A
         .peers)
 }
 
+pub async fn get_network_p2p_allow_list(context: &Context) -> Result<Option<PeerAllowList>, Error> {
+    Ok(context
+        .try_full()?
+        .network_state
+        .allow_list()
+        .map(|allow_list| allow_list.status()))
+}
+
+pub async fn get_network_p2p_reputation(context: &Context) -> Result<Vec<PeerReputation>, Error> {
+    Ok(context
+        .try_full()?
+        .network_state
+        .topology()
+        .reputation()
+        .status())
+}
+
 pub async fn get_network_p2p_view_topic(
     context: &Context,
     topic: &str,
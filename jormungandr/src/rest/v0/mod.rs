@@ -17,11 +17,23 @@ pub fn filter(
         .and_then(handlers::get_shutdown)
         .boxed();
 
-    let account = warp::path!("account" / String)
-        .and(warp::get())
-        .and(with_context.clone())
-        .and_then(handlers::get_account_state)
-        .boxed();
+    let account = {
+        let root = warp::path!("account" / ..);
+
+        let get = warp::path!(String)
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_account_state)
+            .boxed();
+
+        let get_pending = warp::path!(String / "pending")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_account_pending_fragments)
+            .boxed();
+
+        root.and(get.or(get_pending)).boxed()
+    };
 
     let block = {
         let root = warp::path!("block" / ..);
@@ -42,11 +54,31 @@ pub fn filter(
         root.and(get.or(get_next)).boxed()
     };
 
-    let fragment = warp::path!("fragment" / "logs")
-        .and(warp::get())
-        .and(with_context.clone())
-        .and_then(handlers::get_message_logs)
-        .boxed();
+    let fragment = {
+        let root = warp::path!("fragment" / ..);
+
+        let logs = warp::path!("logs")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_message_logs)
+            .boxed();
+
+        let status = warp::path!("status")
+            .and(warp::get())
+            .and(warp::query())
+            .and(with_context.clone())
+            .and_then(handlers::get_fragment_statuses)
+            .boxed();
+
+        let pending = warp::path!("pending")
+            .and(warp::get())
+            .and(warp::query())
+            .and(with_context.clone())
+            .and_then(handlers::get_pending_fragments)
+            .boxed();
+
+        root.and(logs.or(status).or(pending)).boxed()
+    };
 
     let leaders = {
         let root = warp::path!("leaders" / ..).boxed();
@@ -70,13 +102,26 @@ pub fn filter(
             .and_then(handlers::get_leaders_logs)
             .boxed();
 
+        let schedule = warp::path!("schedule")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_leaders_schedule)
+            .boxed();
+
+        let kes = warp::path!("kes")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_leaders_kes)
+            .boxed();
+
         let delete = warp::path!(u32)
             .and(warp::delete())
             .and(with_context.clone())
             .and_then(handlers::delete_leaders)
             .boxed();
 
-        root.and(get.or(post).or(logs).or(delete)).boxed()
+        root.and(get.or(post).or(logs).or(schedule).or(kes).or(delete))
+            .boxed()
     };
 
     let p2p = {
@@ -100,6 +145,18 @@ pub fn filter(
             .and_then(handlers::get_network_p2p_available)
             .boxed();
 
+        let allow_list = warp::path!("allow_list")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_network_p2p_allow_list)
+            .boxed();
+
+        let reputation = warp::path!("reputation")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_network_p2p_reputation)
+            .boxed();
+
         let view = {
             let root = warp::path!("view" / ..);
 
@@ -118,8 +175,15 @@ pub fn filter(
             root.and(view.or(view_topic)).boxed()
         };
 
-        root.and(quarantined.or(non_public).or(available).or(view))
-            .boxed()
+        root.and(
+            quarantined
+                .or(non_public)
+                .or(available)
+                .or(allow_list)
+                .or(reputation)
+                .or(view),
+        )
+        .boxed()
     };
 
     let network = {
@@ -170,11 +234,32 @@ pub fn filter(
         .and_then(handlers::get_stake_pool)
         .boxed();
 
-    let message = warp::path!("message")
+    let message = {
+        let post = warp::path!("message")
+            .and(warp::post())
+            .and(warp::body::bytes())
+            .and(warp::addr::remote())
+            .and(with_context.clone())
+            .and_then(handlers::post_message)
+            .boxed();
+
+        let post_sync = warp::path!("message" / "sync")
+            .and(warp::post())
+            .and(warp::body::bytes())
+            .and(warp::addr::remote())
+            .and(with_context.clone())
+            .and_then(handlers::post_message_sync)
+            .boxed();
+
+        post.or(post_sync).boxed()
+    };
+
+    let fragments = warp::path!("fragments")
         .and(warp::post())
-        .and(warp::body::bytes())
+        .and(warp::body::json())
+        .and(warp::addr::remote())
         .and(with_context.clone())
-        .and_then(handlers::post_message)
+        .and_then(handlers::post_fragments)
         .boxed();
 
     let node_stats = warp::path!("node" / "stats")
@@ -246,6 +331,7 @@ pub fn filter(
         .or(stake_pools)
         .or(stake_pool)
         .or(message)
+        .or(fragments)
         .or(node_stats)
         .or(tip)
         .or(rewards)
@@ -261,9 +347,11 @@ pub fn filter(
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
     if let Some(err) = err.find::<logic::Error>() {
         let (body, code) = match err {
-            logic::Error::PublicKey(_) | logic::Error::Hash(_) => {
-                (err.to_string(), StatusCode::BAD_REQUEST)
-            }
+            logic::Error::PublicKey(_)
+            | logic::Error::Hash(_)
+            | logic::Error::Deserialize(_)
+            | logic::Error::Hex { .. }
+            | logic::Error::ExternalSignerLeaders => (err.to_string(), StatusCode::BAD_REQUEST),
             err => (
                 display_internal_server_error(err),
                 StatusCode::INTERNAL_SERVER_ERROR,
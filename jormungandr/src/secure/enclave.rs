@@ -1,12 +1,34 @@
-use crate::blockcfg::{
-    BlockDate, HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
-    HeaderSetConsensusSignature, SlotId,
+use crate::{
+    blockcfg::{
+        BlockDate, HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
+        HeaderSetConsensusSignature, Ledger, SlotId,
+    },
+    secure::{
+        signer::{Error as SignerError, ExternalSigner},
+        signing_history::{Error as SigningHistoryError, SigningHistory},
+    },
 };
+use chain_crypto::{PublicKey, SumEd25519_12};
+use chain_impl_mockchain::certificate::PoolId;
 use chain_impl_mockchain::leadership::{Leader, LeaderOutput, Leadership};
-use jormungandr_lib::interfaces::EnclaveLeaderId as LeaderId;
+use jormungandr_lib::interfaces::{EnclaveLeaderId as LeaderId, LeaderKesStatus, LeaderStatus};
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::sync::{Mutex, RwLock};
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error("the signing history refused the signature")]
+    SigningHistory(#[from] SigningHistoryError),
+    #[error("the external signer failed")]
+    ExternalSigner(#[from] SignerError),
+}
+
+/// number of periods of the `SumEd25519_12` KES key of the Genesis-Praos
+/// leaders, the key can evolve `KES_PERIODS - 1` times
+pub const KES_PERIODS: u32 = 1 << 12;
 
 #[derive(Default)]
 struct EnclaveLeadersWithCache {
@@ -17,6 +39,12 @@ struct EnclaveLeadersWithCache {
 #[derive(Clone)]
 pub struct Enclave {
     leaders_data: Arc<RwLock<EnclaveLeadersWithCache>>,
+    /// when set, the leaders are held by an external signer and the
+    /// operations needing their secret keys are delegated to it
+    external_signer: Option<ExternalSigner>,
+    /// the last slots signed by the leaders, checked and updated before
+    /// signing any block
+    signing_history: Arc<Mutex<SigningHistory>>,
 }
 
 pub struct LeaderEvent {
@@ -50,10 +78,49 @@ fn leader_identifier(leader: &Leader) -> String {
     }
 }
 
+fn kes_status(
+    id: LeaderId,
+    pool_id: &PoolId,
+    kes_public_key: &PublicKey<SumEd25519_12>,
+    ledger: &Ledger,
+    now: SystemTime,
+) -> LeaderKesStatus {
+    let pool = match ledger.delegation().lookup(pool_id) {
+        Some(pool) => pool,
+        None => return LeaderKesStatus::unregistered(id, pool_id.to_string()),
+    };
+    let pool_id = pool_id.to_string();
+    let key_registered = pool.registration.keys.kes_public_key == *kes_public_key;
+
+    let static_parameters = ledger.get_static_parameters();
+    let update_speed = Duration::from_secs(static_parameters.kes_update_speed.into());
+    let key_start = UNIX_EPOCH
+        + Duration::from_secs(
+            static_parameters.block0_start_time.0 + u64::from(pool.registration.start_validity),
+        );
+    let elapsed = now.duration_since(key_start).unwrap_or_default();
+    let period = (elapsed.as_secs() / update_speed.as_secs()).min(KES_PERIODS.into()) as u32;
+    let remaining_evolutions = (KES_PERIODS - 1).saturating_sub(period);
+    let expiry_time = key_start + update_speed * KES_PERIODS;
+
+    LeaderKesStatus::new(
+        id,
+        pool_id,
+        key_registered,
+        period,
+        remaining_evolutions,
+        expiry_time.into(),
+    )
+}
+
 impl EnclaveLeadersWithCache {
+    /// add the leader, or replace the keys of the leader with the same
+    /// identifier (renewed KES key of a stake pool). A replaced leader keeps
+    /// its identifier so its scheduled slots are still signed.
     fn add(&mut self, leader: Leader) -> LeaderId {
         let identifier = leader_identifier(&leader);
         if let Some(leader_id) = self.added_leaders_cache.get(&identifier) {
+            self.leaders.insert(*leader_id, leader);
             *leader_id
         } else {
             let leader_id = get_maximum_id(&self.leaders).next();
@@ -86,9 +153,33 @@ impl Enclave {
     pub fn new() -> Self {
         Enclave {
             leaders_data: Arc::new(RwLock::new(EnclaveLeadersWithCache::default())),
+            external_signer: None,
+            signing_history: Arc::new(Mutex::new(SigningHistory::in_memory())),
+        }
+    }
+
+    /// create an enclave delegating to the given external signer. The
+    /// leaders cannot be added or removed from the node.
+    pub fn with_external_signer(external_signer: ExternalSigner) -> Self {
+        Enclave {
+            leaders_data: Arc::new(RwLock::new(EnclaveLeadersWithCache::default())),
+            external_signer: Some(external_signer),
+            signing_history: Arc::new(Mutex::new(SigningHistory::in_memory())),
+        }
+    }
+
+    /// use the given signing history instead of the in-memory one
+    pub fn with_signing_history(self, signing_history: SigningHistory) -> Self {
+        Enclave {
+            signing_history: Arc::new(Mutex::new(signing_history)),
+            ..self
         }
     }
 
+    pub fn has_external_signer(&self) -> bool {
+        self.external_signer.is_some()
+    }
+
     pub async fn from_vec(leaders: Vec<Leader>) -> Self {
         let e = Self::new();
         for leader in leaders {
@@ -98,6 +189,9 @@ impl Enclave {
     }
 
     pub async fn get_leader_ids(&self) -> Vec<LeaderId> {
+        if let Some(external_signer) = &self.external_signer {
+            return external_signer.leader_ids();
+        }
         self.leaders_data.read().await.get_leader_ids()
     }
 
@@ -109,6 +203,61 @@ impl Enclave {
         self.leaders_data.write().await.remove(leader_id)
     }
 
+    /// the state of the KES keys of the Genesis-Praos leaders, computed with
+    /// the stake pool registrations of the given ledger. The leaders held by
+    /// an external signer are reported with the public keys it gives.
+    pub async fn kes_status(&self, ledger: &Ledger, now: SystemTime) -> Vec<LeaderKesStatus> {
+        if let Some(external_signer) = &self.external_signer {
+            return external_signer
+                .genesis_leaders()
+                .iter()
+                .map(|(id, pool_id, kes_public_key)| {
+                    kes_status(*id, pool_id, kes_public_key, ledger, now)
+                })
+                .collect();
+        }
+
+        self.leaders_data
+            .read()
+            .await
+            .leaders
+            .iter()
+            .filter_map(|(id, leader)| {
+                leader.genesis_leader.as_ref().map(|genesis_leader| {
+                    kes_status(
+                        *id,
+                        &genesis_leader.node_id,
+                        &genesis_leader.sig_key.to_public(),
+                        ledger,
+                        now,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// the leaders of the enclave ordered by identifier, with the state of
+    /// the KES keys of the Genesis-Praos leaders
+    pub async fn leaders_status(&self, ledger: &Ledger, now: SystemTime) -> Vec<LeaderStatus> {
+        let mut kes_status: BTreeMap<LeaderId, LeaderKesStatus> = self
+            .kes_status(ledger, now)
+            .await
+            .into_iter()
+            .map(|status| (*status.enclave_leader_id(), status))
+            .collect();
+        let mut leader_ids = self.get_leader_ids().await;
+        leader_ids.sort();
+        leader_ids
+            .into_iter()
+            .map(|id| match kes_status.remove(&id) {
+                Some(status) => LeaderStatus::GenesisPraos(status),
+                None => LeaderStatus::Bft {
+                    enclave_leader_id: id,
+                },
+            })
+            .collect()
+    }
+
     // temporary method
     pub async fn leadership_evaluate1(
         &self,
@@ -138,15 +287,26 @@ impl Enclave {
         })
     }
 
+    /// evaluate the leadership of the leaders for the given slots of the
+    /// epoch. `leadership_ledger` is the ledger state the leadership was
+    /// created from, needed by the external signers to recreate it.
     pub async fn leadership_evaluate(
         &self,
         leadership: &Leadership,
+        leadership_ledger: &Arc<Ledger>,
         slot_start: u32,
         nb_slots: u32,
-    ) -> Vec<LeaderEvent> {
+    ) -> Result<Vec<LeaderEvent>, SignerError> {
+        if let Some(external_signer) = &self.external_signer {
+            let epoch = leadership.date_at_slot(slot_start).epoch;
+            return external_signer
+                .leadership_evaluate(epoch, Arc::clone(leadership_ledger), slot_start, nb_slots)
+                .await;
+        }
+
         let leaders = &self.leaders_data.read().await.leaders;
         if leaders.len() == 0 {
-            return vec![];
+            return Ok(vec![]);
         }
 
         let mut output = Vec::new();
@@ -166,22 +326,39 @@ impl Enclave {
                 }
             }
         }
-        output
+        Ok(output)
     }
 
     pub async fn create_header_genesis_praos(
         &self,
         header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
-    ) -> Option<HeaderGenesisPraos> {
+        date: BlockDate,
+    ) -> Result<Option<HeaderGenesisPraos>, SigningError> {
+        let mut signing_history = self.signing_history.lock().await;
+        if let Some(external_signer) = &self.external_signer {
+            let identifier = match external_signer.leader_identifier(id) {
+                Some(identifier) => identifier,
+                None => return Ok(None),
+            };
+            signing_history.record(&identifier, date)?;
+            let data = header_builder.get_authenticated_data().to_vec();
+            let signature = external_signer.sign_genesis_praos(id, date, data).await?;
+            return Ok(signature.map(|signature| header_builder.set_signature(signature.into())));
+        }
+
         let leaders = &self.leaders_data.read().await.leaders;
-        let leader = leaders.get(&id)?;
+        let leader = match leaders.get(&id) {
+            Some(leader) => leader,
+            None => return Ok(None),
+        };
         if let Some(genesis_leader) = &leader.genesis_leader {
+            signing_history.record(&leader_identifier(leader), date)?;
             let data = header_builder.get_authenticated_data();
             let signature = genesis_leader.sig_key.sign_slice(data);
-            Some(header_builder.set_signature(signature.into()))
+            Ok(Some(header_builder.set_signature(signature.into())))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -189,15 +366,32 @@ impl Enclave {
         &self,
         header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
         id: LeaderId,
-    ) -> Option<HeaderBft> {
+        date: BlockDate,
+    ) -> Result<Option<HeaderBft>, SigningError> {
+        let mut signing_history = self.signing_history.lock().await;
+        if let Some(external_signer) = &self.external_signer {
+            let identifier = match external_signer.leader_identifier(id) {
+                Some(identifier) => identifier,
+                None => return Ok(None),
+            };
+            signing_history.record(&identifier, date)?;
+            let data = header_builder.get_authenticated_data().to_vec();
+            let signature = external_signer.sign_bft(id, date, data).await?;
+            return Ok(signature.map(|signature| header_builder.set_signature(signature.into())));
+        }
+
         let leaders = &self.leaders_data.read().await.leaders;
-        let leader = leaders.get(&id)?;
-        if let Some(ref leader) = &leader.bft_leader {
+        let leader = match leaders.get(&id) {
+            Some(leader) => leader,
+            None => return Ok(None),
+        };
+        if let Some(bft_leader) = &leader.bft_leader {
+            signing_history.record(&leader_identifier(leader), date)?;
             let data = header_builder.get_authenticated_data();
-            let signature = leader.sig_key.sign_slice(data);
-            Some(header_builder.set_signature(signature.into()))
+            let signature = bft_leader.sig_key.sign_slice(data);
+            Ok(Some(header_builder.set_signature(signature.into())))
         } else {
-            None
+            Ok(None)
         }
     }
 }
@@ -9,6 +9,8 @@ use std::path::Path;
 use thiserror::Error;
 
 pub mod enclave;
+pub mod signer;
+pub mod signing_history;
 
 /// hold the node's bft secret setting
 #[derive(Clone, Deserialize)]
@@ -0,0 +1,377 @@
+//! client of an external signer, the process holding the secret keys of the
+//! leaders when they are not loaded in the node.
+//!
+//! see `jormungandr_lib::signer` for the protocol.
+
+use crate::{
+    blockcfg::{BlockDate, LeaderOutput, Ledger},
+    secure::enclave::LeaderEvent,
+};
+use chain_core::property::Serialize as _;
+use chain_crypto::{
+    vrf::ProvenOutputSeed, Ed25519, PublicKey, Signature, SumEd25519_12, VerificationAlgorithm,
+};
+use chain_impl_mockchain::{certificate::PoolId, key::BftLeaderId};
+use jormungandr_lib::{
+    interfaces::EnclaveLeaderId as LeaderId,
+    signer::{self, Request, Response},
+};
+use thiserror::Error;
+use tokio::task::spawn_blocking;
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+/// the longest the node waits for the signer to read a request or answer
+/// it. The evaluation of the leadership of a whole epoch is the longest
+/// request. A signing not answered in time fails, the slot is missed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+
+#[cfg(not(unix))]
+type Stream = std::net::TcpStream;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot connect to the external signer at {}", path.display())]
+    Connect {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("communication with the external signer failed")]
+    Protocol(#[from] signer::Error),
+    #[error("cannot serialize the ledger state for the external signer")]
+    Ledger(#[source] io::Error),
+    #[error("the external signer failed: {0}")]
+    Signer(String),
+    #[error("the external signer did not answer in time")]
+    Timeout,
+    #[error("unexpected response from the external signer")]
+    UnexpectedResponse,
+}
+
+/// connection to an external signer, shared between the clones
+#[derive(Clone)]
+pub struct ExternalSigner {
+    connection: Arc<Mutex<Connection>>,
+    /// the leaders held by the signer, updated every time the node connects
+    leaders: Arc<RwLock<BTreeMap<LeaderId, SignerLeader>>>,
+}
+
+/// the public keys of a leader held by the signer
+#[derive(Clone)]
+struct SignerLeader {
+    /// the public identifier of the leader: its stake pool identifier, or
+    /// its BFT public key
+    identifier: String,
+    /// the stake pool and the KES public key of a Genesis-Praos leader
+    genesis: Option<(PoolId, PublicKey<SumEd25519_12>)>,
+}
+
+struct Connection {
+    path: PathBuf,
+    stream: Option<Stream>,
+    /// the epoch and ledger state of the leadership set on the connection
+    leadership: Option<(u32, Arc<Ledger>)>,
+    leaders: Arc<RwLock<BTreeMap<LeaderId, SignerLeader>>>,
+}
+
+impl ExternalSigner {
+    /// connect to the signer listening on the given socket. The connection
+    /// is reopened on the next request if it fails later on.
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let leaders = Arc::new(RwLock::new(BTreeMap::new()));
+        let mut connection = Connection {
+            path: path.as_ref().to_path_buf(),
+            stream: None,
+            leadership: None,
+            leaders: Arc::clone(&leaders),
+        };
+        connection.connect()?;
+
+        Ok(ExternalSigner {
+            connection: Arc::new(Mutex::new(connection)),
+            leaders,
+        })
+    }
+
+    pub fn leader_ids(&self) -> Vec<LeaderId> {
+        self.leaders.read().unwrap().keys().copied().collect()
+    }
+
+    /// the public identifier of the leader: its stake pool identifier, or
+    /// its BFT public key
+    pub fn leader_identifier(&self, leader_id: LeaderId) -> Option<String> {
+        self.leaders
+            .read()
+            .unwrap()
+            .get(&leader_id)
+            .map(|leader| leader.identifier.clone())
+    }
+
+    /// the stake pool and the KES public key of the Genesis-Praos leaders
+    pub fn genesis_leaders(&self) -> Vec<(LeaderId, PoolId, PublicKey<SumEd25519_12>)> {
+        self.leaders
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(leader_id, leader)| {
+                leader
+                    .genesis
+                    .clone()
+                    .map(|(pool_id, kes_public_key)| (*leader_id, pool_id, kes_public_key))
+            })
+            .collect()
+    }
+
+    /// ask the signer to evaluate the leadership of its leaders for the
+    /// given slots of the epoch. The ledger state the epoch leadership was
+    /// created from is only sent when it changes.
+    pub async fn leadership_evaluate(
+        &self,
+        epoch: u32,
+        leadership_ledger: Arc<Ledger>,
+        slot_start: u32,
+        nb_slots: u32,
+    ) -> Result<Vec<LeaderEvent>, Error> {
+        self.with_connection(move |connection| {
+            connection.set_leadership(epoch, leadership_ledger)?;
+            match connection.request(&Request::Evaluate {
+                slot_start,
+                nb_slots,
+            })? {
+                Response::Events(events) => events.into_iter().map(leader_event).collect(),
+                _ => Err(Error::UnexpectedResponse),
+            }
+        })
+        .await
+    }
+
+    /// sign the authenticated data of a BFT header for the given date.
+    /// Returns `None` if the signer does not hold the BFT key of the leader.
+    pub async fn sign_bft(
+        &self,
+        leader_id: LeaderId,
+        date: BlockDate,
+        data: Vec<u8>,
+    ) -> Result<Option<Signature<[u8], Ed25519>>, Error> {
+        self.sign(Request::SignBft {
+            leader_id,
+            date: date.into(),
+            data,
+        })
+        .await
+    }
+
+    /// sign the authenticated data of a Genesis-Praos header for the given
+    /// date. Returns `None` if the signer does not hold the Genesis-Praos
+    /// keys of the leader.
+    pub async fn sign_genesis_praos(
+        &self,
+        leader_id: LeaderId,
+        date: BlockDate,
+        data: Vec<u8>,
+    ) -> Result<Option<Signature<[u8], SumEd25519_12>>, Error> {
+        self.sign(Request::SignGenesisPraos {
+            leader_id,
+            date: date.into(),
+            data,
+        })
+        .await
+    }
+
+    async fn sign<A: VerificationAlgorithm>(
+        &self,
+        request: Request,
+    ) -> Result<Option<Signature<[u8], A>>, Error> {
+        match self
+            .with_connection(move |connection| connection.request(&request))
+            .await?
+        {
+            Response::Signature(signature) => Signature::from_binary(&signature)
+                .map(Some)
+                .map_err(|_| Error::UnexpectedResponse),
+            Response::UnknownLeader => Ok(None),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// the connection is blocking, the requests are run in the blocking
+    /// thread pool
+    async fn with_connection<F, T>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        spawn_blocking(move || f(&mut connection.lock().unwrap()))
+            .await
+            .unwrap()
+    }
+}
+
+impl Connection {
+    fn connect(&mut self) -> Result<(), Error> {
+        let stream = connect_stream(&self.path).map_err(|source| Error::Connect {
+            path: self.path.clone(),
+            source,
+        })?;
+        self.stream = Some(stream);
+        self.leadership = None;
+
+        match self.request(&Request::Leaders)? {
+            Response::Leaders(leaders) => {
+                let leaders = leaders
+                    .into_iter()
+                    .map(signer_leader)
+                    .collect::<Result<_, _>>();
+                match leaders {
+                    Ok(leaders) => {
+                        *self.leaders.write().unwrap() = leaders;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        self.stream = None;
+                        Err(err)
+                    }
+                }
+            }
+            _ => {
+                self.stream = None;
+                Err(Error::UnexpectedResponse)
+            }
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response, Error> {
+        if self.stream.is_none() {
+            self.connect()?;
+        }
+        let stream = self.stream.as_mut().expect("connected to the signer");
+
+        let response =
+            signer::write_message(stream, request).and_then(|()| signer::read_message(stream));
+        match response {
+            Ok(Response::Error(reason)) => Err(Error::Signer(reason)),
+            Ok(response) => Ok(response),
+            Err(err) => {
+                // the state of the connection is unknown, start over with a
+                // new one on the next request
+                self.stream = None;
+                self.leadership = None;
+                match err {
+                    signer::Error::Io(err)
+                        if err.kind() == io::ErrorKind::WouldBlock
+                            || err.kind() == io::ErrorKind::TimedOut =>
+                    {
+                        Err(Error::Timeout)
+                    }
+                    err => Err(err.into()),
+                }
+            }
+        }
+    }
+
+    fn set_leadership(&mut self, epoch: u32, ledger: Arc<Ledger>) -> Result<(), Error> {
+        if let Some((current_epoch, current_ledger)) = &self.leadership {
+            if *current_epoch == epoch && Arc::ptr_eq(current_ledger, &ledger) {
+                return Ok(());
+            }
+        }
+
+        let request = Request::SetLeadership {
+            epoch,
+            ledger: ledger.serialize_as_vec().map_err(Error::Ledger)?,
+        };
+        match self.request(&request)? {
+            Response::LeadershipSet => {
+                self.leadership = Some((epoch, ledger));
+                Ok(())
+            }
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect_stream(path: &Path) -> io::Result<Stream> {
+    let stream = Stream::connect(path)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    Ok(stream)
+}
+
+#[cfg(not(unix))]
+fn connect_stream(_path: &Path) -> io::Result<Stream> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "external signers are only supported on Unix platforms",
+    ))
+}
+
+/// identify the leader the same way as the leaders held by the node, so the
+/// signing history is kept by leader whatever holds its keys
+fn signer_leader(leader: signer::Leader) -> Result<(LeaderId, SignerLeader), Error> {
+    let pool_id = leader
+        .pool_id
+        .map(|pool_id| {
+            <[u8; 32]>::try_from(pool_id.as_slice())
+                .map(PoolId::from)
+                .map_err(|_| Error::UnexpectedResponse)
+        })
+        .transpose()?;
+    let identifier = match (&pool_id, leader.bft_leader) {
+        (Some(pool_id), _) => pool_id.to_string(),
+        (None, Some(bft_leader)) => PublicKey::<Ed25519>::from_binary(&bft_leader)
+            .map_err(|_| Error::UnexpectedResponse)?
+            .to_string(),
+        (None, None) => String::new(),
+    };
+    let genesis = match (pool_id, leader.kes_public_key) {
+        (Some(pool_id), Some(kes_public_key)) => Some((
+            pool_id,
+            PublicKey::<SumEd25519_12>::from_binary(&kes_public_key)
+                .map_err(|_| Error::UnexpectedResponse)?,
+        )),
+        _ => None,
+    };
+    Ok((
+        leader.leader_id,
+        SignerLeader {
+            identifier,
+            genesis,
+        },
+    ))
+}
+
+fn leader_event(event: signer::LeaderEvent) -> Result<LeaderEvent, Error> {
+    let output = match event.output {
+        signer::LeaderOutput::Bft { leader } => {
+            let public_key = PublicKey::<Ed25519>::from_binary(&leader)
+                .map_err(|_| Error::UnexpectedResponse)?;
+            LeaderOutput::Bft(BftLeaderId::from(public_key))
+        }
+        signer::LeaderOutput::GenesisPraos { pool_id, vrf_proof } => {
+            let pool_id =
+                <[u8; 32]>::try_from(pool_id.as_slice()).map_err(|_| Error::UnexpectedResponse)?;
+            let vrf_proof = ProvenOutputSeed::from_bytes_unverified(&vrf_proof)
+                .ok_or(Error::UnexpectedResponse)?;
+            LeaderOutput::GenesisPraos(pool_id.into(), vrf_proof)
+        }
+    };
+
+    Ok(LeaderEvent {
+        id: event.leader_id,
+        date: event.date.into(),
+        output,
+    })
+}
@@ -0,0 +1,187 @@
+//! History of the slots the leaders signed blocks for.
+//!
+//! A leader signing two different blocks for the same slot can be reported
+//! for double signing. Before signing a block, the enclave records its date
+//! as the last one signed by the leader and refuses to sign again for a date
+//! at or before it. The history is kept in a database in the node storage
+//! directory so it survives a restart of the node, or a copy of the storage
+//! directory used to start a second node with the same keys.
+
+use crate::{
+    blockcfg::{BlockDate, HeaderHash},
+    utils::storage_db,
+};
+use rusqlite::{params, Connection, NO_PARAMS};
+use slog::Logger;
+use thiserror::Error;
+
+use std::{collections::HashMap, io, path::Path};
+
+/// name of the database file in the node storage directory
+const DATABASE_FILE: &str = "signing_history.sqlite";
+
+/// version of the database schema. The node refuses to start with a history
+/// stored with another version, dropping it would allow double signing.
+const SCHEMA_VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS signing_history (
+    leader TEXT PRIMARY KEY,
+    epoch INTEGER NOT NULL,
+    slot_id INTEGER NOT NULL
+);
+";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot create the storage directory")]
+    Io(#[from] io::Error),
+    #[error("signing history database error")]
+    Database(#[from] rusqlite::Error),
+    #[error(
+        "the signing history cannot be used, move it away to start over with an empty history"
+    )]
+    Mismatch(#[from] storage_db::Mismatch),
+    #[error("the leader already signed a block for {last_signed}, refusing to sign for {date}")]
+    AlreadySigned {
+        date: BlockDate,
+        last_signed: BlockDate,
+    },
+}
+
+pub struct SigningHistory {
+    /// the date of the last block signed by each leader, by leader identifier
+    last_signed: HashMap<String, BlockDate>,
+    /// the database the history is written to, `None` if it is only kept
+    /// in memory
+    connection: Option<Connection>,
+}
+
+impl SigningHistory {
+    /// history kept in memory only, lost when the node stops
+    pub fn in_memory() -> Self {
+        SigningHistory {
+            last_signed: HashMap::new(),
+            connection: None,
+        }
+    }
+
+    /// open (or create) the database in the given node storage directory and
+    /// load the history. Fails if the history was stored with another schema
+    /// version or for another block0.
+    pub fn open(storage_dir: &Path, block0: HeaderHash, logger: &Logger) -> Result<Self, Error> {
+        let connection = storage_db::open_strict::<Error>(
+            storage_dir,
+            DATABASE_FILE,
+            SCHEMA,
+            SCHEMA_VERSION,
+            block0,
+            logger,
+        )?;
+
+        let mut last_signed = HashMap::new();
+        {
+            let mut statement =
+                connection.prepare("SELECT leader, epoch, slot_id FROM signing_history")?;
+            let rows = statement.query_map(NO_PARAMS, |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    BlockDate {
+                        epoch: row.get(1)?,
+                        slot_id: row.get(2)?,
+                    },
+                ))
+            })?;
+            for row in rows {
+                let (leader, date) = row?;
+                last_signed.insert(leader, date);
+            }
+        }
+
+        Ok(SigningHistory {
+            last_signed,
+            connection: Some(connection),
+        })
+    }
+
+    /// forget the dates signed by all the leaders
+    pub fn reset(&mut self) -> Result<(), Error> {
+        if let Some(connection) = &self.connection {
+            connection.execute("DELETE FROM signing_history", NO_PARAMS)?;
+        }
+        self.last_signed.clear();
+        Ok(())
+    }
+
+    /// number of leaders with a recorded signing
+    pub fn nb_leaders(&self) -> usize {
+        self.last_signed.len()
+    }
+
+    /// record that the leader is about to sign a block for the given date.
+    /// Fails if the leader already signed a block for this date or a later
+    /// one, or if the history cannot be written: the block must not be
+    /// signed then.
+    pub fn record(&mut self, leader: &str, date: BlockDate) -> Result<(), Error> {
+        if let Some(last_signed) = self.last_signed.get(leader) {
+            if date <= *last_signed {
+                return Err(Error::AlreadySigned {
+                    date,
+                    last_signed: *last_signed,
+                });
+            }
+        }
+
+        if let Some(connection) = &self.connection {
+            connection.execute(
+                "INSERT OR REPLACE INTO signing_history (leader, epoch, slot_id) VALUES (?1, ?2, ?3)",
+                params![leader, date.epoch, date.slot_id],
+            )?;
+        }
+        self.last_signed.insert(leader.to_owned(), date);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_signing_at_or_before_last_signed() {
+        let mut history = SigningHistory::in_memory();
+        let date = BlockDate {
+            epoch: 1,
+            slot_id: 10,
+        };
+
+        history.record("leader", date).unwrap();
+        assert!(matches!(
+            history.record("leader", date),
+            Err(Error::AlreadySigned { .. })
+        ));
+        assert!(matches!(
+            history.record(
+                "leader",
+                BlockDate {
+                    epoch: 0,
+                    slot_id: 20
+                }
+            ),
+            Err(Error::AlreadySigned { .. })
+        ));
+        history.record("other leader", date).unwrap();
+        history
+            .record(
+                "leader",
+                BlockDate {
+                    epoch: 1,
+                    slot_id: 11,
+                },
+            )
+            .unwrap();
+
+        history.reset().unwrap();
+        history.record("leader", date).unwrap();
+    }
+}
@@ -44,6 +44,15 @@ pub struct StartArguments {
     /// Start the explorer task and enable associated query endpoints.
     #[structopt(long = "enable-explorer")]
     pub explorer_enabled: bool,
+
+    /// forget the slots the leaders last signed blocks for, stored in the
+    /// storage directory to prevent signing twice for the same slot.
+    ///
+    /// NOTE: this is only meant to recover from a corrupted history or from a
+    /// clock set in the future, the node may sign a second block for a slot
+    /// already signed and its stake pool be reported for double signing.
+    #[structopt(long = "reset-signing-history")]
+    pub reset_signing_history: bool,
 }
 
 #[derive(StructOpt, Debug)]
@@ -1,5 +1,8 @@
 use crate::{
-    network::p2p::{layers::LayersConfig, topic, Address, PolicyConfig},
+    network::{
+        p2p::{layers::LayersConfig, topic, Address, AllowListConfig, PolicyConfig},
+        tls::NodeIdentity,
+    },
     settings::logging::{LogFormat, LogOutput},
     settings::LOG_FILTER_LEVEL_POSSIBLE_VALUES,
 };
@@ -74,6 +77,19 @@ pub struct Tls {
     pub priv_key_file: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct P2pTls {
+    /// Path to the node X.509 certificate chain file, must be PEM-encoded and contain at least 1 item.
+    /// The identity of the node is derived from the public key of the certificate.
+    pub cert_file: PathBuf,
+    /// Path to the node private key file, must be PKCS8 with single PEM-encoded, unencrypted key
+    pub priv_key_file: PathBuf,
+    /// Require the connecting peers to present a certificate too.
+    #[serde(default)]
+    pub mutual: bool,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub struct Cors {
@@ -177,6 +193,17 @@ pub struct P2pConfig {
     /// gossip with the trusted peers if any are defined.
     #[serde(default)]
     pub max_bootstrap_attempts: Option<usize>,
+
+    /// Secure the P2P connections with TLS. The connections are in cleartext
+    /// when not set.
+    #[serde(default)]
+    pub tls: Option<P2pTls>,
+
+    /// Run in permissioned mode: only the peers of the allow-list may
+    /// connect, subscribe or appear in the gossip. Any peer is accepted
+    /// when not set.
+    #[serde(default)]
+    pub permissioned: Option<AllowListConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -190,6 +217,11 @@ pub struct TrustedPeer {
     // TODO: to remove once we can afford having a config breaking change
     #[serde(skip, default)]
     pub id: Option<Id>,
+
+    /// the expected identity of the peer, checked against its TLS
+    /// certificate when the P2P connections use TLS
+    #[serde(default)]
+    pub identity: Option<NodeIdentity>,
 }
 
 // Lifted from poldercast 0.11 for backward compatibility
@@ -232,6 +264,11 @@ pub struct Leadership {
     /// the least recently used log will be erased from the logs for a new one
     /// to be inserted.
     pub logs_capacity: usize,
+
+    /// path of the socket of an external signer holding the secret keys of
+    /// the leaders. The node does not load any secret key when it is set.
+    #[serde(default)]
+    pub external_signer: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
@@ -244,6 +281,10 @@ pub struct InterestLevel(pub poldercast::InterestLevel);
 #[serde(deny_unknown_fields)]
 pub struct Explorer {
     pub enabled: bool,
+    /// directory where the explorer keeps the indexes of the stable part of
+    /// the blockchain. When not set, all the indexes are kept in memory.
+    #[serde(default)]
+    pub storage: Option<PathBuf>,
 }
 
 pub fn default_interests() -> BTreeMap<Topic, InterestLevel> {
@@ -279,6 +320,8 @@ impl Default for P2pConfig {
             gossip_interval: None,
             topology_force_reset_interval: None,
             max_bootstrap_attempts: None,
+            tls: None,
+            permissioned: None,
         }
     }
 }
@@ -287,6 +330,7 @@ impl Default for Leadership {
     fn default() -> Self {
         Leadership {
             logs_capacity: 1_024,
+            external_signer: None,
         }
     }
 }
@@ -314,6 +358,7 @@ impl std::str::FromStr for TrustedPeer {
         Ok(TrustedPeer {
             address,
             id: optional_id,
+            identity: None,
         })
     }
 }
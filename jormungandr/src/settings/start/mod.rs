@@ -4,11 +4,12 @@ pub mod network;
 use self::config::{Config, Leadership};
 pub use self::config::{Cors, Rest, Tls};
 use self::network::Protocol;
+use crate::network::{p2p::AllowList, tls::TlsConfig};
 use crate::settings::logging::{LogFormat, LogOutput, LogSettings, LogSettingsEntry};
 use crate::settings::{command_arguments::*, Block0Info};
 use jormungandr_lib::interfaces::Mempool;
 use slog::{FilterLevel, Logger};
-use std::{fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf, sync::Arc};
 use thiserror::Error;
 
 const DEFAULT_FILTER_LEVEL: FilterLevel = FilterLevel::Info;
@@ -26,6 +27,16 @@ pub enum Error {
     ExpectedBlock0Info,
     #[error("In the node configuration file, the `p2p.listen_address` value is not a valid address. Use format `/ip4/x.x.x.x/tcp/4920")]
     ListenAddressNotValid,
+    #[error("The secret files cannot be used with an external signer, the leaders' keys are held by the signer")]
+    SecretsWithExternalSigner,
+    #[error("Cannot load the TLS certificate of the node")]
+    P2pTls(#[from] crate::network::tls::Error),
+    #[error("The node identities of `p2p.permissioned` can only be checked with `p2p.tls.mutual` enabled")]
+    PermissionedIdentitiesWithoutMutualTls,
+    #[error("The allow-list of `p2p.permissioned` is empty, it needs `addresses` or `identities`")]
+    EmptyAllowList,
+    #[error("The `explorer.storage` directory can only be used with a persistent `storage` of the node, the explorer reads the stable blocks back from it")]
+    ExplorerStorageWithoutStorage,
 }
 
 /// Overall Settings for node
@@ -38,7 +49,9 @@ pub struct Settings {
     pub mempool: Mempool,
     pub rewards_report_all: bool,
     pub leadership: Leadership,
+    pub reset_signing_history: bool,
     pub explorer: bool,
+    pub explorer_storage: Option<PathBuf>,
     pub no_blockchain_updates_warning_interval: std::time::Duration,
 }
 
@@ -142,7 +155,15 @@ impl RawSettings {
             secrets.extend(secret_files);
         }
 
-        if secrets.is_empty() {
+        let leadership = config
+            .as_ref()
+            .map_or(Leadership::default(), |cfg| cfg.leadership.clone());
+
+        if leadership.external_signer.is_some() {
+            if !secrets.is_empty() {
+                return Err(Error::SecretsWithExternalSigner);
+            }
+        } else if secrets.is_empty() {
             warn!(
                 logger,
                 "Node started without path to the stored secret keys (not a stake pool or a BFT leader)"
@@ -166,6 +187,15 @@ impl RawSettings {
                     .map_or(false, |settings| settings.enabled)
             });
 
+        let explorer_storage = config
+            .as_ref()
+            .and_then(|cfg| cfg.explorer.as_ref())
+            .and_then(|settings| settings.storage.clone());
+
+        if explorer_storage.is_some() && storage.is_none() {
+            return Err(Error::ExplorerStorageWithoutStorage);
+        }
+
         Ok(Settings {
             storage,
             block_0,
@@ -176,10 +206,10 @@ impl RawSettings {
             mempool: config
                 .as_ref()
                 .map_or(Mempool::default(), |cfg| cfg.mempool.clone()),
-            leadership: config
-                .as_ref()
-                .map_or(Leadership::default(), |cfg| cfg.leadership.clone()),
+            leadership,
+            reset_signing_history: command_arguments.reset_signing_history,
             explorer,
+            explorer_storage,
             no_blockchain_updates_warning_interval: config
                 .as_ref()
                 .and_then(|config| config.no_blockchain_updates_warning_interval.clone())
@@ -227,6 +257,7 @@ fn generate_network(
                     Some(config::TrustedPeer {
                         address,
                         id: peer.id.clone(),
+                        identity: peer.identity,
                     })
                 }
                 Ok(None) => {
@@ -257,6 +288,30 @@ fn generate_network(
         profile.add_subscription(sub);
     }
 
+    let trusted_peers: Vec<network::TrustedPeer> = p2p
+        .trusted_peers
+        .clone()
+        .unwrap_or(vec![])
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    let allow_list = match &p2p.permissioned {
+        None => None,
+        Some(permissioned) => {
+            if permissioned.is_empty() {
+                return Err(Error::EmptyAllowList);
+            }
+            if permissioned.has_identities() && !p2p.tls.as_ref().map_or(false, |tls| tls.mutual) {
+                return Err(Error::PermissionedIdentitiesWithoutMutualTls);
+            }
+            Some(Arc::new(AllowList::new(
+                permissioned.clone(),
+                &trusted_peers,
+            )))
+        }
+    };
+
     let mut network = network::Configuration {
         profile: profile.build(),
         listen_address: match &p2p.listen_address {
@@ -269,13 +324,7 @@ fn generate_network(
                 }
             }
         },
-        trusted_peers: p2p
-            .trusted_peers
-            .clone()
-            .unwrap_or(vec![])
-            .into_iter()
-            .map(Into::into)
-            .collect(),
+        trusted_peers,
         protocol: Protocol::Grpc,
         policy: p2p.policy.clone(),
         layers: p2p.layers.clone(),
@@ -297,6 +346,16 @@ fn generate_network(
         http_fetch_block0_service,
         bootstrap_from_trusted_peers,
         skip_bootstrap,
+        tls: match &p2p.tls {
+            None => None,
+            Some(tls) => Some(TlsConfig::load(
+                &tls.cert_file,
+                &tls.priv_key_file,
+                tls.mutual,
+                allow_list.clone(),
+            )?),
+        },
+        allow_list,
     };
 
     if network.max_inbound_connections > network.max_connections {
@@ -1,6 +1,9 @@
-use crate::network::p2p::{layers::LayersConfig, Address, PolicyConfig};
+use crate::network::{
+    p2p::{layers::LayersConfig, Address, AllowList, PolicyConfig},
+    tls::{NodeIdentity, TlsConfig},
+};
 use poldercast::NodeProfile;
-use std::{net::SocketAddr, str, time::Duration};
+use std::{net::SocketAddr, str, sync::Arc, time::Duration};
 
 /// Protocol to use for a connection.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -18,6 +21,8 @@ pub struct Peer {
     pub protocol: Protocol,
     /// a timeout in case of inactivity or timout between request.
     pub timeout: Duration,
+    /// the identity the peer must present over TLS, if known
+    pub identity: Option<NodeIdentity>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -92,17 +97,25 @@ pub struct Configuration {
     pub skip_bootstrap: bool,
 
     pub http_fetch_block0_service: Vec<String>,
+
+    /// TLS settings of the P2P connections, in cleartext if not set
+    pub tls: Option<TlsConfig>,
+
+    /// the peers allowed in permissioned mode, any peer is allowed if not set
+    pub allow_list: Option<Arc<AllowList>>,
 }
 
 #[derive(Clone)]
 pub struct TrustedPeer {
     pub address: poldercast::Address,
+    pub identity: Option<NodeIdentity>,
 }
 
 impl From<super::config::TrustedPeer> for TrustedPeer {
     fn from(tp: super::config::TrustedPeer) -> Self {
         TrustedPeer {
             address: tp.address,
+            identity: tp.identity,
         }
     }
 }
@@ -117,9 +130,14 @@ impl Peer {
             connection,
             protocol: Protocol::Grpc,
             timeout,
+            identity: None,
         }
     }
 
+    pub fn with_identity(self, identity: Option<NodeIdentity>) -> Self {
+        Peer { identity, ..self }
+    }
+
     pub fn address(&self) -> SocketAddr {
         self.connection
     }
@@ -156,4 +174,15 @@ impl Configuration {
                 .and_then(|address| multiaddr_to_socket_addr(address.multi_address())))
             .map(|addr| Listen::new(addr))
     }
+
+    /// The identity pinned for the peer at the given address in the
+    /// trusted peers, if any.
+    pub fn trusted_peer_identity(&self, addr: SocketAddr) -> Option<NodeIdentity> {
+        use jormungandr_lib::multiaddr::multiaddr_to_socket_addr;
+
+        self.trusted_peers
+            .iter()
+            .filter(|peer| multiaddr_to_socket_addr(peer.address.multi_address()) == Some(addr))
+            .find_map(|peer| peer.identity)
+    }
 }
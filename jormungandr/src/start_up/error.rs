@@ -1,7 +1,7 @@
 use crate::{
     blockcfg, blockchain,
     diagnostic::DiagnosticError,
-    explorer, network, secure,
+    explorer, fragment, network, secure,
     settings::{self, logging},
 };
 use chain_storage::Error as StorageError;
@@ -51,10 +51,18 @@ pub enum Error {
     NetworkBootstrapError(#[source] network::BootstrapError),
     #[error("Error while loading the node's secrets.")]
     NodeSecrets(#[from] secure::NodeSecretFromFileError),
+    #[error("Error while connecting to the external signer")]
+    ExternalSigner(#[from] secure::signer::Error),
+    #[error("Error while loading the signing history from storage")]
+    SigningHistory(#[from] secure::signing_history::Error),
     #[error("Block 0 is set to start in the future")]
     Block0InFuture,
     #[error("Error while loading the explorer from storage")]
     ExplorerBootstrapError(#[from] explorer::error::Error),
+    #[error("Error while loading the fragment logs from storage")]
+    FragmentLogs(#[from] fragment::PersistentLogsError),
+    #[error("Error while loading the peer table from storage")]
+    PeerTable(#[from] network::p2p::PeerTableError),
     #[error("A service has terminated with an error")]
     ServiceTerminatedWithError,
     #[error("Unable to get system limits: {0}")]
@@ -87,11 +95,15 @@ impl Error {
             Error::Block0Mismatch { .. } => 7,
             Error::Block0InFuture => 7,
             Error::NodeSecrets { .. } => 8,
+            Error::ExternalSigner { .. } => 8,
+            Error::SigningHistory { .. } => 8,
             Error::FetchBlock0 { .. } => 9,
             Error::NetworkBootstrapError { .. } => 10,
             Error::ExplorerBootstrapError { .. } => 11,
             Error::ServiceTerminatedWithError => 12,
             Error::DiagnosticError { .. } => 13,
+            Error::FragmentLogs { .. } => 14,
+            Error::PeerTable { .. } => 15,
         }
     }
 }
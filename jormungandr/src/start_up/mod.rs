@@ -3,7 +3,7 @@ mod error;
 pub use self::error::{Error, ErrorKind};
 use crate::{
     blockcfg::{Block, HeaderId},
-    blockchain::{Blockchain, ErrorKind as BlockchainError, Storage, Tip},
+    blockchain::{Blockchain, ErrorKind as BlockchainError, LedgerSnapshots, Storage, Tip},
     log, network,
     settings::start::Settings,
 };
@@ -191,6 +191,7 @@ pub async fn load_blockchain(
     storage: Storage,
     cache_capacity: usize,
     rewards_report_all: bool,
+    ledger_snapshots: Option<LedgerSnapshots>,
     logger: &Logger,
 ) -> Result<(Blockchain, Tip), Error> {
     let blockchain = Blockchain::new(
@@ -198,6 +199,7 @@ pub async fn load_blockchain(
         storage,
         cache_capacity,
         rewards_report_all,
+        ledger_snapshots,
     );
 
     let main_branch = match blockchain.load_from_block0(block0.clone()).await {
@@ -16,6 +16,7 @@ pub struct StatsCounter {
 struct StatsCounterImpl {
     tx_recv_cnt: AtomicUsize,
     block_recv_cnt: AtomicUsize,
+    deep_fork_rejected_cnt: AtomicUsize,
     start_time: Instant,
     slot_start_time: AtomicU64,
     tip_block: ArcSwapOption<Block>,
@@ -27,6 +28,7 @@ impl Default for StatsCounterImpl {
         Self {
             tx_recv_cnt: AtomicUsize::default(),
             block_recv_cnt: AtomicUsize::default(),
+            deep_fork_rejected_cnt: AtomicUsize::default(),
             start_time: Instant::now(),
             slot_start_time: AtomicU64::new(SLOT_START_TIME_UNDEFINED),
             tip_block: ArcSwapOption::from(None),
@@ -55,6 +57,16 @@ impl StatsCounter {
         self.stats.block_recv_cnt.load(Ordering::Relaxed) as u64
     }
 
+    pub fn add_deep_fork_rejected_cnt(&self, count: usize) {
+        self.stats
+            .deep_fork_rejected_cnt
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn deep_fork_rejected_cnt(&self) -> u64 {
+        self.stats.deep_fork_rejected_cnt.load(Ordering::Relaxed) as u64
+    }
+
     pub fn add_peer_connected_cnt(&self, count: usize) -> usize {
         self.stats
             .peers_connected_cnt
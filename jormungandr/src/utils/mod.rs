@@ -1,4 +1,5 @@
 pub mod async_msg;
 pub mod borrow;
 pub mod fire_forget_scheduler;
+pub mod storage_db;
 pub mod task;
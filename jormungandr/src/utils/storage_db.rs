@@ -0,0 +1,277 @@
+//! SQLite databases kept by the node in its storage directories.
+//!
+//! Every database has a `meta` table recording the version of its schema and
+//! the block0 of the blockchain its data belongs to. When the node opens a
+//! database created with another version or for another blockchain, the
+//! tables are dropped and the database starts over empty, or the node refuses
+//! to open it when the data must not be lost.
+
+use crate::blockcfg::HeaderHash;
+use rusqlite::{params, Connection, OptionalExtension as _, NO_PARAMS};
+use slog::Logger;
+use thiserror::Error;
+
+use std::{fs, io, path::Path};
+
+const META_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value NOT NULL
+);
+";
+
+const META_VERSION: &str = "version";
+const META_BLOCK0: &str = "block0";
+
+/// the database holds data stored with another schema version or for
+/// another blockchain
+#[derive(Debug, Error)]
+#[error("the database {file} was created with the schema version {stored_version:?} for the block0 {stored_block0:?}, expected the version {version} for the block0 {block0}")]
+pub struct Mismatch {
+    pub file: String,
+    pub stored_version: Option<u32>,
+    pub stored_block0: Option<String>,
+    pub version: u32,
+    pub block0: String,
+}
+
+/// open (or create) the database `file` in the given directory and create
+/// the tables of `schema`. The data stored with another schema `version` or
+/// for another block0 is dropped.
+pub fn open<E>(
+    dir: &Path,
+    file: &str,
+    schema: &str,
+    version: u32,
+    block0: HeaderHash,
+    logger: &Logger,
+) -> Result<Connection, E>
+where
+    E: From<io::Error> + From<rusqlite::Error>,
+{
+    fs::create_dir_all(dir)?;
+    let mut connection = Connection::open(dir.join(file))?;
+    init(&mut connection, schema, version, block0, logger)?;
+    Ok(connection)
+}
+
+/// same as `open`, but fails instead of dropping the data stored with
+/// another schema `version` or for another block0
+pub fn open_strict<E>(
+    dir: &Path,
+    file: &str,
+    schema: &str,
+    version: u32,
+    block0: HeaderHash,
+    logger: &Logger,
+) -> Result<Connection, E>
+where
+    E: From<io::Error> + From<rusqlite::Error> + From<Mismatch>,
+{
+    fs::create_dir_all(dir)?;
+    let path = dir.join(file);
+    let mut connection = Connection::open(&path)?;
+    connection.execute_batch(META_SCHEMA)?;
+    if let Some(mismatch) = stored_mismatch(&connection, &path, version, block0)? {
+        return Err(mismatch.into());
+    }
+    init(&mut connection, schema, version, block0, logger)?;
+    Ok(connection)
+}
+
+/// database kept in memory, for the tests
+#[cfg(test)]
+pub fn in_memory(schema: &str, version: u32) -> Connection {
+    let mut connection = Connection::open_in_memory().unwrap();
+    let logger = Logger::root(slog::Discard, o!());
+    init(
+        &mut connection,
+        schema,
+        version,
+        HeaderHash::zero_hash(),
+        &logger,
+    )
+    .unwrap();
+    connection
+}
+
+fn init(
+    connection: &mut Connection,
+    schema: &str,
+    version: u32,
+    block0: HeaderHash,
+    logger: &Logger,
+) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(META_SCHEMA)?;
+
+    let stored_version: Option<u32> = get_meta(connection, META_VERSION)?;
+    let stored_block0: Option<String> = get_meta(connection, META_BLOCK0)?;
+    let block0 = block0.to_string();
+    if stored_version == Some(version) && stored_block0.as_ref() == Some(&block0) {
+        return connection.execute_batch(schema);
+    }
+
+    if stored_block0.is_some() {
+        info!(
+            logger,
+            "dropping the data stored for another blockchain or version";
+            "stored_version" => stored_version,
+            "stored_block0" => stored_block0,
+        );
+    }
+    // the tables of another version may have other columns, they are
+    // dropped so the schema creates them anew
+    let tables = {
+        let mut statement = connection.prepare(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name != 'meta' AND name NOT LIKE 'sqlite_%'",
+        )?;
+        let rows = statement.query_map(NO_PARAMS, |row| row.get::<_, String>(0))?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    let transaction = connection.transaction()?;
+    for table in tables {
+        transaction.execute(&format!("DROP TABLE \"{}\"", table), NO_PARAMS)?;
+    }
+    transaction.execute_batch(schema)?;
+    transaction.execute("DELETE FROM meta", NO_PARAMS)?;
+    transaction.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+        params![META_VERSION, version],
+    )?;
+    transaction.execute(
+        "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+        params![META_BLOCK0, block0],
+    )?;
+    transaction.commit()
+}
+
+/// the version and block0 stored in the database, if they are not the
+/// expected ones. A new database has none stored.
+fn stored_mismatch(
+    connection: &Connection,
+    path: &Path,
+    version: u32,
+    block0: HeaderHash,
+) -> Result<Option<Mismatch>, rusqlite::Error> {
+    let stored_version: Option<u32> = get_meta(connection, META_VERSION)?;
+    let stored_block0: Option<String> = get_meta(connection, META_BLOCK0)?;
+    let block0 = block0.to_string();
+    let is_new = stored_version.is_none() && stored_block0.is_none();
+    if is_new || (stored_version == Some(version) && stored_block0.as_ref() == Some(&block0)) {
+        return Ok(None);
+    }
+    Ok(Some(Mismatch {
+        file: path.display().to_string(),
+        stored_version,
+        stored_block0,
+        version,
+        block0,
+    }))
+}
+
+fn get_meta<T: rusqlite::types::FromSql>(
+    connection: &Connection,
+    key: &str,
+) -> Result<Option<T>, rusqlite::Error> {
+    connection
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY
+    );
+    ";
+
+    const SCHEMA_V2: &str = "
+    CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    ";
+
+    fn logger() -> Logger {
+        Logger::root(slog::Discard, o!())
+    }
+
+    #[test]
+    fn new_schema_version_recreates_the_tables() {
+        let mut connection = in_memory(SCHEMA_V1, 1);
+        connection
+            .execute("INSERT INTO entries (id) VALUES (1)", NO_PARAMS)
+            .unwrap();
+
+        init(
+            &mut connection,
+            SCHEMA_V2,
+            2,
+            HeaderHash::zero_hash(),
+            &logger(),
+        )
+        .unwrap();
+
+        let count: u32 = connection
+            .query_row("SELECT COUNT(*) FROM entries", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        // the table has the columns of the new schema
+        connection
+            .execute("INSERT INTO entries (id, name) VALUES (1, 'a')", NO_PARAMS)
+            .unwrap();
+        assert_eq!(get_meta::<u32>(&connection, META_VERSION).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn same_schema_version_keeps_the_data() {
+        let mut connection = in_memory(SCHEMA_V1, 1);
+        connection
+            .execute("INSERT INTO entries (id) VALUES (1)", NO_PARAMS)
+            .unwrap();
+
+        init(
+            &mut connection,
+            SCHEMA_V1,
+            1,
+            HeaderHash::zero_hash(),
+            &logger(),
+        )
+        .unwrap();
+
+        let count: u32 = connection
+            .query_row("SELECT COUNT(*) FROM entries", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn mismatch_is_reported() {
+        let connection = in_memory(SCHEMA_V1, 1);
+        let path = Path::new("test.sqlite");
+        let block0 = HeaderHash::zero_hash();
+        assert!(stored_mismatch(&connection, path, 1, block0)
+            .unwrap()
+            .is_none());
+        let mismatch = stored_mismatch(&connection, path, 2, block0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(mismatch.stored_version, Some(1));
+        assert_eq!(mismatch.version, 2);
+
+        // a new database has nothing stored yet
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(META_SCHEMA).unwrap();
+        assert!(stored_mismatch(&connection, path, 2, block0)
+            .unwrap()
+            .is_none());
+    }
+}
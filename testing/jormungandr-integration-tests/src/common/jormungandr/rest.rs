@@ -2,8 +2,8 @@ use crate::common::{configuration::jormungandr_config::JormungandrConfig, legacy
 use chain_impl_mockchain::fragment::Fragment;
 use chain_impl_mockchain::{fragment::FragmentId, header::HeaderId};
 use jormungandr_lib::interfaces::{
-    EnclaveLeaderId, EpochRewardsInfo, FragmentLog, Info, NodeStatsDto, PeerRecord, PeerStats,
-    StakeDistributionDto,
+    EnclaveLeaderId, EpochRewardsInfo, FragmentLog, Info, LeaderStatus, NodeStatsDto, PeerRecord,
+    PeerStats, StakeDistributionDto,
 };
 use jormungandr_testing_utils::testing::MemPoolCheck;
 use std::collections::HashMap;
@@ -107,12 +107,15 @@ impl JormungandrRest {
 
     pub fn leaders(&self) -> Result<Vec<EnclaveLeaderId>, RestError> {
         let leaders = self.inner.leaders()?;
-        let leaders: Vec<EnclaveLeaderId> = if leaders.is_empty() {
+        let leaders: Vec<LeaderStatus> = if leaders.is_empty() {
             Vec::new()
         } else {
             serde_json::from_str(&leaders).map_err(|err| RestError::CannotDeserialize(err))?
         };
-        Ok(leaders)
+        Ok(leaders
+            .iter()
+            .map(|leader| *leader.enclave_leader_id())
+            .collect())
     }
 
     pub fn send_fragment(&self, fragment: Fragment) -> Result<MemPoolCheck, reqwest::Error> {
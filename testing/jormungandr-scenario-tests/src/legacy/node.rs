@@ -392,6 +392,21 @@ impl LegacyNodeController {
                         ));
                         return Ok(status);
                     }
+                    Expired => {
+                        self.progress_bar.log_info(format!(
+                            "Fragment '{}' expired in the mempool",
+                            check.fragment_id()
+                        ));
+                        return Ok(status);
+                    }
+                    Evicted { reason } => {
+                        self.progress_bar.log_info(format!(
+                            "Fragment '{}' evicted: {}",
+                            check.fragment_id(),
+                            reason
+                        ));
+                        return Ok(status);
+                    }
                 }
             } else {
                 bail!(ErrorKind::FragmentNoInMemPoolLogs(
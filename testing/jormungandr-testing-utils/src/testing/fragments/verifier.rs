@@ -83,6 +83,15 @@ impl FragmentVerifier {
                         block.clone(),
                     );
                 }
+                FragmentStatus::Expired => {
+                    node.log_rejected_fragment(
+                        check.fragment_id().clone(),
+                        "expired in the mempool".to_string(),
+                    );
+                }
+                FragmentStatus::Evicted { reason } => {
+                    node.log_rejected_fragment(check.fragment_id().clone(), reason.to_string());
+                }
             }
             return Ok(status);
         }
@@ -114,6 +123,8 @@ impl FragmentVerifier {
             match status {
                 FragmentStatus::Rejected { .. } => return Ok(status),
                 FragmentStatus::InABlock { .. } => return Ok(status),
+                FragmentStatus::Expired => return Ok(status),
+                FragmentStatus::Evicted { .. } => return Ok(status),
                 _ => (),
             }
             std::thread::sleep(duration);